@@ -25,7 +25,7 @@ async fn main() -> anyhow::Result<()> {
     let executor = Executor::new(PathBuf::from("/tmp"), true);
     let task = Task::new("echo 'Hello from OCTASKLY'".to_string());
     
-    match executor.execute_with_timeout(&task).await {
+    match executor.execute_with_timeout(&task, None).await {
         Ok(result) => {
             println!("Task executed!");
             println!("   Status: {:?}", result.status);
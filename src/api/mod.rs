@@ -1,47 +1,222 @@
 use axum::{
-    extract::{Path, State, Json},
-    http::StatusCode,
+    extract::{ConnectInfo, Extension, FromRequestParts, Path, Query, State, Json},
+    http::{request::Parts, Extensions, HeaderMap, StatusCode},
     middleware::Next,
+    response::sse::{self, KeepAlive, Sse},
     response::Response,
     routing::{get, post},
     Router,
     http::Request,
     body::Body,
 };
-use serde_json::json;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 use tower_http::cors::CorsLayer;
 use anyhow::Result;
 
-use crate::protocol::Task;
-use crate::scheduler::Scheduler;
+use crate::protocol::{Placement, Resources, Task, TaskResult, TaskStatus};
+use crate::scheduler::{Cancellation, DrainMode, Scheduler};
 use crate::state::DispatcherState;
-use crate::auth::AuthManager;
-use crate::persistence::PersistentStore;
+use crate::auth::{AuthManager, Claims, Role};
+use crate::config::{ReloadReport, Reloader};
+use crate::events::{Event, EventFilter, EventRecord};
+use crate::persistence::{DeliveryStatus, SortOrder, TaskQuery, TaskStore, Webhook};
+use crate::secrets::{self, SecretError};
+use crate::audit::{AuditEntry, AuditQuery};
+
+pub mod openapi;
+pub mod types;
+
+pub use types::*;
 
 #[derive(Clone)]
 pub struct ApiState {
     pub scheduler: Arc<Scheduler>,
     pub dispatcher: Arc<DispatcherState>,
     pub auth: Arc<AuthManager>,
-    pub store: Arc<PersistentStore>,
+    pub store: Arc<dyn TaskStore>,
+    /// Check bearer tokens on /api/v1 (`security.require_auth`)
+    pub require_auth: bool,
+    /// Set when the dispatcher can reload its configuration
+    pub reloader: Option<Arc<Reloader>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateTaskRequest {
     pub command: String,
     pub timeout: Option<u64>,
     pub inputs: Option<Vec<String>>,
     pub outputs: Option<Vec<String>>,
+    pub owner: Option<String>,
+    pub resources: Option<Resources>,
+    pub placement: Option<Placement>,
+    pub max_retries: Option<u32>,
+    pub labels: Option<std::collections::HashMap<String, String>>,
+    pub env: Option<std::collections::HashMap<String, String>>,
+    /// Higher runs earlier among the owner's queued tasks
+    pub priority: Option<i32>,
 }
 
+impl CreateTaskRequest {
+    /// Build the task; `default_timeout` applies when the request sets none
+    fn into_task(self, default_timeout: u64) -> Task {
+        let mut task = Task::new(self.command);
+        task.timeout = self.timeout.unwrap_or(default_timeout);
+        if let Some(inputs) = self.inputs {
+            task.inputs = inputs;
+        }
+        if let Some(outputs) = self.outputs {
+            task.outputs = outputs;
+        }
+        if let Some(owner) = self.owner {
+            task.owner = owner;
+        }
+        if let Some(resources) = self.resources {
+            task.resources = resources;
+        }
+        if let Some(placement) = self.placement {
+            task.placement = placement;
+        }
+        if let Some(max_retries) = self.max_retries {
+            task.max_retries = max_retries;
+        }
+        if let Some(labels) = self.labels {
+            task.labels = labels;
+        }
+        if let Some(env) = self.env {
+            task.env = env;
+        }
+        if let Some(priority) = self.priority {
+            task.priority = priority;
+        }
+        task
+    }
+}
+
+/// Largest number of tasks one batch or array call may create
+pub const MAX_BATCH_SIZE: usize = 10_000;
+
+/// Many tasks submitted in one call; all are accepted or none is
 #[derive(Debug, Serialize, Deserialize)]
-pub struct TaskResponse {
-    pub id: String,
-    pub command: String,
-    pub status: String,
-    pub created_at: String,
+pub struct BatchTaskRequest {
+    pub tasks: Vec<CreateTaskRequest>,
+}
+
+/// Tasks expanded server-side from one command template.
+///
+/// `{index}` in the command becomes the task's position (from 0) and
+/// `{param}` the matching entry of `params`. Give either `count` or `params`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArrayTaskRequest {
+    #[serde(flatten)]
+    pub template: CreateTaskRequest,
+    pub count: Option<usize>,
+    pub params: Option<Vec<String>>,
+}
+
+impl ArrayTaskRequest {
+    /// Expand into tasks sharing a new array id
+    pub fn expand(self, default_timeout: u64) -> Result<(String, Vec<Task>), String> {
+        let size = match (self.count, &self.params) {
+            (Some(_), Some(_)) => return Err("give either count or params, not both".to_string()),
+            (Some(count), None) => count,
+            (None, Some(params)) => params.len(),
+            (None, None) => return Err("an array needs count or params".to_string()),
+        };
+        // Checked before allocating anything for the tasks
+        // Diperiksa sebelum mengalokasikan apa pun untuk tugas
+        if size == 0 || size > MAX_BATCH_SIZE {
+            return Err(format!("an array must have between 1 and {} tasks", MAX_BATCH_SIZE));
+        }
+        let params = match self.params {
+            Some(params) => params.into_iter().map(Some).collect(),
+            None => vec![None; size],
+        };
+
+        let array_id = uuid::Uuid::new_v4().to_string();
+        let tasks = params
+            .into_iter()
+            .enumerate()
+            .map(|(index, param)| {
+                let mut request = self.template.clone();
+                request.command = request.command.replace("{index}", &index.to_string());
+                if let Some(param) = &param {
+                    request.command = request.command.replace("{param}", param);
+                }
+                let mut task = request.into_task(default_timeout);
+                task.parent_id = Some(array_id.clone());
+                task.env.insert("OCTASKLY_ARRAY_ID".to_string(), array_id.clone());
+                task.env.insert("OCTASKLY_ARRAY_INDEX".to_string(), index.to_string());
+                task
+            })
+            .collect();
+        Ok((array_id, tasks))
+    }
+}
+
+/// Query parameters of `GET /api/v1/tasks`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ListTasksParams {
+    pub status: Option<String>,
+    pub worker: Option<String>,
+    pub owner: Option<String>,
+    /// `key=value`
+    pub label: Option<String>,
+    /// RFC 3339, inclusive
+    pub created_after: Option<String>,
+    /// RFC 3339, exclusive
+    pub created_before: Option<String>,
+    /// Substring of the command
+    pub command: Option<String>,
+    /// Array id
+    pub parent: Option<String>,
+    pub order: Option<SortOrder>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+impl ListTasksParams {
+    fn into_query(self) -> Result<TaskQuery, String> {
+        let time = |value: Option<String>, name: &str| -> Result<_, String> {
+            value
+                .map(|v| {
+                    chrono::DateTime::parse_from_rfc3339(&v)
+                        .map(|t| t.with_timezone(&chrono::Utc))
+                        .map_err(|e| format!("invalid {}: {}", name, e))
+                })
+                .transpose()
+        };
+        let label = self
+            .label
+            .map(|l| match l.split_once('=') {
+                Some((key, value)) => Ok((key.to_string(), value.to_string())),
+                None => Err(format!("invalid label '{}': expected KEY=VALUE", l)),
+            })
+            .transpose()?;
+        if let Some(cursor) = &self.cursor {
+            crate::persistence::decode_cursor(cursor).map_err(|e| e.to_string())?;
+        }
+        Ok(TaskQuery {
+            status: self.status,
+            worker_id: self.worker,
+            owner: self.owner,
+            label,
+            created_after: time(self.created_after, "created_after")?,
+            created_before: time(self.created_before, "created_before")?,
+            command_contains: self.command,
+            parent_id: self.parent,
+            order: self.order.unwrap_or_default(),
+            limit: self.limit.unwrap_or(0),
+            cursor: self.cursor,
+        })
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DrainRequest {
+    #[serde(default)]
+    pub mode: DrainMode,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -56,11 +231,214 @@ pub struct AuthResponse {
     pub expires_in: i64,
 }
 
+/// Webhook subscription; without `events` every event is sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub events: Option<Vec<String>>,
+    /// Only events about this task
+    pub task_id: Option<String>,
+    /// Only events about tasks of this owner (project)
+    pub owner: Option<String>,
+    /// Signing key; generated and returned once if not given
+    pub secret: Option<String>,
+}
+
+impl CreateWebhookRequest {
+    fn into_webhook(self) -> Result<Webhook, String> {
+        let url = reqwest::Url::parse(&self.url).map_err(|e| format!("invalid url: {}", e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err("webhook url must be http or https".to_string());
+        }
+        let events = self.events.unwrap_or_default();
+        if let Some(unknown) = events.iter().find(|e| !Event::KINDS.contains(&e.as_str())) {
+            return Err(format!("unknown event type '{}'", unknown));
+        }
+        Ok(Webhook {
+            id: uuid::Uuid::new_v4().to_string(),
+            url: self.url,
+            events,
+            task_id: self.task_id,
+            owner: self.owner,
+            secret: self
+                .secret
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string()),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+/// Query parameters of webhook delivery listings
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DeliveryParams {
+    pub status: Option<DeliveryStatus>,
+    pub limit: Option<usize>,
+}
+
+/// Query parameters of `GET /api/v1/events`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EventStreamParams {
+    /// Comma-separated event types, e.g. `task_completed,worker_left`
+    pub types: Option<String>,
+    /// Only events about this task
+    pub task: Option<String>,
+    /// Resume after this event; the `Last-Event-ID` header takes precedence
+    pub last_event_id: Option<u64>,
+}
+
+impl EventStreamParams {
+    fn into_filter(self) -> Result<(EventFilter, Option<u64>), String> {
+        let kinds = match self.types {
+            Some(types) => {
+                let kinds: std::collections::HashSet<String> = types
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if let Some(unknown) = kinds.iter().find(|k| !Event::KINDS.contains(&k.as_str())) {
+                    return Err(format!(
+                        "unknown event type '{}', expected one of {}",
+                        unknown,
+                        Event::KINDS.join(", ")
+                    ));
+                }
+                Some(kinds)
+            }
+            None => None,
+        };
+        Ok((EventFilter { kinds, task_id: self.task }, self.last_event_id))
+    }
+}
+
+/// Value of a secret and the projects whose tasks may use it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PutSecretRequest {
+    pub value: String,
+    pub projects: Vec<String>,
+}
+
+/// Body of `POST /api/v1/tokens`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueTokenRequest {
+    /// User or service the token is for
+    pub subject: String,
+    /// Role granting its default permissions [default: client]
+    pub role: Option<String>,
+    /// Projects whose secrets the token may use
+    #[serde(default)]
+    pub projects: Vec<String>,
+    /// Lifetime in hours [default: 24]
+    pub hours: Option<i64>,
+}
+
+/// Actor recorded when auth is off
+const ANONYMOUS: &str = "anonymous";
+
+fn source_ip(extensions: &Extensions) -> Option<IpAddr> {
+    extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// Who sent an API request: token subject and source address, for audit entries
+pub struct Origin {
+    actor: String,
+    source: Option<IpAddr>,
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Origin {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            actor: parts
+                .extensions
+                .get::<Claims>()
+                .map(|claims| claims.sub.clone())
+                .unwrap_or_else(|| ANONYMOUS.to_string()),
+            source: source_ip(&parts.extensions),
+        })
+    }
+}
+
+impl Origin {
+    fn entry(&self, action: &str, allowed: bool) -> AuditEntry {
+        let entry = AuditEntry::new(action, &self.actor, allowed);
+        match self.source {
+            Some(ip) => entry.with_source(ip),
+            None => entry,
+        }
+    }
+}
+
+// Audit the outcome of a request: success, or a refusal for lack of rights.
+// Other errors (bad input, unknown ids) are not security decisions.
+// Audit hasil sebuah permintaan: berhasil, atau ditolak karena tidak berhak.
+// Galat lain (masukan salah, id tidak dikenal) bukan keputusan keamanan.
+async fn audit_outcome<T>(
+    state: &ApiState,
+    origin: &Origin,
+    action: &str,
+    subject: &str,
+    result: &Result<T, (StatusCode, String)>,
+) {
+    let entry = match result {
+        Ok(_) => origin.entry(action, true),
+        Err((StatusCode::FORBIDDEN | StatusCode::UNAUTHORIZED, reason)) => {
+            origin.entry(action, false).with_reason(reason.clone())
+        }
+        Err(_) => return,
+    };
+    state.dispatcher.audit.record(entry.with_subject(subject)).await;
+}
+
+// With auth required, the token must carry `permission`
+// Dengan auth wajib, token harus membawa `permission`
+fn require_permission(
+    state: &ApiState,
+    claims: Option<&Claims>,
+    permission: &str,
+) -> Result<(), (StatusCode, String)> {
+    if state.require_auth && !claims.is_some_and(|claims| state.auth.has_permission(claims, permission)) {
+        return Err((StatusCode::FORBIDDEN, format!("{} permission required", permission)));
+    }
+    Ok(())
+}
+
+/// Refuse tasks referencing secrets that the submitter or the task's project may not use
+async fn check_secrets(state: &ApiState, claims: Option<&Claims>, tasks: &[Task]) -> Result<(), (StatusCode, String)> {
+    for task in tasks.iter().filter(|task| !secrets::references(task).is_empty()) {
+        let Some(vault) = &state.dispatcher.secrets else {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, "Secrets are not enabled".to_string()));
+        };
+        // Without auth the project is whatever owner the body names
+        // Tanpa auth proyeknya adalah owner apa pun yang disebut body
+        if !state.require_auth {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Tasks using secrets need security.require_auth on the dispatcher".to_string(),
+            ));
+        }
+        if !state.dispatcher.transport.is_encrypted() {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Tasks using secrets need security.encrypt on the dispatcher and workers".to_string(),
+            ));
+        }
+        require_permission(state, claims, &secrets::project_permission(&task.owner))?;
+        vault.authorize(task).await.map_err(|e| match e {
+            SecretError::Denied(reason) => (StatusCode::FORBIDDEN, reason),
+            SecretError::Store(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()),
+        })?;
+    }
+    Ok(())
+}
+
 /// Middleware for JWT verification
-#[allow(dead_code)]
 async fn auth_middleware(
     State(state): State<ApiState>,
-    request: Request<Body>,
+    mut request: Request<Body>,
     next: Next,
 ) -> Result<Response, StatusCode> {
     let auth_header = request
@@ -68,43 +446,58 @@ async fn auth_middleware(
         .get("Authorization")
         .and_then(|h| h.to_str().ok())
         .map(|h| h.to_string());
+    let source = source_ip(request.extensions());
 
-    if let Some(header) = auth_header {
-        if let Some(token) = header.strip_prefix("Bearer ") {
-            match state.auth.verify_token(token) {
-                Ok(_claims) => {
-                    // Token is valid, proceed
-                    return Ok(next.run(request).await);
-                }
-                Err(_) => {
-                    return Err(StatusCode::UNAUTHORIZED);
+    let refusal = match auth_header.as_deref().and_then(|h| h.strip_prefix("Bearer ")) {
+        Some(token) => match state.auth.verify_token(token) {
+            Ok(claims) => {
+                // A token's first use since start is audited as its login
+                // Pemakaian pertama token sejak start diaudit sebagai login-nya
+                if state.auth.first_use(&claims) {
+                    let mut entry = AuditEntry::new("auth.login", &claims.sub, true).with_subject(claims.token_key());
+                    if let Some(ip) = source {
+                        entry = entry.with_source(ip);
+                    }
+                    state.dispatcher.audit.record(entry).await;
                 }
+                // Token is valid, proceed; handlers may check its permissions
+                request.extensions_mut().insert(claims);
+                return Ok(next.run(request).await);
             }
-        }
-    }
+            Err(e) => e.to_string(),
+        },
+        None => "missing bearer token".to_string(),
+    };
 
+    let mut entry = AuditEntry::denied("auth.login", ANONYMOUS, refusal).with_subject(request.uri().path());
+    if let Some(ip) = source {
+        entry = entry.with_source(ip);
+    }
+    state.dispatcher.audit.record(entry).await;
     Err(StatusCode::UNAUTHORIZED)
 }
 
 /// Create a new task
 async fn create_task(
     State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
     Json(req): Json<CreateTaskRequest>,
 ) -> Result<(StatusCode, Json<TaskResponse>), (StatusCode, String)> {
-    let mut task = Task::new(req.command.clone());
-    
-    if let Some(inputs) = req.inputs {
-        task.inputs = inputs;
-    }
-    if let Some(outputs) = req.outputs {
-        task.outputs = outputs;
-    }
-    if let Some(timeout) = req.timeout {
-        task.timeout = timeout;
+    let task = req.into_task(state.dispatcher.task_timeout);
+    let result = async {
+        require_permission(&state, claims.as_deref(), "create_task")?;
+        check_secrets(&state, claims.as_deref(), std::slice::from_ref(&task)).await?;
+        state
+            .scheduler
+            .submit(task.clone())
+            .await
+            .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))
     }
+    .await;
+    audit_outcome(&state, &origin, "task.submit", &task.id, &result).await;
+    result?;
 
-    state.scheduler.enqueue(task.clone()).await;
-    
     let response = TaskResponse {
         id: task.id,
         command: task.command,
@@ -119,20 +512,9 @@ async fn create_task(
 async fn get_task(
     State(state): State<ApiState>,
     Path(task_id): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    match state.store.get_task(&task_id) {
-        Ok(Some(task)) => Ok(Json(json!({
-            "id": task.id,
-            "command": task.command,
-            "status": task.status,
-            "worker_id": task.worker_id,
-            "stdout": task.stdout,
-            "stderr": task.stderr,
-            "exit_code": task.exit_code,
-            "duration_ms": task.duration_ms,
-            "created_at": task.created_at,
-            "completed_at": task.completed_at,
-        }))),
+) -> Result<Json<TaskDetails>, (StatusCode, String)> {
+    match state.store.get_task(&task_id).await {
+        Ok(Some(task)) => Ok(Json(task.into())),
         Ok(None) => Err((StatusCode::NOT_FOUND, "Task not found".to_string())),
         Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())),
     }
@@ -141,82 +523,768 @@ async fn get_task(
 /// List all tasks
 async fn list_tasks(
     State(state): State<ApiState>,
-) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
-    match state.store.get_all_tasks() {
-        Ok(tasks) => {
-            let response = tasks
-                .iter()
-                .map(|t| {
-                    json!({
-                        "id": t.id,
-                        "command": t.command,
-                        "status": t.status,
-                        "worker_id": t.worker_id,
-                        "created_at": t.created_at,
-                        "completed_at": t.completed_at,
-                    })
-                })
-                .collect();
-            Ok(Json(response))
-        }
+    Query(params): Query<ListTasksParams>,
+) -> Result<Json<TaskList>, (StatusCode, String)> {
+    let query = params.into_query().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    match state.store.query_tasks(&query).await {
+        Ok(page) => Ok(Json(TaskList {
+            tasks: page.tasks.into_iter().map(TaskSummary::from).collect(),
+            next_cursor: page.next_cursor,
+        })),
         Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())),
     }
 }
 
+/// Stop tasks taken back by a cancel: tell their workers and record the outcome
+async fn settle_cancellations(state: &ApiState, cancelled: Vec<Cancellation>) -> Vec<String> {
+    let mut by_worker: std::collections::HashMap<String, Vec<String>> = std::collections::HashMap::new();
+    for cancellation in &cancelled {
+        if let Some(worker_id) = &cancellation.worker_id {
+            by_worker.entry(worker_id.clone()).or_default().push(cancellation.task.id.clone());
+        }
+    }
+    if !by_worker.is_empty() {
+        let workers = state.scheduler.get_workers().await;
+        let transport = &state.dispatcher.transport;
+        for (worker_id, task_ids) in by_worker {
+            if let Some(worker) = workers.iter().find(|w| w.id == worker_id) {
+                if let Err(e) = transport.cancel_on_worker(worker, &task_ids).await {
+                    tracing::warn!("Failed to stop cancelled tasks on worker {}: {}", worker_id, e);
+                }
+            }
+        }
+    }
+
+    let mut task_ids = Vec::with_capacity(cancelled.len());
+    for cancellation in cancelled {
+        state
+            .dispatcher
+            .store_result(TaskResult {
+                task_id: cancellation.task.id.clone(),
+                worker_id: cancellation.worker_id.unwrap_or_default(),
+                status: TaskStatus::Cancelled,
+                stdout: String::new(),
+                stderr: "Cancelled by request".to_string(),
+                exit_code: None,
+                duration_ms: 0,
+                completed_at: chrono::Local::now().timestamp(),
+                signer: None,
+            })
+            .await;
+        task_ids.push(cancellation.task.id);
+    }
+    task_ids
+}
+
 /// Cancel a task
 async fn cancel_task(
-    State(_state): State<ApiState>,
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
     Path(task_id): Path<String>,
+) -> Result<Json<CancelResponse>, (StatusCode, String)> {
+    let result = async {
+        require_permission(&state, claims.as_deref(), "cancel_task")?;
+        match state.scheduler.cancel(&task_id).await {
+            Some(cancellation) => Ok(cancellation),
+            None => match state.store.get_task(&task_id).await {
+                Ok(Some(task)) => Err((
+                    StatusCode::CONFLICT,
+                    format!("Task already finished with status {}", task.status),
+                )),
+                Ok(None) => Err((StatusCode::NOT_FOUND, "Task not found".to_string())),
+                Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())),
+            },
+        }
+    }
+    .await;
+    audit_outcome(&state, &origin, "task.cancel", &task_id, &result).await;
+    settle_cancellations(&state, vec![result?]).await;
+    Ok(Json(CancelResponse {
+        id: task_id,
+        status: "Cancelled".to_string(),
+    }))
+}
+
+/// Submit many tasks atomically
+async fn create_task_batch(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+    Json(req): Json<BatchTaskRequest>,
+) -> Result<(StatusCode, Json<BatchResponse>), (StatusCode, String)> {
+    if req.tasks.is_empty() || req.tasks.len() > MAX_BATCH_SIZE {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("a batch must have between 1 and {} tasks", MAX_BATCH_SIZE),
+        ));
+    }
+    let tasks: Vec<Task> = req
+        .tasks
+        .into_iter()
+        .map(|r| r.into_task(state.dispatcher.task_timeout))
+        .collect();
+    let task_ids: Vec<String> = tasks.iter().map(|t| t.id.clone()).collect();
+    let result = async {
+        require_permission(&state, claims.as_deref(), "create_task")?;
+        check_secrets(&state, claims.as_deref(), &tasks).await?;
+        state
+            .scheduler
+            .submit_batch(tasks)
+            .await
+            .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))
+    }
+    .await;
+    audit_outcome(&state, &origin, "task.submit", &task_ids.join(","), &result).await;
+    result?;
+    Ok((StatusCode::CREATED, Json(BatchResponse { task_ids })))
+}
+
+/// Expand a command template into an array of tasks
+async fn create_array(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+    Json(req): Json<ArrayTaskRequest>,
+) -> Result<(StatusCode, Json<ArrayResponse>), (StatusCode, String)> {
+    let (array_id, tasks) = req.expand(state.dispatcher.task_timeout).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let task_ids: Vec<String> = tasks.iter().map(|t| t.id.clone()).collect();
+    let result = async {
+        require_permission(&state, claims.as_deref(), "create_task")?;
+        check_secrets(&state, claims.as_deref(), &tasks).await?;
+        state
+            .scheduler
+            .submit_batch(tasks)
+            .await
+            .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))
+    }
+    .await;
+    let subject = format!("array {} ({} tasks)", array_id, task_ids.len());
+    audit_outcome(&state, &origin, "task.submit", &subject, &result).await;
+    result?;
+    Ok((StatusCode::CREATED, Json(ArrayResponse { array_id, task_ids })))
+}
+
+/// Aggregate status of an array
+async fn get_array(
+    State(state): State<ApiState>,
+    Path(array_id): Path<String>,
+) -> Result<Json<ArrayStatus>, (StatusCode, String)> {
+    let counts = state
+        .store
+        .array_status_counts(&array_id)
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()))?;
+    let total: usize = counts.values().sum();
+    if total == 0 {
+        return Err((StatusCode::NOT_FOUND, "Array not found".to_string()));
+    }
+    let unfinished: usize = UNFINISHED_STATUSES.iter().filter_map(|s| counts.get(*s)).sum();
+    Ok(Json(ArrayStatus {
+        array_id,
+        total,
+        counts,
+        finished: unfinished == 0,
+    }))
+}
+
+/// Cancel every unfinished task of an array
+async fn cancel_array(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+    Path(array_id): Path<String>,
+) -> Result<Json<ArrayCancelResponse>, (StatusCode, String)> {
+    let result = async {
+        require_permission(&state, claims.as_deref(), "cancel_task")?;
+        let counts = state
+            .store
+            .array_status_counts(&array_id)
+            .await
+            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()))?;
+        if counts.is_empty() {
+            return Err((StatusCode::NOT_FOUND, "Array not found".to_string()));
+        }
+        let cancelled = state.scheduler.cancel_array(&array_id).await;
+        Ok(settle_cancellations(&state, cancelled).await)
+    }
+    .await;
+    let subject = match &result {
+        Ok(cancelled) => format!("array {} ({} tasks)", array_id, cancelled.len()),
+        Err(_) => format!("array {}", array_id),
+    };
+    audit_outcome(&state, &origin, "task.cancel", &subject, &result).await;
+    Ok(Json(ArrayCancelResponse { array_id, cancelled: result? }))
+}
+
+fn sse_event(record: &EventRecord) -> sse::Event {
+    sse::Event::default()
+        .id(record.id.to_string())
+        .event(record.event.kind())
+        .json_data(record)
+        .unwrap_or_default()
+}
+
+/// Stream cluster events as Server-Sent Events.
+///
+/// A subscriber that falls too far behind is disconnected; reconnecting with
+/// its last event id replays what is still in the bus history. Task output is
+/// only replayed when the stream is filtered to one task.
+async fn stream_events(
+    State(state): State<ApiState>,
+    Query(params): Query<EventStreamParams>,
+    headers: HeaderMap,
+) -> Result<Sse<impl futures::Stream<Item = Result<sse::Event, std::convert::Infallible>>>, (StatusCode, String)> {
+    let (filter, last_event_id) = params.into_filter().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let last_event_id = match headers.get("last-event-id") {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .ok_or((StatusCode::BAD_REQUEST, "invalid Last-Event-ID".to_string()))?,
+        ),
+        None => last_event_id,
+    };
+
+    let subscription = state
+        .dispatcher
+        .events
+        .subscribe(last_event_id, filter.task_id.as_deref());
+    let events = futures::stream::unfold(subscription.events, |mut receiver| async move {
+        match receiver.recv().await {
+            Ok(record) => Some((Some(record), receiver)),
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                tracing::warn!("Event subscriber fell behind by {} events, disconnecting", missed);
+                None
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => None,
+        }
+    });
+    // Output that falls behind is skipped rather than disconnecting; the task result keeps all of it
+    // Output yang tertinggal dilewati alih-alih memutus; hasil tugas tetap menyimpan seluruhnya
+    let output = futures::stream::unfold(subscription.output, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(record) => return Some((Some(record), receiver)),
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::debug!("Output subscriber fell behind by {} chunks, skipping them", missed);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    // The stream ends when the event receiver does
+    // Aliran berakhir saat penerima event berakhir
+    let live = futures::StreamExt::filter_map(
+        futures::StreamExt::take_while(
+            futures::stream::select(
+                futures::StreamExt::chain(events, futures::stream::once(async { None })),
+                futures::StreamExt::chain(output, futures::stream::pending()),
+            ),
+            |record| std::future::ready(record.is_some()),
+        ),
+        std::future::ready,
+    );
+    let stream = futures::StreamExt::filter_map(
+        futures::StreamExt::chain(futures::stream::iter(subscription.backlog), live),
+        move |record| {
+            let event = filter.matches(&record.event).then(|| Ok(sse_event(&record)));
+            async move { event }
+        },
+    );
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Subscribe a URL to cluster events
+async fn create_webhook(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+    Json(req): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<WebhookResponse>), (StatusCode, String)> {
+    let result = async {
+        require_permission(&state, claims.as_deref(), "manage_webhooks")?;
+        let webhook = req.into_webhook().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        state
+            .store
+            .save_webhook(&webhook)
+            .await
+            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()))?;
+        Ok(webhook)
+    }
+    .await;
+    let subject = result.as_ref().map(|w| w.id.as_str()).unwrap_or_default();
+    audit_outcome(&state, &origin, "webhook.create", subject, &result).await;
+    let webhook = result?;
+    let body = WebhookResponse {
+        secret: Some(webhook.secret.clone()),
+        ..WebhookResponse::from(&webhook)
+    };
+    Ok((StatusCode::CREATED, Json(body)))
+}
+
+/// List webhook subscriptions
+async fn list_webhooks(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+) -> Result<Json<Vec<WebhookResponse>>, (StatusCode, String)> {
+    require_permission(&state, claims.as_deref(), "manage_webhooks")?;
+    let webhooks = state
+        .store
+        .list_webhooks()
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()))?;
+    Ok(Json(webhooks.iter().map(WebhookResponse::from).collect()))
+}
+
+/// Remove a webhook subscription and its delivery log
+async fn delete_webhook(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+    Path(webhook_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let result = async {
+        require_permission(&state, claims.as_deref(), "manage_webhooks")?;
+        match state.store.delete_webhook(&webhook_id).await {
+            Ok(true) => Ok(StatusCode::NO_CONTENT),
+            Ok(false) => Err((StatusCode::NOT_FOUND, "Webhook not found".to_string())),
+            Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())),
+        }
+    }
+    .await;
+    audit_outcome(&state, &origin, "webhook.delete", &webhook_id, &result).await;
+    result
+}
+
+/// Delivery log of one webhook, newest first
+async fn list_webhook_deliveries(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    Path(webhook_id): Path<String>,
+    Query(params): Query<DeliveryParams>,
+) -> Result<Json<Vec<crate::persistence::WebhookDelivery>>, (StatusCode, String)> {
+    require_permission(&state, claims.as_deref(), "manage_webhooks")?;
+    let limit = params.limit.unwrap_or(crate::persistence::DEFAULT_PAGE_SIZE).min(crate::persistence::MAX_PAGE_SIZE);
+    state
+        .store
+        .list_deliveries(Some(&webhook_id), params.status, limit)
+        .await
+        .map(Json)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()))
+}
+
+/// Deliveries of every webhook that ran out of retries
+async fn list_dead_letters(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    Query(params): Query<DeliveryParams>,
+) -> Result<Json<Vec<crate::persistence::WebhookDelivery>>, (StatusCode, String)> {
+    require_permission(&state, claims.as_deref(), "manage_webhooks")?;
+    let limit = params.limit.unwrap_or(crate::persistence::DEFAULT_PAGE_SIZE).min(crate::persistence::MAX_PAGE_SIZE);
+    state
+        .store
+        .list_deliveries(None, Some(DeliveryStatus::Dead), limit)
+        .await
+        .map(Json)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()))
+}
+
+/// Create or replace a secret
+async fn put_secret(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+    Path(name): Path<String>,
+    Json(req): Json<PutSecretRequest>,
+) -> Result<Json<SecretResponse>, (StatusCode, String)> {
+    let result = async {
+        require_permission(&state, claims.as_deref(), "manage_secrets")?;
+        let vault = vault(&state)?;
+        secrets::validate_name(&name).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        if req.projects.is_empty() || req.projects.iter().any(|p| p.is_empty()) {
+            return Err((StatusCode::BAD_REQUEST, "a secret needs at least one project".to_string()));
+        }
+        vault
+            .put(&name, &req.value, req.projects)
+            .await
+            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()))
+    }
+    .await;
+    audit_outcome(&state, &origin, "secret.set", &name, &result).await;
+    Ok(Json(SecretResponse::from(&result?)))
+}
+
+/// Secret names and projects; values are never returned
+async fn list_secrets(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+) -> Result<Json<Vec<SecretResponse>>, (StatusCode, String)> {
+    require_permission(&state, claims.as_deref(), "manage_secrets")?;
+    let secrets = vault(&state)?
+        .list()
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()))?;
+    Ok(Json(secrets.iter().map(SecretResponse::from).collect()))
+}
+
+/// Remove a secret; queued tasks referencing it will fail to start
+async fn delete_secret(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+    Path(name): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let result = async {
+        require_permission(&state, claims.as_deref(), "manage_secrets")?;
+        match vault(&state)?.delete(&name).await {
+            Ok(true) => Ok(StatusCode::NO_CONTENT),
+            Ok(false) => Err((StatusCode::NOT_FOUND, "Secret not found".to_string())),
+            Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())),
+        }
+    }
+    .await;
+    audit_outcome(&state, &origin, "secret.delete", &name, &result).await;
+    result
+}
+
+// Secret management is only served with auth on, so a token decides who may use it
+// Pengelolaan rahasia hanya dilayani dengan auth aktif, agar token yang menentukan siapa boleh memakainya
+fn vault(state: &ApiState) -> Result<&secrets::SecretVault, (StatusCode, String)> {
+    let vault = state
+        .dispatcher
+        .secrets
+        .as_deref()
+        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Secrets are not enabled".to_string()))?;
+    if !state.require_auth {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "Secrets need security.require_auth".to_string()));
+    }
+    Ok(vault)
+}
+
+/// List registered workers
+async fn list_workers(State(state): State<ApiState>) -> Json<Vec<crate::protocol::WorkerInfo>> {
+    Json(state.scheduler.get_workers().await)
+}
+
+/// Stop scheduling new tasks on a worker
+async fn cordon_worker(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+    Path(worker_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let result = async {
+        require_permission(&state, claims.as_deref(), "manage_workers")?;
+        if state.scheduler.cordon(&worker_id).await {
+            Ok(StatusCode::OK)
+        } else {
+            Err((StatusCode::NOT_FOUND, "Worker not found".to_string()))
+        }
+    }
+    .await;
+    audit_outcome(&state, &origin, "worker.cordon", &worker_id, &result).await;
+    result
+}
+
+/// Put a cordoned or draining worker back into rotation
+async fn uncordon_worker(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+    Path(worker_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let result = async {
+        require_permission(&state, claims.as_deref(), "manage_workers")?;
+        if state.scheduler.uncordon(&worker_id).await {
+            Ok(StatusCode::OK)
+        } else {
+            Err((StatusCode::NOT_FOUND, "Worker not found".to_string()))
+        }
+    }
+    .await;
+    audit_outcome(&state, &origin, "worker.uncordon", &worker_id, &result).await;
+    result
+}
+
+/// Drain a worker: cordon it and either wait for or migrate its running tasks
+async fn drain_worker(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+    Path(worker_id): Path<String>,
+    req: Option<Json<DrainRequest>>,
+) -> Result<Json<DrainResponse>, (StatusCode, String)> {
+    let mode = req.map(|Json(r)| r.mode).unwrap_or_default();
+    let result = async {
+        require_permission(&state, claims.as_deref(), "manage_workers")?;
+        state
+            .scheduler
+            .drain(&worker_id, mode)
+            .await
+            .ok_or((StatusCode::NOT_FOUND, "Worker not found".to_string()))
+    }
+    .await;
+    let subject = format!("{} ({:?})", worker_id, mode);
+    audit_outcome(&state, &origin, "worker.drain", &subject, &result).await;
+    let migrated = result?;
+
+    let task_ids: Vec<String> = migrated.iter().map(|t| t.id.clone()).collect();
+    if !task_ids.is_empty() {
+        let workers = state.scheduler.get_workers().await;
+        if let Some(worker) = workers.iter().find(|w| w.id == worker_id) {
+            if let Err(e) = state.dispatcher.transport.cancel_on_worker(worker, &task_ids).await {
+                tracing::warn!("Failed to stop migrated tasks on worker {}: {}", worker_id, e);
+            }
+        }
+    }
+
+    Ok(Json(DrainResponse {
+        drained: state.scheduler.is_drained(&worker_id).await,
+        worker_id,
+        mode,
+        migrated_tasks: task_ids,
+    }))
+}
+
+/// Re-read the dispatcher configuration, like SIGHUP
+async fn reload_config(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+) -> Result<Json<ReloadReport>, (StatusCode, String)> {
+    let result = async {
+        require_permission(&state, claims.as_deref(), "system_config")?;
+        let reloader = state
+            .reloader
+            .as_ref()
+            .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Reload is not available".to_string()))?;
+        reloader
+            .reload()
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("{:#}", e)))
+    }
+    .await;
+    let applied = result.as_ref().map(|report| report.applied.join(",")).unwrap_or_default();
+    audit_outcome(&state, &origin, "config.reload", &applied, &result).await;
+    result.map(Json)
+}
+
+/// Sign a new API token, like `octaskly token` but audited and revocable
+async fn issue_token(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+    Json(req): Json<IssueTokenRequest>,
+) -> Result<(StatusCode, Json<TokenResponse>), (StatusCode, String)> {
+    let role_name = req.role.clone().unwrap_or_else(|| "client".to_string());
+    let result = async {
+        require_permission(&state, claims.as_deref(), "manage_users")?;
+        let role = Role::parse(&role_name)
+            .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Unknown role '{}'", role_name)))?;
+        if req.subject.is_empty() || req.hours.is_some_and(|hours| hours <= 0) {
+            return Err((StatusCode::BAD_REQUEST, "subject and a positive hours are required".to_string()));
+        }
+        let mut permissions = role.default_permissions();
+        permissions.extend(req.projects.iter().map(|project| secrets::project_permission(project)));
+        let issued = Claims::with_lifetime(
+            req.subject.clone(),
+            role.to_string(),
+            permissions,
+            chrono::Duration::hours(req.hours.unwrap_or(24)),
+        );
+        let token = state
+            .auth
+            .generate_token(&issued)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        Ok(TokenResponse {
+            id: issued.jti,
+            token,
+            subject: issued.sub,
+            role: issued.role,
+            expires_at: issued.exp,
+        })
+    }
+    .await;
+    let subject = match &result {
+        Ok(issued) => format!("{} for {} ({})", issued.id, issued.subject, issued.role),
+        Err(_) => format!("for {} ({})", req.subject, role_name),
+    };
+    audit_outcome(&state, &origin, "token.issue", &subject, &result).await;
+    Ok((StatusCode::CREATED, Json(result?)))
+}
+
+/// Refuse a token from now on, across restarts
+async fn revoke_token(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    origin: Origin,
+    Path(token_id): Path<String>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    // TODO: Implement task cancellation
-    // For now, just return OK
-    println!("Cancel task: {}", task_id);
-    Ok(StatusCode::OK)
+    let result = async {
+        require_permission(&state, claims.as_deref(), "manage_users")?;
+        state
+            .store
+            .revoke_token(&token_id)
+            .await
+            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()))?;
+        state.auth.revoke_token(&token_id);
+        Ok(StatusCode::NO_CONTENT)
+    }
+    .await;
+    audit_outcome(&state, &origin, "token.revoke", &token_id, &result).await;
+    result
+}
+
+/// Audit records, newest first
+async fn list_audit(
+    State(state): State<ApiState>,
+    claims: Option<Extension<Claims>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditPage>, (StatusCode, String)> {
+    require_permission(&state, claims.as_deref(), "view_logs")?;
+    let limit = query
+        .limit
+        .unwrap_or(crate::persistence::DEFAULT_PAGE_SIZE)
+        .min(crate::persistence::MAX_PAGE_SIZE);
+    let records = state
+        .store
+        .query_audit(&query)
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()))?;
+    let next_before = if records.len() == limit { records.last().map(|r| r.seq) } else { None };
+    Ok(Json(AuditPage { records, next_before }))
 }
 
 /// Get dispatcher stats
 async fn get_stats(
     State(state): State<ApiState>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> Result<Json<StatsResponse>, (StatusCode, String)> {
     let workers = state.scheduler.get_workers().await;
     let queue_size = state.scheduler.queue_size().await;
+    let fair_share = state.scheduler.fair_share_stats().await;
     
-    match state.store.get_stats() {
+    match state.store.get_stats().await {
         Ok((total, completed, failed)) => {
-            Ok(Json(json!({
-                "workers_count": workers.len(),
-                "idle_workers": workers.iter().filter(|w| w.is_idle()).count(),
-                "task_queue": queue_size,
-                "total_tasks": total,
-                "completed_tasks": completed,
-                "failed_tasks": failed,
-            })))
+            Ok(Json(StatsResponse {
+                workers_count: workers.len(),
+                idle_workers: workers.iter().filter(|w| w.is_schedulable() && w.is_idle()).count(),
+                cordoned_workers: workers.iter().filter(|w| !w.is_schedulable()).count(),
+                task_queue: queue_size,
+                total_tasks: total,
+                completed_tasks: completed,
+                failed_tasks: failed,
+                fair_share,
+            }))
         }
         Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Stats error".to_string())),
     }
 }
 
+/// Prometheus metrics of the dispatcher
+async fn metrics(State(state): State<ApiState>) -> impl axum::response::IntoResponse {
+    use crate::metrics::{self, Sample};
+    let workers = state.scheduler.get_workers().await;
+    let slots: usize = workers.iter().map(|w| w.max_jobs).sum();
+    let busy: usize = workers.iter().map(|w| w.current_jobs).sum();
+    let samples = [
+        Sample {
+            name: "octaskly_queue_depth",
+            help: "Tasks waiting to be scheduled",
+            value: state.scheduler.queue_size().await as f64,
+        },
+        Sample {
+            name: "octaskly_workers",
+            help: "Registered workers",
+            value: workers.len() as f64,
+        },
+        Sample {
+            name: "octaskly_worker_slots",
+            help: "Task slots across all workers",
+            value: slots as f64,
+        },
+        Sample {
+            name: "octaskly_worker_slots_busy",
+            help: "Task slots in use",
+            value: busy as f64,
+        },
+        Sample {
+            name: "octaskly_worker_utilisation",
+            help: "Share of task slots in use (0-1)",
+            value: if slots == 0 { 0.0 } else { busy as f64 / slots as f64 },
+        },
+    ];
+    (
+        [(axum::http::header::CONTENT_TYPE, metrics::CONTENT_TYPE)],
+        metrics::global().render(&samples),
+    )
+}
+
 /// Health check
-async fn health_check() -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    Ok(Json(json!({
-        "status": "healthy",
-        "version": "1.0.0",
-    })))
+async fn health_check() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "healthy".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
+
+/// OpenAPI document describing this API
+async fn openapi_document() -> Json<serde_json::Value> {
+    Json(openapi::document())
 }
 
 /// Create API router
 pub fn create_router(state: ApiState) -> Router {
-    Router::new()
-        // Public endpoints
+    let public = Router::new()
         .route("/health", get(health_check))
-        
+        .route("/metrics", get(metrics))
+        .route("/api/v1/openapi.json", get(openapi_document));
+
+    let api = Router::new()
         // Task endpoints
         .route("/api/v1/tasks", post(create_task).get(list_tasks))
+        .route("/api/v1/tasks/batch", post(create_task_batch))
         .route("/api/v1/tasks/:id", get(get_task).delete(cancel_task))
+        .route("/api/v1/arrays", post(create_array))
+        .route("/api/v1/arrays/:id", get(get_array).delete(cancel_array))
+        
+        // Worker endpoints
+        .route("/api/v1/workers", get(list_workers))
+        .route("/api/v1/workers/:id/cordon", post(cordon_worker))
+        .route("/api/v1/workers/:id/uncordon", post(uncordon_worker))
+        .route("/api/v1/workers/:id/drain", post(drain_worker))
+        
+        // Webhook endpoints
+        .route("/api/v1/webhooks", post(create_webhook).get(list_webhooks))
+        .route("/api/v1/webhooks/dead-letters", get(list_dead_letters))
+        .route("/api/v1/webhooks/:id", axum::routing::delete(delete_webhook))
+        .route("/api/v1/webhooks/:id/deliveries", get(list_webhook_deliveries))
+
+        // Secret endpoints; names may contain '/'
+        .route("/api/v1/secrets", get(list_secrets))
+        .route("/api/v1/secrets/*name", axum::routing::put(put_secret).delete(delete_secret))
         
         // Stats endpoint
         .route("/api/v1/stats", get(get_stats))
-        
+        .route("/api/v1/events", get(stream_events))
+
+        // Token endpoints
+        .route("/api/v1/tokens", post(issue_token))
+        .route("/api/v1/tokens/:id", axum::routing::delete(revoke_token))
+
+        // Admin endpoints
+        .route("/api/v1/audit", get(list_audit))
+        .route("/api/v1/admin/reload", post(reload_config));
+
+    let api = if state.require_auth {
+        api.route_layer(axum::middleware::from_fn_with_state(state.clone(), auth_middleware))
+    } else {
+        api
+    };
+
+    public
+        .merge(api)
         .with_state(state)
         .layer(CorsLayer::permissive())
 }
@@ -231,7 +1299,9 @@ pub async fn start_api_server(
     let listener = tokio::net::TcpListener::bind(addr).await?;
     tracing::info!("REST API listening on http://{}", addr);
     
-    axum::serve(listener, app).await?;
+    // Peer addresses are kept for audit entries
+    // Alamat peer disimpan untuk entri audit
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
     
     Ok(())
 }
@@ -239,6 +1309,50 @@ pub async fn start_api_server(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::persistence::MemoryStore;
+    use crate::protocol::{Availability, WorkerInfo};
+    use serde_json::json;
+
+    fn state(require_auth: bool) -> ApiState {
+        let store: Arc<dyn TaskStore> = Arc::new(MemoryStore::new());
+        let dispatcher = Arc::new(DispatcherState::new("test".to_string(), 0).with_store(store.clone()));
+        ApiState {
+            scheduler: Arc::new(Scheduler::new().with_store(store.clone())),
+            dispatcher,
+            auth: Arc::new(AuthManager::new("test".to_string())),
+            store,
+            require_auth,
+            reloader: None,
+        }
+    }
+
+    fn token(state: &ApiState, role: Role) -> String {
+        let claims = Claims::new("tester".to_string(), role.to_string(), role.default_permissions());
+        state.auth.generate_token(&claims).unwrap()
+    }
+
+    async fn call(
+        state: &ApiState,
+        method: &str,
+        uri: &str,
+        token: &str,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = create_router(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let method = reqwest::Method::from_bytes(method.as_bytes()).unwrap();
+        let response = reqwest::Client::new()
+            .request(method, format!("http://{}{}", addr, uri))
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        let status = StatusCode::from_u16(response.status().as_u16()).unwrap();
+        (status, response.json().await.unwrap_or_default())
+    }
 
     #[test]
     fn test_create_task_request() {
@@ -247,9 +1361,110 @@ mod tests {
             timeout: Some(60),
             inputs: None,
             outputs: None,
+            owner: None,
+            resources: None,
+            placement: None,
+            max_retries: None,
+            labels: None,
+            env: None,
+            priority: None,
         };
         
         assert_eq!(req.command, "echo test");
         assert_eq!(req.timeout, Some(60));
     }
+
+    #[test]
+    fn test_array_expansion() {
+        let req: ArrayTaskRequest = serde_json::from_value(json!({
+            "command": "run --seed {index} --lr {param}",
+            "params": ["0.1", "0.01"],
+        }))
+        .unwrap();
+        let (array_id, tasks) = req.expand(300).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[1].command, "run --seed 1 --lr 0.01");
+        assert!(tasks.iter().all(|t| t.parent_id.as_deref() == Some(array_id.as_str())));
+        assert_eq!(tasks[1].env["OCTASKLY_ARRAY_INDEX"], "1");
+        assert_eq!(tasks[1].timeout, 300);
+
+        let counted: ArrayTaskRequest =
+            serde_json::from_value(json!({ "command": "echo {index}", "count": 3 })).unwrap();
+        assert_eq!(counted.expand(300).unwrap().1[2].command, "echo 2");
+
+        let empty: ArrayTaskRequest = serde_json::from_value(json!({ "command": "echo" })).unwrap();
+        assert!(empty.expand(300).is_err());
+
+        let huge: ArrayTaskRequest =
+            serde_json::from_value(json!({ "command": "echo", "count": u64::MAX })).unwrap();
+        assert!(huge.expand(300).unwrap_err().contains("between 1 and"));
+    }
+
+    #[tokio::test]
+    async fn test_worker_management_needs_manage_workers() {
+        let state = state(true);
+        let worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 4);
+        let worker_id = worker.id.clone();
+        state.scheduler.register_worker(worker).await;
+
+        let client = token(&state, Role::Client);
+        for action in ["cordon", "uncordon", "drain"] {
+            let uri = format!("/api/v1/workers/{}/{}", worker_id, action);
+            assert_eq!(call(&state, "POST", &uri, &client, json!({})).await.0, StatusCode::FORBIDDEN);
+        }
+        let availability = |workers: Vec<WorkerInfo>| workers[0].availability;
+        assert_eq!(availability(state.scheduler.get_workers().await), Availability::Active);
+
+        let admin = token(&state, Role::Admin);
+        let uri = format!("/api/v1/workers/{}/cordon", worker_id);
+        assert_eq!(call(&state, "POST", &uri, &admin, json!({})).await.0, StatusCode::OK);
+        assert_eq!(availability(state.scheduler.get_workers().await), Availability::Cordoned);
+    }
+
+    #[tokio::test]
+    async fn test_submit_and_cancel_need_their_permissions() {
+        let state = state(true);
+        let worker = token(&state, Role::Worker);
+        let task = json!({ "command": "echo hi" });
+        assert_eq!(call(&state, "POST", "/api/v1/tasks", &worker, task.clone()).await.0, StatusCode::FORBIDDEN);
+        let batch = json!({ "tasks": [task.clone()] });
+        assert_eq!(call(&state, "POST", "/api/v1/tasks/batch", &worker, batch).await.0, StatusCode::FORBIDDEN);
+        let array = json!({ "command": "echo {index}", "count": 2 });
+        assert_eq!(call(&state, "POST", "/api/v1/arrays", &worker, array).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(state.scheduler.queue_size().await, 0);
+
+        let client = token(&state, Role::Client);
+        let (status, created) = call(&state, "POST", "/api/v1/tasks", &client, task).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let uri = format!("/api/v1/tasks/{}", created["id"].as_str().unwrap());
+        assert_eq!(call(&state, "DELETE", &uri, &worker, json!({})).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(call(&state, "DELETE", "/api/v1/arrays/a1", &worker, json!({})).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(state.scheduler.queue_size().await, 1);
+
+        let admin = token(&state, Role::Admin);
+        assert_eq!(call(&state, "DELETE", &uri, &admin, json!({})).await.0, StatusCode::OK);
+        assert_eq!(call(&state, "DELETE", "/api/v1/arrays/a1", &admin, json!({})).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_secrets_are_refused_without_auth() {
+        let mut state = state(false);
+        let vault = secrets::SecretVault::new(secrets::SecretKey::generate().0, state.store.clone());
+        vault.put("ci/token", "hunter2", vec!["ci".to_string()]).await.unwrap();
+        state.dispatcher = Arc::new(
+            DispatcherState::new("test".to_string(), 0)
+                .with_store(state.store.clone())
+                .with_secrets(vault),
+        );
+
+        let put = json!({ "value": "stolen", "projects": ["ci"] });
+        let (status, _) = call(&state, "PUT", "/api/v1/secrets/ci/token", "", put).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(call(&state, "GET", "/api/v1/secrets", "", json!({})).await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+        let task = json!({ "command": "env", "owner": "ci", "env": { "X": "secret://ci/token" } });
+        let (status, _) = call(&state, "POST", "/api/v1/tasks", "", task).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(state.scheduler.queue_size().await, 0);
+    }
 }
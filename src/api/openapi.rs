@@ -0,0 +1,659 @@
+// OpenAPI 3 description of the REST API, served at /api/v1/openapi.json
+// Deskripsi OpenAPI 3 dari REST API, disajikan di /api/v1/openapi.json
+//
+// Kept by hand next to the handlers; the test below checks that every
+// schema reference resolves. Add new endpoints here when adding routes.
+
+use serde_json::{json, Value};
+
+use crate::events::Event;
+
+fn schema(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn array_of(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn map_of(values: Value) -> Value {
+    json!({ "type": "object", "additionalProperties": values })
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn nullable_string() -> Value {
+    json!({ "type": "string", "nullable": true })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+fn number() -> Value {
+    json!({ "type": "number" })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+fn object(required: &[&str], properties: Value) -> Value {
+    json!({ "type": "object", "required": required, "properties": properties })
+}
+
+fn body(schema: Value) -> Value {
+    json!({ "required": true, "content": { "application/json": { "schema": schema } } })
+}
+
+fn response(description: &str, schema: Value) -> Value {
+    json!({ "description": description, "content": { "application/json": { "schema": schema } } })
+}
+
+fn error(description: &str) -> Value {
+    json!({ "description": description, "content": { "text/plain": { "schema": string() } } })
+}
+
+fn path_param(name: &str, description: &str) -> Value {
+    json!({ "name": name, "in": "path", "required": true, "description": description, "schema": string() })
+}
+
+fn query_param(name: &str, description: &str, schema: Value) -> Value {
+    json!({ "name": name, "in": "query", "required": false, "description": description, "schema": schema })
+}
+
+fn operation(id: &str, tag: &str, summary: &str, parameters: Vec<Value>, responses: Value) -> Value {
+    json!({
+        "operationId": id,
+        "tags": [tag],
+        "summary": summary,
+        "parameters": parameters,
+        "responses": responses,
+    })
+}
+
+fn with_body(mut operation: Value, request: Value) -> Value {
+    operation["requestBody"] = body(request);
+    operation
+}
+
+fn paths() -> Value {
+    let task_id = || path_param("id", "Task id");
+    let array_id = || path_param("id", "Array id");
+    let worker_id = || path_param("id", "Worker id");
+    let webhook_id = || path_param("id", "Webhook id");
+    let secret_name = || path_param("name", "Secret name; may contain '/', e.g. ci/token");
+    let delivery_params = || {
+        vec![
+            query_param("status", "Only deliveries in this state", schema("DeliveryStatus")),
+            query_param("limit", "Page size (default 100, max 1000)", integer()),
+        ]
+    };
+
+    let mut paths = json!({
+        "/health": {
+            "get": operation("health", "system", "Liveness check", vec![], json!({
+                "200": response("Dispatcher is up", schema("HealthResponse")),
+            })),
+        },
+        "/metrics": {
+            "get": operation("metrics", "system", "Prometheus metrics", vec![], json!({
+                "200": { "description": "Text exposition format", "content": { "text/plain": { "schema": string() } } },
+            })),
+        },
+        "/api/v1/openapi.json": {
+            "get": operation("openapi", "system", "This document", vec![], json!({
+                "200": response("OpenAPI 3 document", json!({ "type": "object" })),
+            })),
+        },
+        "/api/v1/tasks": {
+            "post": with_body(
+                operation("createTask", "tasks", "Submit a task", vec![], json!({
+                    "201": response("Task queued", schema("TaskResponse")),
+                    "403": error("Token lacks the create_task permission, or a referenced secret is not available to the task's project or token"),
+                    "422": error("Task was rejected by the scheduler, or uses secrets the dispatcher cannot serve"),
+                })),
+                schema("CreateTaskRequest"),
+            ),
+            "get": operation("listTasks", "tasks", "List tasks, newest first by default", vec![
+                query_param("status", "Only tasks with this status", string()),
+                query_param("worker", "Only tasks run by this worker", string()),
+                query_param("owner", "Only tasks of this owner", string()),
+                query_param("label", "Only tasks with this label, as KEY=VALUE", string()),
+                query_param("created_after", "RFC 3339, inclusive", json!({ "type": "string", "format": "date-time" })),
+                query_param("created_before", "RFC 3339, exclusive", json!({ "type": "string", "format": "date-time" })),
+                query_param("command", "Substring of the command", string()),
+                query_param("parent", "Array id", string()),
+                query_param("order", "Sort order by creation time", json!({ "type": "string", "enum": ["desc", "asc"] })),
+                query_param("limit", "Page size (default 100, max 1000)", integer()),
+                query_param("cursor", "next_cursor of the previous page", string()),
+            ], json!({
+                "200": response("One page of tasks", schema("TaskList")),
+                "400": error("Invalid filter or cursor"),
+            })),
+        },
+        "/api/v1/tasks/batch": {
+            "post": with_body(
+                operation("createTaskBatch", "tasks", "Submit many tasks; all are accepted or none is", vec![], json!({
+                    "201": response("Tasks queued", schema("BatchResponse")),
+                    "400": error("Empty or oversized batch"),
+                    "403": error("Token lacks the create_task permission, or a referenced secret is not available"),
+                    "422": error("A task was rejected by the scheduler"),
+                })),
+                schema("BatchTaskRequest"),
+            ),
+        },
+        "/api/v1/tasks/{id}": {
+            "get": operation("getTask", "tasks", "Task details and result", vec![task_id()], json!({
+                "200": response("The task", schema("TaskDetails")),
+                "404": error("Task not found"),
+            })),
+            "delete": operation("cancelTask", "tasks", "Cancel a queued or running task", vec![task_id()], json!({
+                "200": response("Task cancelled", schema("CancelResponse")),
+                "403": error("Token lacks the cancel_task permission"),
+                "404": error("Task not found"),
+                "409": error("Task already finished"),
+            })),
+        },
+        "/api/v1/arrays": {
+            "post": with_body(
+                operation("createArray", "arrays", "Expand a command template into an array of tasks", vec![], json!({
+                    "201": response("Array queued", schema("ArrayResponse")),
+                    "400": error("Invalid count or params"),
+                    "403": error("Token lacks the create_task permission, or a referenced secret is not available"),
+                    "422": error("A task was rejected by the scheduler"),
+                })),
+                schema("ArrayTaskRequest"),
+            ),
+        },
+        "/api/v1/arrays/{id}": {
+            "get": operation("getArray", "arrays", "Aggregate status of an array", vec![array_id()], json!({
+                "200": response("Task counts per status", schema("ArrayStatus")),
+                "404": error("Array not found"),
+            })),
+            "delete": operation("cancelArray", "arrays", "Cancel every unfinished task of an array", vec![array_id()], json!({
+                "200": response("Cancelled tasks", schema("ArrayCancelResponse")),
+                "403": error("Token lacks the cancel_task permission"),
+                "404": error("Array not found"),
+            })),
+        },
+        "/api/v1/workers": {
+            "get": operation("listWorkers", "workers", "Registered workers", vec![], json!({
+                "200": response("Workers", array_of(schema("WorkerInfo"))),
+            })),
+        },
+        "/api/v1/workers/{id}/cordon": {
+            "post": operation("cordonWorker", "workers", "Stop scheduling new tasks on a worker", vec![worker_id()], json!({
+                "200": { "description": "Worker cordoned" },
+                "403": error("Token lacks the manage_workers permission"),
+                "404": error("Worker not found"),
+            })),
+        },
+        "/api/v1/workers/{id}/uncordon": {
+            "post": operation("uncordonWorker", "workers", "Put a worker back into rotation", vec![worker_id()], json!({
+                "200": { "description": "Worker active" },
+                "403": error("Token lacks the manage_workers permission"),
+                "404": error("Worker not found"),
+            })),
+        },
+        "/api/v1/workers/{id}/drain": {
+            "post": {
+                "operationId": "drainWorker",
+                "tags": ["workers"],
+                "summary": "Cordon a worker and wait for or migrate its running tasks",
+                "parameters": [worker_id()],
+                "requestBody": { "required": false, "content": { "application/json": { "schema": schema("DrainRequest") } } },
+                "responses": {
+                    "200": response("Drain started", schema("DrainResponse")),
+                    "403": error("Token lacks the manage_workers permission"),
+                    "404": error("Worker not found"),
+                },
+            },
+        },
+        "/api/v1/webhooks": {
+            "post": with_body(
+                operation("createWebhook", "webhooks", "Subscribe a URL to cluster events", vec![], json!({
+                    "201": response("Subscription, including its secret", schema("WebhookResponse")),
+                    "400": error("Invalid url or event type"),
+                    "403": error("Token lacks the manage_webhooks permission"),
+                })),
+                schema("CreateWebhookRequest"),
+            ),
+            "get": operation("listWebhooks", "webhooks", "Webhook subscriptions", vec![], json!({
+                "200": response("Subscriptions, without secrets", array_of(schema("WebhookResponse"))),
+                "403": error("Token lacks the manage_webhooks permission"),
+            })),
+        },
+        "/api/v1/webhooks/dead-letters": {
+            "get": operation("listDeadLetters", "webhooks", "Deliveries that ran out of retries", delivery_params(), json!({
+                "200": response("Dead deliveries, newest first", array_of(schema("WebhookDelivery"))),
+                "403": error("Token lacks the manage_webhooks permission"),
+            })),
+        },
+        "/api/v1/webhooks/{id}": {
+            "delete": operation("deleteWebhook", "webhooks", "Remove a subscription and its delivery log", vec![webhook_id()], json!({
+                "204": { "description": "Removed" },
+                "403": error("Token lacks the manage_webhooks permission"),
+                "404": error("Webhook not found"),
+            })),
+        },
+        "/api/v1/webhooks/{id}/deliveries": {
+            "get": operation("listWebhookDeliveries", "webhooks", "Delivery log of one webhook", {
+                let mut parameters = vec![webhook_id()];
+                parameters.extend(delivery_params());
+                parameters
+            }, json!({
+                "200": response("Deliveries, newest first", array_of(schema("WebhookDelivery"))),
+                "403": error("Token lacks the manage_webhooks permission"),
+            })),
+        },
+        "/api/v1/secrets": {
+            "get": operation("listSecrets", "secrets", "Secret names and projects, never values", vec![], json!({
+                "200": response("Secrets by name", array_of(schema("SecretResponse"))),
+                "403": error("Token lacks the manage_secrets permission"),
+                "503": error("Secrets are not enabled, or security.require_auth is off"),
+            })),
+        },
+        "/api/v1/secrets/{name}": {
+            "put": with_body(
+                operation("putSecret", "secrets", "Create or replace a secret", vec![secret_name()], json!({
+                    "200": response("Stored secret, without its value", schema("SecretResponse")),
+                    "400": error("Invalid name or no project"),
+                    "403": error("Token lacks the manage_secrets permission"),
+                    "503": error("Secrets are not enabled, or security.require_auth is off"),
+                })),
+                schema("PutSecretRequest"),
+            ),
+            "delete": operation("deleteSecret", "secrets", "Remove a secret", vec![secret_name()], json!({
+                "204": { "description": "Removed" },
+                "404": error("Secret not found"),
+                "503": error("Secrets are not enabled, or security.require_auth is off"),
+            })),
+        },
+        "/api/v1/stats": {
+            "get": operation("getStats", "system", "Queue, worker and fair-share statistics", vec![], json!({
+                "200": response("Statistics", schema("StatsResponse")),
+            })),
+        },
+        "/api/v1/admin/reload": {
+            "post": operation("reloadConfig", "system", "Re-read the configuration files, like SIGHUP", vec![], json!({
+                "200": response("What changed", schema("ReloadReport")),
+                "400": error("Invalid configuration; the running one is kept"),
+                "403": error("Token lacks the system_config permission"),
+            })),
+        },
+        "/api/v1/events": {
+            "get": operation("streamEvents", "events", "Server-Sent Events stream of cluster activity", vec![
+                query_param("types", "Comma-separated event types", string()),
+                query_param("task", "Only events about this task", string()),
+                query_param("last_event_id", "Resume after this event; the Last-Event-ID header takes precedence", integer()),
+            ], json!({
+                "200": {
+                    "description": "One SSE message per event; `event` is the type, `id` the event id and `data` an EventRecord",
+                    "content": { "text/event-stream": { "schema": schema("EventRecord") } },
+                },
+                "400": error("Unknown event type or invalid Last-Event-ID"),
+            })),
+        },
+    });
+    // Kept apart: one json! literal this size exceeds the macro recursion limit
+    paths["/api/v1/tokens"] = json!({
+        "post": with_body(
+            operation("issueToken", "tokens", "Sign a new API token", vec![], json!({
+                "201": response("The token and the id to revoke it by", schema("TokenResponse")),
+                "400": error("Unknown role or invalid lifetime"),
+                "403": error("Token lacks the manage_users permission"),
+            })),
+            schema("IssueTokenRequest"),
+        ),
+    });
+    paths["/api/v1/tokens/{id}"] = json!({
+        "delete": operation("revokeToken", "tokens", "Refuse a token from now on", vec![path_param("id", "Token id")], json!({
+            "204": { "description": "Revoked" },
+            "403": error("Token lacks the manage_users permission"),
+        })),
+    });
+    paths["/api/v1/audit"] = json!({
+        "get": operation("listAudit", "system", "Audit records, newest first", vec![
+            query_param("action", "Exact action, or a prefix ending in '.' such as task.", string()),
+            query_param("actor", "Only actions by this token subject or worker", string()),
+            query_param("source_ip", "Only actions from this address", string()),
+            query_param("allowed", "Only allowed (true) or refused (false) attempts", boolean()),
+            query_param("since", "RFC 3339 UTC, inclusive", json!({ "type": "string", "format": "date-time" })),
+            query_param("until", "RFC 3339 UTC, inclusive", json!({ "type": "string", "format": "date-time" })),
+            query_param("before", "Only records older than this sequence number (next_before of the previous page)", integer()),
+            query_param("limit", "Page size (default 100, max 1000)", integer()),
+        ], json!({
+            "200": response("Page of records", schema("AuditPage")),
+            "403": error("Token lacks the view_logs permission"),
+        })),
+    });
+    paths
+}
+
+fn schemas() -> Value {
+    let labels = || map_of(string());
+    let create_task = json!({
+        "command": string(),
+        "timeout": { "type": "integer", "minimum": 0, "description": "Seconds" },
+        "inputs": array_of(string()),
+        "outputs": array_of(string()),
+        "owner": string(),
+        "resources": schema("Resources"),
+        "placement": schema("Placement"),
+        "max_retries": integer(),
+        "labels": labels(),
+        "env": {
+            "type": "object",
+            "additionalProperties": string(),
+            "description": "A value secret://NAME is replaced by that secret on the worker",
+        },
+        "priority": { "type": "integer", "description": "Higher runs earlier among the owner's queued tasks" },
+    });
+    let mut array_task = create_task.clone();
+    array_task["count"] = integer();
+    array_task["params"] = array_of(string());
+
+    let mut schemas = json!({
+        "CreateTaskRequest": object(&["command"], create_task),
+        "BatchTaskRequest": object(&["tasks"], json!({ "tasks": array_of(schema("CreateTaskRequest")) })),
+        "ArrayTaskRequest": {
+            "type": "object",
+            "required": ["command"],
+            "description": "`{index}` in the command becomes the task's position and `{param}` the matching entry of params; give either count or params",
+            "properties": array_task,
+        },
+        "Resources": {
+            "type": "object",
+            "properties": {
+                "cpu_millicores": integer(),
+                "memory_mb": integer(),
+                "disk_mb": integer(),
+                "gpus": integer(),
+                "custom": map_of(integer()),
+            },
+        },
+        "Placement": {
+            "type": "object",
+            "properties": {
+                "node_selector": labels(),
+                "preferred": array_of(object(&["key", "value"], json!({
+                    "key": string(),
+                    "value": string(),
+                    "weight": integer(),
+                }))),
+                "anti_affinity": nullable_string(),
+            },
+        },
+        "TaskResponse": object(&["id", "command", "status", "created_at"], json!({
+            "id": string(),
+            "command": string(),
+            "status": string(),
+            "created_at": string(),
+        })),
+        "TaskDetails": object(&["id", "command", "status", "stdout", "stderr", "duration_ms", "created_at"], json!({
+            "id": string(),
+            "command": string(),
+            "status": schema("TaskState"),
+            "worker_id": nullable_string(),
+            "stdout": string(),
+            "stderr": string(),
+            "exit_code": { "type": "integer", "nullable": true },
+            "duration_ms": integer(),
+            "created_at": string(),
+            "completed_at": nullable_string(),
+            "owner": string(),
+            "labels": labels(),
+            "parent_id": nullable_string(),
+        })),
+        "TaskSummary": object(&["id", "command", "status", "created_at"], json!({
+            "id": string(),
+            "command": string(),
+            "status": schema("TaskState"),
+            "worker_id": nullable_string(),
+            "owner": string(),
+            "labels": labels(),
+            "created_at": string(),
+            "completed_at": nullable_string(),
+        })),
+        "TaskState": {
+            "type": "string",
+            "enum": ["Pending", "Assigned", "Running", "Completed", "Failed", "Cancelled", "TimedOut", "Lost"],
+        },
+        "TaskList": object(&["tasks"], json!({
+            "tasks": array_of(schema("TaskSummary")),
+            "next_cursor": nullable_string(),
+        })),
+        "CancelResponse": object(&["id", "status"], json!({ "id": string(), "status": string() })),
+        "BatchResponse": object(&["task_ids"], json!({ "task_ids": array_of(string()) })),
+        "ArrayResponse": object(&["array_id", "task_ids"], json!({
+            "array_id": string(),
+            "task_ids": array_of(string()),
+        })),
+        "ArrayStatus": object(&["array_id", "total", "counts", "finished"], json!({
+            "array_id": string(),
+            "total": integer(),
+            "counts": map_of(integer()),
+            "finished": boolean(),
+        })),
+        "ArrayCancelResponse": object(&["array_id", "cancelled"], json!({
+            "array_id": string(),
+            "cancelled": array_of(string()),
+        })),
+        "WorkerInfo": object(&["id", "name", "address", "port", "max_jobs", "current_jobs"], json!({
+            "id": string(),
+            "name": string(),
+            "address": string(),
+            "port": integer(),
+            "max_jobs": integer(),
+            "current_jobs": integer(),
+            "allow_shell": boolean(),
+            "last_heartbeat": { "type": "integer" },
+            "platform": string(),
+            "capacity": schema("Resources"),
+            "allocated": schema("Resources"),
+            "labels": labels(),
+            "affinity_groups": array_of(string()),
+            "availability": { "type": "string", "enum": ["active", "cordoned", "draining"] },
+        })),
+        "DrainMode": { "type": "string", "enum": ["wait", "migrate"] },
+        "DrainRequest": { "type": "object", "properties": { "mode": schema("DrainMode") } },
+        "DrainResponse": object(&["worker_id", "mode", "migrated_tasks", "drained"], json!({
+            "worker_id": string(),
+            "mode": schema("DrainMode"),
+            "migrated_tasks": array_of(string()),
+            "drained": boolean(),
+        })),
+        "OwnerShare": object(&["owner", "weight", "queued", "share", "usage_secs", "usage_share", "fair_factor"], json!({
+            "owner": string(),
+            "weight": number(),
+            "queued": integer(),
+            "share": number(),
+            "usage_secs": number(),
+            "usage_share": number(),
+            "fair_factor": number(),
+        })),
+        "StatsResponse": object(&[
+            "workers_count", "idle_workers", "cordoned_workers", "task_queue",
+            "total_tasks", "completed_tasks", "failed_tasks", "fair_share",
+        ], json!({
+            "workers_count": integer(),
+            "idle_workers": integer(),
+            "cordoned_workers": integer(),
+            "task_queue": integer(),
+            "total_tasks": integer(),
+            "completed_tasks": integer(),
+            "failed_tasks": integer(),
+            "fair_share": array_of(schema("OwnerShare")),
+        })),
+        "ReloadReport": object(&["applied", "restart_required"], json!({
+            "applied": array_of(string()),
+            "restart_required": array_of(string()),
+        })),
+        "HealthResponse": object(&["status", "version"], json!({ "status": string(), "version": string() })),
+        "EventType": { "type": "string", "enum": Event::KINDS },
+        "CreateWebhookRequest": object(&["url"], json!({
+            "url": { "type": "string", "format": "uri" },
+            "events": array_of(schema("EventType")),
+            "task_id": string(),
+            "owner": string(),
+            "secret": { "type": "string", "description": "Signing key; generated if not given" },
+        })),
+        "WebhookResponse": object(&["id", "url", "events", "created_at"], json!({
+            "id": string(),
+            "url": string(),
+            "events": array_of(schema("EventType")),
+            "task_id": nullable_string(),
+            "owner": nullable_string(),
+            "created_at": string(),
+            "secret": { "type": "string", "description": "Only present in the creation response" },
+        })),
+        "DeliveryStatus": { "type": "string", "enum": ["pending", "delivered", "dead"] },
+        "WebhookDelivery": object(&["id", "webhook_id", "event_id", "event_type", "payload", "status", "attempts"], json!({
+            "id": string(),
+            "webhook_id": string(),
+            "event_id": integer(),
+            "event_type": schema("EventType"),
+            "payload": string(),
+            "status": schema("DeliveryStatus"),
+            "attempts": integer(),
+            "response_status": { "type": "integer", "nullable": true },
+            "last_error": nullable_string(),
+            "created_at": string(),
+            "updated_at": string(),
+        })),
+        "EventRecord": {
+            "type": "object",
+            "required": ["id", "timestamp", "type"],
+            "description": "Event fields (task_id, worker_id, status, ...) depend on its type and sit next to these",
+            "additionalProperties": true,
+            "properties": {
+                "id": integer(),
+                "timestamp": { "type": "integer", "description": "Unix seconds" },
+                "type": schema("EventType"),
+            },
+        },
+    });
+    // Kept apart: one json! literal this size exceeds the macro recursion limit
+    schemas["PutSecretRequest"] = object(&["value", "projects"], json!({
+        "value": string(),
+        "projects": { "type": "array", "items": string(), "description": "Task owners whose tasks may use it" },
+    }));
+    schemas["SecretResponse"] = object(&["name", "projects", "created_at", "updated_at"], json!({
+        "name": string(),
+        "projects": array_of(string()),
+        "created_at": string(),
+        "updated_at": string(),
+    }));
+    schemas["IssueTokenRequest"] = object(&["subject"], json!({
+        "subject": string(),
+        "role": { "type": "string", "enum": ["admin", "dispatcher", "worker", "client"], "default": "client" },
+        "projects": { "type": "array", "items": string(), "description": "Projects whose secrets the token may use" },
+        "hours": { "type": "integer", "minimum": 1, "default": 24 },
+    }));
+    schemas["TokenResponse"] = object(&["id", "token", "subject", "role", "expires_at"], json!({
+        "id": string(),
+        "token": string(),
+        "subject": string(),
+        "role": string(),
+        "expires_at": { "type": "integer", "description": "Unix seconds" },
+    }));
+    schemas["AuditRecord"] = object(
+        &["seq", "timestamp", "action", "actor", "subject", "allowed", "prev_hash", "hash"],
+        json!({
+            "seq": integer(),
+            "timestamp": string(),
+            "action": { "type": "string", "description": "e.g. auth.login, token.issue, task.submit, worker.evict" },
+            "actor": { "type": "string", "description": "Token subject, worker id, or dispatcher" },
+            "subject": string(),
+            "source_ip": nullable_string(),
+            "allowed": boolean(),
+            "reason": nullable_string(),
+            "prev_hash": string(),
+            "hash": { "type": "string", "description": "Hex SHA-256 chaining this record to prev_hash" },
+        }),
+    );
+    schemas["AuditPage"] = object(&["records"], json!({
+        "records": array_of(schema("AuditRecord")),
+        "next_before": { "type": "integer", "nullable": true },
+    }));
+    schemas
+}
+
+/// The complete OpenAPI document
+pub fn document() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Octaskly API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Task submission, monitoring and cluster management for an Octaskly dispatcher",
+        },
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" },
+            },
+        },
+        "paths": paths(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(target)) = map.get("$ref") {
+                    refs.push(target.clone());
+                }
+                map.values().for_each(|v| collect_refs(v, refs));
+            }
+            Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_document_references_resolve() {
+        let doc = document();
+        let mut refs = Vec::new();
+        collect_refs(&doc, &mut refs);
+        assert!(!refs.is_empty());
+        for target in refs {
+            let name = target.strip_prefix("#/components/schemas/").unwrap();
+            assert!(doc["components"]["schemas"].get(name).is_some(), "unresolved {}", target);
+        }
+        assert!(doc["paths"]["/api/v1/tasks/{id}"]["delete"].is_object());
+
+        // Every response type round-trips through its documented required fields
+        let details = serde_json::to_value(crate::api::TaskDetails::from(crate::persistence::StoredTask {
+            id: "t1".to_string(),
+            command: "echo".to_string(),
+            status: "Completed".to_string(),
+            worker_id: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            exit_code: Some(0),
+            duration_ms: 5,
+            created_at: String::new(),
+            completed_at: None,
+            owner: String::new(),
+            labels: Default::default(),
+            parent_id: None,
+        }))
+        .unwrap();
+        for field in doc["components"]["schemas"]["TaskDetails"]["required"].as_array().unwrap() {
+            assert!(details.get(field.as_str().unwrap()).is_some(), "missing {}", field);
+        }
+        let entry = crate::audit::AuditEntry::new("task.submit", "alice", true);
+        let record = serde_json::to_value(crate::audit::AuditRecord::chain(1, entry, crate::audit::GENESIS_HASH)).unwrap();
+        for field in doc["components"]["schemas"]["AuditRecord"]["required"].as_array().unwrap() {
+            assert!(record.get(field.as_str().unwrap()).is_some(), "missing {}", field);
+        }
+    }
+}
@@ -0,0 +1,235 @@
+// Response bodies of the REST API, shared by the server and the client
+// Badan respons REST API, dipakai bersama oleh server dan klien
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::audit::AuditRecord;
+use crate::persistence::{StoredSecret, StoredTask, Webhook};
+use crate::scheduler::{DrainMode, OwnerShare};
+
+/// Statuses a task can still leave
+pub const UNFINISHED_STATUSES: &[&str] = &["Pending", "Assigned", "Running"];
+
+/// Whether a stored task status is terminal
+pub fn is_finished(status: &str) -> bool {
+    !UNFINISHED_STATUSES.contains(&status)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskResponse {
+    pub id: String,
+    pub command: String,
+    pub status: String,
+    pub created_at: String,
+}
+
+/// A task with its result, as returned by `GET /api/v1/tasks/:id`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskDetails {
+    pub id: String,
+    pub command: String,
+    pub status: String,
+    pub worker_id: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    pub created_at: String,
+    pub completed_at: Option<String>,
+    #[serde(default)]
+    pub owner: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub parent_id: Option<String>,
+}
+
+impl TaskDetails {
+    pub fn is_finished(&self) -> bool {
+        is_finished(&self.status)
+    }
+}
+
+impl From<StoredTask> for TaskDetails {
+    fn from(task: StoredTask) -> Self {
+        Self {
+            id: task.id,
+            command: task.command,
+            status: task.status,
+            worker_id: task.worker_id,
+            stdout: task.stdout,
+            stderr: task.stderr,
+            exit_code: task.exit_code,
+            duration_ms: task.duration_ms,
+            created_at: task.created_at,
+            completed_at: task.completed_at,
+            owner: task.owner,
+            labels: task.labels,
+            parent_id: task.parent_id,
+        }
+    }
+}
+
+/// A task as shown in listings, without its output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskSummary {
+    pub id: String,
+    pub command: String,
+    pub status: String,
+    pub worker_id: Option<String>,
+    #[serde(default)]
+    pub owner: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    pub created_at: String,
+    pub completed_at: Option<String>,
+}
+
+impl From<StoredTask> for TaskSummary {
+    fn from(task: StoredTask) -> Self {
+        Self {
+            id: task.id,
+            command: task.command,
+            status: task.status,
+            worker_id: task.worker_id,
+            owner: task.owner,
+            labels: task.labels,
+            created_at: task.created_at,
+            completed_at: task.completed_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskList {
+    pub tasks: Vec<TaskSummary>,
+    /// Pass back as `cursor` to get the next page; None on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelResponse {
+    pub id: String,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResponse {
+    pub task_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArrayResponse {
+    pub array_id: String,
+    pub task_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArrayStatus {
+    pub array_id: String,
+    pub total: usize,
+    /// Number of tasks per status
+    pub counts: HashMap<String, usize>,
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArrayCancelResponse {
+    pub array_id: String,
+    /// Tasks that were still unfinished and are now cancelled
+    pub cancelled: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainResponse {
+    pub worker_id: String,
+    pub mode: DrainMode,
+    pub migrated_tasks: Vec<String>,
+    /// True once the worker has no running tasks left
+    pub drained: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsResponse {
+    pub workers_count: usize,
+    pub idle_workers: usize,
+    pub cordoned_workers: usize,
+    pub task_queue: usize,
+    pub total_tasks: usize,
+    pub completed_tasks: usize,
+    pub failed_tasks: usize,
+    pub fair_share: Vec<OwnerShare>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthResponse {
+    pub status: String,
+    pub version: String,
+}
+
+/// Webhook as shown by the API; the secret is only returned on creation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookResponse {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    pub task_id: Option<String>,
+    pub owner: Option<String>,
+    pub created_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+impl From<&Webhook> for WebhookResponse {
+    fn from(webhook: &Webhook) -> Self {
+        Self {
+            id: webhook.id.clone(),
+            url: webhook.url.clone(),
+            events: webhook.events.clone(),
+            task_id: webhook.task_id.clone(),
+            owner: webhook.owner.clone(),
+            created_at: webhook.created_at.clone(),
+            secret: None,
+        }
+    }
+}
+
+/// Secret as listed by the API; the value is never returned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretResponse {
+    pub name: String,
+    pub projects: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<&StoredSecret> for SecretResponse {
+    fn from(secret: &StoredSecret) -> Self {
+        Self {
+            name: secret.name.clone(),
+            projects: secret.projects.clone(),
+            created_at: secret.created_at.clone(),
+            updated_at: secret.updated_at.clone(),
+        }
+    }
+}
+
+/// Token made by `POST /api/v1/tokens`; revoke it by `id`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub id: String,
+    pub token: String,
+    pub subject: String,
+    pub role: String,
+    /// Unix time the token stops being accepted
+    pub expires_at: i64,
+}
+
+/// Page of audit records, newest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditPage {
+    pub records: Vec<AuditRecord>,
+    /// `before` value of the next (older) page, if there may be one
+    pub next_before: Option<u64>,
+}
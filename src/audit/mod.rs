@@ -0,0 +1,492 @@
+// Tamper-evident audit log of security-relevant actions
+// Log audit tahan rusak untuk tindakan yang berkaitan dengan keamanan
+//
+// Every entry is stored in the dispatcher database as a record chained to the
+// one before it: its hash covers its sequence number, its content and the
+// previous record's hash. Editing or deleting a row breaks the chain, which
+// `octaskly audit verify` detects offline. Records are also appended to
+// `audit.log` (one JSON object per line) for log shippers, and mirrored to
+// tracing under the `audit` target. An `audit.log` from before records were
+// chained is renamed to `audit.log.legacy` rather than appended to.
+//
+// Setiap entri disimpan di database dispatcher sebagai rekaman yang terantai ke
+// rekaman sebelumnya: hash-nya mencakup nomor urut, isi dan hash rekaman
+// sebelumnya. Mengubah atau menghapus baris memutus rantai, yang dideteksi
+// secara offline oleh `octaskly audit verify`. Rekaman juga ditambahkan ke
+// `audit.log` (satu objek JSON per baris) untuk pengirim log, dan dicerminkan
+// ke tracing dengan target `audit`. `audit.log` dari sebelum rekaman dirantai
+// diganti namanya menjadi `audit.log.legacy` alih-alih ditambahkan.
+
+use crate::persistence::TaskStore;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// `prev_hash` of the first record
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Actor of decisions the dispatcher takes on its own (timeouts, signals)
+pub const SYSTEM_ACTOR: &str = "dispatcher";
+
+/// One audited action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// RFC 3339 time of the action
+    pub timestamp: String,
+    /// What was attempted, e.g. `worker.register`
+    pub action: String,
+    /// Who attempted it: token subject, worker, or `dispatcher`
+    pub actor: String,
+    /// What it was done to (task id, secret name, ...), if anything
+    pub subject: String,
+    /// Address the attempt came from, if it came over the network
+    pub source_ip: Option<String>,
+    pub allowed: bool,
+    pub reason: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(action: &str, actor: &str, allowed: bool) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            action: action.to_string(),
+            actor: actor.to_string(),
+            subject: String::new(),
+            source_ip: None,
+            allowed,
+            reason: None,
+        }
+    }
+
+    pub fn denied(action: &str, actor: &str, reason: impl Into<String>) -> Self {
+        Self::new(action, actor, false).with_reason(reason)
+    }
+
+    pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = subject.into();
+        self
+    }
+
+    pub fn with_source(mut self, ip: IpAddr) -> Self {
+        self.source_ip = Some(ip.to_string());
+        self
+    }
+
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+}
+
+/// Entry as stored: numbered and chained to the previous record
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// 1 for the first record, then consecutive
+    pub seq: u64,
+    #[serde(flatten)]
+    pub entry: AuditEntry,
+    pub prev_hash: String,
+    /// Hex SHA-256 of the previous hash, `seq` and the entry
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// Record `seq` following the record whose hash is `prev_hash`
+    pub fn chain(seq: u64, entry: AuditEntry, prev_hash: &str) -> Self {
+        let hash = record_hash(seq, &entry, prev_hash);
+        Self {
+            seq,
+            entry,
+            prev_hash: prev_hash.to_string(),
+            hash,
+        }
+    }
+}
+
+fn record_hash(seq: u64, entry: &AuditEntry, prev_hash: &str) -> String {
+    // Struct fields serialize in declaration order, so the JSON is stable
+    // Field struct diserialisasi sesuai urutan deklarasi, jadi JSON-nya stabil
+    let body = serde_json::to_vec(entry).unwrap_or_default();
+    Sha256::new()
+        .chain_update(prev_hash.as_bytes())
+        .chain_update(seq.to_be_bytes())
+        .chain_update(body)
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Filters for audit listings; unset filters match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    /// Exact action, or a prefix ending in `.` such as `task.`
+    pub action: Option<String>,
+    pub actor: Option<String>,
+    pub source_ip: Option<String>,
+    pub allowed: Option<bool>,
+    /// RFC 3339 UTC times, inclusive
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Only records older than this sequence number (paging cursor)
+    pub before: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        let entry = &record.entry;
+        let action = match self.action.as_deref() {
+            Some(prefix) if prefix.ends_with('.') => entry.action.starts_with(prefix),
+            Some(action) => entry.action == action,
+            None => true,
+        };
+        action
+            && self.actor.as_ref().is_none_or(|actor| &entry.actor == actor)
+            && self.source_ip.as_ref().is_none_or(|ip| entry.source_ip.as_ref() == Some(ip))
+            && self.allowed.is_none_or(|allowed| entry.allowed == allowed)
+            && self.since.as_ref().is_none_or(|since| &entry.timestamp >= since)
+            && self.until.as_ref().is_none_or(|until| &entry.timestamp <= until)
+            && self.before.is_none_or(|before| record.seq < before)
+    }
+}
+
+/// Where a chain check failed
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("record {seq}: {reason}")]
+pub struct ChainBreak {
+    pub seq: u64,
+    pub reason: String,
+}
+
+/// Walks records oldest first, checking each one against the last
+#[derive(Debug)]
+pub struct ChainVerifier {
+    count: u64,
+    last_seq: u64,
+    head: String,
+}
+
+impl Default for ChainVerifier {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            last_seq: 0,
+            head: GENESIS_HASH.to_string(),
+        }
+    }
+}
+
+impl ChainVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, record: &AuditRecord) -> Result<(), ChainBreak> {
+        let broken = |reason: String| ChainBreak { seq: record.seq, reason };
+        if record.seq != self.last_seq + 1 {
+            return Err(broken(format!(
+                "expected record {} next, records are missing",
+                self.last_seq + 1
+            )));
+        }
+        if record.prev_hash != self.head {
+            return Err(broken("does not follow the previous record".to_string()));
+        }
+        if record_hash(record.seq, &record.entry, &record.prev_hash) != record.hash {
+            return Err(broken("content does not match its hash, it was modified".to_string()));
+        }
+        self.count += 1;
+        self.last_seq = record.seq;
+        self.head = record.hash.clone();
+        Ok(())
+    }
+
+    /// Records checked so far
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Hash of the last record checked; keep it elsewhere to detect truncation
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+}
+
+/// Check every record of a store's audit chain; returns the verifier at the head
+pub async fn verify_store(store: &dyn TaskStore) -> Result<Result<ChainVerifier, ChainBreak>> {
+    const PAGE: usize = 1000;
+    let mut verifier = ChainVerifier::new();
+    loop {
+        let records = store.audit_after(verifier.last_seq, PAGE).await?;
+        for record in &records {
+            if let Err(e) = verifier.push(record) {
+                return Ok(Err(e));
+            }
+        }
+        if records.len() < PAGE {
+            return Ok(Ok(verifier));
+        }
+    }
+}
+
+#[derive(Default)]
+struct Sink {
+    store: Option<Arc<dyn TaskStore>>,
+    file: Option<tokio::fs::File>,
+    last_seq: u64,
+    head: String,
+}
+
+/// Append-only audit log; without a store entries only go to tracing
+#[derive(Default)]
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    sink: Mutex<Sink>,
+}
+
+impl AuditLog {
+    /// Log to tracing only
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Chain records in `store`, continuing from its last one, and append
+    /// them to the file at `path`, creating it if needed
+    pub async fn open(path: &Path, store: Arc<dyn TaskStore>) -> Result<Self> {
+        set_aside_legacy(path).await?;
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .with_context(|| format!("cannot open audit log {}", path.display()))?;
+        let (last_seq, head) = match store.last_audit().await? {
+            Some(record) => (record.seq, record.hash),
+            None => (0, GENESIS_HASH.to_string()),
+        };
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            sink: Mutex::new(Sink {
+                store: Some(store),
+                file: Some(file),
+                last_seq,
+                head,
+            }),
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Record an entry; write failures are logged, never returned
+    pub async fn record(&self, entry: AuditEntry) {
+        let source = entry.source_ip.as_deref().unwrap_or("-");
+        let reason = entry.reason.as_deref().unwrap_or("-");
+        if entry.allowed {
+            info!(target: "audit", "{} {} allowed for {} from {}", entry.action, entry.subject, entry.actor, source);
+        } else {
+            warn!(
+                target: "audit",
+                "{} {} denied for {} from {}: {}", entry.action, entry.subject, entry.actor, source, reason
+            );
+        }
+
+        // One record at a time, so sequence numbers and hashes stay in order
+        // Satu rekaman pada satu waktu, agar nomor urut dan hash tetap berurutan
+        let mut sink = self.sink.lock().await;
+        let Some(store) = sink.store.clone() else {
+            return;
+        };
+        let mut record = AuditRecord::chain(sink.last_seq + 1, entry.clone(), &sink.head);
+        if let Err(e) = store.append_audit(&record).await {
+            // The store may be ahead of this log (a write that failed after it
+            // committed, another dispatcher on the database), so continue from
+            // its last record instead of retrying a taken sequence number forever
+            // Store mungkin mendahului log ini (penulisan yang gagal setelah
+            // di-commit, dispatcher lain pada database), jadi lanjutkan dari
+            // rekaman terakhirnya alih-alih mengulang nomor urut yang sudah terpakai
+            warn!("Failed to store audit record {}, reloading the chain head: {:#}", record.seq, e);
+            match store.last_audit().await {
+                Ok(last) => {
+                    (sink.last_seq, sink.head) = match last {
+                        Some(last) => (last.seq, last.hash),
+                        None => (0, GENESIS_HASH.to_string()),
+                    };
+                }
+                Err(e) => {
+                    warn!("Failed to read the audit chain head: {:#}", e);
+                    return;
+                }
+            }
+            record = AuditRecord::chain(sink.last_seq + 1, entry, &sink.head);
+            if let Err(e) = store.append_audit(&record).await {
+                warn!("Failed to store audit record: {:#}", e);
+                return;
+            }
+        }
+        sink.last_seq = record.seq;
+        sink.head = record.hash.clone();
+
+        if let Some(file) = sink.file.as_mut() {
+            let mut line = match serde_json::to_string(&record) {
+                Ok(line) => line,
+                Err(e) => {
+                    warn!("Failed to encode audit record: {}", e);
+                    return;
+                }
+            };
+            line.push('\n');
+            if let Err(e) = file.write_all(line.as_bytes()).await {
+                warn!("Failed to write audit log: {}", e);
+            } else if let Err(e) = file.flush().await {
+                warn!("Failed to flush audit log: {}", e);
+            }
+        }
+    }
+}
+
+// Rename an audit log whose lines are not chained records (written by an
+// older version) to `<path>.legacy`, so one file never mixes both formats
+// Ganti nama log audit yang barisnya bukan rekaman berantai (ditulis versi
+// lama) menjadi `<path>.legacy`, agar satu berkas tidak mencampur kedua format
+async fn set_aside_legacy(path: &Path) -> Result<()> {
+    let file = match tokio::fs::File::open(path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("cannot open audit log {}", path.display())),
+    };
+    let legacy = match tokio::io::BufReader::new(file).lines().next_line().await {
+        Ok(Some(line)) => serde_json::from_str::<AuditRecord>(&line).is_err(),
+        Ok(None) => false,
+        Err(_) => true,
+    };
+    if !legacy {
+        return Ok(());
+    }
+    let mut target = path.as_os_str().to_owned();
+    target.push(".legacy");
+    if tokio::fs::try_exists(&target).await.unwrap_or(true) {
+        target.push(format!(".{}", chrono::Utc::now().format("%Y%m%d%H%M%S")));
+    }
+    tokio::fs::rename(path, &target)
+        .await
+        .with_context(|| format!("cannot move old audit log {}", path.display()))?;
+    warn!(
+        "Audit log {} is in an older format; moved it to {}",
+        path.display(),
+        Path::new(&target).display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::MemoryStore;
+
+    #[tokio::test]
+    async fn test_records_are_chained_across_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let store: Arc<dyn TaskStore> = Arc::new(MemoryStore::new());
+        let log = AuditLog::open(&path, store.clone()).await.unwrap();
+        log.record(
+            AuditEntry::denied("worker.register", "w1", "bad key").with_source("10.0.0.5".parse().unwrap()),
+        )
+        .await;
+        drop(log);
+
+        // Reopening continues the chain and appends to the file
+        let log = AuditLog::open(&path, store.clone()).await.unwrap();
+        log.record(AuditEntry::new("task.submit", "alice", true).with_subject("t-1")).await;
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<AuditRecord> = text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].entry.source_ip.as_deref(), Some("10.0.0.5"));
+        assert_eq!(lines[0].entry.reason.as_deref(), Some("bad key"));
+        assert_eq!(lines[1].seq, 2);
+        assert_eq!(lines[1].prev_hash, lines[0].hash);
+
+        let verifier = verify_store(store.as_ref()).await.unwrap().unwrap();
+        assert_eq!(verifier.count(), 2);
+        assert_eq!(verifier.head(), lines[1].hash);
+    }
+
+    #[tokio::test]
+    async fn test_chain_continues_after_the_store_moves_ahead() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn TaskStore> = Arc::new(MemoryStore::new());
+        let log = AuditLog::open(&dir.path().join("audit.log"), store.clone()).await.unwrap();
+        log.record(AuditEntry::new("token.issue", "admin", true)).await;
+
+        // Written behind the log's back, e.g. by a write reported as failed
+        let first = store.last_audit().await.unwrap().unwrap();
+        let other = AuditRecord::chain(2, AuditEntry::new("task.cancel", "bob", true), &first.hash);
+        store.append_audit(&other).await.unwrap();
+
+        log.record(AuditEntry::new("task.submit", "alice", true)).await;
+        log.record(AuditEntry::new("task.submit", "carol", true)).await;
+        let verifier = verify_store(store.as_ref()).await.unwrap().unwrap();
+        assert_eq!(verifier.count(), 4);
+        assert_eq!(store.last_audit().await.unwrap().unwrap().entry.actor, "carol");
+    }
+
+    #[tokio::test]
+    async fn test_old_format_log_is_set_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let old = "{\"timestamp\":\"2026-01-01T00:00:00Z\",\"action\":\"worker.register\",\"subject\":\"w1\",\"peer\":null,\"allowed\":true,\"reason\":null}\n";
+        std::fs::write(&path, old).unwrap();
+
+        let store: Arc<dyn TaskStore> = Arc::new(MemoryStore::new());
+        let log = AuditLog::open(&path, store.clone()).await.unwrap();
+        log.record(AuditEntry::new("task.submit", "alice", true)).await;
+        drop(log);
+        assert_eq!(std::fs::read_to_string(dir.path().join("audit.log.legacy")).unwrap(), old);
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 1);
+        assert!(text.lines().all(|l| serde_json::from_str::<AuditRecord>(l).is_ok()));
+
+        // A log already in the chained format is kept
+        AuditLog::open(&path, store).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+    }
+
+    #[test]
+    fn test_verifier_detects_edits_and_gaps() {
+        let first = AuditRecord::chain(1, AuditEntry::new("token.issue", "admin", true), GENESIS_HASH);
+        let second = AuditRecord::chain(2, AuditEntry::new("task.cancel", "bob", true), &first.hash);
+        let third = AuditRecord::chain(3, AuditEntry::new("config.reload", "admin", true), &second.hash);
+
+        let mut verifier = ChainVerifier::new();
+        for record in [&first, &second, &third] {
+            verifier.push(record).unwrap();
+        }
+
+        let mut edited = second.clone();
+        edited.entry.actor = "mallory".to_string();
+        let mut verifier = ChainVerifier::new();
+        verifier.push(&first).unwrap();
+        assert!(verifier.push(&edited).unwrap_err().reason.contains("modified"));
+
+        let mut verifier = ChainVerifier::new();
+        verifier.push(&first).unwrap();
+        assert_eq!(verifier.push(&third).unwrap_err().seq, 3);
+
+        // Renumbering after a deletion does not help: the hash covers `seq`
+        let mut renumbered = third.clone();
+        renumbered.seq = 2;
+        let mut verifier = ChainVerifier::new();
+        verifier.push(&first).unwrap();
+        assert!(verifier.push(&renumbered).is_err());
+    }
+}
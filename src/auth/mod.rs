@@ -1,7 +1,7 @@
 use anyhow::Result;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::Arc;
 use parking_lot::RwLock;
 use chrono::{Utc, Duration};
@@ -13,68 +13,122 @@ pub struct Claims {
     pub iat: i64,              // issued at
     pub role: String,          // role (dispatcher, worker, admin)
     pub permissions: Vec<String>, // specific permissions
+    /// Token id, to revoke it; empty on tokens made before ids existed
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub jti: String,
 }
 
 impl Claims {
     pub fn new(sub: String, role: String, permissions: Vec<String>) -> Self {
+        Self::with_lifetime(sub, role, permissions, Duration::hours(24))
+    }
+
+    pub fn with_lifetime(sub: String, role: String, permissions: Vec<String>, lifetime: Duration) -> Self {
         let now = Utc::now();
-        let exp = now + Duration::hours(24);
-        
+        let exp = now + lifetime;
+
         Self {
             sub,
             exp: exp.timestamp(),
             iat: now.timestamp(),
             role,
             permissions,
+            jti: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+
+    /// Key identifying the token: its id, or subject and issue time for old tokens
+    pub fn token_key(&self) -> String {
+        if self.jti.is_empty() {
+            format!("{}@{}", self.sub, self.iat)
+        } else {
+            self.jti.clone()
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct AuthManager {
-    secret: String,
-    tokens: Arc<RwLock<HashMap<String, Claims>>>,
+    secret: Arc<RwLock<String>>,
+    /// Replaced secrets still accepted until the timestamp, so tokens signed
+    /// before a rotation keep working for a while
+    previous: Arc<RwLock<Vec<(String, i64)>>>,
+    /// Ids of revoked tokens, refused even before they expire
+    revoked: Arc<RwLock<HashSet<String>>>,
+    /// Tokens already used since start, so only their first use is audited
+    seen: Arc<RwLock<HashSet<String>>>,
 }
 
 impl AuthManager {
     pub fn new(secret: String) -> Self {
         Self {
-            secret,
-            tokens: Arc::new(RwLock::new(HashMap::new())),
+            secret: Arc::new(RwLock::new(secret)),
+            previous: Arc::new(RwLock::new(Vec::new())),
+            revoked: Arc::new(RwLock::new(HashSet::new())),
+            seen: Arc::new(RwLock::new(HashSet::new())),
+        }
+    }
+
+    /// Sign new tokens with `secret`; the old one is still accepted for `overlap`
+    pub fn rotate_secret(&self, secret: String, overlap: Duration) {
+        let mut current = self.secret.write();
+        if *current == secret {
+            return;
+        }
+        let now = Utc::now().timestamp();
+        let old = std::mem::replace(&mut *current, secret);
+        let mut previous = self.previous.write();
+        previous.retain(|(_, until)| *until > now);
+        if overlap > Duration::zero() {
+            previous.push((old, now + overlap.num_seconds()));
         }
     }
 
     /// Generate JWT token
     pub fn generate_token(&self, claims: &Claims) -> Result<String> {
-        let encoding_key = EncodingKey::from_secret(self.secret.as_bytes());
+        let encoding_key = EncodingKey::from_secret(self.secret.read().as_bytes());
         let token = encode(&Header::default(), claims, &encoding_key)?;
         Ok(token)
     }
 
     /// Verify and decode JWT token
     pub fn verify_token(&self, token: &str) -> Result<Claims> {
-        let decoding_key = DecodingKey::from_secret(self.secret.as_bytes());
-        let token_data: TokenData<Claims> = decode(
-            token,
-            &decoding_key,
-            &Validation::default(),
-        )?;
+        let decode_with = |secret: &str| -> Result<TokenData<Claims>, jsonwebtoken::errors::Error> {
+            decode(token, &DecodingKey::from_secret(secret.as_bytes()), &Validation::default())
+        };
+        let mut result = decode_with(&self.secret.read());
+        if result.is_err() {
+            let now = Utc::now().timestamp();
+            for (secret, until) in self.previous.read().iter() {
+                if *until > now {
+                    if let Ok(data) = decode_with(secret) {
+                        result = Ok(data);
+                        break;
+                    }
+                }
+            }
+        }
+        let token_data = result.inspect_err(|_| crate::metrics::global().auth_failures.inc())?;
+        if self.is_revoked(&token_data.claims.jti) {
+            crate::metrics::global().auth_failures.inc();
+            anyhow::bail!("token has been revoked");
+        }
         Ok(token_data.claims)
     }
 
-    /// Register a token (for revocation tracking)
-    pub fn register_token(&self, token_id: String, claims: Claims) {
-        self.tokens.write().insert(token_id, claims);
-    }
-
-    /// Revoke a token
+    /// Refuse the token with this id from now on
     pub fn revoke_token(&self, token_id: &str) {
-        self.tokens.write().remove(token_id);
+        self.revoked.write().insert(token_id.to_string());
     }
 
     /// Check if token is revoked
     pub fn is_revoked(&self, token_id: &str) -> bool {
-        !self.tokens.read().contains_key(token_id)
+        !token_id.is_empty() && self.revoked.read().contains(token_id)
+    }
+
+    /// True the first time these claims are seen since start
+    pub fn first_use(&self, claims: &Claims) -> bool {
+        self.seen.write().insert(claims.token_key())
     }
 
     /// Check if claims have permission
@@ -105,6 +159,17 @@ pub enum Role {
 }
 
 impl Role {
+    /// Parse a role name as written by `to_string`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "admin" => Some(Role::Admin),
+            "dispatcher" => Some(Role::Dispatcher),
+            "worker" => Some(Role::Worker),
+            "client" => Some(Role::Client),
+            _ => None,
+        }
+    }
+
     pub fn to_string(&self) -> String {
         match self {
             Role::Admin => "admin".to_string(),
@@ -123,6 +188,8 @@ impl Role {
                 "view_logs".to_string(),
                 "manage_users".to_string(),
                 "system_config".to_string(),
+                "manage_secrets".to_string(),
+                "manage_webhooks".to_string(),
                 "*".to_string(),
             ],
             Role::Dispatcher => vec![
@@ -165,6 +232,37 @@ mod tests {
         assert_eq!(verified.role, "worker");
     }
 
+    #[test]
+    fn test_rotated_secret_overlap() {
+        let manager = AuthManager::new("old-secret".to_string());
+        let claims = Claims::new("user-1".to_string(), "client".to_string(), Vec::new());
+        let old_token = manager.generate_token(&claims).unwrap();
+
+        manager.rotate_secret("new-secret".to_string(), Duration::minutes(5));
+        assert!(manager.verify_token(&old_token).is_ok());
+        let new_token = manager.generate_token(&claims).unwrap();
+        assert!(manager.verify_token(&new_token).is_ok());
+
+        // Without overlap the old secret stops working at once
+        manager.rotate_secret("newest-secret".to_string(), Duration::zero());
+        assert!(manager.verify_token(&new_token).is_err());
+        assert!(manager.verify_token(&old_token).is_ok());
+    }
+
+    #[test]
+    fn test_revoked_token_is_refused() {
+        let manager = AuthManager::new("test-secret".to_string());
+        let claims = Claims::new("user-1".to_string(), "client".to_string(), Vec::new());
+        let token = manager.generate_token(&claims).unwrap();
+        assert!(manager.first_use(&manager.verify_token(&token).unwrap()));
+        assert!(!manager.first_use(&claims));
+
+        manager.revoke_token(&claims.jti);
+        assert!(manager.verify_token(&token).unwrap_err().to_string().contains("revoked"));
+        let other = Claims::new("user-1".to_string(), "client".to_string(), Vec::new());
+        assert!(manager.verify_token(&manager.generate_token(&other).unwrap()).is_ok());
+    }
+
     #[test]
     fn test_role_permissions() {
         let admin_perms = Role::Admin.default_permissions();
@@ -186,5 +284,11 @@ mod tests {
         
         assert!(manager.has_permission(&claims, "manage_users"));
         assert!(manager.has_permission(&claims, "any_permission")); // admin has *
+
+        // Webhooks see every task event, so only admins manage them
+        for role in [Role::Dispatcher, Role::Worker, Role::Client] {
+            let claims = Claims::new("user-2".to_string(), role.to_string(), role.default_permissions());
+            assert!(!manager.has_permission(&claims, "manage_webhooks"));
+        }
     }
 }
@@ -0,0 +1,564 @@
+// Client subcommands of the octaskly binary (submit, status, logs, ...)
+// Subperintah klien dari binary octaskly (submit, status, logs, ...)
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::{Client, LogChunk, LogStream};
+use crate::api::{CreateTaskRequest, ListTasksParams, PutSecretRequest, TaskDetails};
+use crate::audit::AuditQuery;
+use crate::cmd::{ApiArgs, AuditCommand, ClientCommand, OutputFormat, SecretCommand};
+
+/// API used when no url is given, stored or set in the environment
+pub const DEFAULT_API: &str = "http://localhost:3000";
+
+/// Exit code of `wait` when its timeout runs out, as with timeout(1)
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+// Fallback poll interval while waiting, used when the event stream drops
+// Interval polling cadangan saat menunggu, dipakai ketika aliran event terputus
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+const COMMAND_WIDTH: usize = 48;
+
+/// API url and token saved by `octaskly login`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Credentials {
+    pub api: Option<String>,
+    pub token: Option<String>,
+}
+
+impl Credentials {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("octaskly").join("credentials.json"))
+    }
+
+    /// Saved credentials, or empty ones if nothing was saved
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).with_context(|| format!("invalid credentials in {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("cannot read {}", path.display())),
+        }
+    }
+
+    /// Write the credentials, readable by the current user only
+    pub fn save(&self) -> Result<PathBuf> {
+        let path = Self::path().context("no configuration directory for this user")?;
+        self.save_to(&path)?;
+        Ok(path)
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        crate::util::write_private(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    // Flag, then environment, then the saved value
+    // Flag, lalu environment, lalu nilai tersimpan
+    fn resolve(self, api: &ApiArgs) -> Self {
+        Self {
+            api: api.api.clone().or_else(|| std::env::var("OCTASKLY_API").ok()).or(self.api),
+            token: api.token.clone().or_else(|| std::env::var("OCTASKLY_TOKEN").ok()).or(self.token),
+        }
+    }
+}
+
+fn connect(api: &ApiArgs) -> Result<Client> {
+    let credentials = Credentials::load()?.resolve(api);
+    let client = Client::new(credentials.api.as_deref().unwrap_or(DEFAULT_API))?;
+    Ok(match credentials.token {
+        Some(token) => client.with_token(token),
+        None => client,
+    })
+}
+
+/// `octaskly config reload`: ask the dispatcher to re-read its configuration
+pub async fn reload(api: &ApiArgs) -> Result<i32> {
+    let report = connect(api)?.reload_config().await?;
+    match api.format {
+        OutputFormat::Json => print_json(&report)?,
+        OutputFormat::Table => {
+            println!("Applied:          {}", list_or_none(&report.applied));
+            println!("Restart required: {}", list_or_none(&report.restart_required));
+        }
+    }
+    Ok(0)
+}
+
+/// `octaskly secret ...`: manage the dispatcher's task secrets
+pub async fn secret(action: SecretCommand) -> Result<i32> {
+    match action {
+        SecretCommand::Set { api, name, projects } => {
+            // Read from stdin so the value stays out of shell history and `ps`
+            // Dibaca dari stdin agar nilai tidak masuk riwayat shell dan `ps`
+            let mut value = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut value)?;
+            let value = value.strip_suffix('\n').unwrap_or(&value);
+            let value = value.strip_suffix('\r').unwrap_or(value).to_string();
+            let secret = connect(&api)?
+                .put_secret(&name, &PutSecretRequest { value, projects })
+                .await?;
+            match api.format {
+                OutputFormat::Json => print_json(&secret)?,
+                OutputFormat::Table => println!("Stored {} for {}", secret.name, secret.projects.join(", ")),
+            }
+        }
+        SecretCommand::List { api } => {
+            let secrets = connect(&api)?.secrets().await?;
+            match api.format {
+                OutputFormat::Json => print_json(&secrets)?,
+                OutputFormat::Table => print_table(
+                    &["NAME", "PROJECTS", "UPDATED"],
+                    secrets
+                        .into_iter()
+                        .map(|s| vec![s.name, s.projects.join(","), s.updated_at])
+                        .collect(),
+                ),
+            }
+        }
+        SecretCommand::Rm { api, name } => {
+            connect(&api)?.delete_secret(&name).await?;
+            println!("Removed {}", name);
+        }
+    }
+    Ok(0)
+}
+
+/// Run `octaskly audit`; `verify` reads the database directly, no dispatcher needed
+pub async fn audit(action: AuditCommand) -> Result<i32> {
+    match action {
+        AuditCommand::List { api, action, actor, ip, denied, since, limit, before } => {
+            let query = AuditQuery {
+                action,
+                actor,
+                source_ip: ip,
+                allowed: denied.then_some(false),
+                since,
+                before,
+                limit: Some(limit),
+                ..AuditQuery::default()
+            };
+            let page = connect(&api)?.audit(&query).await?;
+            match api.format {
+                OutputFormat::Json => print_json(&page)?,
+                OutputFormat::Table => {
+                    print_table(
+                        &["SEQ", "TIME", "ACTION", "ACTOR", "SUBJECT", "SOURCE", "RESULT"],
+                        page.records
+                            .iter()
+                            .map(|r| {
+                                let entry = &r.entry;
+                                let result = match (&entry.allowed, &entry.reason) {
+                                    (true, _) => "allowed".to_string(),
+                                    (false, Some(reason)) => format!("denied: {}", reason),
+                                    (false, None) => "denied".to_string(),
+                                };
+                                vec![
+                                    r.seq.to_string(),
+                                    entry.timestamp.clone(),
+                                    entry.action.clone(),
+                                    entry.actor.clone(),
+                                    truncate(&entry.subject, COMMAND_WIDTH),
+                                    entry.source_ip.clone().unwrap_or_else(|| "-".to_string()),
+                                    result,
+                                ]
+                            })
+                            .collect(),
+                    );
+                    if let Some(before) = page.next_before {
+                        eprintln!("More records: --before {}", before);
+                    }
+                }
+            }
+            Ok(0)
+        }
+        AuditCommand::Verify { workdir } => {
+            let path = workdir.join("octaskly.db");
+            if !path.exists() {
+                anyhow::bail!("{} does not exist", path.display());
+            }
+            let path = path.to_string_lossy();
+            // Opening migrates the schema; refuse instead, so verifying never writes
+            // Membuka database memigrasi skema; tolak saja, agar verifikasi tidak pernah menulis
+            let status = crate::persistence::schema_status(&path).await?;
+            if !status.pending.is_empty() {
+                anyhow::bail!("{} needs `octaskly db migrate` first (schema version {})", path, status.current);
+            }
+            let store = crate::persistence::SqliteStore::connect(&path).await?;
+            match crate::audit::verify_store(&store).await? {
+                Ok(verifier) => {
+                    println!("Audit chain intact: {} records", verifier.count());
+                    println!("Head: {}", verifier.head());
+                    Ok(0)
+                }
+                Err(broken) => {
+                    eprintln!("Audit chain broken: {}", broken);
+                    Ok(1)
+                }
+            }
+        }
+    }
+}
+
+fn list_or_none(keys: &[String]) -> String {
+    if keys.is_empty() {
+        "none".to_string()
+    } else {
+        keys.join(", ")
+    }
+}
+
+/// Run a client command and return the process exit code
+pub async fn run(command: ClientCommand) -> Result<i32> {
+    match command {
+        ClientCommand::Login { api } => {
+            let credentials = Credentials::load()?.resolve(&api);
+            let client = connect(&api)?;
+            let health = client
+                .health()
+                .await
+                .with_context(|| format!("cannot reach {}", client.base_url()))?;
+            let path = credentials.save()?;
+            println!("Connected to {} (octaskly {})", client.base_url(), health.version);
+            println!("Saved credentials to {}", path.display());
+            Ok(0)
+        }
+        ClientCommand::Submit {
+            api,
+            timeout,
+            env,
+            priority,
+            inputs,
+            outputs,
+            owner,
+            labels,
+            command,
+        } => {
+            let request = CreateTaskRequest {
+                command: shell_join(&command),
+                timeout,
+                inputs: (!inputs.is_empty()).then_some(inputs),
+                outputs: (!outputs.is_empty()).then_some(outputs),
+                owner,
+                resources: None,
+                placement: None,
+                max_retries: None,
+                labels: (!labels.is_empty()).then(|| labels.into_iter().collect()),
+                env: (!env.is_empty()).then(|| env.into_iter().collect()),
+                priority,
+            };
+            let task = connect(&api)?.submit(&request).await?;
+            match api.format {
+                OutputFormat::Json => print_json(&task)?,
+                OutputFormat::Table => print_table(
+                    &["ID", "STATUS", "COMMAND"],
+                    vec![vec![task.id, task.status, truncate(&task.command, COMMAND_WIDTH)]],
+                ),
+            }
+            Ok(0)
+        }
+        ClientCommand::Status { api, task_id } => {
+            let task = connect(&api)?.task(&task_id).await?;
+            match api.format {
+                OutputFormat::Json => print_json(&task)?,
+                OutputFormat::Table => print_details(&task),
+            }
+            Ok(0)
+        }
+        ClientCommand::Logs { api, task_id, follow } => {
+            let client = connect(&api)?;
+            if !follow {
+                let task = client.task(&task_id).await?;
+                if !task.is_finished() {
+                    eprintln!("Task {} is {}; output is available once it finishes (use --follow)", task_id, task.status);
+                }
+            }
+            let mut chunks = Box::pin(client.logs(&task_id, follow, POLL_INTERVAL));
+            while let Some(chunk) = chunks.next().await {
+                print_chunk(&chunk?, api.format)?;
+            }
+            Ok(0)
+        }
+        ClientCommand::Cancel { api, task_ids } => {
+            let client = connect(&api)?;
+            let mut cancelled = Vec::new();
+            let mut code = 0;
+            for task_id in &task_ids {
+                match client.cancel(task_id).await {
+                    Ok(response) => cancelled.push(response),
+                    Err(e) => {
+                        eprintln!("Cannot cancel {}: {}", task_id, e);
+                        code = 1;
+                    }
+                }
+            }
+            match api.format {
+                OutputFormat::Json => print_json(&cancelled)?,
+                OutputFormat::Table => print_table(
+                    &["ID", "STATUS"],
+                    cancelled.into_iter().map(|c| vec![c.id, c.status]).collect(),
+                ),
+            }
+            Ok(code)
+        }
+        ClientCommand::Wait { api, task_ids, timeout } => {
+            let client = connect(&api)?;
+            let waits = futures::future::try_join_all(task_ids.iter().map(|id| client.wait(id, POLL_INTERVAL)));
+            let tasks = match timeout {
+                Some(secs) => match tokio::time::timeout(Duration::from_secs(secs), waits).await {
+                    Ok(tasks) => tasks?,
+                    Err(_) => {
+                        eprintln!("Timed out after {}s", secs);
+                        return Ok(TIMEOUT_EXIT_CODE);
+                    }
+                },
+                None => waits.await?,
+            };
+            match api.format {
+                OutputFormat::Json => print_json(&tasks)?,
+                OutputFormat::Table => print_table(
+                    &["ID", "STATUS", "EXIT", "DURATION"],
+                    tasks
+                        .iter()
+                        .map(|t| {
+                            vec![
+                                t.id.clone(),
+                                t.status.clone(),
+                                t.exit_code.map(|c| c.to_string()).unwrap_or_else(|| "-".to_string()),
+                                format!("{}ms", t.duration_ms),
+                            ]
+                        })
+                        .collect(),
+                ),
+            }
+            Ok(tasks.iter().map(exit_code).find(|code| *code != 0).unwrap_or(0))
+        }
+        ClientCommand::List {
+            api,
+            status,
+            worker,
+            owner,
+            label,
+            parent,
+            command,
+            limit,
+            cursor,
+        } => {
+            let params = ListTasksParams {
+                status,
+                worker,
+                owner,
+                label,
+                command,
+                parent,
+                limit: Some(limit),
+                cursor,
+                ..ListTasksParams::default()
+            };
+            let page = connect(&api)?.list(&params).await?;
+            match api.format {
+                OutputFormat::Json => print_json(&page)?,
+                OutputFormat::Table => {
+                    print_table(
+                        &["ID", "STATUS", "OWNER", "WORKER", "CREATED", "COMMAND"],
+                        page.tasks
+                            .iter()
+                            .map(|t| {
+                                vec![
+                                    t.id.clone(),
+                                    t.status.clone(),
+                                    t.owner.clone(),
+                                    t.worker_id.clone().unwrap_or_else(|| "-".to_string()),
+                                    t.created_at.clone(),
+                                    truncate(&t.command, COMMAND_WIDTH),
+                                ]
+                            })
+                            .collect(),
+                    );
+                    if let Some(cursor) = page.next_cursor {
+                        eprintln!("More tasks: --cursor {}", cursor);
+                    }
+                }
+            }
+            Ok(0)
+        }
+    }
+}
+
+/// Exit code a waiting shell should see for a finished task
+pub fn exit_code(task: &TaskDetails) -> i32 {
+    match (task.status.as_str(), task.exit_code) {
+        ("Completed", _) => 0,
+        (_, Some(code)) if code != 0 => code,
+        _ => 1,
+    }
+}
+
+/// Join command words into one shell command line. A single word is taken
+/// as a full command line, so `-- "make && make test"` keeps its operators.
+pub fn shell_join(words: &[String]) -> String {
+    if let [line] = words {
+        return line.clone();
+    }
+    words
+        .iter()
+        .map(|word| {
+            let plain = !word.is_empty()
+                && word.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+            if plain {
+                word.clone()
+            } else {
+                format!("'{}'", word.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let kept: String = text.chars().take(width - 3).collect();
+        format!("{}...", kept)
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(headers.to_vec());
+    for row in &rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
+
+fn print_details(task: &TaskDetails) {
+    let mut labels: Vec<String> = task.labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    labels.sort();
+    let fields = [
+        ("ID", task.id.clone()),
+        ("Command", task.command.clone()),
+        ("Status", task.status.clone()),
+        ("Owner", task.owner.clone()),
+        ("Worker", task.worker_id.clone().unwrap_or_else(|| "-".to_string())),
+        ("Exit code", task.exit_code.map(|c| c.to_string()).unwrap_or_else(|| "-".to_string())),
+        ("Duration", format!("{}ms", task.duration_ms)),
+        ("Created", task.created_at.clone()),
+        ("Completed", task.completed_at.clone().unwrap_or_else(|| "-".to_string())),
+        ("Array", task.parent_id.clone().unwrap_or_else(|| "-".to_string())),
+        ("Labels", if labels.is_empty() { "-".to_string() } else { labels.join(",") }),
+    ];
+    for (name, value) in fields {
+        println!("{:<10} {}", format!("{}:", name), value);
+    }
+}
+
+fn print_chunk(chunk: &LogChunk, format: OutputFormat) -> Result<()> {
+    use std::io::Write;
+    match format {
+        OutputFormat::Json => {
+            let stream = match chunk.stream {
+                LogStream::Stdout => "stdout",
+                LogStream::Stderr => "stderr",
+            };
+            let line = HashMap::from([("stream", stream), ("text", chunk.text.as_str())]);
+            println!("{}", serde_json::to_string(&line)?);
+        }
+        OutputFormat::Table => match chunk.stream {
+            LogStream::Stdout => {
+                let mut out = std::io::stdout().lock();
+                out.write_all(chunk.text.as_bytes())?;
+                out.flush()?;
+            }
+            LogStream::Stderr => {
+                let mut err = std::io::stderr().lock();
+                err.write_all(chunk.text.as_bytes())?;
+                err.flush()?;
+            }
+        },
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_saved_credentials_are_private_even_over_an_open_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("octaskly-credentials-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("credentials.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "{}").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let credentials = Credentials {
+            api: Some("http://dispatcher:3000".to_string()),
+            token: Some("secret-token".to_string()),
+        };
+        credentials.save_to(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(std::fs::read_to_string(&path).unwrap().contains("secret-token"));
+        assert!(!path.with_extension("json.tmp").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_shell_join() {
+        let words = |w: &[&str]| w.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(shell_join(&words(&["make && make test"])), "make && make test");
+        assert_eq!(shell_join(&words(&["echo", "a b", "it's", "x=1"])), "echo 'a b' 'it'\\''s' x=1");
+        assert_eq!(shell_join(&words(&["printf", ""])), "printf ''");
+    }
+
+    #[test]
+    fn test_exit_code_mirrors_task() {
+        let task = |status: &str, exit_code: Option<i32>| TaskDetails {
+            id: "t".to_string(),
+            command: "true".to_string(),
+            status: status.to_string(),
+            worker_id: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            exit_code,
+            duration_ms: 0,
+            created_at: String::new(),
+            completed_at: None,
+            owner: String::new(),
+            labels: HashMap::new(),
+            parent_id: None,
+        };
+        assert_eq!(exit_code(&task("Completed", Some(0))), 0);
+        assert_eq!(exit_code(&task("Failed", Some(3))), 3);
+        assert_eq!(exit_code(&task("TimedOut", None)), 1);
+        assert_eq!(exit_code(&task("Cancelled", None)), 1);
+    }
+}
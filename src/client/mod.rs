@@ -0,0 +1,609 @@
+// Typed client for the dispatcher REST API
+// Klien bertipe untuk REST API dispatcher
+
+use anyhow::{Context, Result};
+use futures::Stream;
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::api::{
+    ArrayCancelResponse, ArrayResponse, ArrayStatus, ArrayTaskRequest, BatchResponse, BatchTaskRequest,
+    CancelResponse, CreateTaskRequest, CreateWebhookRequest, DrainRequest, DrainResponse, EventStreamParams,
+    AuditPage, HealthResponse, ListTasksParams, PutSecretRequest, SecretResponse, StatsResponse, TaskDetails, TaskList,
+    TaskResponse, WebhookResponse,
+};
+use crate::audit::AuditQuery;
+use crate::config::ReloadReport;
+use crate::events::{Event, EventRecord};
+use crate::protocol::{OutputStream, WorkerInfo};
+use crate::scheduler::DrainMode;
+
+pub mod cli;
+
+/// A request the API answered with an error status.
+///
+/// Returned inside `anyhow::Error`; use `downcast_ref::<ApiError>()` to
+/// tell e.g. a missing task from a network failure.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{status}: {message}")]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+/// Which output a log chunk came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+impl From<OutputStream> for LogStream {
+    fn from(stream: OutputStream) -> Self {
+        match stream {
+            OutputStream::Stdout => LogStream::Stdout,
+            OutputStream::Stderr => LogStream::Stderr,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogChunk {
+    pub stream: LogStream,
+    pub text: String,
+}
+
+/// Client of one dispatcher
+#[derive(Clone)]
+pub struct Client {
+    base_url: reqwest::Url,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl Client {
+    /// `base_url` is the API address, e.g. `http://dispatcher:7879`
+    pub fn new(base_url: &str) -> Result<Self> {
+        let base_url = reqwest::Url::parse(base_url).with_context(|| format!("invalid API url '{}'", base_url))?;
+        Ok(Self {
+            base_url,
+            token: None,
+            http: reqwest::Client::new(),
+        })
+    }
+
+    /// Send this bearer token with every request
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.base_url.as_str()
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<RequestBuilder> {
+        let url = self.base_url.join(path).with_context(|| format!("invalid API path '{}'", path))?;
+        let mut request = self.http.request(method, url);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        Ok(request)
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response> {
+        let response = request.send().await.context("API request failed")?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let message = response.text().await.unwrap_or_default();
+        Err(ApiError { status, message }.into())
+    }
+
+    async fn call<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        self.send(request)
+            .await?
+            .json()
+            .await
+            .context("unexpected API response")
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        self.call(self.request(Method::GET, path)?).await
+    }
+
+    async fn post<B: Serialize + ?Sized, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        self.call(self.request(Method::POST, path)?.json(body)).await
+    }
+
+    pub async fn health(&self) -> Result<HealthResponse> {
+        self.get("/health").await
+    }
+
+    /// Queue one task
+    pub async fn submit(&self, request: &CreateTaskRequest) -> Result<TaskResponse> {
+        self.post("/api/v1/tasks", request).await
+    }
+
+    /// Queue many tasks; all are accepted or none is
+    pub async fn submit_batch(&self, tasks: Vec<CreateTaskRequest>) -> Result<BatchResponse> {
+        self.post("/api/v1/tasks/batch", &BatchTaskRequest { tasks }).await
+    }
+
+    /// Queue an array of tasks expanded from one template
+    pub async fn submit_array(&self, request: &ArrayTaskRequest) -> Result<ArrayResponse> {
+        self.post("/api/v1/arrays", request).await
+    }
+
+    /// Task details, including its output once finished
+    pub async fn task(&self, task_id: &str) -> Result<TaskDetails> {
+        self.get(&format!("/api/v1/tasks/{}", task_id)).await
+    }
+
+    /// One page of tasks; pass `next_cursor` back as `cursor` for the next
+    pub async fn list(&self, params: &ListTasksParams) -> Result<TaskList> {
+        self.call(self.request(Method::GET, "/api/v1/tasks")?.query(params)).await
+    }
+
+    pub async fn cancel(&self, task_id: &str) -> Result<CancelResponse> {
+        self.call(self.request(Method::DELETE, &format!("/api/v1/tasks/{}", task_id))?)
+            .await
+    }
+
+    pub async fn array(&self, array_id: &str) -> Result<ArrayStatus> {
+        self.get(&format!("/api/v1/arrays/{}", array_id)).await
+    }
+
+    pub async fn cancel_array(&self, array_id: &str) -> Result<ArrayCancelResponse> {
+        self.call(self.request(Method::DELETE, &format!("/api/v1/arrays/{}", array_id))?)
+            .await
+    }
+
+    pub async fn workers(&self) -> Result<Vec<WorkerInfo>> {
+        self.get("/api/v1/workers").await
+    }
+
+    pub async fn drain(&self, worker_id: &str, mode: DrainMode) -> Result<DrainResponse> {
+        self.post(&format!("/api/v1/workers/{}/drain", worker_id), &DrainRequest { mode })
+            .await
+    }
+
+    pub async fn stats(&self) -> Result<StatsResponse> {
+        self.get("/api/v1/stats").await
+    }
+
+    /// Make the dispatcher re-read its configuration
+    pub async fn reload_config(&self) -> Result<ReloadReport> {
+        self.call(self.request(Method::POST, "/api/v1/admin/reload")?).await
+    }
+
+    pub async fn create_webhook(&self, request: &CreateWebhookRequest) -> Result<WebhookResponse> {
+        self.post("/api/v1/webhooks", request).await
+    }
+
+    pub async fn webhooks(&self) -> Result<Vec<WebhookResponse>> {
+        self.get("/api/v1/webhooks").await
+    }
+
+    /// Create or replace a secret
+    pub async fn put_secret(&self, name: &str, request: &PutSecretRequest) -> Result<SecretResponse> {
+        let path = format!("/api/v1/secrets/{}", name);
+        self.call(self.request(Method::PUT, &path)?.json(request)).await
+    }
+
+    pub async fn secrets(&self) -> Result<Vec<SecretResponse>> {
+        self.get("/api/v1/secrets").await
+    }
+
+    pub async fn delete_secret(&self, name: &str) -> Result<()> {
+        let path = format!("/api/v1/secrets/{}", name);
+        self.send(self.request(Method::DELETE, &path)?).await?;
+        Ok(())
+    }
+
+    /// Audit records matching the query, newest first
+    pub async fn audit(&self, query: &AuditQuery) -> Result<AuditPage> {
+        self.call(self.request(Method::GET, "/api/v1/audit")?.query(query)).await
+    }
+
+    /// Subscribe to the cluster event stream
+    pub async fn events(&self, params: &EventStreamParams) -> Result<EventStream> {
+        let request = self
+            .request(Method::GET, "/api/v1/events")?
+            .query(params)
+            .header(reqwest::header::ACCEPT, "text/event-stream");
+        Ok(EventStream {
+            response: self.send(request).await?,
+            buffer: String::new(),
+        })
+    }
+
+    /// Wait until a task reaches a terminal status and return it.
+    ///
+    /// Listens for the task's completion event and falls back to polling
+    /// every `poll` when the stream drops. Wrap in `tokio::time::timeout`
+    /// to bound the wait.
+    pub async fn wait(&self, task_id: &str, poll: Duration) -> Result<TaskDetails> {
+        let params = EventStreamParams {
+            types: Some("task_completed".to_string()),
+            task: Some(task_id.to_string()),
+            last_event_id: None,
+        };
+        loop {
+            // Subscribe before looking, so a completion in between is not missed
+            // Berlangganan sebelum memeriksa, agar penyelesaian di antaranya tidak terlewat
+            let events = self.events(&params).await;
+            let task = self.task(task_id).await?;
+            if task.is_finished() {
+                return Ok(task);
+            }
+            match events {
+                Ok(mut events) => match events.next().await {
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => tracing::debug!("Event stream failed while waiting for {}: {}", task_id, e),
+                    None => {}
+                },
+                Err(e) => tracing::debug!("Cannot subscribe to events, polling {}: {}", task_id, e),
+            }
+            tokio::time::sleep(poll).await;
+        }
+    }
+
+    /// Output of a task. With `follow`, output is streamed from the event
+    /// stream as the worker writes it, until the task finishes; whatever
+    /// the stream did not deliver comes from the finished task.
+    pub fn logs<'a>(
+        &'a self,
+        task_id: &'a str,
+        follow: bool,
+        poll: Duration,
+    ) -> impl Stream<Item = Result<LogChunk>> + 'a {
+        let logs = Logs {
+            client: self,
+            task_id,
+            follow,
+            poll,
+            events: None,
+            last_event_id: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            pending: VecDeque::new(),
+            done: false,
+        };
+        futures::stream::unfold(logs, |mut logs| async move { logs.next().await.map(|chunk| (chunk, logs)) })
+    }
+}
+
+// Progress of one `Client::logs` stream
+// Kemajuan satu aliran `Client::logs`
+struct Logs<'a> {
+    client: &'a Client,
+    task_id: &'a str,
+    follow: bool,
+    poll: Duration,
+    events: Option<EventStream>,
+    last_event_id: u64,
+    // Output streamed so far
+    // Output yang sudah dialirkan
+    stdout: String,
+    stderr: String,
+    pending: VecDeque<LogChunk>,
+    done: bool,
+}
+
+impl Logs<'_> {
+    async fn next(&mut self) -> Option<Result<LogChunk>> {
+        loop {
+            if let Some(chunk) = self.pending.pop_front() {
+                return Some(Ok(chunk));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.advance().await {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+
+    // Wait for more output or for the task to finish
+    // Tunggu output berikutnya atau hingga tugas selesai
+    async fn advance(&mut self) -> Result<()> {
+        if !self.follow {
+            let task = self.client.task(self.task_id).await?;
+            self.finish(&task);
+            return Ok(());
+        }
+        let Some(events) = self.events.as_mut() else {
+            // Subscribe before looking, replaying the output retained so far
+            // Berlangganan sebelum memeriksa, sambil memutar ulang output yang tersimpan
+            let params = EventStreamParams {
+                types: Some("task_output,task_completed".to_string()),
+                task: Some(self.task_id.to_string()),
+                last_event_id: Some(self.last_event_id),
+            };
+            let events = self.client.events(&params).await;
+            let task = self.client.task(self.task_id).await?;
+            if task.is_finished() {
+                self.finish(&task);
+                return Ok(());
+            }
+            match events {
+                Ok(events) => self.events = Some(events),
+                Err(e) => {
+                    tracing::debug!("Cannot subscribe to events, polling {}: {}", self.task_id, e);
+                    tokio::time::sleep(self.poll).await;
+                }
+            }
+            return Ok(());
+        };
+        match events.next().await {
+            Some(Ok(record)) => {
+                self.last_event_id = record.id;
+                match record.event {
+                    Event::TaskOutput { stream, text, .. } => {
+                        match stream {
+                            OutputStream::Stdout => self.stdout.push_str(&text),
+                            OutputStream::Stderr => self.stderr.push_str(&text),
+                        }
+                        self.pending.push_back(LogChunk {
+                            stream: stream.into(),
+                            text,
+                        });
+                    }
+                    // A failed attempt may be retried; only a finished task ends the output
+                    // Percobaan yang gagal bisa diulang; hanya tugas yang selesai mengakhiri output
+                    _ => {
+                        let task = self.client.task(self.task_id).await?;
+                        if task.is_finished() {
+                            self.finish(&task);
+                        }
+                    }
+                }
+            }
+            end => {
+                if let Some(Err(e)) = end {
+                    tracing::debug!("Event stream failed while following {}: {}", self.task_id, e);
+                }
+                self.events = None;
+                tokio::time::sleep(self.poll).await;
+            }
+        }
+        Ok(())
+    }
+
+    // The rest of a finished task's output: all of it when nothing streamed,
+    // otherwise what the stream missed, such as lines written just before the end
+    // Sisa output tugas yang selesai: seluruhnya bila tidak ada yang dialirkan,
+    // selain itu yang terlewat, seperti baris yang ditulis tepat sebelum selesai
+    fn finish(&mut self, task: &TaskDetails) {
+        for (stream, text, streamed) in [
+            (LogStream::Stdout, &task.stdout, &self.stdout),
+            (LogStream::Stderr, &task.stderr, &self.stderr),
+        ] {
+            if let Some(rest) = text.strip_prefix(streamed.as_str()).filter(|rest| !rest.is_empty()) {
+                self.pending.push_back(LogChunk {
+                    stream,
+                    text: rest.to_string(),
+                });
+            }
+        }
+        self.done = true;
+    }
+}
+
+/// Events received from `GET /api/v1/events`
+pub struct EventStream {
+    response: reqwest::Response,
+    buffer: String,
+}
+
+impl EventStream {
+    /// Next event; None once the server ends the stream
+    pub async fn next(&mut self) -> Option<Result<EventRecord>> {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let message: String = self.buffer.drain(..end + 2).collect();
+                match parse_sse_data(&message) {
+                    Some(data) => return Some(serde_json::from_str(&data).context("invalid event")),
+                    // Keep-alive comments carry no data
+                    // Komentar keep-alive tidak membawa data
+                    None => continue,
+                }
+            }
+            match self.response.chunk().await {
+                Ok(Some(bytes)) => self.buffer.push_str(&String::from_utf8_lossy(&bytes).replace("\r\n", "\n")),
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
+
+/// Joined `data:` lines of one SSE message
+fn parse_sse_data(message: &str) -> Option<String> {
+    let lines: Vec<&str> = message
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+    use crate::api::{create_router, ApiState};
+    use crate::auth::AuthManager;
+    use crate::persistence::{MemoryStore, TaskStore};
+    use crate::protocol::{TaskResult, TaskStatus};
+    use crate::scheduler::Scheduler;
+    use crate::state::DispatcherState;
+    use std::sync::Arc;
+
+    async fn serve() -> (Client, ApiState) {
+        let store: Arc<dyn TaskStore> = Arc::new(MemoryStore::new());
+        let dispatcher = Arc::new(DispatcherState::new("test".to_string(), 0).with_store(store.clone()));
+        let state = ApiState {
+            scheduler: Arc::new(
+                Scheduler::new()
+                    .with_store(store.clone())
+                    .with_events(dispatcher.events.clone()),
+            ),
+            dispatcher,
+            auth: Arc::new(AuthManager::new("test".to_string())),
+            store,
+            require_auth: false,
+            reloader: None,
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = create_router(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (Client::new(&format!("http://{}", addr)).unwrap(), state)
+    }
+
+    fn request(command: &str) -> CreateTaskRequest {
+        CreateTaskRequest {
+            command: command.to_string(),
+            timeout: None,
+            inputs: None,
+            outputs: None,
+            owner: None,
+            resources: None,
+            placement: None,
+            max_retries: None,
+            labels: None,
+            env: None,
+            priority: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_sse_data() {
+        assert_eq!(
+            parse_sse_data("id: 3\nevent: task_completed\ndata: {\"a\":1}\n\n").as_deref(),
+            Some("{\"a\":1}")
+        );
+        assert_eq!(parse_sse_data(":\n\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_submit_wait_logs_and_cancel() {
+        let (client, state) = serve().await;
+        assert_eq!(client.health().await.unwrap().status, "healthy");
+
+        let submitted = client.submit(&request("echo hi")).await.unwrap();
+        let task = client.task(&submitted.id).await.unwrap();
+        assert!(!task.is_finished());
+
+        let waiter = {
+            let client = client.clone();
+            let id = submitted.id.clone();
+            tokio::spawn(async move { client.wait(&id, Duration::from_millis(50)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        state.scheduler.cancel(&submitted.id).await;
+        state
+            .dispatcher
+            .store_result(TaskResult {
+                task_id: submitted.id.clone(),
+                worker_id: "w1".to_string(),
+                status: TaskStatus::Completed,
+                stdout: "hi\n".to_string(),
+                stderr: String::new(),
+                exit_code: Some(0),
+                duration_ms: 3,
+                completed_at: chrono::Local::now().timestamp(),
+                signer: None,
+            })
+            .await;
+        let finished = tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(finished.status, "Completed");
+        assert_eq!(finished.exit_code, Some(0));
+
+        let logs: Vec<LogChunk> = client
+            .logs(&submitted.id, true, Duration::from_millis(50))
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(
+            logs,
+            vec![LogChunk {
+                stream: LogStream::Stdout,
+                text: "hi\n".to_string()
+            }]
+        );
+
+        let other = client.submit(&request("sleep 60")).await.unwrap();
+        assert_eq!(client.cancel(&other.id).await.unwrap().status, "Cancelled");
+        let conflict = client.cancel(&other.id).await.unwrap_err();
+        assert_eq!(conflict.downcast_ref::<ApiError>().unwrap().status, StatusCode::CONFLICT);
+        let missing = client.task("nope").await.unwrap_err();
+        assert_eq!(missing.downcast_ref::<ApiError>().unwrap().status, StatusCode::NOT_FOUND);
+
+        let page = client.list(&ListTasksParams::default()).await.unwrap();
+        assert_eq!(page.tasks.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_follow_streams_output_while_the_task_runs() {
+        let (client, state) = serve().await;
+        let submitted = client.submit(&request("make")).await.unwrap();
+        let output = |text: &str| Event::TaskOutput {
+            task_id: submitted.id.clone(),
+            stream: OutputStream::Stdout,
+            text: text.to_string(),
+        };
+        // Written before anyone follows; replayed from the retained events
+        state.dispatcher.events.publish(output("one\n"));
+
+        let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let follower = {
+            let client = client.clone();
+            let id = submitted.id.clone();
+            tokio::spawn(async move {
+                let mut logs = Box::pin(client.logs(&id, true, Duration::from_millis(50)));
+                while let Some(chunk) = futures::StreamExt::next(&mut logs).await {
+                    sender.send(chunk.unwrap().text).unwrap();
+                }
+            })
+        };
+        let first = tokio::time::timeout(Duration::from_secs(5), received.recv()).await.unwrap();
+        assert_eq!(first.as_deref(), Some("one\n"));
+
+        state.dispatcher.events.publish(output("two\n"));
+        let second = tokio::time::timeout(Duration::from_secs(5), received.recv()).await.unwrap();
+        assert_eq!(second.as_deref(), Some("two\n"));
+
+        state.scheduler.cancel(&submitted.id).await;
+        state
+            .dispatcher
+            .store_result(TaskResult {
+                task_id: submitted.id.clone(),
+                worker_id: "w1".to_string(),
+                status: TaskStatus::Completed,
+                stdout: "one\ntwo\n".to_string(),
+                stderr: "late\n".to_string(),
+                exit_code: Some(0),
+                duration_ms: 3,
+                completed_at: chrono::Local::now().timestamp(),
+                signer: None,
+            })
+            .await;
+        tokio::time::timeout(Duration::from_secs(5), follower).await.unwrap().unwrap();
+        // Streamed output is not printed again; what never streamed comes from the result
+        assert_eq!(received.recv().await.as_deref(), Some("late\n"));
+        assert_eq!(received.recv().await, None);
+    }
+}
@@ -1,5 +1,9 @@
-use clap::{Parser, Subcommand};
+use crate::config::set_flag;
+use crate::scheduler::PolicyKind;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::collections::HashMap;
 use std::path::PathBuf;
+use toml::Table;
 
 /// Validates input arguments for commands
 /// Memvalidasi argumen input untuk perintah
@@ -67,6 +71,63 @@ impl Validator {
     }
 }
 
+/// Parse a fair-share weight given as OWNER=WEIGHT
+/// Parsing bobot fair-share yang diberikan sebagai PEMILIK=BOBOT
+pub fn parse_share(value: &str) -> Result<(String, f64), String> {
+    let (owner, weight) = value
+        .split_once('=')
+        .ok_or_else(|| format!("Expected OWNER=WEIGHT, got '{}'", value))?;
+    if owner.is_empty() {
+        return Err("Owner cannot be empty".to_string());
+    }
+    let weight: f64 = weight
+        .parse()
+        .map_err(|_| format!("Invalid weight '{}'", weight))?;
+    if weight <= 0.0 {
+        return Err("Weight must be greater than 0".to_string());
+    }
+    Ok((owner.to_string(), weight))
+}
+
+/// Parse a custom worker resource given as NAME=AMOUNT
+/// Parsing resource khusus worker yang diberikan sebagai NAMA=JUMLAH
+pub fn parse_resource(value: &str) -> Result<(String, u64), String> {
+    let (name, amount) = value
+        .split_once('=')
+        .ok_or_else(|| format!("Expected NAME=AMOUNT, got '{}'", value))?;
+    if name.is_empty() {
+        return Err("Resource name cannot be empty".to_string());
+    }
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("Invalid amount '{}'", amount))?;
+    Ok((name.to_string(), amount))
+}
+
+/// Parse a worker label given as KEY=VALUE
+/// Parsing label worker yang diberikan sebagai KUNCI=NILAI
+pub fn parse_label(value: &str) -> Result<(String, String), String> {
+    let (key, label) = value
+        .split_once('=')
+        .ok_or_else(|| format!("Expected KEY=VALUE, got '{}'", value))?;
+    if key.is_empty() {
+        return Err("Label key cannot be empty".to_string());
+    }
+    Ok((key.to_string(), label.to_string()))
+}
+
+/// Parse a task environment variable given as KEY=VALUE
+/// Parsing variabel lingkungan tugas yang diberikan sebagai KUNCI=NILAI
+pub fn parse_env(value: &str) -> Result<(String, String), String> {
+    let (key, env) = value
+        .split_once('=')
+        .ok_or_else(|| format!("Expected KEY=VALUE, got '{}'", value))?;
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return Err(format!("Invalid variable name '{}'", key));
+    }
+    Ok((key.to_string(), env.to_string()))
+}
+
 /// Distributed task orchestration with P2P resource sharing
 #[derive(Parser, Debug)]
 #[command(name = "octaskly")]
@@ -85,6 +146,10 @@ pub struct Cli {
     /// Show verbose debug output
     #[arg(global = true, short = 'v', long)]
     pub verbose: bool,
+
+    /// Config file layered over the system and user ones (or OCTASKLY_CONFIG)
+    #[arg(global = true, long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
 }
 
 /// Available application commands
@@ -94,56 +159,80 @@ pub enum Command {
     #[command(about = "Central task coordinator - manages workers and distributes tasks")]
     Dispatcher {
         /// Bind address for worker connections [default: 0.0.0.0]
-        #[arg(short = 'b', long, default_value = "0.0.0.0")]
-        bind: String,
+        #[arg(short = 'b', long)]
+        bind: Option<String>,
 
         /// TCP port for dispatcher to listen on [default: 7878]
-        #[arg(short = 'p', long, default_value = "7878")]
-        port: u16,
+        #[arg(short = 'p', long)]
+        port: Option<u16>,
 
         /// Directory for storing task data and results [default: ./tasks]
-        #[arg(long, default_value = "./tasks")]
-        workdir: PathBuf,
+        #[arg(long)]
+        workdir: Option<PathBuf>,
 
         /// Maximum number of worker connections [default: 10]
-        #[arg(long, default_value = "10")]
-        max_workers: usize,
+        #[arg(long)]
+        max_workers: Option<usize>,
 
-        /// Task execution timeout in seconds [default: 300]
-        #[arg(short = 't', long, default_value = "300")]
-        task_timeout: u64,
+        /// Timeout in seconds for tasks submitted without one [default: 300]
+        #[arg(short = 't', long)]
+        task_timeout: Option<u64>,
 
         /// Enable P2P peer-to-peer task distribution [default: true]
-        #[arg(long, default_value = "true")]
-        p2p_enabled: bool,
+        #[arg(long, value_name = "BOOL")]
+        p2p_enabled: Option<bool>,
 
         /// UDP port for P2P peer discovery and announcements [default: 5555]
-        #[arg(long, default_value = "5555")]
-        discovery_port: u16,
+        #[arg(long)]
+        discovery_port: Option<u16>,
 
         /// Enable interactive terminal UI dashboard
         #[arg(long)]
         ui: bool,
+
+        /// Fair-share weight for a task owner, e.g. --share team-a=2 (repeatable)
+        #[arg(long = "share", value_name = "OWNER=WEIGHT", value_parser = parse_share)]
+        shares: Vec<(String, f64)>,
+
+        /// Half-life in seconds for fair-share usage decay [default: 3600]
+        #[arg(long)]
+        usage_half_life: Option<u64>,
+
+        /// Worker selection policy [default: first-fit]
+        #[arg(long, value_enum)]
+        scheduling_policy: Option<PolicyKind>,
+
+        /// Port for the REST API (disabled if not set)
+        #[arg(long)]
+        api_port: Option<u16>,
+
+        /// Seconds without a worker heartbeat before its tasks are requeued [default: 30]
+        #[arg(long)]
+        lease_timeout: Option<u64>,
+
+        /// Start even if a pre-shared key is a well-known default
+        #[arg(long)]
+        allow_default_key: bool,
     },
 
     /// Start as worker (task execution node)
     #[command(about = "Task execution node - receives and executes tasks")]
     Worker {
-        /// Unique worker identifier within cluster (required)
+        /// Unique worker identifier within cluster (required, here or in the config)
         #[arg(short = 'n', long)]
-        name: String,
+        name: Option<String>,
 
-        /// Address of dispatcher to connect to [default: localhost]
-        #[arg(short = 'd', long, default_value = "localhost")]
-        dispatcher: String,
+        /// Address of dispatcher to connect to [default: found via mDNS, else localhost]
+        #[arg(short = 'd', long)]
+        dispatcher: Option<String>,
 
         /// Port of dispatcher to connect to [default: 7878]
-        #[arg(short = 'p', long, default_value = "7878")]
-        dispatcher_port: u16,
+        #[arg(short = 'p', long)]
+        dispatcher_port: Option<u16>,
 
         /// Maximum number of concurrent tasks this worker can execute [default: 4]
-        #[arg(short = 'j', long, default_value = "4")]
-        max_jobs: usize,
+        #[arg(short = 'j', long)]
+        max_jobs: Option<usize>,
 
         /// Number of CPU cores available on this worker (auto-detect if not set)
         #[arg(short = 'c', long)]
@@ -153,22 +242,61 @@ pub enum Command {
         #[arg(short = 'm', long)]
         memory_mb: Option<u64>,
 
-        /// GPU hardware availability
-        #[arg(long, default_value = "false")]
-        gpu: bool,
+        /// GPU hardware availability [default: false]
+        #[arg(long, value_name = "BOOL")]
+        gpu: Option<bool>,
+
+        /// Scratch disk space available to tasks in MB [default: 0]
+        #[arg(long)]
+        disk_mb: Option<u64>,
+
+        /// Custom resource capacity, e.g. --resource license=2 (repeatable)
+        #[arg(long = "resource", value_name = "NAME=AMOUNT", value_parser = parse_resource)]
+        resources: Vec<(String, u64)>,
+
+        /// Worker label for task node selectors, e.g. --label cuda=12 (repeatable)
+        #[arg(short = 'l', long = "label", value_name = "KEY=VALUE", value_parser = parse_label)]
+        labels: Vec<(String, String)>,
 
-        /// Allow execution of shell commands on this worker
-        #[arg(long, default_value = "true")]
-        allow_shell: bool,
+        /// Seconds to let running tasks finish on shutdown before handing them back [default: 30]
+        #[arg(long)]
+        grace_period: Option<u64>,
+
+        /// Allow execution of shell commands on this worker [default: true]
+        #[arg(long, value_name = "BOOL")]
+        allow_shell: Option<bool>,
+
+        /// Serve Prometheus metrics on this port (disabled if not set)
+        #[arg(long)]
+        metrics_port: Option<u16>,
+
+        /// Advertise over mDNS and find the dispatcher there when --dispatcher is not set and it can be authenticated [default: true]
+        #[arg(long, value_name = "BOOL")]
+        mdns: Option<bool>,
+
+        /// Start even if a pre-shared key is a well-known default
+        #[arg(long)]
+        allow_default_key: bool,
     },
 
+    /// Inspect or upgrade the dispatcher database schema
+    #[command(about = "Database schema maintenance (migrate | status)")]
+    Db {
+        #[command(subcommand)]
+        action: DbCommand,
+    },
+
+    /// Talk to a running dispatcher over its REST API
+    #[command(flatten)]
+    Client(ClientCommand),
+
     /// Quick dispatcher launch
     #[command(about = "Quick dispatcher (same as: dispatcher -b 0.0.0.0 -p 7878 --ui)")]
     D {
-        #[arg(long, default_value = "0.0.0.0")]
-        bind: String,
-        #[arg(long, default_value = "7878")]
-        port: u16,
+        #[arg(long)]
+        bind: Option<String>,
+        #[arg(long)]
+        port: Option<u16>,
         #[arg(long)]
         ui: bool,
     },
@@ -177,111 +305,470 @@ pub enum Command {
     #[command(about = "Quick worker (same as: worker -n NAME -j 4)")]
     W {
         #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        max_jobs: Option<usize>,
+    },
+
+    /// Inspect the layered configuration
+    #[command(about = "Configuration files and overrides (show | reload)")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+
+    /// Manage the key file used to derive cluster keys from pre-shared keys
+    #[command(about = "Cluster key file (init | id)")]
+    Keys {
+        #[command(subcommand)]
+        action: KeysCommand,
+    },
+
+    /// Sign an API token with security.api_secret from the configuration
+    #[command(about = "Create a REST API bearer token")]
+    Token {
+        /// User or service the token is for
+        #[arg(long)]
+        subject: String,
+
+        /// Role granting its default permissions [default: client]
+        #[arg(long, default_value = "client", value_parser = ["admin", "dispatcher", "worker", "client"])]
+        role: String,
+
+        /// Hours until the token expires [default: 24]
+        #[arg(long, default_value = "24")]
+        hours: i64,
+
+        /// Project whose secrets the token's tasks may use (repeatable)
+        #[arg(long = "project", value_name = "NAME")]
+        projects: Vec<String>,
+    },
+
+    /// Store secrets tasks can reference as secret://NAME
+    #[command(about = "Task secrets (set | list | rm)")]
+    Secret {
+        #[command(subcommand)]
+        action: SecretCommand,
+    },
+
+    /// Query the audit log or check that it was not tampered with
+    #[command(about = "Audit log (list | verify)")]
+    Audit {
+        #[command(subcommand)]
+        action: AuditCommand,
+    },
+}
+
+/// How client commands print their results
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+/// Where to reach the dispatcher API; falls back to OCTASKLY_API and
+/// OCTASKLY_TOKEN, then to the credentials saved by `octaskly login`
+#[derive(Args, Debug, Clone, Default)]
+pub struct ApiArgs {
+    /// Dispatcher API url, e.g. http://dispatcher:3000
+    #[arg(long, value_name = "URL")]
+    pub api: Option<String>,
+
+    /// Bearer token for the API
+    #[arg(long)]
+    pub token: Option<String>,
+
+    /// Output format [default: table]
+    #[arg(long, value_enum, default_value = "table")]
+    pub format: OutputFormat,
+}
+
+/// Commands run against a dispatcher's REST API
+#[derive(Subcommand, Debug, Clone)]
+pub enum ClientCommand {
+    /// Save the API url and token used by the other client commands
+    #[command(about = "Store the dispatcher API url and token")]
+    Login {
+        #[command(flatten)]
+        api: ApiArgs,
+    },
+
+    /// Queue a command, e.g. octaskly submit -t 60 -- make test
+    #[command(about = "Submit a task to the dispatcher")]
+    Submit {
+        #[command(flatten)]
+        api: ApiArgs,
+
+        /// Timeout in seconds
+        #[arg(short = 't', long)]
+        timeout: Option<u64>,
+
+        /// Environment variable for the task, e.g. -e RUST_LOG=debug (repeatable)
+        #[arg(short = 'e', long = "env", value_name = "KEY=VALUE", value_parser = parse_env)]
+        env: Vec<(String, String)>,
+
+        /// Higher runs earlier among your queued tasks [default: 0]
+        #[arg(long, allow_negative_numbers = true)]
+        priority: Option<i32>,
+
+        /// Input file to send to the worker (repeatable)
+        #[arg(long = "input", value_name = "PATH")]
+        inputs: Vec<String>,
+
+        /// Output file to collect from the worker (repeatable)
+        #[arg(long = "output", value_name = "PATH")]
+        outputs: Vec<String>,
+
+        /// Owner (user or project) for fair-share scheduling
+        #[arg(long)]
+        owner: Option<String>,
+
+        /// Task label, e.g. --label exp=42 (repeatable)
+        #[arg(short = 'l', long = "label", value_name = "KEY=VALUE", value_parser = parse_label)]
+        labels: Vec<(String, String)>,
+
+        /// Command to run, after `--`
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+
+    /// Show one task
+    #[command(about = "Show the status and result of a task")]
+    Status {
+        #[command(flatten)]
+        api: ApiArgs,
+
+        task_id: String,
+    },
+
+    /// Print a task's output
+    #[command(about = "Print the output of a task")]
+    Logs {
+        #[command(flatten)]
+        api: ApiArgs,
+
+        task_id: String,
+
+        /// Stream output as it is written until the task finishes
+        #[arg(short = 'f', long)]
+        follow: bool,
+    },
+
+    /// Cancel queued or running tasks
+    #[command(about = "Cancel tasks")]
+    Cancel {
+        #[command(flatten)]
+        api: ApiArgs,
+
+        #[arg(required = true)]
+        task_ids: Vec<String>,
+    },
+
+    /// Wait for tasks; exits with the first failed task's exit code
+    #[command(about = "Wait for tasks to finish (exit code mirrors the tasks')")]
+    Wait {
+        #[command(flatten)]
+        api: ApiArgs,
+
+        #[arg(required = true)]
+        task_ids: Vec<String>,
+
+        /// Give up after this many seconds (exit code 124)
+        #[arg(short = 't', long)]
+        timeout: Option<u64>,
+    },
+
+    /// List tasks, newest first
+    #[command(about = "List tasks with filters")]
+    List {
+        #[command(flatten)]
+        api: ApiArgs,
+
+        /// Only tasks with this status, e.g. Running
+        #[arg(long)]
+        status: Option<String>,
+
+        /// Only tasks run by this worker
+        #[arg(long)]
+        worker: Option<String>,
+
+        /// Only tasks of this owner
+        #[arg(long)]
+        owner: Option<String>,
+
+        /// Only tasks with this label, as KEY=VALUE
+        #[arg(short = 'l', long)]
+        label: Option<String>,
+
+        /// Only tasks of this array
+        #[arg(long)]
+        parent: Option<String>,
+
+        /// Only tasks whose command contains this text
+        #[arg(long)]
+        command: Option<String>,
+
+        /// Number of tasks to show [default: 50]
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: usize,
+
+        /// Continue from the cursor printed by a previous listing
+        #[arg(long)]
+        cursor: Option<String>,
+    },
+}
+
+/// Database maintenance actions
+#[derive(Subcommand, Debug, Clone)]
+pub enum DbCommand {
+    /// Apply pending schema migrations
+    Migrate {
+        /// Dispatcher work directory holding octaskly.db [default: ./tasks]
+        #[arg(long, default_value = "./tasks")]
+        workdir: PathBuf,
+    },
+
+    /// Show the current and latest schema version
+    Status {
+        /// Dispatcher work directory holding octaskly.db [default: ./tasks]
+        #[arg(long, default_value = "./tasks")]
+        workdir: PathBuf,
+    },
+}
+
+impl DbCommand {
+    /// Path of the database the action works on
+    pub fn db_path(&self) -> PathBuf {
+        match self {
+            DbCommand::Migrate { workdir } | DbCommand::Status { workdir } => workdir.join("octaskly.db"),
+        }
+    }
+}
+
+/// Configuration actions
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Print the effective configuration and where each value came from
+    Show,
+
+    /// Make a running dispatcher re-read its configuration (like SIGHUP)
+    Reload {
+        #[command(flatten)]
+        api: ApiArgs,
+    },
+}
+
+/// Secret actions, run against a dispatcher's REST API
+#[derive(Subcommand, Debug, Clone)]
+pub enum SecretCommand {
+    /// Create or replace a secret; the value is read from stdin
+    Set {
+        #[command(flatten)]
+        api: ApiArgs,
+
+        /// Name, e.g. ci/token
+        name: String,
+
+        /// Project (task owner) allowed to use it (repeatable)
+        #[arg(long = "project", value_name = "NAME", required = true)]
+        projects: Vec<String>,
+    },
+
+    /// List secret names and their projects
+    List {
+        #[command(flatten)]
+        api: ApiArgs,
+    },
+
+    /// Remove a secret
+    Rm {
+        #[command(flatten)]
+        api: ApiArgs,
+
         name: String,
-        #[arg(long, default_value = "4")]
-        max_jobs: usize,
     },
 }
 
-impl Cli {
-    /// Parse CLI arguments and normalize command shortcuts with validation
-    pub fn parse_and_run() -> Result<Command, anyhow::Error> {
-        let cli = Self::parse();
-        
-        let cmd = match cli.command {
-            Some(Command::Dispatcher { 
-                bind, 
-                port, 
-                workdir, 
-                max_workers, 
-                task_timeout, 
-                p2p_enabled, 
-                discovery_port, 
-                ui 
-            }) => {
-                // Validate dispatcher arguments
-                if let Err(e) = Validator::dispatcher(&bind, port, max_workers) {
-                    eprintln!("❌ Dispatcher validation failed: {}", e);
-                    std::process::exit(1);
-                }
-                Command::Dispatcher {
-                    bind,
-                    port,
-                    workdir,
-                    max_workers,
-                    task_timeout,
-                    p2p_enabled,
-                    discovery_port,
-                    ui,
-                }
+/// Audit log actions
+#[derive(Subcommand, Debug, Clone)]
+pub enum AuditCommand {
+    /// List audit records, newest first
+    List {
+        #[command(flatten)]
+        api: ApiArgs,
+
+        /// Only this action, or actions starting with a prefix ending in '.' (e.g. task.)
+        #[arg(long)]
+        action: Option<String>,
+
+        /// Only actions by this token subject or worker
+        #[arg(long)]
+        actor: Option<String>,
+
+        /// Only actions from this address
+        #[arg(long)]
+        ip: Option<String>,
+
+        /// Only refused attempts
+        #[arg(long)]
+        denied: bool,
+
+        /// Only records at or after this RFC 3339 time
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Number of records to show [default: 50]
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: usize,
+
+        /// Continue from the cursor printed by a previous listing
+        #[arg(long)]
+        before: Option<u64>,
+    },
+
+    /// Check the hash chain of a dispatcher database, offline
+    Verify {
+        /// Dispatcher work directory holding octaskly.db [default: ./tasks]
+        #[arg(long, default_value = "./tasks")]
+        workdir: PathBuf,
+    },
+}
+
+/// Key file actions
+#[derive(Subcommand, Debug, Clone)]
+pub enum KeysCommand {
+    /// Write a new key file with a random salt; copy it to every node
+    Init {
+        /// Where to write it [default: security.keyfile]
+        #[arg(long)]
+        path: Option<PathBuf>,
+
+        /// Replace an existing key file (every node must then get the new one)
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Print the ids of the configured pre-shared keys, to compare across nodes
+    Id,
+
+    /// Create the dispatcher's task signing key if needed and print its public key
+    Signing {
+        /// Key file [default: security.signing_key, else <config dir>/octaskly/signing.key]
+        #[arg(long)]
+        path: Option<PathBuf>,
+
+        /// Replace an existing key (workers must then trust the new public key)
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+impl Command {
+    /// Values given explicitly on the command line, to layer over the config
+    /// Nilai yang diberikan eksplisit di baris perintah, untuk dilapis di atas konfigurasi
+    pub fn config_overrides(&self) -> Table {
+        let mut flags = Table::new();
+        match self {
+            Command::Dispatcher {
+                bind,
+                port,
+                workdir,
+                max_workers,
+                task_timeout,
+                p2p_enabled,
+                discovery_port,
+                ui,
+                shares,
+                usage_half_life,
+                scheduling_policy,
+                api_port,
+                lease_timeout,
+                allow_default_key,
+            } => {
+                let section = "dispatcher";
+                set_flag(&mut flags, section, "bind", bind.as_ref());
+                set_flag(&mut flags, section, "port", *port);
+                set_flag(&mut flags, section, "workdir", workdir.as_ref());
+                set_flag(&mut flags, section, "max_workers", *max_workers);
+                set_flag(&mut flags, section, "task_timeout", *task_timeout);
+                set_flag(&mut flags, section, "p2p_enabled", *p2p_enabled);
+                set_flag(&mut flags, section, "discovery_port", *discovery_port);
+                set_flag(&mut flags, section, "ui", ui.then_some(true));
+                let shares: HashMap<_, _> = shares.iter().cloned().collect();
+                set_flag(&mut flags, section, "shares", (!shares.is_empty()).then_some(shares));
+                set_flag(&mut flags, section, "usage_half_life", *usage_half_life);
+                set_flag(&mut flags, section, "scheduling_policy", *scheduling_policy);
+                set_flag(&mut flags, section, "api_port", *api_port);
+                set_flag(&mut flags, section, "lease_timeout", *lease_timeout);
+                set_flag(&mut flags, "security", "allow_default_key", allow_default_key.then_some(true));
             }
-            Some(Command::Worker { 
-                name, 
-                dispatcher, 
-                dispatcher_port, 
-                max_jobs, 
-                cpu_cores, 
-                memory_mb, 
-                gpu, 
-                allow_shell 
-            }) => {
-                // Validate worker arguments
-                if let Err(e) = Validator::worker(&name, &dispatcher, dispatcher_port, max_jobs) {
-                    eprintln!("❌ Worker validation failed: {}", e);
-                    std::process::exit(1);
-                }
-                Command::Worker {
-                    name,
-                    dispatcher,
-                    dispatcher_port,
-                    max_jobs,
-                    cpu_cores,
-                    memory_mb,
-                    gpu,
-                    allow_shell,
-                }
+            Command::D { bind, port, ui } => {
+                set_flag(&mut flags, "dispatcher", "bind", bind.as_ref());
+                set_flag(&mut flags, "dispatcher", "port", *port);
+                set_flag(&mut flags, "dispatcher", "ui", ui.then_some(true));
             }
-            Some(Command::D { bind, port, ui }) => {
-                // Quick dispatcher - validate
-                if let Err(e) = Validator::dispatcher(&bind, port, 10) {
-                    eprintln!("❌ Dispatcher validation failed: {}", e);
-                    std::process::exit(1);
-                }
-                Command::Dispatcher {
-                    bind,
-                    port,
-                    workdir: PathBuf::from("./tasks"),
-                    max_workers: 10,
-                    task_timeout: 300,
-                    p2p_enabled: true,
-                    discovery_port: 5555,
-                    ui,
-                }
+            Command::Worker {
+                name,
+                dispatcher,
+                dispatcher_port,
+                max_jobs,
+                cpu_cores,
+                memory_mb,
+                gpu,
+                disk_mb,
+                resources,
+                labels,
+                grace_period,
+                allow_shell,
+                metrics_port,
+                mdns,
+                allow_default_key,
+            } => {
+                let section = "worker";
+                set_flag(&mut flags, section, "name", name.as_ref());
+                set_flag(&mut flags, section, "dispatcher", dispatcher.as_ref());
+                set_flag(&mut flags, section, "dispatcher_port", *dispatcher_port);
+                set_flag(&mut flags, section, "max_jobs", *max_jobs);
+                set_flag(&mut flags, section, "cpu_cores", *cpu_cores);
+                set_flag(&mut flags, section, "memory_mb", *memory_mb);
+                set_flag(&mut flags, section, "gpu", *gpu);
+                set_flag(&mut flags, section, "disk_mb", *disk_mb);
+                let resources: HashMap<_, _> = resources.iter().cloned().collect();
+                set_flag(&mut flags, section, "resources", (!resources.is_empty()).then_some(resources));
+                let labels: HashMap<_, _> = labels.iter().cloned().collect();
+                set_flag(&mut flags, section, "labels", (!labels.is_empty()).then_some(labels));
+                set_flag(&mut flags, section, "grace_period", *grace_period);
+                set_flag(&mut flags, section, "allow_shell", *allow_shell);
+                set_flag(&mut flags, section, "metrics_port", *metrics_port);
+                set_flag(&mut flags, section, "mdns", *mdns);
+                set_flag(&mut flags, "security", "allow_default_key", allow_default_key.then_some(true));
             }
-            Some(Command::W { name, max_jobs }) => {
-                // Quick worker - validate
-                if let Err(e) = Validator::worker(&name, "localhost", 7878, max_jobs) {
-                    eprintln!("❌ Worker validation failed: {}", e);
-                    std::process::exit(1);
-                }
-                Command::Worker {
-                    name,
-                    dispatcher: "localhost".to_string(),
-                    dispatcher_port: 7878,
-                    max_jobs,
-                    cpu_cores: None,
-                    memory_mb: None,
-                    gpu: false,
-                    allow_shell: true,
-                }
+            Command::W { name, max_jobs } => {
+                set_flag(&mut flags, "worker", "name", name.as_ref());
+                set_flag(&mut flags, "worker", "max_jobs", *max_jobs);
             }
+            _ => {}
+        }
+        flags
+    }
+}
+
+impl Cli {
+    /// Parse CLI arguments, showing the short help when no command is given
+    ///
+    /// Options of `dispatcher`/`worker` are validated after layering them over
+    /// the config files, see `octaskly::config`
+    pub fn parse_and_run() -> Result<Command, anyhow::Error> {
+        let cli = Self::parse();
+
+        match cli.command {
+            Some(command) => Ok(command),
             None => {
                 Self::show_default_help();
                 std::process::exit(1);
             }
-        };
-
-        Ok(cmd)
+        }
     }
 
     /// Display minimalist default help message
@@ -294,10 +781,19 @@ impl Cli {
         println!("  octaskly worker [OPTIONS]       Task execution node");
         println!("  octaskly d [OPTIONS]            Quick dispatcher");
         println!("  octaskly w [OPTIONS]            Quick worker");
+        println!("  octaskly db <migrate | status>  Database schema maintenance");
+        println!("  octaskly submit [OPTIONS] -- CMD Submit a task");
+        println!("  octaskly status | logs | cancel | wait | list");
+        println!("                                  Inspect and control tasks");
+        println!("  octaskly login --api URL        Store API url and token");
+        println!("  octaskly config show | reload   Effective configuration / reload it");
+        println!("  octaskly token --subject NAME   Create an API token (--role admin)");
+        println!("  octaskly audit list | verify    Query the audit log / check its chain");
         println!();
         println!("OPTIONS (Global):");
         println!("  --monitor                       Enable real-time TUI dashboard");
         println!("  -v, --verbose                   Show debug output");
+        println!("  --config PATH                   Extra config file (or OCTASKLY_CONFIG)");
         println!("  -h, --help                      Show detailed help");
         println!("  -V, --version                   Show version");
         println!();
@@ -310,22 +806,43 @@ impl Cli {
         println!("  --p2p-enabled BOOL              Enable P2P [default: true]");
         println!("  --discovery-port NUM            P2P port [default: 5555]");
         println!("  --ui                            Enable terminal UI dashboard");
+        println!("  --share OWNER=WEIGHT            Fair-share weight (repeatable)");
+        println!("  --usage-half-life SECS          Usage decay half-life [default: 3600]");
+        println!("  --scheduling-policy NAME        first-fit | least-loaded | round-robin |");
+        println!("                                  bin-packing | random-two [default: first-fit]");
+        println!("  --api-port NUM                  Enable REST API on this port");
+        println!("  --lease-timeout SECS            Requeue tasks of silent workers [default: 30]");
         println!();
         println!("WORKER OPTIONS:");
         println!("  -n, --name NAME                 Unique worker name (required)");
-        println!("  -d, --dispatcher ADDR           Dispatcher address [default: localhost]");
+        println!("  -d, --dispatcher ADDR           Dispatcher address [default: found via mDNS,");
+        println!("                                  else localhost]");
         println!("  -p, --dispatcher-port NUM       Dispatcher port [default: 7878]");
         println!("  -j, --max-jobs NUM              Max concurrent jobs [default: 4]");
         println!("  -c, --cpu-cores NUM             CPU cores (auto-detect if empty)");
         println!("  -m, --memory-mb NUM             RAM in MB (auto-detect if empty)");
         println!("  --gpu BOOL                      GPU available [default: false]");
+        println!("  --disk-mb NUM                   Scratch disk in MB [default: 0]");
+        println!("  --resource NAME=AMOUNT          Custom resource capacity (repeatable)");
+        println!("  -l, --label KEY=VALUE           Worker label (repeatable)");
+        println!("  --grace-period SECS             Shutdown grace period [default: 30]");
         println!("  --allow-shell BOOL              Allow shell exec [default: true]");
+        println!("  --metrics-port PORT             Serve Prometheus metrics on PORT");
+        println!("  --mdns BOOL                     Advertise and browse over mDNS [default: true]");
+        println!();
+        println!("CLIENT OPTIONS:");
+        println!("  --api URL                       Dispatcher API [default: http://localhost:3000]");
+        println!("  --token TOKEN                   API bearer token");
+        println!("  --format table|json             Output format [default: table]");
         println!();
         println!("EXAMPLES:");
         println!("  octaskly dispatcher --port 7878 --ui");
         println!("  octaskly worker -n worker-01");
         println!("  octaskly d --monitor");
         println!("  octaskly w -n prod-worker -d 192.168.1.10");
+        println!("  octaskly db status --workdir ./tasks");
+        println!("  octaskly submit -t 60 -e MODE=fast -- ./build.sh");
+        println!("  octaskly logs --follow <TASK_ID>");
         println!();
         println!("Use 'octaskly --help' for full documentation");
         println!();
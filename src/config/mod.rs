@@ -0,0 +1,714 @@
+// Layered configuration: defaults < system file < user file < --config file < env < flags
+// Konfigurasi berlapis: bawaan < file sistem < file pengguna < file --config < env < flag
+//
+// Layers are merged per `section.key`, so a map such as `worker.labels` is
+// replaced as a whole by the highest layer that sets it. The source of every
+// value is kept for `octaskly config show`.
+//
+// Lapisan digabung per `bagian.kunci`, sehingga map seperti `worker.labels`
+// diganti utuh oleh lapisan tertinggi yang menetapkannya. Sumber setiap nilai
+// disimpan untuk `octaskly config show`.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+use crate::cmd::Validator;
+
+pub mod reload;
+
+pub use reload::{ConfigLayers, ReloadReport, Reloader, RELOADABLE};
+use crate::scheduler::{FairShareConfig, PolicyKind};
+
+/// Environment variable naming a config file, like `--config`
+pub const CONFIG_ENV: &str = "OCTASKLY_CONFIG";
+
+/// Prefix of per-key environment overrides, e.g. OCTASKLY_DISPATCHER_PORT
+pub const ENV_PREFIX: &str = "OCTASKLY_";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DispatcherConfig {
+    pub bind: String,
+    pub port: u16,
+    pub workdir: PathBuf,
+    pub max_workers: usize,
+    /// Timeout in seconds for tasks submitted without one
+    pub task_timeout: u64,
+    pub p2p_enabled: bool,
+    pub discovery_port: u16,
+    pub ui: bool,
+    /// Fair-share weight per owner
+    pub shares: HashMap<String, f64>,
+    pub usage_half_life: u64,
+    pub scheduling_policy: PolicyKind,
+    pub api_port: Option<u16>,
+    pub lease_timeout: u64,
+}
+
+impl Default for DispatcherConfig {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0".to_string(),
+            port: 7878,
+            workdir: PathBuf::from("./tasks"),
+            max_workers: 10,
+            task_timeout: 300,
+            p2p_enabled: true,
+            discovery_port: 5555,
+            ui: false,
+            shares: HashMap::new(),
+            usage_half_life: 3600,
+            scheduling_policy: PolicyKind::default(),
+            api_port: None,
+            lease_timeout: 30,
+        }
+    }
+}
+
+impl DispatcherConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        Validator::dispatcher(&self.bind, self.port, self.max_workers)?;
+        if let Some((owner, _)) = self.shares.iter().find(|(_, weight)| **weight <= 0.0) {
+            return Err(format!("Fair-share weight of '{}' must be greater than 0", owner));
+        }
+        Ok(())
+    }
+
+    pub fn fair_share(&self) -> FairShareConfig {
+        FairShareConfig {
+            weights: self.shares.clone(),
+            usage_half_life_secs: self.usage_half_life,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkerConfig {
+    /// Required, from a flag or any layer
+    pub name: Option<String>,
+    pub dispatcher: String,
+    pub dispatcher_port: u16,
+    pub max_jobs: usize,
+    /// Auto-detected if not set
+    pub cpu_cores: Option<usize>,
+    /// Auto-detected if not set
+    pub memory_mb: Option<u64>,
+    pub gpu: bool,
+    pub disk_mb: u64,
+    /// Custom resource capacities
+    pub resources: HashMap<String, u64>,
+    pub labels: HashMap<String, String>,
+    pub grace_period: u64,
+    pub allow_shell: bool,
+    pub metrics_port: Option<u16>,
+    /// Advertise over mDNS, and find the dispatcher there if none is set and it can be authenticated
+    pub mdns: bool,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            dispatcher: "localhost".to_string(),
+            dispatcher_port: 7878,
+            max_jobs: 4,
+            cpu_cores: None,
+            memory_mb: None,
+            gpu: false,
+            disk_mb: 0,
+            resources: HashMap::new(),
+            labels: HashMap::new(),
+            grace_period: 30,
+            allow_shell: true,
+            metrics_port: None,
+            mdns: true,
+        }
+    }
+}
+
+impl WorkerConfig {
+    /// Validate and return the worker name
+    pub fn validate(&self) -> Result<&str, String> {
+        let name = self
+            .name
+            .as_deref()
+            .ok_or("Worker name is required (--name or worker.name in the config)")?;
+        Validator::worker(name, &self.dispatcher, self.dispatcher_port, self.max_jobs)?;
+        Ok(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityConfig {
+    /// Worker names or ids allowed to register; empty allows all
+    pub whitelist: Vec<String>,
+    /// Pre-shared key workers must prove on registration; none disables the check
+    pub psk: Option<String>,
+    /// Further pre-shared keys still accepted but never sent with, for rotation
+    pub accepted_psks: Vec<String>,
+    /// Encrypt every dispatcher/worker frame with keys derived from `psk`
+    pub encrypt: bool,
+    /// KDF parameters and salt shared by the cluster [default: <config dir>/octaskly/keys.toml]
+    pub keyfile: Option<PathBuf>,
+    /// Start even if a pre-shared key is a well-known default
+    pub allow_default_key: bool,
+    /// Ed25519 key the dispatcher signs task assignments with; unsigned if not set
+    pub signing_key: Option<PathBuf>,
+    /// Public keys (base64) a worker accepts tasks from, by name; empty accepts unsigned tasks
+    pub trusted_keys: HashMap<String, String>,
+    /// Seconds a signed assignment stays valid
+    pub envelope_ttl: u64,
+    /// Key sealing task secrets at rest [default: <workdir>/secrets.key, created if missing]
+    pub secrets_key: Option<PathBuf>,
+    /// Signs REST API tokens; random per run if not set
+    pub api_secret: Option<String>,
+    /// Seconds a replaced `api_secret` keeps verifying tokens
+    pub secret_overlap: u64,
+    /// Require a bearer token on /api/v1
+    pub require_auth: bool,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            whitelist: Vec::new(),
+            psk: None,
+            accepted_psks: Vec::new(),
+            encrypt: false,
+            keyfile: None,
+            allow_default_key: false,
+            signing_key: None,
+            trusted_keys: HashMap::new(),
+            envelope_ttl: 300,
+            secrets_key: None,
+            api_secret: None,
+            secret_overlap: 300,
+            require_auth: false,
+        }
+    }
+}
+
+impl SecurityConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.require_auth && self.api_secret.is_none() {
+            return Err("security.require_auth needs security.api_secret".to_string());
+        }
+        self.validate_transport()
+    }
+
+    /// The part of the security settings a worker uses
+    pub fn validate_transport(&self) -> Result<(), String> {
+        if self.encrypt && self.psk.is_none() {
+            return Err("security.encrypt needs security.psk".to_string());
+        }
+        if !self.accepted_psks.is_empty() && self.psk.is_none() {
+            return Err("security.accepted_psks needs security.psk".to_string());
+        }
+        let default = self.psks().any(crate::security_enhanced::is_default_key);
+        if default && !self.allow_default_key {
+            return Err(
+                "a pre-shared key is empty or a well-known default; choose another or pass --allow-default-key"
+                    .to_string(),
+            );
+        }
+        crate::signing::TrustedKeys::new(&self.trusted_keys).map_err(|e| format!("{:#}", e))?;
+        Ok(())
+    }
+
+    /// The current pre-shared key followed by the accepted ones
+    pub fn psks(&self) -> impl Iterator<Item = &str> {
+        self.psk.iter().chain(&self.accepted_psks).map(String::as_str)
+    }
+
+    /// Where the key file is read from
+    pub fn keyfile_path(&self) -> Option<PathBuf> {
+        self.keyfile
+            .clone()
+            .or_else(|| dirs::config_dir().map(|dir| dir.join("octaskly").join("keys.toml")))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Filter such as "info" or "octaskly=debug,warn"; RUST_LOG applies if not set
+    pub level: Option<String>,
+}
+
+impl LogConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.level {
+            Some(level) => tracing_subscriber::EnvFilter::try_new(level)
+                .map(|_| ())
+                .map_err(|e| format!("Invalid log level '{}': {}", level, e)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Every setting of the dispatcher and worker
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub dispatcher: DispatcherConfig,
+    pub worker: WorkerConfig,
+    pub security: SecurityConfig,
+    pub log: LogConfig,
+}
+
+impl Config {
+    /// Validate everything a dispatcher uses
+    pub fn validate_dispatcher(&self) -> Result<(), String> {
+        self.dispatcher.validate()?;
+        self.security.validate()?;
+        self.log.validate()
+    }
+}
+
+// Value type of a key, for parsing environment overrides
+// Tipe nilai sebuah kunci, untuk parsing override environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Str,
+    Int,
+    Float,
+    Bool,
+    /// KEY=VALUE,KEY=VALUE with values of the given kind
+    Map(&'static Kind),
+    /// VALUE,VALUE with values of the given kind
+    List(&'static Kind),
+}
+
+const KEYS: &[(&str, &[(&str, Kind)])] = &[
+    (
+        "dispatcher",
+        &[
+            ("bind", Kind::Str),
+            ("port", Kind::Int),
+            ("workdir", Kind::Str),
+            ("max_workers", Kind::Int),
+            ("task_timeout", Kind::Int),
+            ("p2p_enabled", Kind::Bool),
+            ("discovery_port", Kind::Int),
+            ("ui", Kind::Bool),
+            ("shares", Kind::Map(&Kind::Float)),
+            ("usage_half_life", Kind::Int),
+            ("scheduling_policy", Kind::Str),
+            ("api_port", Kind::Int),
+            ("lease_timeout", Kind::Int),
+        ],
+    ),
+    (
+        "worker",
+        &[
+            ("name", Kind::Str),
+            ("dispatcher", Kind::Str),
+            ("dispatcher_port", Kind::Int),
+            ("max_jobs", Kind::Int),
+            ("cpu_cores", Kind::Int),
+            ("memory_mb", Kind::Int),
+            ("gpu", Kind::Bool),
+            ("disk_mb", Kind::Int),
+            ("resources", Kind::Map(&Kind::Int)),
+            ("labels", Kind::Map(&Kind::Str)),
+            ("grace_period", Kind::Int),
+            ("allow_shell", Kind::Bool),
+            ("metrics_port", Kind::Int),
+            ("mdns", Kind::Bool),
+        ],
+    ),
+    (
+        "security",
+        &[
+            ("whitelist", Kind::List(&Kind::Str)),
+            ("psk", Kind::Str),
+            ("accepted_psks", Kind::List(&Kind::Str)),
+            ("encrypt", Kind::Bool),
+            ("keyfile", Kind::Str),
+            ("allow_default_key", Kind::Bool),
+            ("signing_key", Kind::Str),
+            ("trusted_keys", Kind::Map(&Kind::Str)),
+            ("envelope_ttl", Kind::Int),
+            ("secrets_key", Kind::Str),
+            ("api_secret", Kind::Str),
+            ("secret_overlap", Kind::Int),
+            ("require_auth", Kind::Bool),
+        ],
+    ),
+    ("log", &[("level", Kind::Str)]),
+];
+
+// Values `config show` does not print
+// Nilai yang tidak dicetak oleh `config show`
+const SECRET_KEYS: &[&str] = &["security.psk", "security.accepted_psks", "security.api_secret"];
+
+fn kind_of(section: &str, key: &str) -> Option<Kind> {
+    KEYS.iter()
+        .find(|(name, _)| *name == section)
+        .and_then(|(_, keys)| keys.iter().find(|(name, _)| *name == key))
+        .map(|(_, kind)| *kind)
+}
+
+fn parse_env_value(raw: &str, kind: Kind) -> Result<Value, String> {
+    let raw = raw.trim();
+    match kind {
+        Kind::Str => Ok(Value::String(raw.to_string())),
+        Kind::Int => raw.parse().map(Value::Integer).map_err(|_| format!("expected an integer, got '{}'", raw)),
+        Kind::Float => raw.parse().map(Value::Float).map_err(|_| format!("expected a number, got '{}'", raw)),
+        Kind::Bool => match raw.to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Ok(Value::Boolean(true)),
+            "false" | "0" | "no" | "off" => Ok(Value::Boolean(false)),
+            _ => Err(format!("expected true or false, got '{}'", raw)),
+        },
+        Kind::Map(inner) => {
+            let mut table = Table::new();
+            for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                let (key, value) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", entry))?;
+                table.insert(key.trim().to_string(), parse_env_value(value, *inner)?);
+            }
+            Ok(Value::Table(table))
+        }
+        Kind::List(inner) => raw
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .map(|entry| parse_env_value(entry, *inner))
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+    }
+}
+
+/// Where an effective value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Env(String),
+    Flag,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Env(name) => write!(f, "env {}", name),
+            Source::Flag => write!(f, "command line"),
+        }
+    }
+}
+
+/// The effective configuration with the source of each `section.key`
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    pub config: Config,
+    pub sources: BTreeMap<String, Source>,
+    /// Files that were read, lowest precedence first
+    pub files: Vec<PathBuf>,
+}
+
+/// System-wide config file
+pub fn system_config_path() -> PathBuf {
+    if cfg!(windows) {
+        let base = std::env::var_os("ProgramData").unwrap_or_else(|| "C:\\ProgramData".into());
+        PathBuf::from(base).join("octaskly").join("config.toml")
+    } else {
+        PathBuf::from("/etc/octaskly/config.toml")
+    }
+}
+
+/// Per-user config file
+pub fn user_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("octaskly").join("config.toml"))
+}
+
+/// Builds a `LoadedConfig` from layers; `load` wires up the real ones
+#[derive(Debug, Default)]
+pub struct ConfigLoader {
+    /// Optional files, read if they exist
+    files: Vec<PathBuf>,
+    /// File that must exist (`--config`)
+    explicit: Option<PathBuf>,
+    env: Vec<(String, String)>,
+    flags: Table,
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn file(mut self, path: PathBuf) -> Self {
+        self.files.push(path);
+        self
+    }
+
+    pub fn explicit(mut self, path: Option<PathBuf>) -> Self {
+        self.explicit = path;
+        self
+    }
+
+    pub fn env(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.env = vars.into_iter().filter(|(name, _)| name.starts_with(ENV_PREFIX)).collect();
+        self
+    }
+
+    /// Values given on the command line, as `{ section = { key = value } }`
+    pub fn flags(mut self, flags: Table) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn load(self) -> Result<LoadedConfig> {
+        let mut merged = Table::try_from(Config::default())?;
+        let mut sources = BTreeMap::new();
+        for (section, keys) in KEYS {
+            for (key, _) in *keys {
+                sources.insert(format!("{}.{}", section, key), Source::Default);
+            }
+        }
+
+        let mut files = Vec::new();
+        let optional = self.files.into_iter().filter(|p| p.is_file()).map(|p| (p, false));
+        for (path, _) in optional.chain(self.explicit.map(|p| (p, true))) {
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("cannot read config file {}", path.display()))?;
+            let table: Table = toml::from_str(&text).with_context(|| format!("invalid config file {}", path.display()))?;
+            overlay(&mut merged, &mut sources, table, &Source::File(path.clone()))
+                .with_context(|| format!("in config file {}", path.display()))?;
+            files.push(path);
+        }
+
+        for (name, raw) in self.env {
+            let Some((section, key)) = env_key(&name) else {
+                continue;
+            };
+            let kind = kind_of(&section, &key).context("env key without a kind")?;
+            let value = parse_env_value(&raw, kind).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+            let table = Table::from_iter([(section, Value::Table(Table::from_iter([(key, value)])))]);
+            overlay(&mut merged, &mut sources, table, &Source::Env(name))?;
+        }
+
+        overlay(&mut merged, &mut sources, self.flags, &Source::Flag)?;
+
+        let config = Config::deserialize(Value::Table(merged)).context("invalid configuration")?;
+        Ok(LoadedConfig { config, sources, files })
+    }
+}
+
+/// Load from the system, user and `--config` (or OCTASKLY_CONFIG) files,
+/// the environment and the given command-line values
+pub fn load(explicit: Option<&Path>, flags: Table) -> Result<LoadedConfig> {
+    let explicit = explicit
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from));
+    let mut loader = ConfigLoader::new().file(system_config_path());
+    if let Some(user) = user_config_path() {
+        loader = loader.file(user);
+    }
+    loader.explicit(explicit).env(prefixed_env(std::env::vars_os())).flags(flags).load()
+}
+
+// OCTASKLY_ variables as text; one that is not valid UTF-8 cannot hold a
+// setting and is skipped with a warning instead of failing the load
+// Variabel OCTASKLY_ sebagai teks; yang bukan UTF-8 valid tidak dapat berisi
+// pengaturan dan dilewati dengan peringatan alih-alih menggagalkan pemuatan
+fn prefixed_env(vars: impl IntoIterator<Item = (OsString, OsString)>) -> Vec<(String, String)> {
+    vars.into_iter()
+        .filter(|(name, _)| name.to_string_lossy().starts_with(ENV_PREFIX))
+        .filter_map(|(name, value)| match (name.into_string(), value.into_string()) {
+            (Ok(name), Ok(value)) => Some((name, value)),
+            (name, _) => {
+                let name = name.unwrap_or_else(|name| name.to_string_lossy().into_owned());
+                tracing::warn!("Ignoring environment variable {}: not valid UTF-8", name);
+                None
+            }
+        })
+        .collect()
+}
+
+// Map OCTASKLY_WORKER_MAX_JOBS to ("worker", "max_jobs") if it names a known key
+// Petakan OCTASKLY_WORKER_MAX_JOBS ke ("worker", "max_jobs") jika menamai kunci yang dikenal
+fn env_key(name: &str) -> Option<(String, String)> {
+    let rest = name.strip_prefix(ENV_PREFIX)?.to_ascii_lowercase();
+    KEYS.iter().find_map(|(section, keys)| {
+        let key = rest.strip_prefix(section)?.strip_prefix('_')?;
+        keys.iter()
+            .any(|(k, _)| *k == key)
+            .then(|| (section.to_string(), key.to_string()))
+    })
+}
+
+fn overlay(merged: &mut Table, sources: &mut BTreeMap<String, Source>, layer: Table, source: &Source) -> Result<()> {
+    for (section, values) in layer {
+        let Value::Table(values) = values else {
+            bail!("'{}' must be a table", section);
+        };
+        for (key, value) in values {
+            if kind_of(&section, &key).is_none() {
+                bail!("unknown setting '{}.{}'", section, key);
+            }
+            let target = merged
+                .entry(section.clone())
+                .or_insert_with(|| Value::Table(Table::new()));
+            if let Value::Table(target) = target {
+                target.insert(key.clone(), value);
+            }
+            sources.insert(format!("{}.{}", section, key), source.clone());
+        }
+    }
+    Ok(())
+}
+
+impl LoadedConfig {
+    /// Effective configuration as TOML, each value commented with its source
+    pub fn render(&self) -> Result<String> {
+        let table = Table::try_from(&self.config)?;
+        let mut out = String::new();
+        for file in &self.files {
+            out.push_str(&format!("# read {}\n", file.display()));
+        }
+        for (section, keys) in KEYS {
+            out.push_str(&format!("\n[{}]\n", section));
+            let values = table.get(*section).and_then(Value::as_table);
+            let lines: Vec<(String, String)> = keys
+                .iter()
+                .map(|(key, _)| {
+                    let secret = SECRET_KEYS.contains(&format!("{}.{}", section, key).as_str());
+                    let line = match values.and_then(|v| v.get(*key)) {
+                        Some(_) if secret => format!("{} = \"<redacted>\"", key),
+                        Some(value) => format!("{} = {}", key, value),
+                        None => format!("# {} is not set", key),
+                    };
+                    let source = self
+                        .sources
+                        .get(&format!("{}.{}", section, key))
+                        .cloned()
+                        .unwrap_or(Source::Default);
+                    (line, source.to_string())
+                })
+                .collect();
+            let width = lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0);
+            for (line, source) in lines {
+                out.push_str(&format!("{:<width$}  # {}\n", line, source, width = width));
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Put `value` at `section.key` of a flags table if it is set
+pub fn set_flag<T: Serialize>(flags: &mut Table, section: &str, key: &str, value: Option<T>) {
+    let Some(value) = value else {
+        return;
+    };
+    let Ok(value) = Value::try_from(value) else {
+        return;
+    };
+    if let Value::Table(section) = flags
+        .entry(section.to_string())
+        .or_insert_with(|| Value::Table(Table::new()))
+    {
+        section.insert(key.to_string(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = dir.path().join("system.toml");
+        let user = dir.path().join("user.toml");
+        std::fs::write(&system, "[dispatcher]\nport = 8000\nmax_workers = 50\n[worker]\nlabels = { zone = \"a\" }\n").unwrap();
+        std::fs::write(&user, "[dispatcher]\nport = 8100\nscheduling_policy = \"least-loaded\"\n").unwrap();
+
+        let mut flags = Table::new();
+        set_flag(&mut flags, "dispatcher", "api_port", Some(3000u16));
+        set_flag(&mut flags, "dispatcher", "ui", None::<bool>);
+        let loaded = ConfigLoader::new()
+            .file(system.clone())
+            .file(user.clone())
+            .file(dir.path().join("missing.toml"))
+            .env([
+                ("OCTASKLY_DISPATCHER_MAX_WORKERS".to_string(), "5".to_string()),
+                ("OCTASKLY_WORKER_LABELS".to_string(), "zone=b,gpu=a100".to_string()),
+                ("OCTASKLY_JWT_SECRET".to_string(), "ignored".to_string()),
+            ])
+            .flags(flags)
+            .load()
+            .unwrap();
+
+        let config = &loaded.config;
+        assert_eq!(config.dispatcher.port, 8100);
+        assert_eq!(config.dispatcher.max_workers, 5);
+        assert_eq!(config.dispatcher.scheduling_policy, PolicyKind::LeastLoaded);
+        assert_eq!(config.dispatcher.api_port, Some(3000));
+        assert_eq!(config.dispatcher.lease_timeout, 30);
+        assert_eq!(config.worker.labels["zone"], "b");
+        assert_eq!(config.worker.labels.len(), 2);
+
+        assert_eq!(loaded.sources["dispatcher.port"], Source::File(user));
+        assert_eq!(loaded.sources["dispatcher.max_workers"], Source::Env("OCTASKLY_DISPATCHER_MAX_WORKERS".to_string()));
+        assert_eq!(loaded.sources["dispatcher.api_port"], Source::Flag);
+        assert_eq!(loaded.sources["dispatcher.ui"], Source::Default);
+        assert_eq!(loaded.files.len(), 2);
+
+        let rendered = loaded.render().unwrap();
+        assert!(rendered.contains("port = 8100"));
+        assert!(rendered.contains("# name is not set"));
+    }
+
+    #[test]
+    fn test_rejects_unknown_keys_and_bad_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[dispatcher]\nprot = 8000\n").unwrap();
+        let err = ConfigLoader::new().explicit(Some(path)).load().unwrap_err();
+        assert!(format!("{:#}", err).contains("unknown setting 'dispatcher.prot'"));
+
+        let err = ConfigLoader::new()
+            .env([("OCTASKLY_WORKER_GPU".to_string(), "maybe".to_string())])
+            .load()
+            .unwrap_err();
+        assert!(err.to_string().contains("OCTASKLY_WORKER_GPU"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStringExt;
+            let env = prefixed_env([
+                ("OCTASKLY_WORKER_MAX_JOBS".into(), "4".into()),
+                ("OCTASKLY_WORKER_NAME".into(), OsString::from_vec(b"w\xff".to_vec())),
+                (OsString::from_vec(b"OCTASKLY_\xff".to_vec()), "x".into()),
+                (OsString::from_vec(b"HOME\xff".to_vec()), "x".into()),
+                ("PATH".into(), "/bin".into()),
+            ]);
+            assert_eq!(env, vec![("OCTASKLY_WORKER_MAX_JOBS".to_string(), "4".to_string())]);
+        }
+
+        let missing = ConfigLoader::new().explicit(Some(dir.path().join("nope.toml"))).load();
+        assert!(missing.is_err());
+
+        let mut config = Config::default();
+        config.security.encrypt = true;
+        assert!(config.security.validate_transport().is_err());
+        config.security.psk = Some("k".to_string());
+        assert!(config.validate_dispatcher().is_ok());
+        config.security.accepted_psks = vec!["default-secret-key".to_string()];
+        assert!(config.validate_dispatcher().is_err());
+        config.security.allow_default_key = true;
+        assert!(config.validate_dispatcher().is_ok());
+    }
+}
@@ -0,0 +1,231 @@
+// Re-read the configuration of a running dispatcher (SIGHUP or POST /api/v1/admin/reload)
+// Baca ulang konfigurasi dispatcher yang berjalan (SIGHUP atau POST /api/v1/admin/reload)
+//
+// The new configuration is loaded and validated as a whole before anything is
+// applied, so a broken file leaves the running settings untouched. Settings
+// outside RELOADABLE keep their running value and are reported as needing a
+// restart.
+//
+// Konfigurasi baru dimuat dan divalidasi utuh sebelum apa pun diterapkan,
+// sehingga file yang rusak tidak mengubah pengaturan yang berjalan. Pengaturan
+// di luar RELOADABLE mempertahankan nilai berjalannya dan dilaporkan perlu restart.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use toml::{Table, Value};
+use tracing::info;
+
+use super::{Config, KEYS};
+use crate::auth::AuthManager;
+use crate::scheduler::Scheduler;
+use crate::state::DispatcherState;
+
+/// Settings a running dispatcher picks up on reload
+pub const RELOADABLE: &[&str] = &[
+    "security.whitelist",
+    "security.psk",
+    "security.accepted_psks",
+    "security.allow_default_key",
+    "security.api_secret",
+    "security.secret_overlap",
+    "dispatcher.scheduling_policy",
+    "dispatcher.shares",
+    "dispatcher.usage_half_life",
+    "log.level",
+];
+
+/// What a reload changed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReloadReport {
+    /// Settings that changed and are now in effect
+    pub applied: Vec<String>,
+    /// Settings that changed but only take effect after a restart
+    pub restart_required: Vec<String>,
+}
+
+/// The layers a configuration was built from, to build it again
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLayers {
+    /// `--config` path
+    pub explicit: Option<PathBuf>,
+    /// Command-line values, which keep overriding the files on reload
+    pub flags: Table,
+}
+
+impl ConfigLayers {
+    pub fn load(&self) -> Result<super::LoadedConfig> {
+        super::load(self.explicit.as_deref(), self.flags.clone())
+    }
+}
+
+/// Applies re-read configuration to the dispatcher's components
+pub struct Reloader {
+    layers: ConfigLayers,
+    current: tokio::sync::Mutex<Config>,
+    scheduler: Arc<Scheduler>,
+    dispatcher: Arc<DispatcherState>,
+    auth: Arc<AuthManager>,
+}
+
+impl Reloader {
+    pub fn new(
+        layers: ConfigLayers,
+        config: Config,
+        scheduler: Arc<Scheduler>,
+        dispatcher: Arc<DispatcherState>,
+        auth: Arc<AuthManager>,
+    ) -> Self {
+        Self {
+            layers,
+            current: tokio::sync::Mutex::new(config),
+            scheduler,
+            dispatcher,
+            auth,
+        }
+    }
+
+    /// Re-read the config files and environment and apply what changed
+    pub async fn reload(&self) -> Result<ReloadReport> {
+        let loaded = self.layers.load()?;
+        self.apply(loaded.config).await
+    }
+
+    /// Apply the reloadable part of `config`
+    pub async fn apply(&self, config: Config) -> Result<ReloadReport> {
+        config.validate_dispatcher().map_err(anyhow::Error::msg)?;
+        // One reload at a time, so reports never interleave
+        // Satu reload pada satu waktu, agar laporan tidak bercampur
+        let mut current = self.current.lock().await;
+        let old = Table::try_from(&*current)?;
+        let new = Table::try_from(&config)?;
+
+        let mut report = ReloadReport::default();
+        let mut next = old.clone();
+        for (section, keys) in KEYS {
+            // Worker settings are not used by the dispatcher
+            if *section == "worker" {
+                continue;
+            }
+            for (key, _) in *keys {
+                let before = old.get(*section).and_then(|s| s.get(key));
+                let after = new.get(*section).and_then(|s| s.get(key));
+                if before == after {
+                    continue;
+                }
+                let name = format!("{}.{}", section, key);
+                // Encrypted sessions derive their keys from the psk the
+                // transport started with
+                // Sesi terenkripsi menurunkan kuncinya dari psk yang dipakai
+                // saat transport dimulai
+                let pinned = matches!(name.as_str(), "security.psk" | "security.accepted_psks") && current.security.encrypt;
+                if pinned || !RELOADABLE.contains(&name.as_str()) {
+                    report.restart_required.push(name);
+                    continue;
+                }
+                if let Some(Value::Table(target)) = next.get_mut(*section) {
+                    match after {
+                        Some(value) => target.insert(key.to_string(), value.clone()),
+                        None => target.remove(*key),
+                    };
+                }
+                report.applied.push(name);
+            }
+        }
+
+        let next = Config::deserialize(Value::Table(next))?;
+        let changed = |key: &str| report.applied.iter().any(|name| name == key);
+        if changed("log.level") {
+            let level = next
+                .log
+                .level
+                .clone()
+                .or_else(|| std::env::var("RUST_LOG").ok())
+                .unwrap_or_else(|| "warn".to_string());
+            crate::util::set_log_level(&level)?;
+        }
+        if changed("security.whitelist") || changed("security.psk") || changed("security.accepted_psks") {
+            let mut security = self.dispatcher.security.write().await;
+            security.set_whitelist(next.security.whitelist.clone());
+            security.set_key(next.security.psk.clone());
+            security.set_accepted_keys(next.security.accepted_psks.clone());
+        }
+        if changed("security.api_secret") {
+            let secret = next
+                .security
+                .api_secret
+                .clone()
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            let overlap = chrono::Duration::seconds(next.security.secret_overlap as i64);
+            self.auth.rotate_secret(secret, overlap);
+        }
+        if changed("dispatcher.scheduling_policy") {
+            self.scheduler.set_policy(next.dispatcher.scheduling_policy.build()).await;
+        }
+        if changed("dispatcher.shares") || changed("dispatcher.usage_half_life") {
+            self.scheduler.set_fair_share(next.dispatcher.fair_share()).await;
+        }
+        *current = next;
+
+        info!(
+            "[CONFIG] Reloaded: applied {:?}, restart required for {:?}",
+            report.applied, report.restart_required
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Claims;
+    use crate::scheduler::PolicyKind;
+
+    #[tokio::test]
+    async fn test_reload_applies_and_reports() {
+        let mut config = Config::default();
+        config.security.api_secret = Some("first".to_string());
+        let scheduler = Arc::new(Scheduler::new());
+        let dispatcher = Arc::new(DispatcherState::new("test".to_string(), 0));
+        let auth = Arc::new(AuthManager::new("first".to_string()));
+        let reloader = Reloader::new(
+            ConfigLayers::default(),
+            config.clone(),
+            scheduler.clone(),
+            dispatcher.clone(),
+            auth.clone(),
+        );
+        let token = auth
+            .generate_token(&Claims::new("alice".to_string(), "client".to_string(), Vec::new()))
+            .unwrap();
+
+        let mut changed = config.clone();
+        changed.security.whitelist = vec!["w1".to_string()];
+        changed.security.api_secret = Some("second".to_string());
+        changed.dispatcher.scheduling_policy = PolicyKind::LeastLoaded;
+        changed.dispatcher.port = 9000;
+        let report = reloader.apply(changed.clone()).await.unwrap();
+
+        assert_eq!(
+            report.applied,
+            vec!["dispatcher.scheduling_policy", "security.whitelist", "security.api_secret"]
+        );
+        assert_eq!(report.restart_required, vec!["dispatcher.port"]);
+        assert_eq!(scheduler.policy_name().await, "least-loaded");
+        assert!(!dispatcher.security.read().await.is_whitelisted("w2"));
+        // Tokens signed with the old secret still verify during the overlap
+        assert!(auth.verify_token(&token).is_ok());
+
+        // The port was not applied, so it is reported again
+        let report = reloader.apply(changed).await.unwrap();
+        assert!(report.applied.is_empty());
+        assert_eq!(report.restart_required, vec!["dispatcher.port"]);
+
+        let mut invalid = config;
+        invalid.security.require_auth = true;
+        invalid.security.api_secret = None;
+        assert!(reloader.apply(invalid).await.is_err());
+        assert_eq!(scheduler.policy_name().await, "least-loaded");
+    }
+}
@@ -104,6 +104,11 @@ impl Discovery {
                                                     .unwrap()
                                                     .as_secs() as i64,
                                                 platform: "linux".to_string(),
+                                                capacity: Default::default(),
+                                                allocated: Default::default(),
+                                                labels: Default::default(),
+                                                affinity_groups: Default::default(),
+                                                availability: Default::default(),
                                             };
                                             // Tambahkan atau perbarui worker dalam daftar
                                             let mut w = workers.write().await;
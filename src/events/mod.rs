@@ -0,0 +1,313 @@
+// Cluster activity events, published by the dispatcher and streamed to API clients
+// Event aktivitas klaster, diterbitkan oleh dispatcher dan dialirkan ke klien API
+
+use crate::protocol::{OutputStream, TaskStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+/// Events kept for clients resuming with a last event id
+pub const HISTORY_SIZE: usize = 1024;
+
+/// Output chunks kept per task, apart from the event history
+pub const OUTPUT_HISTORY_SIZE: usize = 256;
+
+/// Tasks whose recent output is kept for replay
+pub const OUTPUT_TASKS: usize = 64;
+
+/// Something that happened in the cluster
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    TaskSubmitted {
+        task_id: String,
+        command: String,
+        owner: String,
+        parent_id: Option<String>,
+    },
+    TaskAssigned {
+        task_id: String,
+        worker_id: String,
+    },
+    TaskProgress {
+        task_id: String,
+        progress: f32,
+    },
+    TaskOutput {
+        task_id: String,
+        stream: OutputStream,
+        text: String,
+    },
+    TaskCompleted {
+        task_id: String,
+        worker_id: String,
+        status: TaskStatus,
+        exit_code: Option<i32>,
+        duration_ms: u64,
+    },
+    WorkerJoined {
+        worker_id: String,
+        name: String,
+        address: String,
+        port: u16,
+    },
+    WorkerLeft {
+        worker_id: String,
+        reason: String,
+    },
+    PeerDiscovered {
+        peer_id: String,
+        name: String,
+        address: String,
+        port: u16,
+    },
+}
+
+impl Event {
+    /// Every event type name, as used by filters and the SSE `event:` field
+    pub const KINDS: &'static [&'static str] = &[
+        "task_submitted",
+        "task_assigned",
+        "task_progress",
+        "task_output",
+        "task_completed",
+        "worker_joined",
+        "worker_left",
+        "peer_discovered",
+    ];
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::TaskSubmitted { .. } => "task_submitted",
+            Event::TaskAssigned { .. } => "task_assigned",
+            Event::TaskProgress { .. } => "task_progress",
+            Event::TaskOutput { .. } => "task_output",
+            Event::TaskCompleted { .. } => "task_completed",
+            Event::WorkerJoined { .. } => "worker_joined",
+            Event::WorkerLeft { .. } => "worker_left",
+            Event::PeerDiscovered { .. } => "peer_discovered",
+        }
+    }
+
+    /// Task the event is about, if any
+    pub fn task_id(&self) -> Option<&str> {
+        match self {
+            Event::TaskSubmitted { task_id, .. }
+            | Event::TaskAssigned { task_id, .. }
+            | Event::TaskProgress { task_id, .. }
+            | Event::TaskOutput { task_id, .. }
+            | Event::TaskCompleted { task_id, .. } => Some(task_id),
+            _ => None,
+        }
+    }
+}
+
+/// An event with its position in the stream
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventRecord {
+    /// Increases by one per event; clients resume after it
+    pub id: u64,
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Which events a subscriber wants; empty means all
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    pub kinds: Option<HashSet<String>>,
+    pub task_id: Option<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &Event) -> bool {
+        self.kinds.as_ref().is_none_or(|kinds| kinds.contains(event.kind()))
+            && self.task_id.as_deref().is_none_or(|id| event.task_id() == Some(id))
+    }
+}
+
+struct History {
+    next_id: u64,
+    records: VecDeque<EventRecord>,
+    // Recent output per task, oldest task first
+    // Output terbaru per tugas, tugas terlama lebih dahulu
+    output: VecDeque<(String, VecDeque<EventRecord>)>,
+}
+
+/// A subscription to the bus. Task output arrives on its own receiver, so a
+/// chatty task can neither evict events from the history nor make an event
+/// subscriber lag.
+pub struct Subscription {
+    /// Retained events and output after the last id, in id order
+    pub backlog: Vec<EventRecord>,
+    pub events: broadcast::Receiver<EventRecord>,
+    pub output: broadcast::Receiver<EventRecord>,
+}
+
+// Broadcast bus fanning events out to every subscriber, with a bounded history for resuming
+// Bus siaran yang menyebarkan event ke setiap pelanggan, dengan riwayat terbatas untuk melanjutkan
+pub struct EventBus {
+    sender: broadcast::Sender<EventRecord>,
+    output: broadcast::Sender<EventRecord>,
+    history: Mutex<History>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(HISTORY_SIZE);
+        let (output, _) = broadcast::channel(HISTORY_SIZE);
+        Self {
+            sender,
+            output,
+            history: Mutex::new(History {
+                next_id: 1,
+                records: VecDeque::with_capacity(HISTORY_SIZE),
+                output: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Number the event and hand it to every subscriber
+    pub fn publish(&self, event: Event) -> u64 {
+        // Sending under the lock keeps ids in order for subscribers
+        // Mengirim di bawah kunci menjaga urutan id bagi pelanggan
+        let mut history = self.history.lock().unwrap();
+        let record = EventRecord {
+            id: history.next_id,
+            timestamp: chrono::Local::now().timestamp(),
+            event,
+        };
+        history.next_id += 1;
+        if let Event::TaskOutput { task_id, .. } = &record.event {
+            // Output shares the id sequence but is kept per task, off the event history
+            // Output berbagi urutan id tetapi disimpan per tugas, di luar riwayat event
+            let position = history.output.iter().position(|(id, _)| id == task_id);
+            let mut chunks = match position.and_then(|i| history.output.remove(i)) {
+                Some((_, chunks)) => chunks,
+                None => VecDeque::new(),
+            };
+            if chunks.len() == OUTPUT_HISTORY_SIZE {
+                chunks.pop_front();
+            }
+            chunks.push_back(record.clone());
+            if history.output.len() == OUTPUT_TASKS {
+                history.output.pop_front();
+            }
+            history.output.push_back((task_id.clone(), chunks));
+            let _ = self.output.send(record.clone());
+            return record.id;
+        }
+        if history.records.len() == HISTORY_SIZE {
+            history.records.pop_front();
+        }
+        history.records.push_back(record.clone());
+        let _ = self.sender.send(record.clone());
+        record.id
+    }
+
+    /// Subscribe to new events, together with the retained events after
+    /// `last_id`. No event is missed or repeated between the two. Task output
+    /// is not included; see [`EventBus::subscribe`].
+    pub fn subscribe_from(&self, last_id: Option<u64>) -> (Vec<EventRecord>, broadcast::Receiver<EventRecord>) {
+        let history = self.history.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        let backlog = match last_id {
+            Some(last_id) => history.records.iter().filter(|r| r.id > last_id).cloned().collect(),
+            None => Vec::new(),
+        };
+        (backlog, receiver)
+    }
+
+    /// Subscribe to new events and task output. The backlog holds the
+    /// retained events after `last_id`, and the retained output of
+    /// `output_task` when given.
+    pub fn subscribe(&self, last_id: Option<u64>, output_task: Option<&str>) -> Subscription {
+        let history = self.history.lock().unwrap();
+        let events = self.sender.subscribe();
+        let output = self.output.subscribe();
+        let mut backlog: Vec<EventRecord> = Vec::new();
+        if let Some(last_id) = last_id {
+            backlog.extend(history.records.iter().filter(|r| r.id > last_id).cloned());
+            if let Some((_, chunks)) = output_task.and_then(|task| history.output.iter().find(|(id, _)| id == task)) {
+                backlog.extend(chunks.iter().filter(|r| r.id > last_id).cloned());
+            }
+            backlog.sort_by_key(|r| r.id);
+        }
+        Subscription { backlog, events, output }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assigned(task_id: &str) -> Event {
+        Event::TaskAssigned {
+            task_id: task_id.to_string(),
+            worker_id: "w1".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_and_filter() {
+        let bus = EventBus::new();
+        bus.publish(assigned("t1"));
+        let second = bus.publish(assigned("t2"));
+        bus.publish(Event::WorkerLeft {
+            worker_id: "w1".to_string(),
+            reason: "left".to_string(),
+        });
+
+        let (backlog, mut receiver) = bus.subscribe_from(Some(second - 1));
+        assert_eq!(backlog.iter().map(|r| r.id).collect::<Vec<_>>(), vec![second, second + 1]);
+        bus.publish(assigned("t3"));
+        assert_eq!(receiver.recv().await.unwrap().id, second + 2);
+
+        let filter = EventFilter {
+            kinds: Some(HashSet::from(["task_assigned".to_string()])),
+            task_id: Some("t2".to_string()),
+        };
+        let kept: Vec<_> = backlog.iter().filter(|r| filter.matches(&r.event)).collect();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].event.task_id(), Some("t2"));
+
+        let json = serde_json::to_value(kept[0]).unwrap();
+        assert_eq!(json["type"], "task_assigned");
+        assert!(Event::KINDS.contains(&kept[0].event.kind()));
+    }
+
+    #[tokio::test]
+    async fn test_output_stays_out_of_the_event_history() {
+        let bus = EventBus::new();
+        let first = bus.publish(assigned("t1"));
+        let (_, mut events) = bus.subscribe_from(None);
+        for i in 0..HISTORY_SIZE + 1 {
+            bus.publish(Event::TaskOutput {
+                task_id: "t1".to_string(),
+                stream: OutputStream::Stdout,
+                text: format!("{}\n", i),
+            });
+        }
+        let last = bus.publish(assigned("t2"));
+
+        // The chatty task neither evicted t1 nor made the event subscriber lag
+        let (backlog, _) = bus.subscribe_from(Some(0));
+        assert_eq!(backlog.iter().map(|r| r.id).collect::<Vec<_>>(), vec![first, last]);
+        assert_eq!(events.recv().await.unwrap().id, last);
+
+        // Its latest output is still replayed to a follower of the task
+        let subscription = bus.subscribe(Some(first), Some("t1"));
+        assert_eq!(subscription.backlog.len(), OUTPUT_HISTORY_SIZE + 1);
+        assert_eq!(subscription.backlog.last().unwrap().id, last);
+        assert!(subscription.backlog[..OUTPUT_HISTORY_SIZE]
+            .iter()
+            .all(|r| r.event.kind() == "task_output"));
+    }
+}
@@ -1,10 +1,15 @@
-use crate::protocol::{Task, TaskStatus};
+use crate::protocol::{OutputStream, Task, TaskStatus};
 use anyhow::Result;
 use std::path::PathBuf;
 use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 use tokio::process::Command;
+use tokio::sync::mpsc;
 use tracing::{error, info};
 
+/// Receives a task's output while it runs, one line at a time
+pub type OutputSink = mpsc::UnboundedSender<(OutputStream, String)>;
+
 // Task execution engine for running shell commands
 // Mesin eksekusi tugas untuk menjalankan perintah shell
 pub struct Executor {
@@ -22,9 +27,9 @@ impl Executor {
         }
     }
 
-    // Execute task asynchronously with output capture
-    // Jalankan tugas secara asinkron dengan penangkapan output
-    pub async fn execute(&self, task: &Task) -> Result<ExecutionResult> {
+    // Execute task asynchronously with output capture, passing each line to `output` as well
+    // Jalankan tugas secara asinkron dengan penangkapan output, sambil meneruskan tiap baris ke `output`
+    pub async fn execute(&self, task: &Task, output: Option<OutputSink>) -> Result<ExecutionResult> {
         if !self.allow_shell {
             return Err(anyhow::anyhow!("Shell execution is not allowed"));
         }
@@ -40,21 +45,21 @@ impl Executor {
         let mut child = Command::new("sh")
             .arg("-c")
             .arg(&task.command)
+            .envs(&task.env)
             .current_dir(&self.workdir)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            // Stopping the task (cancel, shutdown) must not leave the process behind
+            .kill_on_drop(true)
             .spawn()?;
 
-        let mut stdout = String::new();
-        let mut stderr = String::new();
-
-        if let Some(mut out) = child.stdout.take() {
-            tokio::io::AsyncReadExt::read_to_string(&mut out, &mut stdout).await.ok();
-        }
-
-        if let Some(mut err) = child.stderr.take() {
-            tokio::io::AsyncReadExt::read_to_string(&mut err, &mut stderr).await.ok();
-        }
+        // Both outputs are read together, so a task filling one pipe cannot stall on it
+        // Kedua output dibaca bersamaan, agar tugas yang memenuhi satu pipe tidak macet
+        let (stdout, stderr) = tokio::join!(
+            read_output(child.stdout.take(), OutputStream::Stdout, output.as_ref()),
+            read_output(child.stderr.take(), OutputStream::Stderr, output.as_ref()),
+        );
+        drop(output);
 
         let status = child.wait().await?;
         let exit_code = status.code();
@@ -85,10 +90,10 @@ impl Executor {
 
     // Execute task with timeout protection to prevent infinite runs
     // Jalankan tugas dengan perlindungan timeout untuk mencegah proses tak terbatas
-    pub async fn execute_with_timeout(&self, task: &Task) -> Result<ExecutionResult> {
+    pub async fn execute_with_timeout(&self, task: &Task, output: Option<OutputSink>) -> Result<ExecutionResult> {
         let timeout_duration = std::time::Duration::from_secs(task.timeout);
         
-        match tokio::time::timeout(timeout_duration, self.execute(task)).await {
+        match tokio::time::timeout(timeout_duration, self.execute(task, output)).await {
             Ok(result) => result,
             Err(_) => {
                 error!("Task {} timed out after {}s", task.id, task.timeout);
@@ -129,6 +134,35 @@ impl Executor {
     }
 }
 
+// Read one output of a task to its end, handing each line to `sink` as it arrives
+// Baca satu output tugas sampai habis, menyerahkan tiap baris ke `sink` saat tiba
+async fn read_output<R: AsyncRead + Unpin>(
+    reader: Option<R>,
+    stream: OutputStream,
+    sink: Option<&OutputSink>,
+) -> String {
+    let mut text = String::new();
+    let Some(reader) = reader else {
+        return text;
+    };
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&line);
+                if let Some(sink) = sink {
+                    let _ = sink.send((stream, line.to_string()));
+                }
+                text.push_str(&line);
+            }
+        }
+    }
+    text
+}
+
 // Result structure returned after task execution
 // Struktur hasil yang dikembalikan setelah eksekusi tugas
 pub struct ExecutionResult {
@@ -154,4 +188,23 @@ mod tests {
         assert!(executor.validate_command("ls -la"));
         assert!(!executor.validate_command("rm -rf /"));
     }
+
+    #[tokio::test]
+    async fn test_output_is_passed_on_line_by_line() {
+        let executor = Executor::new(std::env::temp_dir(), true);
+        let task = Task::new("echo one; echo two >&2; printf three".to_string());
+        let (sink, mut lines) = mpsc::unbounded_channel();
+
+        let result = executor.execute(&task, Some(sink)).await.unwrap();
+        assert_eq!(result.stdout, "one\nthree");
+        assert_eq!(result.stderr, "two\n");
+
+        let mut received = Vec::new();
+        while let Some(line) = lines.recv().await {
+            received.push(line);
+        }
+        let stdout: Vec<_> = received.iter().filter(|(s, _)| *s == OutputStream::Stdout).map(|(_, l)| l.as_str()).collect();
+        assert_eq!(stdout, vec!["one\n", "three"]);
+        assert!(received.contains(&(OutputStream::Stderr, "two\n".to_string())));
+    }
 }
@@ -2,9 +2,13 @@
 // P2P network resource sharing for compute tasks
 // Koordinator Tugas Komputasi Offline - Berbagi sumber daya jaringan peer-to-peer untuk tugas komputasi
 
+pub mod client;
 pub mod cmd;
+pub mod config;
 pub mod discovery;
+pub mod events;
 pub mod executor;
+pub mod metrics;
 pub mod protocol;
 pub mod scheduler;
 pub mod security;
@@ -27,9 +31,14 @@ pub mod resources;
 pub mod api;
 pub mod transport_quic;
 pub mod sandbox;
+pub mod webhooks;
+pub mod audit;
+pub mod secrets;
+pub mod signing;
 
 pub use cmd::Command;
 pub use discovery::Discovery;
+pub use events::{Event, EventBus};
 pub use executor::Executor;
 pub use protocol::{Task, TaskResult, WorkerInfo};
 pub use scheduler::Scheduler;
@@ -40,7 +49,7 @@ pub use p2p_distribution::P2PDistributor;
 
 // New exports
 pub use security_enhanced::SecurityManager;
-pub use persistence::PersistentStore;
+pub use persistence::{MemoryStore, SqliteStore, TaskStore};
 pub use auth::AuthManager;
 pub use resources::ResourceLimits;
 pub use api::ApiState;
@@ -7,17 +7,17 @@
 use anyhow::Result;
 use clap::Parser;
 use octaskly::cmd::Cli;
-use octaskly::scheduler::Scheduler;
+use octaskly::config::{Config, ConfigLayers, DispatcherConfig, Reloader};
+use octaskly::scheduler::{DrainMode, ResultDisposition, Scheduler};
 use octaskly::state::{DispatcherState, WorkerState};
 use octaskly::executor::Executor;
-use octaskly::protocol::{Message, WorkerInfo};
+use octaskly::protocol::{Message, Resources, WorkerInfo};
 use octaskly::util;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use tokio::time::{Duration, interval};
-use tokio::sync::RwLock;
 use tracing::{error, info, warn, debug};
 
 #[tokio::main]
@@ -64,11 +64,11 @@ async fn main() -> Result<()> {
 
     util::setup_logging();
 
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
     let _monitor = cli.monitor;
     let _verbose = cli.verbose;
     
-    let cmd = match cli.command {
+    let cmd = match cli.command.take() {
         Some(c) => c,
         None => {
             Cli::parse_and_run()?
@@ -76,73 +76,353 @@ async fn main() -> Result<()> {
     };
 
     match cmd {
-        octaskly::cmd::Command::Dispatcher {
-            bind,
-            port,
-            workdir,
-            max_workers: _,
-            task_timeout: _,
-            p2p_enabled: _,
-            discovery_port: _,
-            ui: _ui,
-        } => {
+        cmd @ (octaskly::cmd::Command::Dispatcher { .. } | octaskly::cmd::Command::D { .. }) => {
+            let layers = ConfigLayers {
+                explicit: cli.config.clone(),
+                flags: cmd.config_overrides(),
+            };
+            let config = layers.load()?.config;
+            if let Err(e) = config.validate_dispatcher() {
+                eprintln!("❌ Dispatcher validation failed: {}", e);
+                std::process::exit(1);
+            }
+            if let Some(level) = &config.log.level {
+                util::set_log_level(level)?;
+            }
             if _monitor {
                 info!("[DISPATCHER] Monitor mode enabled");
             }
-            run_dispatcher(&bind, port, workdir).await?;
-        }
-        octaskly::cmd::Command::Worker {
-            name,
-            dispatcher: _,
-            dispatcher_port: _,
-            max_jobs,
-            cpu_cores: _,
-            memory_mb: _,
-            gpu: _,
-            allow_shell,
-        } => {
+            run_dispatcher(config, layers).await?;
+        }
+        cmd @ (octaskly::cmd::Command::Worker { .. } | octaskly::cmd::Command::W { .. }) => {
+            let loaded = octaskly::config::load(cli.config.as_deref(), cmd.config_overrides())?;
+            if let Err(e) = loaded.config.log.validate().and(loaded.config.security.validate_transport()) {
+                eprintln!("❌ Worker validation failed: {}", e);
+                std::process::exit(1);
+            }
+            if let Some(level) = &loaded.config.log.level {
+                util::set_log_level(level)?;
+            }
+            let config = loaded.config.worker;
+            let name = match config.validate() {
+                Ok(name) => name.to_string(),
+                Err(e) => {
+                    eprintln!("❌ Worker validation failed: {}", e);
+                    std::process::exit(1);
+                }
+            };
             if _monitor {
                 info!("[WORKER] Monitor mode enabled");
             }
-            run_worker(&name, allow_shell, max_jobs).await?;
+            // Advertised capacity: explicit settings override auto-detection
+            // Kapasitas yang diumumkan: pengaturan eksplisit menggantikan deteksi otomatis
+            let detected = Resources::detect_local();
+            if config.memory_mb.is_none() && detected.memory_mb == 0 {
+                warn!("[WORKER] Could not detect memory; set --memory-mb or tasks requesting memory will not be placed here");
+            }
+            let capacity = Resources {
+                cpu_millicores: config
+                    .cpu_cores
+                    .map(|cores| cores as u64 * 1000)
+                    .unwrap_or(detected.cpu_millicores),
+                memory_mb: config.memory_mb.unwrap_or(detected.memory_mb),
+                disk_mb: config.disk_mb,
+                gpus: if config.gpu { 1 } else { 0 },
+                custom: config.resources,
+            };
+            // Only a dispatcher set nowhere is looked up over mDNS
+            // Hanya dispatcher yang tidak disetel di mana pun yang dicari melalui mDNS
+            let discover_dispatcher = config.mdns
+                && loaded.sources.get("worker.dispatcher") == Some(&octaskly::config::Source::Default);
+            run_worker(WorkerOptions {
+                name,
+                dispatcher: config.dispatcher,
+                dispatcher_port: config.dispatcher_port,
+                discover_dispatcher,
+                mdns: config.mdns,
+                allow_shell: config.allow_shell,
+                max_jobs: config.max_jobs,
+                capacity,
+                labels: config.labels.into_iter().collect(),
+                grace_period: Duration::from_secs(config.grace_period),
+                metrics_port: config.metrics_port,
+                transport: frame_transport(&loaded.config.security)?,
+                trusted_keys: octaskly::signing::TrustedKeys::new(&loaded.config.security.trusted_keys)?,
+                psk: loaded.config.security.psk,
+                accepted_psks: loaded.config.security.accepted_psks,
+            })
+            .await?;
         }
-        _ => {
-            eprintln!("Usage: octaskly <dispatcher | worker | d | w>");
-            std::process::exit(1);
+        octaskly::cmd::Command::Config { action } => match action {
+            octaskly::cmd::ConfigCommand::Show => {
+                let loaded = octaskly::config::load(cli.config.as_deref(), toml::Table::new())?;
+                print!("{}", loaded.render()?);
+            }
+            octaskly::cmd::ConfigCommand::Reload { api } => {
+                let code = octaskly::client::cli::reload(&api).await?;
+                std::process::exit(code);
+            }
+        },
+        octaskly::cmd::Command::Token { subject, role, hours, projects } => {
+            let loaded = octaskly::config::load(cli.config.as_deref(), toml::Table::new())?;
+            let secret = loaded
+                .config
+                .security
+                .api_secret
+                .ok_or_else(|| anyhow::anyhow!("security.api_secret is not set"))?;
+            let role = octaskly::auth::Role::parse(&role)
+                .ok_or_else(|| anyhow::anyhow!("Unknown role '{}'", role))?;
+            let mut permissions = role.default_permissions();
+            permissions.extend(projects.iter().map(|project| octaskly::secrets::project_permission(project)));
+            let claims = octaskly::auth::Claims::with_lifetime(
+                subject,
+                role.to_string(),
+                permissions,
+                chrono::Duration::hours(hours),
+            );
+            println!("{}", octaskly::AuthManager::new(secret).generate_token(&claims)?);
+            // To revoke it later with DELETE /api/v1/tokens/<id>
+            // Untuk mencabutnya nanti dengan DELETE /api/v1/tokens/<id>
+            eprintln!("Token id: {}", claims.jti);
+        }
+        octaskly::cmd::Command::Secret { action } => {
+            let code = octaskly::client::cli::secret(action).await?;
+            std::process::exit(code);
+        }
+        octaskly::cmd::Command::Audit { action } => {
+            let code = octaskly::client::cli::audit(action).await?;
+            std::process::exit(code);
+        }
+        octaskly::cmd::Command::Keys { action } => {
+            let loaded = octaskly::config::load(cli.config.as_deref(), toml::Table::new())?;
+            match action {
+                octaskly::cmd::KeysCommand::Init { path, force } => {
+                    let path = path
+                        .or_else(|| loaded.config.security.keyfile_path())
+                        .ok_or_else(|| anyhow::anyhow!("No config directory; pass --path"))?;
+                    if path.exists() && !force {
+                        anyhow::bail!("{} already exists (use --force to replace it)", path.display());
+                    }
+                    octaskly::security_enhanced::KeyFile::generate().save(&path)?;
+                    println!("Wrote {}", path.display());
+                    println!("Copy it to every node, the same file must be used cluster-wide");
+                }
+                octaskly::cmd::KeysCommand::Id => {
+                    let manager = cluster_keys(&loaded.config.security)?;
+                    for (i, id) in manager.key_ids().iter().enumerate() {
+                        println!("{:08x}  {}", id, if i == 0 { "current" } else { "accepted" });
+                    }
+                }
+                octaskly::cmd::KeysCommand::Signing { path, force } => {
+                    let path = path
+                        .or_else(|| loaded.config.security.signing_key.clone())
+                        .or_else(|| dirs::config_dir().map(|dir| dir.join("octaskly").join("signing.key")))
+                        .ok_or_else(|| anyhow::anyhow!("No config directory; pass --path"))?;
+                    let signer = if path.exists() && !force {
+                        octaskly::signing::TaskSigner::load(&path)?
+                    } else {
+                        let signer = octaskly::signing::TaskSigner::generate();
+                        signer.save(&path)?;
+                        println!("Wrote {}", path.display());
+                        signer
+                    };
+                    println!("Public key: {}", signer.public_key());
+                    println!();
+                    println!("Dispatcher config:");
+                    println!("  [security]");
+                    println!("  signing_key = {:?}", path.display().to_string());
+                    println!("Worker config:");
+                    println!("  [security.trusted_keys]");
+                    println!("  dispatcher = \"{}\"", signer.public_key());
+                }
+            }
+        }
+        octaskly::cmd::Command::Db { action } => {
+            let db_path = action.db_path();
+            let db_path = db_path.to_string_lossy();
+            match action {
+                octaskly::cmd::DbCommand::Migrate { .. } => {
+                    let applied = octaskly::persistence::migrate_database(&db_path).await?;
+                    if applied.is_empty() {
+                        println!("{} is up to date", db_path);
+                    } else {
+                        println!("Applied migrations {:?} to {}", applied, db_path);
+                    }
+                }
+                octaskly::cmd::DbCommand::Status { .. } => {
+                    let status = octaskly::persistence::schema_status(&db_path).await?;
+                    println!("Database:       {}", db_path);
+                    println!("Schema version: {} (latest {})", status.current, status.latest);
+                    if status.current > status.latest {
+                        println!("Status:         newer than this build, upgrade octaskly");
+                    } else if status.pending.is_empty() {
+                        println!("Status:         up to date");
+                    } else {
+                        println!("Pending:        {:?}", status.pending);
+                    }
+                }
+            }
+        }
+        octaskly::cmd::Command::Client(command) => {
+            let code = octaskly::client::cli::run(command).await?;
+            std::process::exit(code);
         }
     }
 
     Ok(())
 }
 
+async fn run_dispatcher(config: Config, layers: ConfigLayers) -> Result<()> {
+    let full_config = config.clone();
+    let Config { dispatcher: config, security, .. } = config;
+    let fair_share = config.fair_share();
+    let DispatcherConfig {
+        bind,
+        port,
+        workdir,
+        max_workers,
+        task_timeout,
+        p2p_enabled,
+        discovery_port,
+        ui,
+        scheduling_policy,
+        api_port,
+        lease_timeout,
+        ..
+    } = config;
 
-async fn run_dispatcher(bind: &str, port: u16, workdir: PathBuf) -> Result<()> {
     // Initialize dispatcher with state management
     // Inisialisasi dispatcher dengan manajemen status
     info!("[DISPATCHER] Starting Octaskly Dispatcher on {}:{}", bind, port);
 
-    let dispatcher_state = Arc::new(DispatcherState::new("dispatcher".to_string(), port));
-    let scheduler = Arc::new(Scheduler::new());
-    let active_tasks: Arc<RwLock<std::collections::HashMap<String, String>>> = 
-        Arc::new(RwLock::new(std::collections::HashMap::new()));
+    // Create work directory if not exists
+    // Buat direktori kerja jika belum ada
+    util::ensure_dir(&workdir).await?;
+
+    // Every task state transition is written here so a restart loses nothing
+    // Setiap transisi status tugas ditulis di sini agar restart tidak kehilangan apa pun
+    let store: Arc<dyn octaskly::TaskStore> =
+        Arc::new(octaskly::SqliteStore::connect(&workdir.join("octaskly.db").to_string_lossy()).await?);
+    let mut dispatcher_state = DispatcherState::new("dispatcher".to_string(), port)
+        .with_store(store.clone())
+        .with_audit(octaskly::audit::AuditLog::open(&workdir.join("audit.log"), store.clone()).await?)
+        .with_transport(frame_transport(&security)?)
+        .with_secrets(secret_vault(&security, &workdir, store.clone())?)
+        .with_task_timeout(task_timeout);
+    if let Some(path) = &security.signing_key {
+        let signer = octaskly::signing::TaskSigner::load(path)?;
+        info!("[SECURITY] Signing task assignments with key {}", signer.public_key());
+        dispatcher_state = dispatcher_state.with_signer(signer, security.envelope_ttl);
+    }
+    let dispatcher_state = Arc::new(dispatcher_state);
+    if dispatcher_state.transport.is_encrypted() {
+        info!("[DISPATCHER] Worker connections are encrypted");
+    }
+    if security.psk.is_none() {
+        warn!("[SECURITY] No security.psk: workers do not prove their id, any peer can report for one");
+    } else if !dispatcher_state.transport.is_encrypted() {
+        warn!("[SECURITY] security.psk without security.encrypt: worker reports are proven per connection but sent in the clear");
+    }
+    {
+        let mut registration = dispatcher_state.security.write().await;
+        registration.set_whitelist(security.whitelist.clone());
+        registration.set_key(security.psk.clone());
+        registration.set_accepted_keys(security.accepted_psks.clone());
+    }
+    let scheduler = Arc::new(
+        Scheduler::with_fair_share(fair_share)
+            .with_max_workers(max_workers)
+            .with_lease_timeout(lease_timeout as i64)
+            .with_store(store.clone())
+            .with_events(dispatcher_state.events.clone()),
+    );
+    scheduler.set_policy(scheduling_policy.build()).await;
+
+    // Rebuild the queue and assignments left by a previous run
+    // Bangun ulang antrian dan penugasan dari eksekusi sebelumnya
+    let recovery = scheduler.recover().await?;
+    let restored = dispatcher_state.restore_results(1000).await?;
+    if recovery.queued + recovery.in_flight + restored > 0 {
+        info!(
+            "[DISPATCHER] Recovered {} queued tasks, {} in-flight assignments and {} results",
+            recovery.queued, recovery.in_flight, restored
+        );
+    }
+
+    // Push events to webhook subscribers, first finishing deliveries cut short by a restart
+    // Kirim event ke pelanggan webhook, selesaikan dulu pengiriman yang terpotong restart
+    let webhooks = Arc::new(octaskly::webhooks::WebhookDispatcher::new(store.clone()));
+    {
+        let webhooks = webhooks.clone();
+        tokio::spawn(async move {
+            match webhooks.resume_pending().await {
+                Ok(0) => {}
+                Ok(resumed) => info!("[WEBHOOK] Resumed {} pending deliveries", resumed),
+                Err(e) => warn!("[WEBHOOK] Failed to resume pending deliveries: {}", e),
+            }
+        });
+    }
+    tokio::spawn(webhooks.run(dispatcher_state.events.clone()));
 
     // Initialize P2P peer discovery and task distribution
     // Inisialisasi penemuan peer P2P dan distribusi task
-    let p2p_network = match start_p2p_discovery(
-        format!("dispatcher-{}", port),
-        "Octaskly-Dispatcher".to_string(),
-        5555,
-    ).await {
-        Ok(network) => {
-            info!("[P2P] P2P network initialized successfully");
-            Some(network)
+    let p2p_network = if !p2p_enabled {
+        info!("[P2P] P2P disabled by configuration");
+        None
+    } else {
+        // Advertised over mDNS so workers can pick a compatible dispatcher
+        // Diiklankan melalui mDNS agar worker dapat memilih dispatcher yang cocok
+        let mut capabilities = Vec::new();
+        if security.encrypt {
+            capabilities.push("encrypt".to_string());
         }
-        Err(e) => {
-            warn!("[P2P] P2P initialization warning: {}", e);
-            None
+        if security.signing_key.is_some() {
+            capabilities.push("signed".to_string());
+        }
+        if api_port.is_some() {
+            capabilities.push("api".to_string());
+        }
+        match start_p2p_discovery(
+            format!("dispatcher-{}", port),
+            "Octaskly-Dispatcher".to_string(),
+            discovery_port,
+            port,
+            capabilities,
+        ).await {
+            Ok(network) => {
+                info!("[P2P] P2P network initialized successfully");
+                Some(network)
+            }
+            Err(e) => {
+                warn!("[P2P] P2P initialization warning: {}", e);
+                None
+            }
         }
     };
 
+    // Forward newly discovered peers to the event stream
+    // Teruskan peer yang baru ditemukan ke aliran event
+    if let Some(network) = &p2p_network {
+        let mut discovered = network.subscribe_discoveries();
+        let events = dispatcher_state.events.clone();
+        tokio::spawn(async move {
+            loop {
+                match discovered.recv().await {
+                    Ok(peer) => {
+                        events.publish(octaskly::Event::PeerDiscovered {
+                            peer_id: peer.id,
+                            name: peer.name,
+                            address: peer.ip_address,
+                            port: peer.port,
+                        });
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
     // Initialize P2P task distributor for resource sharing
     // Inisialisasi distributor task P2P untuk berbagi resource
     // Default: 4 CPU cores, 8GB RAM, no GPU, 4 task slots
@@ -155,10 +435,6 @@ async fn run_dispatcher(bind: &str, port: u16, workdir: PathBuf) -> Result<()> {
         4,           // Task slots
     ));
 
-    // Create work directory if not exists
-    // Buat direktori kerja jika belum ada
-    util::ensure_dir(&workdir).await?;
-
     // Create network listener on specified address and port
     // Buat listener jaringan pada alamat dan port yang ditentukan
     let addr = format!("{}:{}", bind, port);
@@ -174,7 +450,6 @@ async fn run_dispatcher(bind: &str, port: u16, workdir: PathBuf) -> Result<()> {
     let listener_clone = listener.clone();
     let scheduler_clone = scheduler.clone();
     let dispatcher_state_clone = dispatcher_state.clone();
-    let active_tasks_clone = active_tasks.clone();
     
     tokio::spawn(async move {
         loop {
@@ -184,25 +459,88 @@ async fn run_dispatcher(bind: &str, port: u16, workdir: PathBuf) -> Result<()> {
                     
                     let scheduler = scheduler_clone.clone();
                     let dispatcher_state = dispatcher_state_clone.clone();
-                    let active_tasks = active_tasks_clone.clone();
                     
                     tokio::spawn(async move {
+                        let mut connection = match dispatcher_state.transport.accept(stream).await {
+                            Ok(connection) => connection,
+                            Err(e) => {
+                                warn!("[DISPATCHER] Refused connection from {}: {}", peer_addr, e);
+                                return;
+                            }
+                        };
+                        // A connection opening with an announcement is a registration handshake
+                        // Koneksi yang dibuka dengan pengumuman adalah jabat tangan pendaftaran
+                        let first = match connection.recv().await {
+                            Ok(message) => message,
+                            Err(e) => {
+                                debug!("[DISPATCHER] No message from {}: {}", peer_addr, e);
+                                return;
+                            }
+                        };
+                        if let Message::WorkerAnnounce(worker_info) = first {
+                            if let Err(e) =
+                                register_worker(&mut connection, peer_addr, worker_info, &scheduler, &dispatcher_state).await
+                            {
+                                warn!("[DISPATCHER] Registration from {} failed: {}", peer_addr, e);
+                            }
+                            return;
+                        }
+                        // Worker reports are only taken on a connection that proved the worker's id
+                        // Laporan worker hanya diterima pada koneksi yang membuktikan id worker
+                        let (worker, first) = match first {
+                            Message::WorkerHello { worker_id } => {
+                                match identify_worker(&mut connection, peer_addr, &worker_id, &scheduler, &dispatcher_state).await {
+                                    Ok(true) => {}
+                                    Ok(false) => return,
+                                    Err(e) => {
+                                        warn!("[DISPATCHER] Hello from {} failed: {}", peer_addr, e);
+                                        return;
+                                    }
+                                }
+                                match connection.recv().await {
+                                    Ok(message) => (Some(worker_id), message),
+                                    Err(e) => {
+                                        debug!("[DISPATCHER] No report from {}: {}", peer_addr, e);
+                                        return;
+                                    }
+                                }
+                            }
+                            message => (None, message),
+                        };
+                        if let Message::SecretRequest { task_id, worker_id } = first {
+                            if worker.as_deref() != Some(worker_id.as_str()) {
+                                warn!("[DISPATCHER] Refusing secret request for worker {} from {}", worker_id, peer_addr);
+                                return;
+                            }
+                            if let Err(e) = serve_secrets(
+                                &mut connection,
+                                peer_addr,
+                                &task_id,
+                                &worker_id,
+                                &scheduler,
+                                &dispatcher_state,
+                            )
+                            .await
+                            {
+                                warn!("[DISPATCHER] Secret request from {} failed: {}", peer_addr, e);
+                            }
+                            return;
+                        }
+                        if let Err(e) = handle_dispatcher_message(first, worker.as_deref(), &scheduler, &dispatcher_state).await {
+                            error!("Connection handler error: {}", e);
+                            return;
+                        }
+
                         if let Err(e) = 
                             octaskly::transport::Transport::handle_connection(
-                                stream,
+                                connection,
                                 move |msg| {
                                     let scheduler = scheduler.clone();
                                     let dispatcher_state = dispatcher_state.clone();
-                                    let active_tasks = active_tasks.clone();
+                                    let worker = worker.clone();
                                     
                                     Box::pin(async move {
-                                        handle_dispatcher_message(
-                                            msg,
-                                            &scheduler,
-                                            &dispatcher_state,
-                                            &active_tasks,
-                                        )
-                                        .await
+                                        handle_dispatcher_message(msg, worker.as_deref(), &scheduler, &dispatcher_state).await
                                     })
                                 }
                             ).await 
@@ -221,7 +559,7 @@ async fn run_dispatcher(bind: &str, port: u16, workdir: PathBuf) -> Result<()> {
     // Scheduler loop - assign tasks to idle workers
     // Loop penjadwal - tugaskan tugas ke worker yang menganggur
     let scheduler_clone = scheduler.clone();
-    let active_tasks_clone = active_tasks.clone();
+    let dispatcher_state_clone = dispatcher_state.clone();
     
     tokio::spawn(async move {
         let mut interval = interval(Duration::from_millis(500));
@@ -229,24 +567,32 @@ async fn run_dispatcher(bind: &str, port: u16, workdir: PathBuf) -> Result<()> {
         loop {
             interval.tick().await;
             
-            if let Some((task, mut worker)) = scheduler_clone.schedule_next_task().await {
+            for (task, worker) in scheduler_clone.schedule_batch(usize::MAX).await {
                 debug!("[SCHEDULER] Assigning task {} to worker {}", task.id, worker.id);
                 
-                // Mark task as assigned
-                active_tasks_clone.write().await.insert(task.id.clone(), worker.id.clone());
-                
-                // Update worker current jobs
-                worker.current_jobs += 1;
-                scheduler_clone.update_worker(&worker.id, worker.clone()).await;
-                
                 // Try to send task to worker
                 let worker_addr = format!("{}:{}", worker.address, worker.port);
                 if let Ok(socket_addr) = worker_addr.parse::<SocketAddr>() {
-                    let message = Message::AssignTask(task.clone());
-                    if let Err(e) = octaskly::transport::Transport::new().send_message(socket_addr, &message).await {
-                        warn!("Failed to send task to worker {}: {}", worker.id, e);
-                        // Requeue task
-                        scheduler_clone.enqueue(task).await;
+                    let sent = match dispatcher_state_clone.assignment(task.clone(), &worker.id) {
+                        Ok(message) => dispatcher_state_clone.transport.send_message(socket_addr, &message).await,
+                        Err(e) => Err(e),
+                    };
+                    match sent {
+                        Ok(()) => scheduler_clone.mark_running(&task.id).await,
+                        Err(e) => {
+                            warn!("Failed to send task to worker {}: {}", worker.id, e);
+                            // Retry in place, counting the attempt, or give the task up
+                            // Ulangi di tempat, dengan menghitung percobaan, atau lepaskan tugas
+                            if let Some(expiry) = scheduler_clone.delivery_failed(&task.id).await {
+                                if !expiry.requeued {
+                                    let reason = format!(
+                                        "Could not deliver the task to worker {} in {} attempts",
+                                        expiry.worker_id, expiry.task.attempts
+                                    );
+                                    dispatcher_state_clone.store_result(lost_result(&expiry, reason)).await;
+                                }
+                            }
+                        }
                     }
                 }
             }
@@ -255,14 +601,34 @@ async fn run_dispatcher(bind: &str, port: u16, workdir: PathBuf) -> Result<()> {
 
     // Heartbeat cleanup loop - remove offline workers
     // Loop pembersihan detak jantung - hapus worker yang offline
+    //
+    // Tasks of dead workers (or with expired leases) are requeued, or recorded
+    // as lost once their retry budget is used up
+    // Tugas dari worker yang mati (atau dengan lease kedaluwarsa) dimasukkan
+    // ulang, atau dicatat hilang setelah batas pengulangannya habis
     let scheduler_clone = scheduler.clone();
+    let dispatcher_state_clone = dispatcher_state.clone();
     
     tokio::spawn(async move {
-        let mut interval = interval(Duration::from_secs(10));
+        let mut interval = interval(Duration::from_secs(5));
         
         loop {
             interval.tick().await;
-            scheduler_clone.cleanup_offline_workers(30).await;
+            let (evicted, mut expired) = scheduler_clone.cleanup_offline_workers(lease_timeout as i64).await;
+            expired.extend(scheduler_clone.expire_leases().await);
+            for worker_id in evicted {
+                let entry = octaskly::audit::AuditEntry::new("worker.evict", octaskly::audit::SYSTEM_ACTOR, true)
+                    .with_subject(worker_id)
+                    .with_reason("heartbeat timeout");
+                dispatcher_state_clone.audit.record(entry).await;
+            }
+            
+            for expiry in expired {
+                if !expiry.requeued {
+                    let reason = format!("Worker {} was lost after {} attempts", expiry.worker_id, expiry.task.attempts);
+                    dispatcher_state_clone.store_result(lost_result(&expiry, reason)).await;
+                }
+            }
         }
     });
 
@@ -298,40 +664,443 @@ async fn run_dispatcher(bind: &str, port: u16, workdir: PathBuf) -> Result<()> {
         }
     });
 
+    // REST API for task submission and worker management
+    // REST API untuk pengiriman tugas dan manajemen worker
+    let secret = security
+        .api_secret
+        .clone()
+        .or_else(|| std::env::var("OCTASKLY_JWT_SECRET").ok())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let auth = Arc::new(octaskly::AuthManager::new(secret));
+    for token_id in store.revoked_tokens().await? {
+        auth.revoke_token(&token_id);
+    }
+
+    // Re-read the configuration on SIGHUP; the API offers the same as POST /api/v1/admin/reload
+    // Baca ulang konfigurasi saat SIGHUP; API menyediakan hal yang sama lewat POST /api/v1/admin/reload
+    let reloader = Arc::new(Reloader::new(
+        layers,
+        full_config,
+        scheduler.clone(),
+        dispatcher_state.clone(),
+        auth.clone(),
+    ));
+    #[cfg(unix)]
+    {
+        use octaskly::audit::{AuditEntry, SYSTEM_ACTOR};
+        let reloader = reloader.clone();
+        let dispatcher_state = dispatcher_state.clone();
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                info!("[CONFIG] SIGHUP received, reloading configuration");
+                match reloader.reload().await {
+                    Ok(report) => {
+                        if !report.restart_required.is_empty() {
+                            warn!("[CONFIG] Restart needed to apply {}", report.restart_required.join(", "));
+                        }
+                        let entry =
+                            AuditEntry::new("config.reload", SYSTEM_ACTOR, true).with_subject(report.applied.join(","));
+                        dispatcher_state.audit.record(entry).await;
+                    }
+                    Err(e) => error!("[CONFIG] Reload failed, keeping the running configuration: {:#}", e),
+                }
+            }
+        });
+    }
+
+    if let Some(api_port) = api_port {
+        let api_state = octaskly::ApiState {
+            scheduler: scheduler.clone(),
+            dispatcher: dispatcher_state.clone(),
+            auth: auth.clone(),
+            store: store.clone(),
+            require_auth: security.require_auth,
+            reloader: Some(reloader.clone()),
+        };
+        let api_addr = format!("{}:{}", bind, api_port);
+        tokio::spawn(async move {
+            if let Err(e) = octaskly::api::start_api_server(&api_addr, api_state).await {
+                error!("[API] Server error: {}", e);
+            }
+        });
+    }
+
+    // Terminal dashboard; quitting it stops the dispatcher
+    // Dashboard terminal; keluar darinya menghentikan dispatcher
+    let (ui_quit_tx, ui_quit_rx) = tokio::sync::oneshot::channel();
+    if ui {
+        let scheduler = scheduler.clone();
+        let dispatcher_state = dispatcher_state.clone();
+        let handle = tokio::runtime::Handle::current();
+        std::thread::spawn(move || {
+            if let Err(e) = run_dashboard(handle, scheduler, dispatcher_state) {
+                error!("[UI] Dashboard error: {}", e);
+            }
+            let _ = ui_quit_tx.send(());
+        });
+    }
+
     // Handle graceful shutdown
     // Tangani penutupan yang elegan
-    tokio::signal::ctrl_c().await?;
+    tokio::select! {
+        _ = shutdown_signal() => {}
+        _ = async {
+            if ui {
+                let _ = ui_quit_rx.await;
+            } else {
+                std::future::pending::<()>().await;
+            }
+        } => {}
+    }
     info!("[DISPATCHER] Shutting down gracefully...");
 
     Ok(())
 }
 
+// Drive the terminal dashboard on a dedicated thread until the user quits
+// Jalankan dashboard terminal pada thread khusus hingga pengguna keluar
+fn run_dashboard(
+    handle: tokio::runtime::Handle,
+    scheduler: Arc<Scheduler>,
+    dispatcher_state: Arc<DispatcherState>,
+) -> Result<()> {
+    use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+    use crossterm::terminal;
+    use octaskly::tui::{Ui, WorkerAction};
+
+    terminal::enable_raw_mode()?;
+    crossterm::execute!(std::io::stdout(), terminal::EnterAlternateScreen)?;
+    let mut ui = Ui::new()?;
+
+    let result = (|| -> Result<()> {
+        loop {
+            let workers = handle.block_on(scheduler.get_workers());
+            let queued = handle.block_on(scheduler.queue_size());
+            let completed = handle.block_on(dispatcher_state.get_history_count());
+            ui.update_workers(workers.clone());
+            ui.update_tasks(completed, queued);
+            ui.refresh()?;
+
+            if !event::poll(std::time::Duration::from_millis(250))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                KeyCode::Tab | KeyCode::Right => ui.next_tab(),
+                KeyCode::BackTab | KeyCode::Left => ui.prev_tab(),
+                KeyCode::Down => ui.select_next_worker(),
+                KeyCode::Up => ui.select_prev_worker(),
+                KeyCode::Char(c) => match ui.worker_action(c) {
+                    Some(WorkerAction::Cordon(id)) => {
+                        handle.block_on(scheduler.cordon(&id));
+                        ui.add_log(format!("Cordoned worker {}", id));
+                    }
+                    Some(WorkerAction::Uncordon(id)) => {
+                        handle.block_on(scheduler.uncordon(&id));
+                        ui.add_log(format!("Uncordoned worker {}", id));
+                    }
+                    Some(WorkerAction::Drain(id, mode)) => {
+                        let migrated = handle.block_on(scheduler.drain(&id, mode)).unwrap_or_default();
+                        let task_ids: Vec<String> = migrated.into_iter().map(|t| t.id).collect();
+                        if let Some(worker) = workers.iter().find(|w| w.id == id) {
+                            if !task_ids.is_empty() {
+                                let transport = &dispatcher_state.transport;
+                                if let Err(e) = handle.block_on(transport.cancel_on_worker(worker, &task_ids)) {
+                                    ui.add_log(format!("Failed to stop tasks on {}: {}", id, e));
+                                }
+                            }
+                        }
+                        ui.add_log(format!("Draining worker {} ({} tasks migrated)", id, task_ids.len()));
+                    }
+                    None => {}
+                },
+                _ => {}
+            }
+        }
+    })();
+
+    terminal::disable_raw_mode()?;
+    crossterm::execute!(std::io::stdout(), terminal::LeaveAlternateScreen)?;
+    result
+}
+
+// Resolve on Ctrl-C or, on Unix, SIGTERM
+// Selesai saat Ctrl-C atau, di Unix, SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+// Register an announcing worker: prove the pre-shared key, check the whitelist,
+// then add it to the scheduler. The outcome is sent back on the same connection.
+// Daftarkan worker yang mengumumkan diri: buktikan kunci pra-bagi, periksa
+// whitelist, lalu tambahkan ke penjadwal. Hasilnya dikirim balik pada koneksi yang sama.
+async fn register_worker(
+    connection: &mut octaskly::transport::Connection,
+    peer_addr: SocketAddr,
+    mut worker_info: WorkerInfo,
+    scheduler: &Scheduler,
+    dispatcher_state: &DispatcherState,
+) -> Result<()> {
+    use octaskly::audit::AuditEntry;
+
+    let worker_id = worker_info.id.clone();
+    let name = worker_info.name.clone();
+    if let Some(reason) = verify_worker(connection, &worker_id, &name, dispatcher_state).await? {
+        octaskly::metrics::global().auth_failures.inc();
+        let entry = AuditEntry::denied("worker.register", &worker_id, reason)
+            .with_subject(&name)
+            .with_source(peer_addr.ip());
+        dispatcher_state.audit.record(entry).await;
+        let result = Message::RegisterResult { accepted: false, reason: Some(reason.to_string()) };
+        return connection.send(&result).await;
+    }
+
+    // An announcement also counts as a heartbeat
+    // Pengumuman juga dihitung sebagai detak jantung
+    worker_info.last_heartbeat = chrono::Local::now().timestamp();
+    let (address, worker_port) = (worker_info.address.clone(), worker_info.port);
+    // Workers re-announce every 15s; only joins (first, or after eviction) are audited
+    // Worker mengumumkan ulang setiap 15 detik; hanya bergabung (pertama, atau setelah dikeluarkan) yang diaudit
+    let joining = !scheduler.has_worker(&worker_id).await;
+    let (result, entry) = if scheduler.register_worker(worker_info).await {
+        info!("[DISPATCHER] Worker registered: {} ({}:{})", name, address, worker_port);
+        scheduler.renew_leases(&worker_id).await;
+        (
+            Message::RegisterResult { accepted: true, reason: None },
+            joining.then(|| AuditEntry::new("worker.register", &worker_id, true)),
+        )
+    } else {
+        let reason = "worker limit reached";
+        (
+            Message::RegisterResult { accepted: false, reason: Some(reason.to_string()) },
+            Some(AuditEntry::denied("worker.register", &worker_id, reason)),
+        )
+    };
+    if let Some(entry) = entry {
+        let entry = entry.with_subject(&name).with_source(peer_addr.ip());
+        dispatcher_state.audit.record(entry).await;
+    }
+    connection.send(&result).await
+}
+
+// Challenge a worker for the pre-shared key and check the whitelist by id or
+// name; the reason when the worker is refused
+// Tantang worker untuk kunci pra-bagi dan periksa whitelist berdasarkan id atau
+// nama; alasannya bila worker ditolak
+async fn verify_worker(
+    connection: &mut octaskly::transport::Connection,
+    worker_id: &str,
+    name: &str,
+    dispatcher_state: &DispatcherState,
+) -> Result<Option<&'static str>> {
+    let security = dispatcher_state.security.read().await;
+    Ok(if !octaskly::transport::Transport::challenge(connection, worker_id, &security).await? {
+        Some("pre-shared key proof failed")
+    } else if !(security.is_whitelisted(worker_id) || security.is_whitelisted(name)) {
+        Some("not in security.whitelist")
+    } else {
+        None
+    })
+}
+
+// Answer a worker's hello: true once it proved its id and may send reports
+// on this connection
+// Jawab sapaan worker: true setelah ia membuktikan id-nya dan boleh mengirim
+// laporan pada koneksi ini
+async fn identify_worker(
+    connection: &mut octaskly::transport::Connection,
+    peer_addr: SocketAddr,
+    worker_id: &str,
+    scheduler: &Scheduler,
+    dispatcher_state: &DispatcherState,
+) -> Result<bool> {
+    use octaskly::audit::AuditEntry;
+
+    let name = scheduler
+        .get_workers()
+        .await
+        .into_iter()
+        .find(|w| w.id == worker_id)
+        .map(|w| w.name)
+        .unwrap_or_default();
+    let refusal = verify_worker(connection, worker_id, &name, dispatcher_state).await?;
+    if let Some(reason) = refusal {
+        octaskly::metrics::global().auth_failures.inc();
+        let entry = AuditEntry::denied("worker.hello", worker_id, reason).with_source(peer_addr.ip());
+        dispatcher_state.audit.record(entry).await;
+    }
+    let result = Message::RegisterResult {
+        accepted: refusal.is_none(),
+        reason: refusal.map(str::to_string),
+    };
+    connection.send(&result).await?;
+    Ok(refusal.is_none())
+}
+
+// Answer a worker's request for the secrets of a task. Values only go to the
+// worker the task is assigned to, over an encrypted connection.
+// Jawab permintaan worker atas rahasia sebuah tugas. Nilai hanya dikirim ke
+// worker yang ditugaskan, melalui koneksi terenkripsi.
+async fn serve_secrets(
+    connection: &mut octaskly::transport::Connection,
+    peer_addr: SocketAddr,
+    task_id: &str,
+    worker_id: &str,
+    scheduler: &Scheduler,
+    dispatcher_state: &DispatcherState,
+) -> Result<()> {
+    use octaskly::audit::AuditEntry;
+    use octaskly::protocol::SecretValues;
+    use octaskly::secrets::SecretError;
+
+    let reservation = scheduler.reservation(task_id).await.filter(|r| r.worker_id == worker_id);
+    let outcome = match (&reservation, &dispatcher_state.secrets) {
+        _ if !connection.is_encrypted() => {
+            Err("secrets are only sent over encrypted connections (security.encrypt)".to_string())
+        }
+        (None, _) => Err(format!("task {} is not assigned to worker {}", task_id, worker_id)),
+        (Some(_), None) => Err("secrets are not enabled on this dispatcher".to_string()),
+        (Some(reservation), Some(vault)) => match vault.resolve(&reservation.task).await {
+            Ok(values) => Ok(values),
+            Err(SecretError::Denied(reason)) => Err(reason),
+            Err(SecretError::Store(e)) => {
+                error!("[DISPATCHER] Cannot read secrets of task {}: {:#}", task_id, e);
+                Err("cannot read secrets".to_string())
+            }
+        },
+    };
+
+    let names = reservation
+        .as_ref()
+        .map(|r| octaskly::secrets::references(&r.task).into_values().collect::<Vec<_>>().join(","))
+        .unwrap_or_default();
+    let subject = format!("task {} ({})", task_id, names);
+    let entry = match &outcome {
+        Ok(_) => AuditEntry::new("secret.read", worker_id, true),
+        Err(reason) => AuditEntry::denied("secret.read", worker_id, reason.clone()),
+    };
+    dispatcher_state.audit.record(entry.with_subject(subject).with_source(peer_addr.ip())).await;
+
+    let reply = match outcome {
+        Ok(values) => Message::SecretResponse { values: SecretValues(values), error: None },
+        Err(reason) => Message::SecretResponse { values: SecretValues::default(), error: Some(reason) },
+    };
+    connection.send(&reply).await
+}
+
+// Final result of a task given up after its retries
+// Hasil akhir tugas yang dilepas setelah batas pengulangannya
+fn lost_result(expiry: &octaskly::scheduler::LeaseExpiry, reason: String) -> octaskly::protocol::TaskResult {
+    octaskly::protocol::TaskResult {
+        task_id: expiry.task.id.clone(),
+        worker_id: expiry.worker_id.clone(),
+        status: octaskly::protocol::TaskStatus::Lost,
+        stdout: String::new(),
+        stderr: reason,
+        exit_code: None,
+        duration_ms: 0,
+        completed_at: chrono::Local::now().timestamp(),
+        signer: None,
+    }
+}
+
 // Handle incoming messages from workers at dispatcher
 // Tangani pesan masuk dari worker di dispatcher
 async fn handle_dispatcher_message(
     msg: Message,
+    worker: Option<&str>,
     scheduler: &Scheduler,
     dispatcher_state: &DispatcherState,
-    _active_tasks: &Arc<RwLock<std::collections::HashMap<String, String>>>,
 ) -> Result<()> {
+    // A report about a worker only counts on a connection that proved that worker's id
+    // Laporan tentang worker hanya dihitung pada koneksi yang membuktikan id worker tersebut
+    let claimed = match &msg {
+        Message::TaskCompleted(result) => Some(result.worker_id.as_str()),
+        Message::TaskOutput { worker_id, .. }
+        | Message::Heartbeat { worker_id, .. }
+        | Message::WorkerDraining { worker_id }
+        | Message::TaskReturned { worker_id, .. }
+        | Message::WorkerLeaving { worker_id } => Some(worker_id.as_str()),
+        _ => None,
+    };
+    if let Some(claimed) = claimed.filter(|claimed| worker != Some(*claimed)) {
+        octaskly::metrics::global().auth_failures.inc();
+        anyhow::bail!("report about worker {} on a connection that did not prove its id", claimed);
+    }
+
     match msg {
-        // Register worker when it announces itself
-        // Daftarkan worker ketika mengumumkan dirinya
-        Message::WorkerAnnounce(worker_info) => {
-            info!("[DISPATCHER] Worker registered: {} ({}:{})", worker_info.name, worker_info.address, worker_info.port);
-            scheduler.register_worker(worker_info).await;
-        }
-        
         // Task completion notification from worker
         // Notifikasi penyelesaian tugas dari worker
         Message::TaskCompleted(result) => {
             info!("[DISPATCHER] Task {} completed - status: {:?}", result.task_id, result.status);
-            dispatcher_state.store_result(result.clone()).await;
-            scheduler.worker_job_completed(&result.worker_id).await;
+            match scheduler.accept_result(&result).await {
+                ResultDisposition::Accepted | ResultDisposition::Reconciled => {
+                    dispatcher_state.store_result(result.clone()).await;
+                    scheduler.record_usage(&result.task_id, result.duration_ms).await;
+                }
+                // Another worker already runs the task again; its result will count
+                // Worker lain sudah menjalankan ulang tugas; hasilnya yang akan dihitung
+                ResultDisposition::Stale => {
+                    warn!(
+                        "[DISPATCHER] Ignoring late result of task {} from worker {}",
+                        result.task_id, result.worker_id
+                    );
+                }
+                // Finished already, unknown, or never held by this worker
+                // Sudah selesai, tidak dikenal, atau tidak pernah dipegang worker ini
+                ResultDisposition::Untracked => {
+                    warn!(
+                        "[DISPATCHER] Dropping result of task {} from worker {}: not assigned to it",
+                        result.task_id, result.worker_id
+                    );
+                }
+            }
         }
         
         Message::TaskProgress { task_id, progress } => {
-            debug!("[DISPATCHER] Task {} progress: {:.1}%", task_id, progress * 100.0);
+            if scheduler.reservation(&task_id).await.is_some_and(|r| Some(r.worker_id.as_str()) == worker) {
+                debug!("[DISPATCHER] Task {} progress: {:.1}%", task_id, progress * 100.0);
+                dispatcher_state
+                    .events
+                    .publish(octaskly::Event::TaskProgress { task_id, progress });
+            } else {
+                debug!("[DISPATCHER] Ignoring progress of task {} from {:?}", task_id, worker);
+            }
+        }
+        
+        // Only the worker holding the task speaks for its output
+        // Hanya worker pemegang tugas yang mewakili output-nya
+        Message::TaskOutput { task_id, worker_id, stream, text } => {
+            if scheduler.reservation(&task_id).await.is_some_and(|r| r.worker_id == worker_id) {
+                dispatcher_state
+                    .events
+                    .publish(octaskly::Event::TaskOutput { task_id, stream, text });
+            } else {
+                debug!("[DISPATCHER] Ignoring output of task {} from worker {}", task_id, worker_id);
+            }
         }
         
         // P2P: Resource availability announcement
@@ -367,13 +1136,31 @@ async fn handle_dispatcher_message(
             debug!("[DISPATCHER] Heartbeat received from {}", worker_id);
             // Update worker last_heartbeat in scheduler
             // Perbarui last_heartbeat worker di penjadwal
-            let workers = scheduler.get_workers().await;
-            if let Some(mut worker) = workers.iter().find(|w| w.id == worker_id).cloned() {
-                worker.last_heartbeat = chrono::Local::now().timestamp();
-                scheduler.update_worker(&worker_id, worker).await;
+            if scheduler.touch_heartbeat(&worker_id, chrono::Local::now().timestamp()).await {
+                scheduler.renew_leases(&worker_id).await;
+            }
+        }
+        
+        // Worker received a shutdown signal: stop sending it tasks
+        // Worker menerima sinyal penutupan: berhenti mengirim tugas kepadanya
+        Message::WorkerDraining { worker_id } => {
+            info!("[DISPATCHER] Worker {} is shutting down, draining", worker_id);
+            scheduler.drain(&worker_id, DrainMode::Wait).await;
+        }
+        
+        // Unfinished task handed back by a draining worker
+        // Tugas yang belum selesai dikembalikan oleh worker yang di-drain
+        Message::TaskReturned { task_id, worker_id } => {
+            if scheduler.requeue_task(&task_id, &worker_id).await {
+                info!("[DISPATCHER] Task {} returned by worker {}", task_id, worker_id);
             }
         }
         
+        Message::WorkerLeaving { worker_id } => {
+            info!("[DISPATCHER] Worker {} left", worker_id);
+            scheduler.remove_worker(&worker_id).await;
+        }
+        
         _ => {
             warn!("Unexpected message type: {:?}", msg);
         }
@@ -382,79 +1169,157 @@ async fn handle_dispatcher_message(
     Ok(())
 }
 
+// Settings for a worker process
+// Pengaturan untuk proses worker
+struct WorkerOptions {
+    name: String,
+    dispatcher: String,
+    dispatcher_port: u16,
+    /// Look for the dispatcher over mDNS; set when none was configured
+    discover_dispatcher: bool,
+    /// Advertise this worker over mDNS
+    mdns: bool,
+    allow_shell: bool,
+    max_jobs: usize,
+    capacity: Resources,
+    labels: Vec<(String, String)>,
+    grace_period: Duration,
+    metrics_port: Option<u16>,
+    /// Connections to the dispatcher
+    transport: octaskly::transport::Transport,
+    /// Pre-shared key proven to the dispatcher on registration
+    psk: Option<String>,
+    /// Keys the dispatcher may still prove with during a rotation
+    accepted_psks: Vec<String>,
+    /// Keys assignments must be signed with
+    trusted_keys: octaskly::signing::TrustedKeys,
+}
+
 // Worker process initialization and main loop
 // Inisialisasi proses worker dan loop utama
-async fn run_worker(name: &str, allow_shell: bool, max_jobs: usize) -> Result<()> {
+async fn run_worker(options: WorkerOptions) -> Result<()> {
+    let WorkerOptions {
+        name,
+        dispatcher,
+        dispatcher_port,
+        discover_dispatcher,
+        mdns,
+        allow_shell,
+        max_jobs,
+        capacity,
+        labels,
+        grace_period,
+        metrics_port,
+        transport,
+        psk,
+        accepted_psks,
+        trusted_keys,
+    } = options;
     info!("[WORKER] Starting Worker '{}' with max_jobs={}", name, max_jobs);
 
     let local_ip = util::get_local_ip().unwrap_or_else(|| "127.0.0.1".to_string());
     let port = find_available_port(7879).await?;
 
-    let worker_state = Arc::new(WorkerState::new(name.to_string(), port));
-    let executor = Arc::new(Executor::new(PathBuf::from("./work"), allow_shell));
+    // Start listening for incoming connections from dispatcher; bound before
+    // looking for the dispatcher so no other worker takes the port meanwhile
+    // Mulai mendengarkan koneksi masuk dari dispatcher; diikat sebelum mencari
+    // dispatcher agar worker lain tidak mengambil port ini sementara itu
+    let addr = format!("0.0.0.0:{}", port);
+    let listener = TcpListener::bind(&addr).await?;
+    info!("[WORKER] Listening on {}", addr);
 
-    let worker_info = WorkerInfo::new(
-        name.to_string(),
+    let mut worker_info = WorkerInfo::new(
+        name.clone(),
         local_ip.clone(),
         port,
         max_jobs,
     );
-
+    worker_info.allow_shell = allow_shell;
     info!(
-        "[WORKER] Registered at {}:{}",
-        local_ip, port
+        "[WORKER] Capacity: {} mCPU, {} MB memory, {} MB disk, {} GPU",
+        capacity.cpu_millicores, capacity.memory_mb, capacity.disk_mb, capacity.gpus
     );
-    info!("[WORKER] Waiting for dispatcher assignment...");
+    worker_info.capacity = capacity;
+    // User labels extend (and may override) the automatic platform/arch labels
+    // Label pengguna menambah (dan dapat menimpa) label platform/arch otomatis
+    worker_info.labels.extend(labels);
+    info!("[WORKER] Labels: {:?}", worker_info.labels);
 
-    // Start listening for incoming connections from dispatcher
-    // Mulai mendengarkan koneksi masuk dari dispatcher
-    let addr = format!("0.0.0.0:{}", port);
-    let listener = TcpListener::bind(&addr).await?;
-    info!("[WORKER] Listening on {}", addr);
+    // Advertise this worker over mDNS and, with no dispatcher configured,
+    // look for one there before falling back to the default address
+    // Iklankan worker ini melalui mDNS dan, tanpa dispatcher yang dikonfigurasi,
+    // cari di sana sebelum kembali ke alamat bawaan
+    let p2p_network = if mdns { start_worker_mdns(&worker_info).await } else { None };
+    // Anyone can advertise a dispatcher, so one is only taken from mDNS when this
+    // worker can tell it is genuine: by its task signatures or the shared key
+    // Siapa pun dapat mengiklankan dispatcher, sehingga hanya diambil dari mDNS bila
+    // worker ini dapat memastikan keasliannya: lewat tanda tangan tugas atau kunci bersama
+    let can_authenticate = !trusted_keys.is_empty() || transport.is_encrypted();
+    let discovered = match p2p_network.as_ref().filter(|_| discover_dispatcher) {
+        Some(_) if !can_authenticate => {
+            warn!(
+                "[WORKER] Skipping dispatcher discovery over mDNS: set security.trusted_keys, or security.encrypt with security.psk, to authenticate an advertised dispatcher"
+            );
+            None
+        }
+        Some(network) => find_dispatcher(network).await,
+        None => None,
+    };
+    let dispatcher_addr = match discovered {
+        Some(addr) => addr,
+        None => tokio::net::lookup_host((dispatcher.as_str(), dispatcher_port))
+            .await?
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Cannot resolve dispatcher {}:{}", dispatcher, dispatcher_port))?,
+    };
 
-    let listener = Arc::new(listener);
-    let worker_info_announced = Arc::new(RwLock::new(false));
-    let worker_info_to_announce = worker_info.clone();
+    if trusted_keys.is_empty() {
+        warn!("[SECURITY] No security.trusted_keys: task signatures are not checked, any sender's tasks will run");
+    } else {
+        info!("[WORKER] Only running tasks signed by a trusted key");
+    }
+    let mut security = octaskly::security::Security::new();
+    security.set_key(psk);
+    security.set_accepted_keys(accepted_psks);
+    let mut worker_state = WorkerState::new(name.clone(), port)
+        .with_transport(transport)
+        .with_trusted_keys(trusted_keys)
+        .with_security(security);
+    worker_state.id = worker_info.id.clone();
+    let worker_state = Arc::new(worker_state);
+    let executor = Arc::new(Executor::new(PathBuf::from("./work"), allow_shell));
 
     // Spawn connection handler task
     // Jalankan task penanganan koneksi
-    let listener_clone = listener.clone();
     let worker_state_clone = worker_state.clone();
     let executor_clone = executor.clone();
-    let worker_info_announced_clone = worker_info_announced.clone();
-    let worker_info_for_handler = worker_info_to_announce.clone();
     
     tokio::spawn(async move {
         loop {
-            match listener_clone.accept().await {
+            match listener.accept().await {
                 Ok((stream, peer_addr)) => {
                     debug!("[WORKER] Connection established with dispatcher at {}", peer_addr);
                     
-                    // Announce worker to dispatcher if not already done
-                    // Umumkan worker ke dispatcher jika belum dilakukan
-                    if !*worker_info_announced_clone.read().await {
-                        *worker_info_announced_clone.write().await = true;
-                        let announce_msg = Message::WorkerAnnounce(worker_info_for_handler.clone());
-                        let announce_addr = peer_addr;
-                        
-                        if let Err(e) = octaskly::transport::Transport::new().send_message(announce_addr, &announce_msg).await {
-                            warn!("Failed to announce worker: {}", e);
-                        }
-                    }
-                    
                     let worker_state = worker_state_clone.clone();
                     let executor = executor_clone.clone();
                     
                     tokio::spawn(async move {
+                        let connection = match worker_state.transport.accept(stream).await {
+                            Ok(connection) => connection,
+                            Err(e) => {
+                                warn!("[WORKER] Refused connection from {}: {}", peer_addr, e);
+                                return;
+                            }
+                        };
                         if let Err(e) = 
                             octaskly::transport::Transport::handle_connection(
-                                stream,
+                                connection,
                                 move |msg| {
                                     let worker_state = worker_state.clone();
                                     let executor = executor.clone();
                                     
                                     Box::pin(async move {
-                                        handle_worker_message(msg, &worker_state, &executor, peer_addr).await
+                                        handle_worker_message(msg, worker_state, executor, dispatcher_addr).await
                                     })
                                 }
                             ).await 
@@ -470,73 +1335,360 @@ async fn run_worker(name: &str, allow_shell: bool, max_jobs: usize) -> Result<()
         }
     });
 
-    // Heartbeat loop - send periodic heartbeats to dispatcher
-    // Loop detak jantung - kirim detak jantung berkala ke dispatcher
+    // Optional Prometheus endpoint for this worker
+    // Endpoint Prometheus opsional untuk worker ini
+    if let Some(metrics_port) = metrics_port {
+        let worker_state = worker_state.clone();
+        tokio::spawn(async move {
+            let samples = move || {
+                let worker_state = worker_state.clone();
+                async move {
+                    let running = worker_state.running_count().await;
+                    vec![
+                        octaskly::metrics::Sample {
+                            name: "octaskly_worker_running_tasks",
+                            help: "Tasks executing on this worker",
+                            value: running as f64,
+                        },
+                        octaskly::metrics::Sample {
+                            name: "octaskly_worker_utilisation",
+                            help: "Share of task slots in use (0-1)",
+                            value: running as f64 / max_jobs.max(1) as f64,
+                        },
+                    ]
+                }
+            };
+            let addr = format!("0.0.0.0:{}", metrics_port);
+            if let Err(e) = octaskly::metrics::serve(&addr, samples).await {
+                error!("[WORKER] Metrics server error: {}", e);
+            }
+        });
+    }
+
+    // Announce to the dispatcher, then keep sending heartbeats; re-announce
+    // whenever the dispatcher could not be reached, and periodically so a
+    // restarted dispatcher learns about this worker and its running tasks
+    // Umumkan ke dispatcher, lalu terus kirim detak jantung; umumkan ulang
+    // setiap kali dispatcher tidak dapat dijangkau, dan secara berkala agar
+    // dispatcher yang di-restart mengenali worker ini dan tugas yang berjalan
+    let worker_id = worker_info.id.clone();
+    let heartbeat_state = worker_state.clone();
     tokio::spawn(async move {
+        let mut announced = false;
+        let mut ticks: u64 = 0;
         let mut interval = interval(Duration::from_secs(5));
         
         loop {
             interval.tick().await;
-            debug!("[WORKER] Heartbeat check");
-            // Note: In production, we'd track dispatcher address and send heartbeat
-            // Catatan: Dalam produksi, kami akan melacak alamat dispatcher dan mengirim heartbeat
+            ticks += 1;
+            // Every third tick (15s) is a full announcement
+            // Setiap detak ketiga (15 detik) adalah pengumuman penuh
+            let sent = if announced && !ticks.is_multiple_of(3) {
+                let heartbeat = Message::Heartbeat {
+                    worker_id: worker_info.id.clone(),
+                    timestamp: chrono::Local::now().timestamp(),
+                };
+                heartbeat_state.report(dispatcher_addr, &heartbeat).await
+            } else {
+                heartbeat_state
+                    .transport
+                    .register(dispatcher_addr, &worker_info, &heartbeat_state.security)
+                    .await
+            };
+            match sent {
+                Ok(()) => {
+                    if !announced {
+                        info!("[WORKER] Registered with dispatcher at {}", dispatcher_addr);
+                    }
+                    announced = true;
+                }
+                Err(e) => {
+                    warn!("[WORKER] Dispatcher {} unreachable: {}", dispatcher_addr, e);
+                    announced = false;
+                }
+            }
         }
     });
 
-    // Keep running
-    // Tetap berjalan
-    tokio::signal::ctrl_c().await?;
-    info!("[WORKER] Shutting down gracefully...");
+    info!("[WORKER] Waiting for dispatcher assignment...");
+
+    // Graceful shutdown: stop taking tasks, let running ones finish within the
+    // grace period, then hand the rest back to the dispatcher. A second signal
+    // ends the grace period early.
+    // Penutupan yang elegan: berhenti menerima tugas, biarkan tugas berjalan
+    // selesai dalam masa tenggang, lalu kembalikan sisanya ke dispatcher.
+    // Sinyal kedua mengakhiri masa tenggang lebih awal.
+    shutdown_signal().await;
+    worker_state.begin_drain();
+    let draining = Message::WorkerDraining { worker_id: worker_id.clone() };
+    if let Err(e) = worker_state.report(dispatcher_addr, &draining).await {
+        warn!("[WORKER] Failed to notify dispatcher of shutdown: {}", e);
+    }
+    info!(
+        "[WORKER] Shutting down, waiting up to {}s for {} running tasks...",
+        grace_period.as_secs(),
+        worker_state.running_count().await
+    );
+
+    let wait_for_tasks = async {
+        while worker_state.running_count().await > 0 {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    };
+    tokio::select! {
+        _ = tokio::time::timeout(grace_period, wait_for_tasks) => {}
+        _ = shutdown_signal() => warn!("[WORKER] Second signal received, stopping tasks now"),
+    }
+
+    for task in worker_state.abort_all().await {
+        info!("[WORKER] Handing back unfinished task {}", task.id);
+        let returned = Message::TaskReturned {
+            task_id: task.id,
+            worker_id: worker_id.clone(),
+        };
+        if let Err(e) = worker_state.report(dispatcher_addr, &returned).await {
+            warn!("[WORKER] Failed to hand back task: {}", e);
+        }
+    }
+    let leaving = Message::WorkerLeaving { worker_id };
+    let _ = worker_state.report(dispatcher_addr, &leaving).await;
+    if let Some(network) = &p2p_network {
+        network.stop_mdns_discovery().await;
+    }
+    info!("[WORKER] Shutdown complete");
     
     Ok(())
 }
 
-// Handle task execution messages on worker
-// Tangani pesan eksekusi tugas di worker
-async fn handle_worker_message(
-    msg: Message,
-    worker_state: &WorkerState,
-    executor: &Executor,
+// Advertise a worker over mDNS; workers run fine without it
+// Iklankan worker melalui mDNS; worker tetap berjalan tanpanya
+async fn start_worker_mdns(worker_info: &WorkerInfo) -> Option<octaskly::P2PNetwork> {
+    let mut capabilities = Vec::new();
+    if worker_info.capacity.gpus > 0 {
+        capabilities.push("gpu".to_string());
+    }
+    let started = async {
+        let network = octaskly::P2PNetwork::new(worker_info.id.clone(), worker_info.name.clone(), worker_info.port)?
+            .with_role(octaskly::p2p::PeerRole::Worker)
+            .with_capabilities(capabilities);
+        network.start_mdns_discovery().await?;
+        anyhow::Ok(network)
+    };
+    match started.await {
+        Ok(network) => Some(network),
+        Err(e) => {
+            warn!("[WORKER] mDNS unavailable: {}", e);
+            None
+        }
+    }
+}
+
+// Address of a dispatcher advertised over mDNS, if one answers in time
+// Alamat dispatcher yang diiklankan melalui mDNS, jika ada yang menjawab tepat waktu
+async fn find_dispatcher(network: &octaskly::P2PNetwork) -> Option<SocketAddr> {
+    info!("[WORKER] Looking for a dispatcher over mDNS...");
+    let peer = network
+        .find_peer(octaskly::p2p::PeerRole::Dispatcher, Duration::from_secs(5))
+        .await;
+    let Some(peer) = peer else {
+        warn!("[WORKER] No dispatcher advertised over mDNS, using the configured address");
+        return None;
+    };
+    match peer.socket_addr() {
+        Ok(addr) => {
+            info!("[WORKER] Found dispatcher {} at {}", peer.name, addr);
+            Some(addr)
+        }
+        Err(e) => {
+            warn!("[WORKER] Ignoring advertised dispatcher {}: {}", peer.name, e);
+            None
+        }
+    }
+}
+
+// Send a running task's output lines to the dispatcher, those already waiting
+// together, so a chatty task does not cost a message per line
+// Kirim baris output tugas yang berjalan ke dispatcher, yang sudah menunggu
+// sekaligus, agar tugas yang banyak menulis tidak memakan satu pesan per baris
+async fn forward_output(
+    mut lines: tokio::sync::mpsc::UnboundedReceiver<(octaskly::protocol::OutputStream, String)>,
+    task_id: &str,
+    state: &WorkerState,
     dispatcher_addr: SocketAddr,
-) -> Result<()> {
-    match msg {
-        // Execute assigned task from dispatcher
-        // Jalankan tugas yang ditugaskan dari dispatcher
-        Message::AssignTask(task) => {
-            info!("[WORKER] Task received for execution: {}", task.id);
-            
+    secrets: &std::collections::HashMap<String, String>,
+) {
+    let mut stdout = octaskly::secrets::StreamRedactor::new(secrets.values());
+    let mut stderr = octaskly::secrets::StreamRedactor::new(secrets.values());
+    let send = |stream, text: String| async move {
+        if text.is_empty() {
+            return;
+        }
+        let message = Message::TaskOutput {
+            task_id: task_id.to_string(),
+            worker_id: state.id.clone(),
+            stream,
+            text,
+        };
+        if let Err(e) = state.report(dispatcher_addr, &message).await {
+            debug!("[WORKER] Failed to send output of task {}: {}", task_id, e);
+        }
+    };
+    while let Some(first) = lines.recv().await {
+        let mut chunks = vec![first];
+        while let Ok((stream, line)) = lines.try_recv() {
+            match chunks.last_mut() {
+                Some((last, text)) if *last == stream => text.push_str(&line),
+                _ => chunks.push((stream, line)),
+            }
+        }
+        // Redacted per stream, holding back what may be the start of a secret
+        // Disamarkan per stream, menahan bagian yang mungkin awal sebuah rahasia
+        for (stream, text) in chunks {
+            let redactor = match stream {
+                octaskly::protocol::OutputStream::Stdout => &mut stdout,
+                octaskly::protocol::OutputStream::Stderr => &mut stderr,
+            };
+            send(stream, redactor.push(&text)).await;
+        }
+    }
+    send(octaskly::protocol::OutputStream::Stdout, stdout.finish()).await;
+    send(octaskly::protocol::OutputStream::Stderr, stderr.finish()).await;
+}
+
+// Run a task in the background and report its result to the dispatcher
+// Jalankan tugas di latar belakang dan laporkan hasilnya ke dispatcher
+async fn spawn_execution(
+    task: octaskly::protocol::Task,
+    worker_state: Arc<WorkerState>,
+    executor: Arc<Executor>,
+    dispatcher_addr: SocketAddr,
+    signer: Option<String>,
+) {
+    let state = worker_state.clone();
+    worker_state
+        .spawn_task(task.clone(), async move {
             let task_id = task.id.clone();
-            worker_state.set_current_task(Some(task.clone())).await;
-            
-            // Execute task with timeout protection
-            // Jalankan tugas dengan perlindungan timeout
-            match executor.execute_with_timeout(&task).await {
+            // Secrets are fetched just before the run and never leave this future
+            // Rahasia diambil tepat sebelum dijalankan dan tidak keluar dari future ini
+            let mut run = task.clone();
+            let mut secrets = std::collections::HashMap::new();
+            if !octaskly::secrets::references(&task).is_empty() {
+                match state.transport.fetch_secrets(dispatcher_addr, &task_id, &state.id, &state.security).await {
+                    Ok(values) => secrets = values,
+                    Err(e) => {
+                        if state.finish_task(&task_id).await.is_some() {
+                            let _ = refuse_task(&task_id, &e.to_string(), &state, dispatcher_addr).await;
+                        }
+                        return;
+                    }
+                }
+                run.env.extend(secrets.clone());
+            }
+            // Output goes to the dispatcher while the task runs; the forwarder
+            // ends once the run does, before the result is sent
+            // Output dikirim ke dispatcher selama tugas berjalan; penerus
+            // selesai bersama eksekusi, sebelum hasil dikirim
+            let (output, lines) = tokio::sync::mpsc::unbounded_channel();
+            let (outcome, ()) = tokio::join!(
+                executor.execute_with_timeout(&run, Some(output)),
+                forward_output(lines, &task_id, &state, dispatcher_addr, &secrets),
+            );
+            drop(run);
+
+            // A task that was aborted (cancel, shutdown) is no longer tracked
+            // and must not report a result
+            // Tugas yang dibatalkan (cancel, shutdown) tidak lagi dilacak
+            // dan tidak boleh melaporkan hasil
+            if state.finish_task(&task_id).await.is_none() {
+                return;
+            }
+
+            match outcome {
                 Ok(result) => {
-                    info!("[WORKER] Task {} execution completed successfully", task_id);
+                    info!("[WORKER] Task {} execution completed", task_id);
                     
                     let task_result = octaskly::protocol::TaskResult {
                         task_id: task_id.clone(),
-                        worker_id: "unknown".to_string(),
+                        worker_id: state.id.clone(),
                         status: result.status,
-                        stdout: result.stdout,
-                        stderr: result.stderr,
+                        stdout: octaskly::secrets::redact(&result.stdout, secrets.values()),
+                        stderr: octaskly::secrets::redact(&result.stderr, secrets.values()),
                         exit_code: result.exit_code,
                         duration_ms: result.duration_ms,
                         completed_at: chrono::Local::now().timestamp(),
+                        signer: signer.clone(),
                     };
+                    let metrics = octaskly::metrics::global();
+                    metrics.tasks_finished.inc(&format!("{:?}", task_result.status));
+                    metrics.task_duration.observe(task_result.duration_ms as f64 / 1000.0);
+                    state.add_completed_task(task_result.clone()).await;
                     
                     // Send result back to dispatcher
                     // Kirim hasil kembali ke dispatcher
                     let result_msg = Message::TaskCompleted(task_result);
-                    if let Err(e) = octaskly::transport::Transport::new().send_message(dispatcher_addr, &result_msg).await {
+                    if let Err(e) = state.report(dispatcher_addr, &result_msg).await {
                         error!("[WORKER] Failed to send task result: {}", e);
                     }
-                    
-                    worker_state.set_current_task(None).await;
                 }
+                // A task that could not start (shell not allowed, spawn error)
+                // would fail the same way again, so it is reported as failed
+                // Tugas yang tidak dapat dimulai (shell tidak diizinkan, galat spawn)
+                // akan gagal dengan cara yang sama lagi, jadi dilaporkan gagal
                 Err(e) => {
-                    error!("Task execution failed: {}", e);
-                    worker_state.set_current_task(None).await;
+                    error!("[WORKER] Task {} could not start: {}", task_id, e);
+                    let result = octaskly::protocol::TaskResult::failed(
+                        &task_id,
+                        &state.id,
+                        format!("could not start on worker {}: {}", state.name, e),
+                    );
+                    octaskly::metrics::global().tasks_finished.inc("Failed");
+                    state.add_completed_task(result.clone()).await;
+                    if let Err(e) = state.report(dispatcher_addr, &Message::TaskCompleted(result)).await {
+                        error!("[WORKER] Failed to send task result: {}", e);
+                    }
+                }
+            }
+        })
+        .await;
+}
+
+// Handle task execution messages on worker
+// Tangani pesan eksekusi tugas di worker
+async fn handle_worker_message(
+    msg: Message,
+    worker_state: Arc<WorkerState>,
+    executor: Arc<Executor>,
+    dispatcher_addr: SocketAddr,
+) -> Result<()> {
+    match msg {
+        // Execute assigned task from dispatcher
+        // Jalankan tugas yang ditugaskan dari dispatcher
+        Message::AssignTask(task) => {
+            // Workers with trusted keys only run signed tasks; anything else is
+            // dropped, since whoever sent it must not be able to fail the task
+            // Worker dengan kunci tepercaya hanya menjalankan tugas bertanda tangan;
+            // selainnya dibuang, karena pengirimnya tidak boleh dapat menggagalkan tugas
+            if !worker_state.trusted_keys.is_empty() {
+                error!("[SECURITY] Dropping unsigned assignment of task {}", task.id);
+                return Ok(());
+            }
+            run_assigned(task, None, worker_state, executor, dispatcher_addr).await?;
+        }
+
+        // Execute a signed task once its signature checks out
+        // Jalankan tugas bertanda tangan setelah tanda tangannya valid
+        Message::AssignSignedTask(envelope) => {
+            let task_id = envelope.task.id.clone();
+            if worker_state.trusted_keys.is_empty() {
+                let task = envelope.task;
+                return run_assigned(task, None, worker_state, executor, dispatcher_addr).await;
+            }
+            match worker_state.trusted_keys.verify(envelope, &worker_state.id) {
+                Ok((task, signer)) => {
+                    run_assigned(task, Some(signer), worker_state, executor, dispatcher_addr).await?;
+                }
+                Err(e) => {
+                    error!("[SECURITY] Dropping assignment of task {}: {}", task_id, e);
                 }
             }
         }
@@ -545,42 +1697,20 @@ async fn handle_worker_message(
         // Permintaan pembatalan tugas
         Message::CancelTask { task_id } => {
             info!("[WORKER] Cancel request received for task: {}", task_id);
-            worker_state.set_current_task(None).await;
+            if worker_state.abort_task(&task_id).await.is_none() {
+                debug!("[WORKER] Task {} was not running", task_id);
+            }
         }
         
         // P2P: Shared task from peer
         // P2P: Task bersama dari peer
         Message::P2PShareTask { task, requester_id } => {
             info!("[P2P] Shared task received from {}: {}", requester_id, task.id);
-            
-            // For now, execute like normal task
-            let task_id = task.id.clone();
-            worker_state.set_current_task(Some(task.clone())).await;
-            
-            match executor.execute_with_timeout(&task).await {
-                Ok(result) => {
-                    info!("[P2P] Shared task {} completed", task_id);
-                    let task_result = octaskly::protocol::TaskResult {
-                        task_id: task_id.clone(),
-                        worker_id: "unknown".to_string(),
-                        status: result.status,
-                        stdout: result.stdout,
-                        stderr: result.stderr,
-                        exit_code: result.exit_code,
-                        duration_ms: result.duration_ms,
-                        completed_at: chrono::Local::now().timestamp(),
-                    };
-                    
-                    // Send result back to requester
-                    let result_msg = Message::TaskCompleted(task_result);
-                    let _ = octaskly::transport::Transport::new().send_message(dispatcher_addr, &result_msg).await;
-                }
-                Err(e) => {
-                    error!("[P2P] Shared task execution failed: {}", e);
-                }
+            if !worker_state.trusted_keys.is_empty() {
+                error!("[SECURITY] Dropping unsigned shared task {} from {}", task.id, requester_id);
+                return Ok(());
             }
-            
-            worker_state.set_current_task(None).await;
+            spawn_execution(task, worker_state, executor, dispatcher_addr, None).await;
         }
         
         // P2P: Peer discovery request
@@ -600,6 +1730,93 @@ async fn handle_worker_message(
     Ok(())
 }
 
+// Start an assigned task, or hand it back while draining
+// Mulai tugas yang ditugaskan, atau kembalikan saat draining
+async fn run_assigned(
+    task: octaskly::protocol::Task,
+    signer: Option<String>,
+    worker_state: Arc<WorkerState>,
+    executor: Arc<Executor>,
+    dispatcher_addr: SocketAddr,
+) -> Result<()> {
+    if worker_state.is_draining() {
+        info!("[WORKER] Draining, handing back task {}", task.id);
+        let returned = Message::TaskReturned {
+            task_id: task.id,
+            worker_id: worker_state.id.clone(),
+        };
+        worker_state.report(dispatcher_addr, &returned).await?;
+        return Ok(());
+    }
+    info!("[WORKER] Task received for execution: {}", task.id);
+    spawn_execution(task, worker_state, executor, dispatcher_addr, signer).await;
+    Ok(())
+}
+
+// Report a task this worker will not run as failed, with the reason in stderr
+// Laporkan tugas yang tidak akan dijalankan worker ini sebagai gagal, dengan alasan di stderr
+async fn refuse_task(
+    task_id: &str,
+    reason: &str,
+    worker_state: &WorkerState,
+    dispatcher_addr: SocketAddr,
+) -> Result<()> {
+    warn!("[SECURITY] Refusing task: {}", reason);
+    let result = octaskly::protocol::TaskResult::failed(
+        task_id,
+        &worker_state.id,
+        format!("refused by worker {}: {}", worker_state.name, reason),
+    );
+    worker_state
+        .report(dispatcher_addr, &Message::TaskCompleted(result))
+        .await
+}
+
+// Transport for dispatcher/worker traffic, sealing frames with keys derived
+// from security.psk (and accepting security.accepted_psks) through the key file
+// when security.encrypt is set
+// Transport untuk lalu lintas dispatcher/worker, menyegel frame dengan kunci
+// turunan security.psk (dan menerima security.accepted_psks) melalui file kunci
+// bila security.encrypt diaktifkan
+fn frame_transport(security: &octaskly::config::SecurityConfig) -> Result<octaskly::transport::Transport> {
+    let transport = octaskly::transport::Transport::new();
+    if !security.encrypt {
+        return Ok(transport);
+    }
+    let manager = cluster_keys(security)?;
+    let accepted: Vec<String> = manager.key_ids()[1..].iter().map(|id| format!("{:08x}", id)).collect();
+    info!("[SECURITY] Encrypting with key {:08x}, also accepting {:?}", manager.key_id(), accepted);
+    Ok(transport.with_encryption(Some(Arc::new(manager))))
+}
+
+// Secrets sealed with security.secrets_key (created in the work directory by default)
+// Rahasia yang disegel dengan security.secrets_key (dibuat di direktori kerja secara bawaan)
+fn secret_vault(
+    security: &octaskly::config::SecurityConfig,
+    workdir: &std::path::Path,
+    store: Arc<dyn octaskly::TaskStore>,
+) -> Result<octaskly::secrets::SecretVault> {
+    let path = security.secrets_key.clone().unwrap_or_else(|| workdir.join("secrets.key"));
+    let key = octaskly::secrets::SecretKey::load_or_create(&path)?;
+    Ok(octaskly::secrets::SecretVault::new(key, store))
+}
+
+// Derive the cluster keys from the configured pre-shared keys
+// Turunkan kunci klaster dari kunci pra-bagi yang dikonfigurasi
+fn cluster_keys(security: &octaskly::config::SecurityConfig) -> Result<octaskly::SecurityManager> {
+    let path = security
+        .keyfile_path()
+        .ok_or_else(|| anyhow::anyhow!("No config directory; set security.keyfile"))?;
+    let keyfile = octaskly::security_enhanced::KeyFile::load(&path)?;
+    let mut psks = security.psks();
+    let psk = psks.next().ok_or_else(|| anyhow::anyhow!("security.psk is not set"))?;
+    let mut manager = octaskly::SecurityManager::new(&keyfile, psk)?;
+    for psk in psks {
+        manager = manager.accept(psk)?;
+    }
+    Ok(manager)
+}
+
 /// Find an available port starting from the given port
 /// Cari port yang tersedia dimulai dari port yang diberikan
 async fn find_available_port(start_port: u16) -> Result<u16> {
@@ -619,18 +1836,23 @@ async fn start_p2p_discovery(
     peer_id: String,
     peer_name: String,
     broadcast_port: u16,
+    service_port: u16,
+    capabilities: Vec<String>,
 ) -> Result<Arc<octaskly::P2PNetwork>> {
-    let p2p_network = Arc::new(octaskly::P2PNetwork::new(
-        peer_id.clone(),
-        peer_name.clone(),
-        broadcast_port,
-    )?);
+    let p2p_network = Arc::new(
+        octaskly::P2PNetwork::new(peer_id.clone(), peer_name.clone(), broadcast_port)?
+            .with_role(octaskly::p2p::PeerRole::Dispatcher)
+            .with_service_port(service_port)
+            .with_capabilities(capabilities),
+    );
 
-    // Start mDNS service discovery
-    p2p_network.start_mdns_discovery().await?;
+    // Start mDNS service discovery; the broadcast below still works without it
+    if let Err(e) = p2p_network.start_mdns_discovery().await {
+        warn!("[P2P] mDNS unavailable, using broadcast only: {}", e);
+    }
 
     // Start discovery listener on broadcast port
-    p2p_network.start_discovery_listener(5555).await?;
+    p2p_network.start_discovery_listener(broadcast_port).await?;
 
     // Start periodic peer discovery announcements
     p2p_network.start_periodic_discovery(10).await?;
@@ -667,7 +1889,11 @@ async fn manage_p2p_distribution(
         // Process pending tasks and distribute to available peers
         while let Some(task) = distributor.get_next_task().await {
             // Find best peer for this task
-            let best_peer = distributor.find_best_peer(&task, 1, 256, false).await;
+            // Use the task's declared requirements (CPU rounded up to whole cores)
+            let min_cpu = task.resources.cpu_millicores.div_ceil(1000).max(1) as usize;
+            let min_ram_mb = task.resources.memory_mb;
+            let require_gpu = task.resources.gpus > 0;
+            let best_peer = distributor.find_best_peer(&task, min_cpu, min_ram_mb, require_gpu).await;
 
             match best_peer {
                 Some(peer_id) => {
@@ -710,6 +1936,7 @@ async fn handle_p2p_peer_updates(
 
         // Log current peer status
         let peers = distributor.get_all_peers().await;
+        octaskly::metrics::global().p2p_peers.set(peers.len() as i64);
         for peer in peers {
             debug!(
                 "[P2P] Peer: {} - CPU: {}, RAM: {}MB, GPU: {}, Slots: {}",
@@ -0,0 +1,271 @@
+// Process-wide metrics rendered in the Prometheus text exposition format
+// Metrik seluruh proses yang ditampilkan dalam format teks Prometheus
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+/// Buckets for task execution time, in seconds
+pub const DURATION_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 5.0, 15.0, 60.0, 300.0, 900.0, 3600.0];
+
+/// Buckets for the wait between submission and assignment, in seconds
+pub const LATENCY_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 30.0, 120.0];
+
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicI64);
+
+impl Gauge {
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Counter split by the value of one label
+#[derive(Debug, Default)]
+pub struct LabeledCounter(Mutex<BTreeMap<String, u64>>);
+
+impl LabeledCounter {
+    pub fn inc(&self, label: &str) {
+        *self.0.lock().unwrap().entry(label.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn get(&self, label: &str) -> u64 {
+        self.0.lock().unwrap().get(label).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Observations per bucket, not cumulative; the last one is +Inf
+    buckets: Vec<AtomicU64>,
+    /// Sum of observations as f64 bits
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0f64.to_bits()),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        let index = self.bounds.iter().position(|b| value <= *b).unwrap_or(self.bounds.len());
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self.sum.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            Some((f64::from_bits(bits) + value).to_bits())
+        });
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn sum(&self) -> f64 {
+        f64::from_bits(self.sum.load(Ordering::Relaxed))
+    }
+}
+
+/// Content type of the text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// A value read at scrape time (queue depth, worker count, ...)
+pub struct Sample {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub value: f64,
+}
+
+// Counters and histograms updated where things happen
+// Counter dan histogram yang diperbarui di tempat kejadian
+#[derive(Debug)]
+pub struct Metrics {
+    pub tasks_submitted: Counter,
+    pub tasks_finished: LabeledCounter,
+    pub schedule_latency: Histogram,
+    pub task_duration: Histogram,
+    pub transport_bytes_sent: Counter,
+    pub transport_bytes_received: Counter,
+    pub transport_frames_sent: Counter,
+    pub transport_frames_received: Counter,
+    pub transport_errors: Counter,
+    pub p2p_peers: Gauge,
+    pub auth_failures: Counter,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static GLOBAL: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Metrics of this process
+pub fn global() -> &'static Metrics {
+    &GLOBAL
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            tasks_submitted: Counter::default(),
+            tasks_finished: LabeledCounter::default(),
+            schedule_latency: Histogram::new(LATENCY_BUCKETS),
+            task_duration: Histogram::new(DURATION_BUCKETS),
+            transport_bytes_sent: Counter::default(),
+            transport_bytes_received: Counter::default(),
+            transport_frames_sent: Counter::default(),
+            transport_frames_received: Counter::default(),
+            transport_errors: Counter::default(),
+            p2p_peers: Gauge::default(),
+            auth_failures: Counter::default(),
+        }
+    }
+
+    /// Render every metric, plus the given scrape-time samples as gauges
+    pub fn render(&self, samples: &[Sample]) -> String {
+        let mut out = String::new();
+        counter(&mut out, "octaskly_tasks_submitted_total", "Tasks accepted for scheduling", self.tasks_submitted.get());
+        header(&mut out, "octaskly_tasks_finished_total", "Tasks that reached a terminal status", "counter");
+        for (status, value) in self.tasks_finished.0.lock().unwrap().iter() {
+            let _ = writeln!(out, "octaskly_tasks_finished_total{{status=\"{}\"}} {}", escape(status), value);
+        }
+        histogram(
+            &mut out,
+            "octaskly_schedule_latency_seconds",
+            "Time from submission to assignment",
+            &self.schedule_latency,
+        );
+        histogram(&mut out, "octaskly_task_duration_seconds", "Task execution time", &self.task_duration);
+        counter(&mut out, "octaskly_transport_bytes_sent_total", "Bytes written to peers", self.transport_bytes_sent.get());
+        counter(
+            &mut out,
+            "octaskly_transport_bytes_received_total",
+            "Bytes read from peers",
+            self.transport_bytes_received.get(),
+        );
+        counter(&mut out, "octaskly_transport_frames_sent_total", "Messages sent", self.transport_frames_sent.get());
+        counter(
+            &mut out,
+            "octaskly_transport_frames_received_total",
+            "Messages received",
+            self.transport_frames_received.get(),
+        );
+        counter(&mut out, "octaskly_transport_errors_total", "Failed sends and receives", self.transport_errors.get());
+        gauge(&mut out, "octaskly_p2p_peers", "Known P2P peers", self.p2p_peers.get() as f64);
+        counter(&mut out, "octaskly_auth_failures_total", "Rejected credentials", self.auth_failures.get());
+        for sample in samples {
+            gauge(&mut out, sample.name, sample.help, sample.value);
+        }
+        out
+    }
+}
+
+/// Serve `/metrics` on its own listener (used by workers, which have no API)
+pub async fn serve<F, Fut>(addr: &str, samples: F) -> anyhow::Result<()>
+where
+    F: Fn() -> Fut + Clone + Send + Sync + 'static,
+    Fut: std::future::Future<Output = Vec<Sample>> + Send,
+{
+    let app = axum::Router::new().route(
+        "/metrics",
+        axum::routing::get(move || {
+            let samples = samples.clone();
+            async move {
+                let samples = samples().await;
+                ([(axum::http::header::CONTENT_TYPE, CONTENT_TYPE)], global().render(&samples))
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("Metrics listening on http://{}/metrics", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, help, "counter");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    header(out, name, help, "gauge");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram) {
+    header(out, name, help, "histogram");
+    let mut cumulative = 0;
+    for (bound, bucket) in histogram.bounds.iter().zip(&histogram.buckets) {
+        cumulative += bucket.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+    }
+    cumulative += histogram.buckets[histogram.bounds.len()].load(Ordering::Relaxed);
+    let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, cumulative);
+    let _ = writeln!(out, "{}_sum {}", name, histogram.sum());
+    let _ = writeln!(out, "{}_count {}", name, histogram.count());
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_text_format() {
+        let metrics = Metrics::new();
+        metrics.tasks_finished.inc("Completed");
+        metrics.tasks_finished.inc("Completed");
+        metrics.task_duration.observe(0.3);
+        metrics.task_duration.observe(7200.0);
+        let text = metrics.render(&[Sample {
+            name: "octaskly_queue_depth",
+            help: "Tasks waiting",
+            value: 3.0,
+        }]);
+
+        assert!(text.contains("octaskly_tasks_finished_total{status=\"Completed\"} 2\n"));
+        assert!(text.contains("octaskly_task_duration_seconds_bucket{le=\"0.1\"} 0\n"));
+        assert!(text.contains("octaskly_task_duration_seconds_bucket{le=\"0.5\"} 1\n"));
+        assert!(text.contains("octaskly_task_duration_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("octaskly_task_duration_seconds_sum 7200.3\n"));
+        assert!(text.contains("# TYPE octaskly_queue_depth gauge\noctaskly_queue_depth 3\n"));
+    }
+}
@@ -10,13 +10,56 @@
 // - Direct P2P connectivity without central dispatcher (Konektivitas P2P langsung tanpa dispatcher pusat)
 // - Network interface detection and management (Deteksi dan pengelolaan antarmuka jaringan)
 
+use crate::protocol::PROTOCOL_VERSION;
 use anyhow::{anyhow, Result};
-use std::net::{SocketAddr, UdpSocket};
+use mdns_sd::{IfKind, ServiceDaemon, ServiceEvent, ServiceInfo};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::str::FromStr;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use tracing::{debug, error, info, warn};
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// DNS-SD service type peers advertise and browse for
+/// Jenis layanan DNS-SD yang diiklankan dan dicari oleh peer
+pub const SERVICE_TYPE: &str = "_octaskly._udp.local.";
+
+/// Role of a peer, advertised in its mDNS TXT record
+/// Peran peer, diiklankan dalam record TXT mDNS-nya
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeerRole {
+    Dispatcher,
+    Worker,
+}
+
+impl PeerRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PeerRole::Dispatcher => "dispatcher",
+            PeerRole::Worker => "worker",
+        }
+    }
+}
+
+impl fmt::Display for PeerRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PeerRole {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "dispatcher" => Ok(PeerRole::Dispatcher),
+            "worker" => Ok(PeerRole::Worker),
+            other => Err(anyhow!("Unknown peer role: {}", other)),
+        }
+    }
+}
 
 /// P2P Peer information
 /// Informasi Peer P2P
@@ -45,6 +88,14 @@ pub struct P2PPeer {
     /// Service type identifier
     /// Pengenal jenis layanan
     pub service_type: String,
+
+    /// Role advertised over mDNS; unknown for broadcast announcements
+    /// Peran yang diiklankan melalui mDNS; tidak diketahui untuk pengumuman siaran
+    pub role: Option<PeerRole>,
+
+    /// Capabilities advertised over mDNS, e.g. "encrypt" or "gpu"
+    /// Kemampuan yang diiklankan melalui mDNS, mis. "encrypt" atau "gpu"
+    pub capabilities: Vec<String>,
 }
 
 impl P2PPeer {
@@ -63,7 +114,63 @@ impl P2PPeer {
             port,
             last_seen: now,
             service_type,
+            role: None,
+            capabilities: Vec::new(),
+        }
+    }
+
+    /// Build a peer from a resolved DNS-SD service and its TXT record
+    /// Bangun peer dari layanan DNS-SD yang ter-resolve dan record TXT-nya
+    ///
+    /// Services speaking another protocol version are rejected
+    /// Layanan dengan versi protokol lain ditolak
+    pub fn from_service(info: &ServiceInfo) -> Result<Self> {
+        let version = info
+            .get_property_val_str("proto")
+            .and_then(|v| v.parse::<u16>().ok())
+            .ok_or_else(|| anyhow!("missing protocol version"))?;
+        if version != PROTOCOL_VERSION {
+            return Err(anyhow!(
+                "protocol version {} (expected {})",
+                version, PROTOCOL_VERSION
+            ));
         }
+        let id = info
+            .get_property_val_str("id")
+            .ok_or_else(|| anyhow!("missing id"))?;
+        let name = info.get_property_val_str("name").unwrap_or(id);
+        let port = match info.get_property_val_str("port") {
+            Some(port) => port.parse().map_err(|_| anyhow!("invalid port: {}", port))?,
+            None => info.get_port(),
+        };
+
+        // Prefer a routable IPv4 address, the transport listens on 0.0.0.0;
+        // IPv6 link-local addresses are unusable without their interface
+        // Utamakan alamat IPv4 yang dapat dirutekan, transport mendengarkan di 0.0.0.0;
+        // alamat IPv6 link-local tidak dapat dipakai tanpa antarmukanya
+        let ip = info
+            .get_addresses()
+            .iter()
+            .filter(|ip| !matches!(ip, IpAddr::V6(v6) if v6.is_unicast_link_local()))
+            .min_by_key(|ip| (!ip.is_ipv4(), ip.is_loopback()))
+            .ok_or_else(|| anyhow!("no usable address"))?;
+
+        let mut peer = P2PPeer::new(
+            id.to_string(),
+            name.to_string(),
+            ip.to_string(),
+            port,
+            info.get_type().to_string(),
+        );
+        peer.role = info.get_property_val_str("role").map(str::parse).transpose()?;
+        peer.capabilities = info
+            .get_property_val_str("caps")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|c| !c.is_empty())
+            .map(String::from)
+            .collect();
+        Ok(peer)
     }
 
     /// Check if peer is still considered active (seen within timeout)
@@ -80,9 +187,11 @@ impl P2PPeer {
     /// Get socket address for peer
     /// Dapatkan alamat soket untuk peer
     pub fn socket_addr(&self) -> Result<SocketAddr> {
-        format!("{}:{}", self.ip_address, self.port)
+        let ip: IpAddr = self
+            .ip_address
             .parse()
-            .map_err(|e| anyhow!("Invalid socket address: {}", e))
+            .map_err(|e| anyhow!("Invalid socket address: {}", e))?;
+        Ok(SocketAddr::new(ip, self.port))
     }
 }
 
@@ -112,6 +221,30 @@ pub struct P2PNetwork {
     /// mDNS service type
     /// Jenis layanan mDNS
     service_type: String,
+
+    /// Peers seen for the first time, for subscribers
+    /// Peer yang baru pertama kali terlihat, untuk pelanggan
+    discovered: broadcast::Sender<P2PPeer>,
+
+    /// Role advertised over mDNS
+    /// Peran yang diiklankan melalui mDNS
+    role: Option<PeerRole>,
+
+    /// Port of this peer's dispatcher or worker service, advertised over mDNS
+    /// Port layanan dispatcher atau worker peer ini, diiklankan melalui mDNS
+    service_port: u16,
+
+    /// Capabilities advertised over mDNS
+    /// Kemampuan yang diiklankan melalui mDNS
+    capabilities: Vec<String>,
+
+    /// Also advertise and browse on the loopback interface
+    /// Juga iklankan dan cari di antarmuka loopback
+    loopback: bool,
+
+    /// Running mDNS responder, once started
+    /// Responder mDNS yang berjalan, setelah dimulai
+    mdns: parking_lot::Mutex<Option<ServiceDaemon>>,
 }
 
 impl P2PNetwork {
@@ -132,16 +265,54 @@ impl P2PNetwork {
             local_name, local_ip, local_port
         );
 
+        // Without a network only peers on this machine can be found
+        // Tanpa jaringan hanya peer di mesin ini yang dapat ditemukan
+        let loopback = local_ip == "127.0.0.1";
+
         Ok(Self {
             local_id: local_id.clone(),
             local_name,
             local_ip,
             local_port,
             peers: Arc::new(RwLock::new(HashMap::new())),
-            service_type: "_octaskly._udp.local.".to_string(),
+            service_type: SERVICE_TYPE.to_string(),
+            discovered: broadcast::channel(64).0,
+            role: None,
+            service_port: local_port,
+            capabilities: Vec::new(),
+            loopback,
+            mdns: parking_lot::Mutex::new(None),
         })
     }
 
+    /// Set the role advertised over mDNS
+    /// Setel peran yang diiklankan melalui mDNS
+    pub fn with_role(mut self, role: PeerRole) -> Self {
+        self.role = Some(role);
+        self
+    }
+
+    /// Set the service port advertised over mDNS (defaults to the P2P port)
+    /// Setel port layanan yang diiklankan melalui mDNS (bawaan: port P2P)
+    pub fn with_service_port(mut self, port: u16) -> Self {
+        self.service_port = port;
+        self
+    }
+
+    /// Set the capabilities advertised over mDNS
+    /// Setel kemampuan yang diiklankan melalui mDNS
+    pub fn with_capabilities(mut self, capabilities: Vec<String>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Advertise and browse on loopback too, for peers on the same machine
+    /// Iklankan dan cari juga di loopback, untuk peer di mesin yang sama
+    pub fn with_loopback(mut self, loopback: bool) -> Self {
+        self.loopback = loopback;
+        self
+    }
+
     /// Detect local IP address (preferred method)
     /// Deteksi alamat IP lokal (metode pilihan)
     ///
@@ -173,8 +344,24 @@ impl P2PNetwork {
     /// Register a discovered peer
     /// Daftarkan peer yang ditemukan
     pub async fn add_peer(&self, peer: P2PPeer) {
-        let mut peers = self.peers.write().await;
-        if let Some(_existing) = peers.get(&peer.id) {
+        Self::insert_peer(&self.peers, &self.discovered, peer).await;
+    }
+
+    // Record a peer, telling subscribers about peers not seen before
+    // Catat peer, beri tahu pelanggan tentang peer yang belum pernah terlihat
+    async fn insert_peer(
+        peers: &RwLock<HashMap<String, P2PPeer>>,
+        discovered: &broadcast::Sender<P2PPeer>,
+        peer: P2PPeer,
+    ) {
+        let mut peers = peers.write().await;
+        if let Some(existing) = peers.get_mut(&peer.id) {
+            // A broadcast announcement only refreshes what mDNS told us
+            // Pengumuman siaran hanya menyegarkan apa yang diberitahukan mDNS
+            if existing.role.is_some() && peer.role.is_none() {
+                existing.last_seen = peer.last_seen;
+                return;
+            }
             debug!(
                 "[P2P] Updating peer: {} ({}:{})",
                 peer.name, peer.ip_address, peer.port
@@ -184,10 +371,17 @@ impl P2PNetwork {
                 "[P2P] New peer discovered: {} ({}:{})",
                 peer.name, peer.ip_address, peer.port
             );
+            let _ = discovered.send(peer.clone());
         }
         peers.insert(peer.id.clone(), peer);
     }
 
+    /// Receive every peer discovered from now on
+    /// Terima setiap peer yang ditemukan mulai sekarang
+    pub fn subscribe_discoveries(&self) -> broadcast::Receiver<P2PPeer> {
+        self.discovered.subscribe()
+    }
+
     /// Get all active peers
     /// Dapatkan semua peer yang aktif
     pub async fn get_active_peers(&self, timeout_secs: i64) -> Vec<P2PPeer> {
@@ -256,22 +450,127 @@ impl P2PNetwork {
     /// Start mDNS service discovery
     /// Mulai penemuan layanan mDNS
     ///
-    /// This will advertise this peer on the local network using mDNS
-    /// Ini akan mengiklankan peer ini di jaringan lokal menggunakan mDNS
+    /// Advertises this peer as a DNS-SD service whose TXT record carries its
+    /// id, name, role, service port, protocol version and capabilities, and
+    /// browses for other peers. Unlike the 255.255.255.255 broadcast, multicast
+    /// DNS is usually passed on by Wi-Fi access points.
+    /// Mengiklankan peer ini sebagai layanan DNS-SD yang record TXT-nya memuat
+    /// id, nama, peran, port layanan, versi protokol, dan kemampuannya, serta
+    /// mencari peer lain. Tidak seperti siaran 255.255.255.255, DNS multicast
+    /// biasanya diteruskan oleh access point Wi-Fi.
     pub async fn start_mdns_discovery(&self) -> Result<()> {
-        // Note: Actual mDNS implementation would go here
-        // In production, use zeroconf or mdns-sd crate for actual service discovery
-        // Catatan: Implementasi mDNS aktual akan dilakukan di sini
-        // Dalam produksi, gunakan crate zeroconf atau mdns-sd untuk penemuan layanan aktual
+        let daemon = ServiceDaemon::new()
+            .map_err(|e| anyhow!("Failed to start mDNS responder: {}", e))?;
+        if self.loopback {
+            daemon.enable_interface(IfKind::LoopbackV4)?;
+        }
+        daemon.register(self.service_info()?)?;
+        let events = daemon.browse(SERVICE_TYPE)?;
+
+        let peers = self.peers.clone();
+        let discovered = self.discovered.clone();
+        let local_id = self.local_id.clone();
+        tokio::spawn(async move {
+            while let Ok(event) = events.recv_async().await {
+                match event {
+                    ServiceEvent::ServiceResolved(info) => match P2PPeer::from_service(&info) {
+                        Ok(peer) if peer.id != local_id => {
+                            Self::insert_peer(&peers, &discovered, peer).await;
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            debug!("[P2P] Ignoring mDNS service {}: {}", info.get_fullname(), e);
+                        }
+                    },
+                    // Instances are named after the peer id
+                    // Instance dinamai sesuai id peer
+                    ServiceEvent::ServiceRemoved(_, fullname) => {
+                        let id = fullname
+                            .strip_suffix(SERVICE_TYPE)
+                            .and_then(|name| name.strip_suffix('.'));
+                        if let Some(id) = id {
+                            if peers.write().await.remove(id).is_some() {
+                                info!("[P2P] Peer left: {}", id);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
 
+        *self.mdns.lock() = Some(daemon);
         info!(
-            "[P2P] mDNS service starting: {}._octaskly._udp.local",
-            self.local_name
+            "[P2P] mDNS service advertised: {}.{}",
+            self.local_id, SERVICE_TYPE
         );
 
         Ok(())
     }
 
+    /// Withdraw the mDNS advertisement and stop browsing
+    /// Tarik iklan mDNS dan berhenti mencari
+    pub async fn stop_mdns_discovery(&self) {
+        let daemon = self.mdns.lock().take();
+        if let Some(daemon) = daemon {
+            // Wait briefly so the goodbye packet goes out before shutdown
+            // Tunggu sebentar agar paket goodbye terkirim sebelum dimatikan
+            let fullname = format!("{}.{}", self.local_id, SERVICE_TYPE);
+            if let Ok(status) = daemon.unregister(&fullname) {
+                let _ = tokio::time::timeout(Duration::from_secs(1), status.recv_async()).await;
+            }
+            let _ = daemon.shutdown();
+        }
+    }
+
+    // DNS-SD record of this peer
+    // Record DNS-SD dari peer ini
+    fn service_info(&self) -> Result<ServiceInfo> {
+        let mut properties = HashMap::from([
+            ("id".to_string(), self.local_id.clone()),
+            ("name".to_string(), self.local_name.clone()),
+            ("port".to_string(), self.service_port.to_string()),
+            ("proto".to_string(), PROTOCOL_VERSION.to_string()),
+            ("caps".to_string(), self.capabilities.join(",")),
+        ]);
+        if let Some(role) = self.role {
+            properties.insert("role".to_string(), role.to_string());
+        }
+        let host = format!("{}.local.", self.local_id);
+        let info = ServiceInfo::new(
+            SERVICE_TYPE,
+            &self.local_id,
+            &host,
+            "",
+            self.service_port,
+            properties,
+        )?;
+        Ok(info.enable_addr_auto())
+    }
+
+    /// Wait up to `timeout` for a peer with the given role, e.g. a dispatcher
+    /// Tunggu hingga `timeout` untuk peer dengan peran tertentu, mis. dispatcher
+    pub async fn find_peer(&self, role: PeerRole, timeout: Duration) -> Option<P2PPeer> {
+        // Subscribe first so a peer found meanwhile is not missed
+        // Berlangganan dulu agar peer yang ditemukan sementara tidak terlewat
+        let mut discovered = self.subscribe_discoveries();
+        if let Some(peer) = self.peers.read().await.values().find(|p| p.role == Some(role)) {
+            return Some(peer.clone());
+        }
+        tokio::time::timeout(timeout, async {
+            loop {
+                match discovered.recv().await {
+                    Ok(peer) if peer.role == Some(role) => return Some(peer),
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
     /// Periodic peer discovery via broadcast
     /// Penemuan peer berkala melalui siaran
     pub async fn start_periodic_discovery(&self, interval_secs: u64) -> Result<()> {
@@ -316,9 +615,12 @@ impl P2PNetwork {
         // Spawn tugas async untuk mendengarkan paket discovery
         let peers = self.peers.clone();
         let local_id = self.local_id.clone();
+        let discovered = self.discovered.clone();
 
         tokio::spawn(async move {
-            match UdpSocket::bind(addr) {
+            // Async socket: a blocking recv would stall a runtime thread
+            // Soket async: recv yang memblokir akan menahan thread runtime
+            match tokio::net::UdpSocket::bind(addr).await {
                 Ok(socket) => {
                     // Set socket to allow broadcast
                     // Setel soket untuk mengizinkan siaran
@@ -327,7 +629,7 @@ impl P2PNetwork {
                     let mut buffer = [0u8; 512];
 
                     loop {
-                        match socket.recv_from(&mut buffer) {
+                        match socket.recv_from(&mut buffer).await {
                             Ok((size, peer_addr)) => {
                                 // Process discovery packet
                                 // Proses paket discovery
@@ -349,11 +651,9 @@ impl P2PNetwork {
                                                     parts[1].to_string(),
                                                     peer_addr.ip().to_string(),
                                                     port,
-                                                    "_octaskly._udp.local.".to_string(),
+                                                    SERVICE_TYPE.to_string(),
                                                 );
-
-                                                let mut p = peers.write().await;
-                                                p.insert(peer.id.clone(), peer);
+                                                Self::insert_peer(&peers, &discovered, peer).await;
                                             }
                                         }
                                     }
@@ -463,4 +763,81 @@ mod tests {
         network.add_peer(peer).await;
         assert_eq!(network.peer_count().await, 1);
     }
+
+    #[test]
+    fn test_peer_from_service() {
+        let service = |proto: &str| {
+            let properties = HashMap::from([
+                ("id".to_string(), "dispatcher-7878".to_string()),
+                ("name".to_string(), "Dispatcher".to_string()),
+                ("role".to_string(), "dispatcher".to_string()),
+                ("port".to_string(), "7878".to_string()),
+                ("proto".to_string(), proto.to_string()),
+                ("caps".to_string(), "encrypt,api".to_string()),
+            ]);
+            ServiceInfo::new(
+                SERVICE_TYPE,
+                "dispatcher-7878",
+                "dispatcher-7878.local.",
+                "127.0.0.1,fe80::1,192.168.1.10",
+                7878,
+                properties,
+            )
+            .unwrap()
+        };
+
+        let peer = P2PPeer::from_service(&service(&PROTOCOL_VERSION.to_string())).unwrap();
+        assert_eq!(peer.id, "dispatcher-7878");
+        assert_eq!(peer.role, Some(PeerRole::Dispatcher));
+        assert_eq!(peer.ip_address, "192.168.1.10");
+        assert_eq!(peer.port, 7878);
+        assert_eq!(peer.capabilities, vec!["encrypt", "api"]);
+
+        let err = P2PPeer::from_service(&service("999")).unwrap_err();
+        assert!(err.to_string().contains("protocol version"));
+    }
+
+    #[tokio::test]
+    async fn test_mdns_discovery_on_loopback() {
+        let dispatcher = P2PNetwork::new(
+            "mdns-test-dispatcher".to_string(),
+            "Dispatcher".to_string(),
+            5555,
+        )
+        .unwrap()
+        .with_role(PeerRole::Dispatcher)
+        .with_service_port(7878)
+        .with_capabilities(vec!["encrypt".to_string()])
+        .with_loopback(true);
+        let worker = P2PNetwork::new(
+            "mdns-test-worker".to_string(),
+            "Worker-1".to_string(),
+            7879,
+        )
+        .unwrap()
+        .with_role(PeerRole::Worker)
+        .with_loopback(true);
+
+        dispatcher.start_mdns_discovery().await.unwrap();
+        worker.start_mdns_discovery().await.unwrap();
+
+        // Other dispatchers on the network may answer first
+        let any = worker.find_peer(PeerRole::Dispatcher, Duration::from_secs(10)).await;
+        assert!(any.is_some_and(|peer| peer.role == Some(PeerRole::Dispatcher)));
+        let found = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let Some(peer) = worker.get_peer("mdns-test-dispatcher").await {
+                    return peer;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("dispatcher found over mDNS");
+        assert_eq!(found.port, 7878);
+        assert_eq!(found.capabilities, vec!["encrypt"]);
+
+        worker.stop_mdns_discovery().await;
+        dispatcher.stop_mdns_discovery().await;
+    }
 }
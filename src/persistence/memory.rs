@@ -0,0 +1,362 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::{
+    decode_cursor, DeliveryStatus, PendingTask, QueueState, SortOrder, StoredTask, TaskPage, TaskQuery, TaskStore,
+    StoredSecret, Transition, Webhook, WebhookDelivery,
+};
+use crate::audit::{AuditQuery, AuditRecord};
+use crate::protocol::TaskResult;
+
+/// Task store kept in process memory; nothing survives a restart.
+/// Meant for unit tests and throwaway dispatchers.
+#[derive(Default)]
+pub struct MemoryStore {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default, Clone)]
+struct Inner {
+    tasks: HashMap<String, StoredTask>,
+    queue: HashMap<String, (u64, PendingTask)>,
+    results: Vec<TaskResult>,
+    next_seq: u64,
+    webhooks: Vec<Webhook>,
+    deliveries: HashMap<String, WebhookDelivery>,
+    secrets: std::collections::BTreeMap<String, StoredSecret>,
+    audit: Vec<AuditRecord>,
+    revoked_tokens: std::collections::BTreeSet<String>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Inner {
+    fn apply(&mut self, transition: &Transition<'_>) -> Result<()> {
+        match *transition {
+            Transition::Queued(task) | Transition::Assigned(task, _) => {
+                let (state, worker_id) = match *transition {
+                    Transition::Assigned(_, worker_id) => (QueueState::Assigned, Some(worker_id.to_string())),
+                    _ => (QueueState::Queued, None),
+                };
+                let seq = match self.queue.get(&task.id) {
+                    Some((seq, _)) => *seq,
+                    None => {
+                        self.next_seq += 1;
+                        self.next_seq
+                    }
+                };
+                self.queue.insert(
+                    task.id.clone(),
+                    (
+                        seq,
+                        PendingTask {
+                            task: task.clone(),
+                            state,
+                            worker_id: worker_id.clone(),
+                        },
+                    ),
+                );
+                let entry = self.tasks.entry(task.id.clone()).or_insert_with(|| StoredTask {
+                    id: task.id.clone(),
+                    command: task.command.clone(),
+                    status: String::new(),
+                    worker_id: None,
+                    stdout: String::new(),
+                    stderr: String::new(),
+                    exit_code: None,
+                    duration_ms: 0,
+                    created_at: chrono::DateTime::from_timestamp(task.created_at, 0)
+                        .unwrap_or_else(chrono::Utc::now)
+                        .to_rfc3339(),
+                    completed_at: None,
+                    owner: String::new(),
+                    labels: HashMap::new(),
+                    parent_id: task.parent_id.clone(),
+                });
+                entry.status = state.task_status().to_string();
+                entry.worker_id = worker_id;
+                entry.owner = task.owner.clone();
+                entry.labels = task.labels.clone();
+            }
+            Transition::Running(task_id) => {
+                if let Some((_, pending)) = self.queue.get_mut(task_id) {
+                    pending.state = QueueState::Running;
+                }
+                if let Some(task) = self.tasks.get_mut(task_id) {
+                    task.status = QueueState::Running.task_status().to_string();
+                }
+            }
+            Transition::Finished(result) => {
+                if let Some(task) = self.tasks.get_mut(&result.task_id) {
+                    task.status = format!("{:?}", result.status);
+                    task.worker_id = Some(result.worker_id.clone());
+                    task.stdout = result.stdout.clone();
+                    task.stderr = result.stderr.clone();
+                    task.exit_code = result.exit_code;
+                    task.duration_ms = result.duration_ms;
+                    task.completed_at = chrono::DateTime::from_timestamp(result.completed_at, 0)
+                        .map(|t| t.to_rfc3339());
+                }
+                self.results.retain(|r| r.task_id != result.task_id);
+                self.results.push(result.clone());
+                self.queue.remove(&result.task_id);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn matches(task: &StoredTask, query: &TaskQuery) -> bool {
+    let created_at = chrono::DateTime::parse_from_rfc3339(&task.created_at).ok();
+    query.status.as_ref().is_none_or(|s| &task.status == s)
+        && query.worker_id.as_ref().is_none_or(|w| task.worker_id.as_ref() == Some(w))
+        && query.owner.as_ref().is_none_or(|o| &task.owner == o)
+        && query.label.as_ref().is_none_or(|(k, v)| task.labels.get(k) == Some(v))
+        && query.created_after.is_none_or(|after| created_at.is_some_and(|c| c >= after))
+        && query.created_before.is_none_or(|before| created_at.is_some_and(|c| c < before))
+        && query.parent_id.as_ref().is_none_or(|p| task.parent_id.as_ref() == Some(p))
+        && query.command_contains.as_ref().is_none_or(|needle| task.command.contains(needle.as_str()))
+}
+
+fn newest_first(mut tasks: Vec<StoredTask>) -> Vec<StoredTask> {
+    tasks.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    tasks
+}
+
+#[async_trait]
+impl TaskStore for MemoryStore {
+    async fn record(&self, transitions: &[Transition<'_>]) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        // Work on a copy so a failed batch leaves nothing behind
+        let mut next = inner.clone();
+        for transition in transitions {
+            next.apply(transition)?;
+        }
+        *inner = next;
+        Ok(())
+    }
+
+    async fn load_pending(&self) -> Result<Vec<PendingTask>> {
+        let inner = self.inner.lock().unwrap();
+        let mut pending: Vec<&(u64, PendingTask)> = inner.queue.values().collect();
+        pending.sort_by_key(|(seq, _)| *seq);
+        Ok(pending.into_iter().map(|(_, p)| p.clone()).collect())
+    }
+
+    async fn load_results(&self, limit: usize) -> Result<Vec<TaskResult>> {
+        let inner = self.inner.lock().unwrap();
+        let mut results = inner.results.clone();
+        results.sort_by_key(|r| std::cmp::Reverse(r.completed_at));
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    async fn store_task(&self, task: &StoredTask) -> Result<()> {
+        self.inner.lock().unwrap().tasks.insert(task.id.clone(), task.clone());
+        Ok(())
+    }
+
+    async fn get_task(&self, task_id: &str) -> Result<Option<StoredTask>> {
+        Ok(self.inner.lock().unwrap().tasks.get(task_id).cloned())
+    }
+
+    async fn get_all_tasks(&self) -> Result<Vec<StoredTask>> {
+        let tasks = self.inner.lock().unwrap().tasks.values().cloned().collect();
+        Ok(newest_first(tasks))
+    }
+
+    async fn query_tasks(&self, query: &TaskQuery) -> Result<TaskPage> {
+        let cursor = query.cursor.as_deref().map(decode_cursor).transpose()?;
+        let mut tasks: Vec<StoredTask> = self
+            .inner
+            .lock()
+            .unwrap()
+            .tasks
+            .values()
+            .filter(|t| matches(t, query))
+            .cloned()
+            .collect();
+        tasks.sort_by(|a, b| (&a.created_at, &a.id).cmp(&(&b.created_at, &b.id)));
+        if query.order == SortOrder::Desc {
+            tasks.reverse();
+        }
+        if let Some((created_at, id)) = cursor {
+            let key = (created_at, id);
+            tasks.retain(|t| {
+                let position = (t.created_at.clone(), t.id.clone());
+                match query.order {
+                    SortOrder::Desc => position < key,
+                    SortOrder::Asc => position > key,
+                }
+            });
+        }
+        let page_size = query.page_size();
+        tasks.truncate(page_size + 1);
+        Ok(TaskPage::from_rows(tasks, page_size))
+    }
+
+    async fn array_status_counts(&self, parent_id: &str) -> Result<HashMap<String, usize>> {
+        let mut counts = HashMap::new();
+        for task in self.inner.lock().unwrap().tasks.values() {
+            if task.parent_id.as_deref() == Some(parent_id) {
+                *counts.entry(task.status.clone()).or_insert(0) += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn get_worker_tasks(&self, worker_id: &str) -> Result<Vec<StoredTask>> {
+        let tasks = self
+            .inner
+            .lock()
+            .unwrap()
+            .tasks
+            .values()
+            .filter(|t| t.worker_id.as_deref() == Some(worker_id))
+            .cloned()
+            .collect();
+        Ok(newest_first(tasks))
+    }
+
+    async fn save_webhook(&self, webhook: &Webhook) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.webhooks.iter_mut().find(|w| w.id == webhook.id) {
+            Some(existing) => *existing = webhook.clone(),
+            None => inner.webhooks.push(webhook.clone()),
+        }
+        Ok(())
+    }
+
+    async fn list_webhooks(&self) -> Result<Vec<Webhook>> {
+        Ok(self.inner.lock().unwrap().webhooks.clone())
+    }
+
+    async fn delete_webhook(&self, webhook_id: &str) -> Result<bool> {
+        let mut inner = self.inner.lock().unwrap();
+        let before = inner.webhooks.len();
+        inner.webhooks.retain(|w| w.id != webhook_id);
+        inner.deliveries.retain(|_, d| d.webhook_id != webhook_id);
+        Ok(inner.webhooks.len() < before)
+    }
+
+    async fn record_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        self.inner
+            .lock()
+            .unwrap()
+            .deliveries
+            .insert(delivery.id.clone(), delivery.clone());
+        Ok(())
+    }
+
+    async fn list_deliveries(
+        &self,
+        webhook_id: Option<&str>,
+        status: Option<DeliveryStatus>,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let mut deliveries: Vec<WebhookDelivery> = self
+            .inner
+            .lock()
+            .unwrap()
+            .deliveries
+            .values()
+            .filter(|d| webhook_id.is_none_or(|id| d.webhook_id == id))
+            .filter(|d| status.is_none_or(|s| d.status == s))
+            .cloned()
+            .collect();
+        deliveries.sort_by(|a, b| (&b.created_at, &b.id).cmp(&(&a.created_at, &a.id)));
+        deliveries.truncate(limit);
+        Ok(deliveries)
+    }
+
+    async fn save_secret(&self, secret: &StoredSecret) -> Result<()> {
+        self.inner
+            .lock()
+            .unwrap()
+            .secrets
+            .insert(secret.name.clone(), secret.clone());
+        Ok(())
+    }
+
+    async fn get_secret(&self, name: &str) -> Result<Option<StoredSecret>> {
+        Ok(self.inner.lock().unwrap().secrets.get(name).cloned())
+    }
+
+    async fn list_secrets(&self) -> Result<Vec<StoredSecret>> {
+        Ok(self.inner.lock().unwrap().secrets.values().cloned().collect())
+    }
+
+    async fn delete_secret(&self, name: &str) -> Result<bool> {
+        Ok(self.inner.lock().unwrap().secrets.remove(name).is_some())
+    }
+
+    async fn append_audit(&self, record: &AuditRecord) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.audit.last().is_some_and(|last| last.seq >= record.seq) {
+            anyhow::bail!("audit record {} already exists", record.seq);
+        }
+        inner.audit.push(record.clone());
+        Ok(())
+    }
+
+    async fn last_audit(&self) -> Result<Option<AuditRecord>> {
+        Ok(self.inner.lock().unwrap().audit.last().cloned())
+    }
+
+    async fn query_audit(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        let limit = query.limit.unwrap_or(super::DEFAULT_PAGE_SIZE).min(super::MAX_PAGE_SIZE);
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .audit
+            .iter()
+            .rev()
+            .filter(|record| query.matches(record))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn audit_after(&self, seq: u64, limit: usize) -> Result<Vec<AuditRecord>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.audit.iter().filter(|r| r.seq > seq).take(limit).cloned().collect())
+    }
+
+    async fn revoke_token(&self, token_id: &str) -> Result<()> {
+        self.inner.lock().unwrap().revoked_tokens.insert(token_id.to_string());
+        Ok(())
+    }
+
+    async fn revoked_tokens(&self) -> Result<Vec<String>> {
+        Ok(self.inner.lock().unwrap().revoked_tokens.iter().cloned().collect())
+    }
+
+    async fn get_stats(&self) -> Result<(usize, usize, usize)> {
+        let inner = self.inner.lock().unwrap();
+        let count = |status: &str| inner.tasks.values().filter(|t| t.status == status).count();
+        Ok((inner.tasks.len(), count("Completed"), count("Failed")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::store_tests;
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        store_tests::store_and_retrieve_task(&MemoryStore::new()).await;
+        store_tests::durable_queue_transitions(&MemoryStore::new()).await;
+        store_tests::filter_and_paginate(&MemoryStore::new()).await;
+        store_tests::array_counts(&MemoryStore::new()).await;
+        store_tests::webhooks_and_deliveries(&MemoryStore::new()).await;
+        store_tests::secrets(&MemoryStore::new()).await;
+        store_tests::audit_chain(&MemoryStore::new()).await;
+        store_tests::revoked_tokens(&MemoryStore::new()).await;
+    }
+}
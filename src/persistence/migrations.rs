@@ -0,0 +1,316 @@
+use anyhow::{bail, Result};
+use chrono::Utc;
+use sqlx::sqlite::SqliteConnection;
+use sqlx::{Connection, Executor};
+
+/// One step of the database schema; never edit a released migration, add a new one
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every schema change in order. Version 1 uses `IF NOT EXISTS` so that
+/// databases created before versioning existed are adopted as-is.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "task history, results and audit log",
+        sql: "CREATE TABLE IF NOT EXISTS tasks (
+                id TEXT PRIMARY KEY,
+                command TEXT NOT NULL,
+                status TEXT NOT NULL,
+                worker_id TEXT,
+                stdout TEXT,
+                stderr TEXT,
+                exit_code INTEGER,
+                duration_ms INTEGER,
+                created_at TEXT NOT NULL,
+                completed_at TEXT
+            );
+            CREATE TABLE IF NOT EXISTS results (
+                task_id TEXT PRIMARY KEY,
+                worker_id TEXT NOT NULL,
+                status TEXT NOT NULL,
+                stdout TEXT,
+                stderr TEXT,
+                exit_code INTEGER,
+                duration_ms INTEGER,
+                completed_at TEXT NOT NULL,
+                FOREIGN KEY(task_id) REFERENCES tasks(id)
+            );
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                event_type TEXT NOT NULL,
+                worker_id TEXT,
+                task_id TEXT,
+                details TEXT
+            );",
+    },
+    Migration {
+        version: 2,
+        description: "durable task queue",
+        sql: "CREATE TABLE IF NOT EXISTS task_queue (
+                task_id TEXT PRIMARY KEY,
+                payload TEXT NOT NULL,
+                state TEXT NOT NULL,
+                worker_id TEXT,
+                seq INTEGER NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+    },
+    Migration {
+        version: 3,
+        description: "task owner and attempt count",
+        sql: "ALTER TABLE tasks ADD COLUMN owner TEXT NOT NULL DEFAULT 'default';
+            ALTER TABLE tasks ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;",
+    },
+    Migration {
+        version: 4,
+        description: "task labels and listing indexes",
+        sql: "ALTER TABLE tasks ADD COLUMN labels TEXT NOT NULL DEFAULT '{}';
+            CREATE INDEX IF NOT EXISTS idx_tasks_created ON tasks(created_at, id);
+            CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status, created_at, id);
+            CREATE INDEX IF NOT EXISTS idx_tasks_worker ON tasks(worker_id, created_at, id);
+            CREATE INDEX IF NOT EXISTS idx_tasks_owner ON tasks(owner, created_at, id);
+            CREATE INDEX IF NOT EXISTS idx_results_completed ON results(completed_at);",
+    },
+    Migration {
+        version: 5,
+        description: "array task parent",
+        sql: "ALTER TABLE tasks ADD COLUMN parent_id TEXT;
+            CREATE INDEX IF NOT EXISTS idx_tasks_parent ON tasks(parent_id, status);",
+    },
+    Migration {
+        version: 6,
+        description: "webhook subscriptions and delivery log",
+        sql: "CREATE TABLE IF NOT EXISTS webhooks (
+                id TEXT PRIMARY KEY,
+                url TEXT NOT NULL,
+                events TEXT NOT NULL DEFAULT '[]',
+                task_id TEXT,
+                owner TEXT,
+                secret TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                id TEXT PRIMARY KEY,
+                webhook_id TEXT NOT NULL,
+                event_id INTEGER NOT NULL,
+                event_type TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                response_status INTEGER,
+                last_error TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_deliveries_webhook ON webhook_deliveries(webhook_id, created_at);
+            CREATE INDEX IF NOT EXISTS idx_deliveries_status ON webhook_deliveries(status, created_at);",
+    },
+    Migration {
+        version: 7,
+        description: "signer of task results",
+        sql: "ALTER TABLE results ADD COLUMN signer TEXT;",
+    },
+    Migration {
+        version: 8,
+        description: "encrypted task secrets",
+        sql: "CREATE TABLE IF NOT EXISTS secrets (
+                name TEXT PRIMARY KEY,
+                projects TEXT NOT NULL,
+                sealed BLOB NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+    },
+    Migration {
+        version: 9,
+        description: "hash-chained audit log and revoked tokens",
+        // The version 1 table was never written to
+        sql: "DROP TABLE IF EXISTS audit_log;
+            CREATE TABLE audit_log (
+                seq INTEGER PRIMARY KEY,
+                timestamp TEXT NOT NULL,
+                action TEXT NOT NULL,
+                actor TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                source_ip TEXT,
+                allowed INTEGER NOT NULL,
+                reason TEXT,
+                prev_hash TEXT NOT NULL,
+                hash TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_audit_action ON audit_log(action, seq);
+            CREATE INDEX IF NOT EXISTS idx_audit_actor ON audit_log(actor, seq);
+            CREATE TABLE IF NOT EXISTS revoked_tokens (
+                id TEXT PRIMARY KEY,
+                revoked_at TEXT NOT NULL
+            );",
+    },
+];
+
+/// Newest schema version this build understands
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Where a database stands relative to this build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaStatus {
+    pub current: u32,
+    pub latest: u32,
+    /// Versions not applied yet, in order
+    pub pending: Vec<u32>,
+}
+
+async fn ensure_version_table(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )",
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Version of the newest migration applied to the database (0 if none)
+pub async fn current_version(conn: &mut SqliteConnection) -> Result<u32> {
+    let tracked: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'schema_version'",
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    if !tracked {
+        return Ok(0);
+    }
+    let version: u32 = sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(version)
+}
+
+/// Compare the database with the migrations known to this build
+pub async fn status(conn: &mut SqliteConnection) -> Result<SchemaStatus> {
+    let current = current_version(conn).await?;
+    Ok(SchemaStatus {
+        current,
+        latest: latest_version(),
+        pending: MIGRATIONS
+            .iter()
+            .filter(|m| m.version > current)
+            .map(|m| m.version)
+            .collect(),
+    })
+}
+
+/// Apply every pending migration; returns the versions applied
+pub async fn migrate(conn: &mut SqliteConnection) -> Result<Vec<u32>> {
+    migrate_to(conn, latest_version()).await
+}
+
+/// Apply pending migrations up to and including `target`, each in its own
+/// transaction together with its `schema_version` row. Refuses databases
+/// written by a newer build.
+pub async fn migrate_to(conn: &mut SqliteConnection, target: u32) -> Result<Vec<u32>> {
+    ensure_version_table(conn).await?;
+    let current = current_version(conn).await?;
+    if current > latest_version() {
+        bail!(
+            "database schema version {} is newer than this build supports ({}); upgrade octaskly",
+            current,
+            latest_version()
+        );
+    }
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current && m.version <= target) {
+        let mut tx = conn.begin().await?;
+        tx.execute(migration.sql).await.map_err(|e| {
+            anyhow::anyhow!("migration {} ({}) failed: {}", migration.version, migration.description, e)
+        })?;
+        sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?1, ?2, ?3)")
+            .bind(migration.version)
+            .bind(migration.description)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        applied.push(migration.version);
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Row;
+
+    async fn columns(conn: &mut SqliteConnection, table: &str) -> Vec<String> {
+        sqlx::query(&format!("PRAGMA table_info({})", table))
+            .fetch_all(&mut *conn)
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get::<String, _>("name"))
+            .collect()
+    }
+
+    // Fixture: a database as written by the given schema version, with one task in it
+    async fn fixture(version: u32) -> SqliteConnection {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        if version == 0 {
+            // Before versioning: the tables existed but no schema_version did
+            conn.execute(MIGRATIONS[0].sql).await.unwrap();
+        } else {
+            migrate_to(&mut conn, version).await.unwrap();
+        }
+        conn.execute(
+            "INSERT INTO tasks (id, command, status, created_at) VALUES ('t1', 'echo hi', 'Completed', '2024-01-01T00:00:00Z')",
+        )
+        .await
+        .unwrap();
+        conn
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_from_every_past_version() {
+        for version in 0..latest_version() {
+            let mut conn = fixture(version).await;
+            let applied = migrate(&mut conn).await.unwrap();
+            assert_eq!(applied, ((version + 1)..=latest_version()).collect::<Vec<_>>());
+            assert_eq!(current_version(&mut conn).await.unwrap(), latest_version());
+
+            let owner: String = sqlx::query_scalar("SELECT owner FROM tasks WHERE id = 't1'")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+            assert_eq!(owner, "default", "upgrade from version {}", version);
+            assert!(columns(&mut conn, "task_queue").await.contains(&"payload".to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_migrate_is_idempotent() {
+        let mut conn = fixture(latest_version()).await;
+        assert!(migrate(&mut conn).await.unwrap().is_empty());
+        assert!(status(&mut conn).await.unwrap().pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_refuses_newer_database() {
+        let mut conn = fixture(latest_version()).await;
+        sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?1, 'future', '')")
+            .bind(latest_version() + 1)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        let err = migrate(&mut conn).await.unwrap_err();
+        assert!(err.to_string().contains("newer than this build"));
+    }
+}
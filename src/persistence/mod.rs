@@ -1,8 +1,21 @@
 use anyhow::Result;
-use rusqlite::{Connection, params};
+use async_trait::async_trait;
+use base64::Engine;
 use serde::{Serialize, Deserialize};
-use std::sync::{Arc, Mutex};
-use chrono::Utc;
+use std::collections::HashMap;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::ConnectOptions;
+
+use crate::audit::{AuditQuery, AuditRecord};
+use crate::protocol::{Task, TaskResult};
+
+pub mod memory;
+pub mod migrations;
+pub mod sqlite;
+
+pub use memory::MemoryStore;
+pub use migrations::SchemaStatus;
+pub use sqlite::SqliteStore;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoredTask {
@@ -16,283 +29,364 @@ pub struct StoredTask {
     pub duration_ms: u64,
     pub created_at: String,
     pub completed_at: Option<String>,
+    #[serde(default)]
+    pub owner: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub parent_id: Option<String>,
 }
 
-/// Persistent storage for task history using SQLite
-pub struct PersistentStore {
-    conn: Arc<Mutex<Connection>>,
+/// Page size used when a listing does not ask for one
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+/// Largest page a listing may request
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Direction of a task listing by creation time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Newest first
+    #[default]
+    Desc,
+    /// Oldest first
+    Asc,
 }
 
-impl PersistentStore {
-    pub fn new(db_path: &str) -> Result<Self> {
-        let conn = Connection::open(db_path)?;
-        
-        // Enable WAL mode for better concurrency
-        conn.execute("PRAGMA journal_mode = WAL", [])?;
-        
-        // Create tables
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS tasks (
-                id TEXT PRIMARY KEY,
-                command TEXT NOT NULL,
-                status TEXT NOT NULL,
-                worker_id TEXT,
-                stdout TEXT,
-                stderr TEXT,
-                exit_code INTEGER,
-                duration_ms INTEGER,
-                created_at TEXT NOT NULL,
-                completed_at TEXT
-            )",
-            [],
-        )?;
-
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS results (
-                task_id TEXT PRIMARY KEY,
-                worker_id TEXT NOT NULL,
-                status TEXT NOT NULL,
-                stdout TEXT,
-                stderr TEXT,
-                exit_code INTEGER,
-                duration_ms INTEGER,
-                completed_at TEXT NOT NULL,
-                FOREIGN KEY(task_id) REFERENCES tasks(id)
-            )",
-            [],
-        )?;
-
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS audit_log (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                timestamp TEXT NOT NULL,
-                event_type TEXT NOT NULL,
-                worker_id TEXT,
-                task_id TEXT,
-                details TEXT
-            )",
-            [],
-        )?;
-
-        Ok(Self {
-            conn: Arc::new(Mutex::new(conn)),
-        })
+/// Filters and paging for task listings; unset filters match everything
+#[derive(Debug, Clone, Default)]
+pub struct TaskQuery {
+    pub status: Option<String>,
+    pub worker_id: Option<String>,
+    pub owner: Option<String>,
+    /// Label key and value that must both match
+    pub label: Option<(String, String)>,
+    /// Inclusive lower bound on creation time
+    pub created_after: Option<chrono::DateTime<chrono::Utc>>,
+    /// Exclusive upper bound on creation time
+    pub created_before: Option<chrono::DateTime<chrono::Utc>>,
+    /// Case-sensitive substring of the command
+    pub command_contains: Option<String>,
+    /// Only tasks of this array
+    pub parent_id: Option<String>,
+    pub order: SortOrder,
+    /// Page size, capped at `MAX_PAGE_SIZE`; 0 means `DEFAULT_PAGE_SIZE`
+    pub limit: usize,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+impl TaskQuery {
+    /// Page size after applying the default and the cap
+    pub fn page_size(&self) -> usize {
+        match self.limit {
+            0 => DEFAULT_PAGE_SIZE,
+            n => n.min(MAX_PAGE_SIZE),
+        }
     }
+}
 
-    /// Store a task
-    pub fn store_task(&self, task: &StoredTask) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
-        
-        conn.execute(
-            "INSERT OR REPLACE INTO tasks 
-             (id, command, status, worker_id, stdout, stderr, exit_code, duration_ms, created_at, completed_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-            params![
-                task.id,
-                task.command,
-                task.status,
-                task.worker_id,
-                task.stdout,
-                task.stderr,
-                task.exit_code,
-                task.duration_ms,
-                task.created_at,
-                task.completed_at,
-            ],
-        )?;
-        
-        Ok(())
+/// One page of a task listing
+#[derive(Debug, Clone, Default)]
+pub struct TaskPage {
+    pub tasks: Vec<StoredTask>,
+    /// Pass back as `cursor` to get the next page; None on the last page
+    pub next_cursor: Option<String>,
+}
+
+impl TaskPage {
+    // Trim a page fetched with one extra row and derive the cursor from its last task
+    fn from_rows(mut tasks: Vec<StoredTask>, page_size: usize) -> Self {
+        let more = tasks.len() > page_size;
+        tasks.truncate(page_size);
+        let next_cursor = if more {
+            tasks.last().map(|t| encode_cursor(&t.created_at, &t.id))
+        } else {
+            None
+        };
+        Self { tasks, next_cursor }
     }
+}
 
-    /// Retrieve a task
-    pub fn get_task(&self, task_id: &str) -> Result<Option<StoredTask>> {
-        let conn = self.conn.lock().unwrap();
-        
-        let mut stmt = conn.prepare(
-            "SELECT id, command, status, worker_id, stdout, stderr, exit_code, duration_ms, created_at, completed_at
-             FROM tasks WHERE id = ?1"
-        )?;
-        
-        let result = stmt.query_row(params![task_id], |row| {
-            Ok(StoredTask {
-                id: row.get(0)?,
-                command: row.get(1)?,
-                status: row.get(2)?,
-                worker_id: row.get(3)?,
-                stdout: row.get(4)?,
-                stderr: row.get(5)?,
-                exit_code: row.get(6)?,
-                duration_ms: row.get(7)?,
-                created_at: row.get(8)?,
-                completed_at: row.get(9)?,
-            })
-        });
-        
-        match result {
-            Ok(task) => Ok(Some(task)),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(e.into()),
-        }
+// Keyset cursors point just past the last task of a page: (created_at, id)
+fn encode_cursor(created_at: &str, id: &str) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!("{}\n{}", created_at, id))
+}
+
+pub(crate) fn decode_cursor(cursor: &str) -> Result<(String, String)> {
+    let raw = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(cursor)
+        .map_err(|_| anyhow::anyhow!("invalid cursor"))?;
+    let raw = String::from_utf8(raw).map_err(|_| anyhow::anyhow!("invalid cursor"))?;
+    match raw.split_once('\n') {
+        Some((created_at, id)) => Ok((created_at.to_string(), id.to_string())),
+        None => anyhow::bail!("invalid cursor"),
     }
+}
 
-    /// Get all tasks
-    pub fn get_all_tasks(&self) -> Result<Vec<StoredTask>> {
-        let conn = self.conn.lock().unwrap();
-        
-        let mut stmt = conn.prepare(
-            "SELECT id, command, status, worker_id, stdout, stderr, exit_code, duration_ms, created_at, completed_at
-             FROM tasks ORDER BY created_at DESC LIMIT 1000"
-        )?;
-        
-        let tasks = stmt.query_map([], |row| {
-            Ok(StoredTask {
-                id: row.get(0)?,
-                command: row.get(1)?,
-                status: row.get(2)?,
-                worker_id: row.get(3)?,
-                stdout: row.get(4)?,
-                stderr: row.get(5)?,
-                exit_code: row.get(6)?,
-                duration_ms: row.get(7)?,
-                created_at: row.get(8)?,
-                completed_at: row.get(9)?,
-            })
-        })?;
-        
-        let mut result = Vec::new();
-        for task in tasks {
-            result.push(task?);
+/// Dispatch state of a task that has not reached a terminal state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueState {
+    /// Waiting in the scheduler queue
+    Queued,
+    /// Reserved on a worker by the scheduler
+    Assigned,
+    /// Delivered to the worker
+    Running,
+}
+
+impl QueueState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            QueueState::Queued => "queued",
+            QueueState::Assigned => "assigned",
+            QueueState::Running => "running",
         }
-        Ok(result)
     }
 
-    /// Get tasks for a worker
-    pub fn get_worker_tasks(&self, worker_id: &str) -> Result<Vec<StoredTask>> {
-        let conn = self.conn.lock().unwrap();
-        
-        let mut stmt = conn.prepare(
-            "SELECT id, command, status, worker_id, stdout, stderr, exit_code, duration_ms, created_at, completed_at
-             FROM tasks WHERE worker_id = ?1 ORDER BY created_at DESC LIMIT 100"
-        )?;
-        
-        let tasks = stmt.query_map(params![worker_id], |row| {
-            Ok(StoredTask {
-                id: row.get(0)?,
-                command: row.get(1)?,
-                status: row.get(2)?,
-                worker_id: row.get(3)?,
-                stdout: row.get(4)?,
-                stderr: row.get(5)?,
-                exit_code: row.get(6)?,
-                duration_ms: row.get(7)?,
-                created_at: row.get(8)?,
-                completed_at: row.get(9)?,
-            })
-        })?;
-        
-        let mut result = Vec::new();
-        for task in tasks {
-            result.push(task?);
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "queued" => Some(QueueState::Queued),
+            "assigned" => Some(QueueState::Assigned),
+            "running" => Some(QueueState::Running),
+            _ => None,
         }
-        Ok(result)
     }
 
-    /// Delete old tasks (cleanup)
-    pub fn cleanup_old_tasks(&self, days: i64) -> Result<usize> {
-        let conn = self.conn.lock().unwrap();
-        
-        let cutoff = Utc::now() - chrono::Duration::days(days);
-        let cutoff_str = cutoff.to_rfc3339();
-        
-        let rows = conn.execute(
-            "DELETE FROM tasks WHERE created_at < ?1",
-            params![cutoff_str],
-        )?;
-        
-        Ok(rows)
+    // Status shown in the task history for this state
+    fn task_status(&self) -> &'static str {
+        match self {
+            QueueState::Queued => "Pending",
+            QueueState::Assigned => "Assigned",
+            QueueState::Running => "Running",
+        }
     }
+}
+
+/// Unfinished task loaded back from the database after a restart
+#[derive(Debug, Clone)]
+pub struct PendingTask {
+    pub task: Task,
+    pub state: QueueState,
+    pub worker_id: Option<String>,
+}
+
+/// One task state change; several can be written in a single transaction
+#[derive(Debug, Clone, Copy)]
+pub enum Transition<'a> {
+    /// Task entered the queue (new submission or requeue)
+    Queued(&'a Task),
+    /// Task reserved on a worker by the scheduler
+    Assigned(&'a Task, &'a str),
+    /// Assigned task delivered to its worker
+    Running(&'a str),
+    /// Terminal result; the task leaves the durable queue
+    Finished(&'a TaskResult),
+}
+
+/// Subscription pushing cluster events to a URL
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    /// Event types delivered; empty means all
+    pub events: Vec<String>,
+    /// Only events about this task
+    pub task_id: Option<String>,
+    /// Only events about tasks of this owner (project)
+    pub owner: Option<String>,
+    /// Key of the HMAC-SHA256 signature on each delivery
+    pub secret: String,
+    pub created_at: String,
+}
+
+/// Named secret, sealed with the dispatcher's secrets key (see `crate::secrets`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredSecret {
+    /// Name referenced as `secret://<name>`, e.g. `ci/token`
+    pub name: String,
+    /// Projects (task owners) whose tasks may use it
+    pub projects: Vec<String>,
+    /// Nonce and AES-256-GCM ciphertext of the value
+    pub sealed: Vec<u8>,
+    pub created_at: String,
+    pub updated_at: String,
+}
 
-    /// Record audit log entry
-    pub fn log_event(&self, event_type: &str, worker_id: Option<&str>, task_id: Option<&str>, details: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
-        
-        let timestamp = Utc::now().to_rfc3339();
-        
-        conn.execute(
-            "INSERT INTO audit_log (timestamp, event_type, worker_id, task_id, details)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![timestamp, event_type, worker_id, task_id, details],
-        )?;
-        
-        Ok(())
+/// State of one webhook delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Not accepted yet, retries remain
+    Pending,
+    /// Endpoint answered with a 2xx status
+    Delivered,
+    /// Retries used up; kept as a dead letter
+    Dead,
+}
+
+impl DeliveryStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Dead => "dead",
+        }
     }
 
-    /// Get audit logs
-    pub fn get_audit_logs(&self, limit: usize) -> Result<Vec<(String, String, Option<String>, Option<String>, String)>> {
-        let conn = self.conn.lock().unwrap();
-        
-        let mut stmt = conn.prepare(
-            "SELECT timestamp, event_type, worker_id, task_id, details
-             FROM audit_log ORDER BY timestamp DESC LIMIT ?1"
-        )?;
-        
-        let logs = stmt.query_map(params![limit as i64], |row| {
-            Ok((
-                row.get(0)?,
-                row.get(1)?,
-                row.get(2)?,
-                row.get(3)?,
-                row.get(4)?,
-            ))
-        })?;
-        
-        let mut result = Vec::new();
-        for log in logs {
-            result.push(log?);
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(DeliveryStatus::Pending),
+            "delivered" => Some(DeliveryStatus::Delivered),
+            "dead" => Some(DeliveryStatus::Dead),
+            _ => None,
         }
-        Ok(result)
+    }
+}
+
+/// One event sent (or being sent) to one webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event_id: u64,
+    pub event_type: String,
+    /// JSON body posted to the endpoint
+    pub payload: String,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    /// HTTP status of the last attempt, if the endpoint answered
+    pub response_status: Option<u16>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Storage for task state and history, shared by the dispatcher, the API and tests
+#[async_trait]
+pub trait TaskStore: Send + Sync {
+    /// Apply transitions in order, all or nothing
+    async fn record(&self, transitions: &[Transition<'_>]) -> Result<()>;
+
+    /// Unfinished tasks in submission order
+    async fn load_pending(&self) -> Result<Vec<PendingTask>>;
+
+    /// Most recent terminal results, newest first
+    async fn load_results(&self, limit: usize) -> Result<Vec<TaskResult>>;
+
+    /// Insert or replace a task history row
+    async fn store_task(&self, task: &StoredTask) -> Result<()>;
+
+    /// Retrieve a task
+    async fn get_task(&self, task_id: &str) -> Result<Option<StoredTask>>;
+
+    /// Retrieve all tasks, newest first
+    async fn get_all_tasks(&self) -> Result<Vec<StoredTask>>;
+
+    /// Filtered, sorted page of tasks
+    async fn query_tasks(&self, query: &TaskQuery) -> Result<TaskPage>;
+
+    /// Number of tasks of an array in each status
+    async fn array_status_counts(&self, parent_id: &str) -> Result<HashMap<String, usize>>;
+
+    /// Retrieve tasks run by a worker, newest first
+    async fn get_worker_tasks(&self, worker_id: &str) -> Result<Vec<StoredTask>>;
+
+    /// Total, completed and failed task counts
+    async fn get_stats(&self) -> Result<(usize, usize, usize)>;
+
+    /// Insert or replace a webhook subscription
+    async fn save_webhook(&self, webhook: &Webhook) -> Result<()>;
+
+    /// Every webhook subscription, oldest first
+    async fn list_webhooks(&self) -> Result<Vec<Webhook>>;
+
+    /// Remove a webhook and its delivery log; false if it did not exist
+    async fn delete_webhook(&self, webhook_id: &str) -> Result<bool>;
+
+    /// Insert or update a delivery
+    async fn record_delivery(&self, delivery: &WebhookDelivery) -> Result<()>;
+
+    /// Deliveries newest first, optionally of one webhook and in one status
+    async fn list_deliveries(
+        &self,
+        webhook_id: Option<&str>,
+        status: Option<DeliveryStatus>,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>>;
+
+    /// Insert or replace a secret
+    async fn save_secret(&self, secret: &StoredSecret) -> Result<()>;
+
+    async fn get_secret(&self, name: &str) -> Result<Option<StoredSecret>>;
+
+    /// Every secret, by name
+    async fn list_secrets(&self) -> Result<Vec<StoredSecret>>;
+
+    /// Remove a secret; false if it did not exist
+    async fn delete_secret(&self, name: &str) -> Result<bool>;
+
+    /// Append a record to the audit chain; its `seq` must be new
+    async fn append_audit(&self, record: &AuditRecord) -> Result<()>;
+
+    /// Newest audit record, the head of the chain
+    async fn last_audit(&self) -> Result<Option<AuditRecord>>;
+
+    /// Audit records matching the query, newest first
+    async fn query_audit(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>>;
+
+    /// Up to `limit` audit records after `seq`, oldest first, to walk the chain
+    async fn audit_after(&self, seq: u64, limit: usize) -> Result<Vec<AuditRecord>>;
+
+    /// Refuse an API token from now on; revoking twice is not an error
+    async fn revoke_token(&self, token_id: &str) -> Result<()>;
+
+    /// Ids of every revoked API token
+    async fn revoked_tokens(&self) -> Result<Vec<String>>;
+
+    async fn record_queued(&self, task: &Task) -> Result<()> {
+        self.record(&[Transition::Queued(task)]).await
     }
 
-    /// Get statistics
-    pub fn get_stats(&self) -> Result<(usize, usize, usize)> {
-        let conn = self.conn.lock().unwrap();
-        
-        let total: usize = conn.query_row(
-            "SELECT COUNT(*) FROM tasks",
-            [],
-            |row| row.get(0),
-        )?;
-        
-        let completed: usize = conn.query_row(
-            "SELECT COUNT(*) FROM tasks WHERE status = 'Completed'",
-            [],
-            |row| row.get(0),
-        )?;
-        
-        let failed: usize = conn.query_row(
-            "SELECT COUNT(*) FROM tasks WHERE status = 'Failed'",
-            [],
-            |row| row.get(0),
-        )?;
-        
-        Ok((total, completed, failed))
+    async fn record_assigned(&self, task: &Task, worker_id: &str) -> Result<()> {
+        self.record(&[Transition::Assigned(task, worker_id)]).await
     }
+
+    async fn record_running(&self, task_id: &str) -> Result<()> {
+        self.record(&[Transition::Running(task_id)]).await
+    }
+
+    async fn record_finished(&self, result: &TaskResult) -> Result<()> {
+        self.record(&[Transition::Finished(result)]).await
+    }
+}
+
+/// Report the schema version of a database file without changing it
+pub async fn schema_status(db_path: &str) -> Result<SchemaStatus> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(db_path)
+        .read_only(true)
+        .connect()
+        .await?;
+    migrations::status(&mut conn).await
+}
+
+/// Apply pending migrations to a database file; returns the versions applied
+pub async fn migrate_database(db_path: &str) -> Result<Vec<u32>> {
+    let mut conn: SqliteConnection = SqliteConnectOptions::new()
+        .filename(db_path)
+        .create_if_missing(true)
+        .connect()
+        .await?;
+    migrations::migrate(&mut conn).await
 }
 
+// Shared checks run against every TaskStore implementation
 #[cfg(test)]
-mod tests {
+pub(crate) mod store_tests {
     use super::*;
 
-    #[test]
-    fn test_persistent_storage() {
-        let store = match PersistentStore::new(":memory:") {
-            Ok(s) => s,
-            Err(e) => {
-                println!("Failed to create store: {}", e);
-                return;
-            }
-        };
-        
+    pub async fn store_and_retrieve_task(store: &dyn TaskStore) {
         let task = StoredTask {
             id: "test-1".to_string(),
             command: "echo hello".to_string(),
@@ -304,19 +398,267 @@ mod tests {
             duration_ms: 100,
             created_at: chrono::Utc::now().to_rfc3339(),
             completed_at: Some(chrono::Utc::now().to_rfc3339()),
+            owner: "default".to_string(),
+            labels: HashMap::new(),
+            parent_id: None,
+        };
+        store.store_task(&task).await.unwrap();
+
+        let retrieved = store.get_task("test-1").await.unwrap().expect("task not found");
+        assert_eq!(retrieved.command, "echo hello");
+        assert_eq!(store.get_worker_tasks("worker-1").await.unwrap().len(), 1);
+        assert_eq!(store.get_stats().await.unwrap(), (1, 1, 0));
+        assert!(store.get_task("missing").await.unwrap().is_none());
+    }
+
+    pub async fn durable_queue_transitions(store: &dyn TaskStore) {
+        let first = Task::new("echo one".to_string());
+        let second = Task::new("echo two".to_string());
+        store.record(&[Transition::Queued(&first), Transition::Queued(&second)]).await.unwrap();
+        store.record_assigned(&first, "worker-1").await.unwrap();
+        store.record_running(&first.id).await.unwrap();
+
+        let pending = store.load_pending().await.unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].task.id, first.id);
+        assert_eq!(pending[0].state, QueueState::Running);
+        assert_eq!(pending[0].worker_id.as_deref(), Some("worker-1"));
+        assert_eq!(pending[1].state, QueueState::Queued);
+        assert_eq!(store.get_task(&first.id).await.unwrap().unwrap().status, "Running");
+
+        // Requeueing keeps the original submission order
+        store.record_queued(&first).await.unwrap();
+        assert_eq!(store.load_pending().await.unwrap()[0].task.id, first.id);
+
+        let result = TaskResult {
+            task_id: first.id.clone(),
+            worker_id: "worker-1".to_string(),
+            status: crate::protocol::TaskStatus::Completed,
+            stdout: "one".to_string(),
+            stderr: String::new(),
+            exit_code: Some(0),
+            duration_ms: 5,
+            completed_at: chrono::Utc::now().timestamp(),
+            signer: Some("ops".to_string()),
         };
-        
-        if let Err(e) = store.store_task(&task) {
-            println!("Failed to store task: {}", e);
-            return;
+        store.record_finished(&result).await.unwrap();
+
+        let pending = store.load_pending().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].task.id, second.id);
+        let results = store.load_results(10).await.unwrap();
+        assert_eq!(results[0].stdout, "one");
+        assert_eq!(results[0].signer.as_deref(), Some("ops"));
+        assert_eq!(store.get_task(&first.id).await.unwrap().unwrap().status, "Completed");
+    }
+
+    pub async fn filter_and_paginate(store: &dyn TaskStore) {
+        let base = chrono::Utc::now().timestamp() - 100;
+        let mut tasks = Vec::new();
+        for i in 0..5 {
+            let mut task = Task::new(format!("build part-{}", i));
+            task.created_at = base + i;
+            task.owner = if i % 2 == 0 { "team-a" } else { "team-b" }.to_string();
+            if i == 3 {
+                task.labels.insert("suite".to_string(), "nightly".to_string());
+            }
+            store.record_queued(&task).await.unwrap();
+            tasks.push(task);
         }
-        
-        match store.get_task("test-1") {
-            Ok(Some(retrieved)) => {
-                assert_eq!(retrieved.command, "echo hello");
+        store.record_assigned(&tasks[4], "w1").await.unwrap();
+        let ids: Vec<String> = tasks.iter().map(|t| t.id.clone()).collect();
+
+        // Walk every page newest first
+        let mut query = TaskQuery { limit: 2, ..Default::default() };
+        let mut seen = Vec::new();
+        loop {
+            let page = store.query_tasks(&query).await.unwrap();
+            seen.extend(page.tasks.iter().map(|t| t.id.clone()));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
             }
-            Ok(None) => panic!("Task not found"),
-            Err(e) => panic!("Failed to retrieve task: {}", e),
         }
+        assert_eq!(seen, ids.iter().rev().cloned().collect::<Vec<_>>());
+
+        let oldest = TaskQuery { order: SortOrder::Asc, limit: 1, ..Default::default() };
+        assert_eq!(store.query_tasks(&oldest).await.unwrap().tasks[0].id, ids[0]);
+
+        let by_owner = TaskQuery { owner: Some("team-a".to_string()), ..Default::default() };
+        assert_eq!(store.query_tasks(&by_owner).await.unwrap().tasks.len(), 3);
+
+        let by_label = TaskQuery {
+            label: Some(("suite".to_string(), "nightly".to_string())),
+            ..Default::default()
+        };
+        assert_eq!(store.query_tasks(&by_label).await.unwrap().tasks[0].id, ids[3]);
+
+        let by_state = TaskQuery {
+            status: Some("Assigned".to_string()),
+            worker_id: Some("w1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(store.query_tasks(&by_state).await.unwrap().tasks.len(), 1);
+
+        let range = TaskQuery {
+            created_after: chrono::DateTime::from_timestamp(base + 1, 0),
+            created_before: chrono::DateTime::from_timestamp(base + 3, 0),
+            command_contains: Some("part-".to_string()),
+            ..Default::default()
+        };
+        let page = store.query_tasks(&range).await.unwrap();
+        assert_eq!(page.tasks.len(), 2);
+        assert!(page.next_cursor.is_none());
+
+        let bad = TaskQuery { cursor: Some("???".to_string()), ..Default::default() };
+        assert!(store.query_tasks(&bad).await.is_err());
+    }
+
+    pub async fn webhooks_and_deliveries(store: &dyn TaskStore) {
+        let webhook = Webhook {
+            id: "hook-1".to_string(),
+            url: "http://127.0.0.1:9/hook".to_string(),
+            events: vec!["task_completed".to_string()],
+            task_id: None,
+            owner: Some("team-a".to_string()),
+            secret: "s3cret".to_string(),
+            created_at: "2024-01-01T00:00:00+00:00".to_string(),
+        };
+        store.save_webhook(&webhook).await.unwrap();
+        assert_eq!(store.list_webhooks().await.unwrap(), vec![webhook.clone()]);
+
+        let mut delivery = WebhookDelivery {
+            id: "d-1".to_string(),
+            webhook_id: webhook.id.clone(),
+            event_id: 7,
+            event_type: "task_completed".to_string(),
+            payload: "{}".to_string(),
+            status: DeliveryStatus::Pending,
+            attempts: 1,
+            response_status: Some(500),
+            last_error: Some("HTTP 500".to_string()),
+            created_at: "2024-01-01T00:00:01+00:00".to_string(),
+            updated_at: "2024-01-01T00:00:01+00:00".to_string(),
+        };
+        store.record_delivery(&delivery).await.unwrap();
+        delivery.status = DeliveryStatus::Dead;
+        delivery.attempts = 5;
+        store.record_delivery(&delivery).await.unwrap();
+
+        let log = store.list_deliveries(Some("hook-1"), None, 10).await.unwrap();
+        assert_eq!(log, vec![delivery.clone()]);
+        let dead = store.list_deliveries(None, Some(DeliveryStatus::Dead), 10).await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert!(store
+            .list_deliveries(None, Some(DeliveryStatus::Delivered), 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert!(store.delete_webhook("hook-1").await.unwrap());
+        assert!(!store.delete_webhook("hook-1").await.unwrap());
+        assert!(store.list_webhooks().await.unwrap().is_empty());
+        assert!(store.list_deliveries(Some("hook-1"), None, 10).await.unwrap().is_empty());
+    }
+
+    pub async fn secrets(store: &dyn TaskStore) {
+        let mut secret = StoredSecret {
+            name: "ci/token".to_string(),
+            projects: vec!["ci".to_string()],
+            sealed: vec![1, 2, 3],
+            created_at: "2024-01-01T00:00:00+00:00".to_string(),
+            updated_at: "2024-01-01T00:00:00+00:00".to_string(),
+        };
+        store.save_secret(&secret).await.unwrap();
+        secret.projects.push("release".to_string());
+        secret.sealed = vec![4, 5];
+        store.save_secret(&secret).await.unwrap();
+        assert_eq!(store.get_secret("ci/token").await.unwrap(), Some(secret.clone()));
+        assert_eq!(store.list_secrets().await.unwrap(), vec![secret]);
+        assert!(store.get_secret("ci/other").await.unwrap().is_none());
+
+        assert!(store.delete_secret("ci/token").await.unwrap());
+        assert!(!store.delete_secret("ci/token").await.unwrap());
+        assert!(store.list_secrets().await.unwrap().is_empty());
+    }
+
+    pub async fn audit_chain(store: &dyn TaskStore) {
+        use crate::audit::{AuditEntry, GENESIS_HASH};
+        assert!(store.last_audit().await.unwrap().is_none());
+        let mut prev = GENESIS_HASH.to_string();
+        for (seq, (action, actor, allowed)) in
+            [("auth.login", "alice", false), ("task.submit", "alice", true), ("task.cancel", "bob", true)]
+                .into_iter()
+                .enumerate()
+        {
+            let entry = AuditEntry::new(action, actor, allowed).with_source("10.0.0.1".parse().unwrap());
+            let record = AuditRecord::chain(seq as u64 + 1, entry, &prev);
+            store.append_audit(&record).await.unwrap();
+            prev = record.hash;
+        }
+        let head = store.last_audit().await.unwrap().unwrap();
+        assert_eq!((head.seq, head.hash.clone()), (3, prev));
+        // A sequence number is only used once
+        assert!(store.append_audit(&head).await.is_err());
+
+        let seqs = |records: Vec<AuditRecord>| records.iter().map(|r| r.seq).collect::<Vec<_>>();
+        assert_eq!(seqs(store.query_audit(&AuditQuery::default()).await.unwrap()), vec![3, 2, 1]);
+        let query = AuditQuery { action: Some("task.".to_string()), ..Default::default() };
+        assert_eq!(seqs(store.query_audit(&query).await.unwrap()), vec![3, 2]);
+        let query = AuditQuery { actor: Some("alice".to_string()), allowed: Some(true), ..Default::default() };
+        assert_eq!(seqs(store.query_audit(&query).await.unwrap()), vec![2]);
+        let query = AuditQuery { before: Some(3), limit: Some(1), ..Default::default() };
+        assert_eq!(seqs(store.query_audit(&query).await.unwrap()), vec![2]);
+        let query = AuditQuery { source_ip: Some("10.0.0.2".to_string()), ..Default::default() };
+        assert!(store.query_audit(&query).await.unwrap().is_empty());
+
+        assert_eq!(seqs(store.audit_after(1, 10).await.unwrap()), vec![2, 3]);
+        assert_eq!(store.audit_after(0, 10).await.unwrap()[0], {
+            let mut all = store.query_audit(&AuditQuery::default()).await.unwrap();
+            all.pop().unwrap()
+        });
+    }
+
+    pub async fn revoked_tokens(store: &dyn TaskStore) {
+        store.revoke_token("t1").await.unwrap();
+        store.revoke_token("t1").await.unwrap();
+        store.revoke_token("t2").await.unwrap();
+        let mut revoked = store.revoked_tokens().await.unwrap();
+        revoked.sort();
+        assert_eq!(revoked, vec!["t1".to_string(), "t2".to_string()]);
+    }
+
+    pub async fn array_counts(store: &dyn TaskStore) {
+        let tasks: Vec<Task> = (0..3)
+            .map(|i| Task {
+                parent_id: Some("array-1".to_string()),
+                ..Task::new(format!("echo {}", i))
+            })
+            .collect();
+        let queued: Vec<Transition> = tasks.iter().map(Transition::Queued).collect();
+        store.record(&queued).await.unwrap();
+        store.record_queued(&Task::new("echo other".to_string())).await.unwrap();
+        store
+            .record_finished(&TaskResult {
+                task_id: tasks[0].id.clone(),
+                worker_id: "w1".to_string(),
+                status: crate::protocol::TaskStatus::Cancelled,
+                stdout: String::new(),
+                stderr: String::new(),
+                exit_code: None,
+                duration_ms: 0,
+                completed_at: chrono::Utc::now().timestamp(),
+                signer: None,
+            })
+            .await
+            .unwrap();
+
+        let counts = store.array_status_counts("array-1").await.unwrap();
+        assert_eq!(counts.get("Pending"), Some(&2));
+        assert_eq!(counts.get("Cancelled"), Some(&1));
+        assert!(store.array_status_counts("missing").await.unwrap().is_empty());
+
+        let members = TaskQuery { parent_id: Some("array-1".to_string()), ..Default::default() };
+        assert_eq!(store.query_tasks(&members).await.unwrap().tasks.len(), 3);
     }
 }
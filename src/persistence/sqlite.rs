@@ -0,0 +1,766 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::sqlite::{
+    Sqlite, SqliteConnectOptions, SqliteConnection, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow,
+};
+use sqlx::{QueryBuilder, Row};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use super::{
+    decode_cursor, migrations, DeliveryStatus, PendingTask, QueueState, SortOrder, StoredTask, TaskPage, TaskQuery,
+    StoredSecret, TaskStore, Transition, Webhook, WebhookDelivery,
+};
+use crate::audit::{AuditEntry, AuditQuery, AuditRecord};
+use crate::protocol::TaskResult;
+
+const TASK_COLUMNS: &str =
+    "id, command, status, worker_id, stdout, stderr, exit_code, duration_ms, created_at, completed_at, owner, labels, parent_id";
+
+/// SQLite task store on an async connection pool.
+///
+/// Statements are prepared once per connection and cached by sqlx; batches of
+/// transitions share one transaction.
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    /// Open (creating if needed) the database file and bring its schema up to date
+    pub async fn connect(db_path: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::new()
+            .filename(db_path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .foreign_keys(false);
+        let pool = SqlitePoolOptions::new().max_connections(4).connect_with(options).await?;
+        Self::with_pool(pool).await
+    }
+
+    /// Private in-memory database, for tests
+    pub async fn in_memory() -> Result<Self> {
+        // Every connection to :memory: is a separate database, so keep exactly one
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(SqliteConnectOptions::from_str("sqlite::memory:")?)
+            .await?;
+        Self::with_pool(pool).await
+    }
+
+    async fn with_pool(pool: SqlitePool) -> Result<Self> {
+        // Refuses databases from newer builds
+        let mut conn = pool.acquire().await?;
+        migrations::migrate(&mut conn).await?;
+        drop(conn);
+        Ok(Self { pool })
+    }
+
+    /// Delete old tasks (cleanup)
+    pub async fn cleanup_old_tasks(&self, days: i64) -> Result<u64> {
+        let cutoff = (Utc::now() - chrono::Duration::days(days)).to_rfc3339();
+        let done = sqlx::query("DELETE FROM tasks WHERE created_at < ?1")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await?;
+        Ok(done.rows_affected())
+    }
+
+    async fn fetch_tasks(&self, sql: &str, bind: Option<&str>) -> Result<Vec<StoredTask>> {
+        let mut query = sqlx::query(sql);
+        if let Some(value) = bind {
+            query = query.bind(value);
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+        rows.iter().map(stored_task).collect()
+    }
+}
+
+fn stored_task(row: &SqliteRow) -> Result<StoredTask> {
+    Ok(StoredTask {
+        id: row.try_get("id")?,
+        command: row.try_get("command")?,
+        status: row.try_get("status")?,
+        worker_id: row.try_get("worker_id")?,
+        stdout: row.try_get::<Option<String>, _>("stdout")?.unwrap_or_default(),
+        stderr: row.try_get::<Option<String>, _>("stderr")?.unwrap_or_default(),
+        exit_code: row.try_get("exit_code")?,
+        duration_ms: row.try_get::<Option<i64>, _>("duration_ms")?.unwrap_or(0) as u64,
+        created_at: row.try_get("created_at")?,
+        completed_at: row.try_get("completed_at")?,
+        owner: row.try_get("owner")?,
+        labels: serde_json::from_str(row.try_get("labels")?)?,
+        parent_id: row.try_get("parent_id")?,
+    })
+}
+
+fn rfc3339(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_else(Utc::now)
+        .to_rfc3339()
+}
+
+// Rebuild a webhook subscription from its row
+// Bangun ulang langganan webhook dari barisnya
+fn webhook(row: &SqliteRow) -> Result<Webhook> {
+    Ok(Webhook {
+        id: row.try_get("id")?,
+        url: row.try_get("url")?,
+        events: serde_json::from_str(&row.try_get::<String, _>("events")?)?,
+        task_id: row.try_get("task_id")?,
+        owner: row.try_get("owner")?,
+        secret: row.try_get("secret")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn secret(row: &SqliteRow) -> Result<StoredSecret> {
+    Ok(StoredSecret {
+        name: row.try_get("name")?,
+        projects: serde_json::from_str(&row.try_get::<String, _>("projects")?)?,
+        sealed: row.try_get("sealed")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+fn audit_record(row: &SqliteRow) -> Result<AuditRecord> {
+    Ok(AuditRecord {
+        seq: row.try_get::<i64, _>("seq")? as u64,
+        entry: AuditEntry {
+            timestamp: row.try_get("timestamp")?,
+            action: row.try_get("action")?,
+            actor: row.try_get("actor")?,
+            subject: row.try_get("subject")?,
+            source_ip: row.try_get("source_ip")?,
+            allowed: row.try_get("allowed")?,
+            reason: row.try_get("reason")?,
+        },
+        prev_hash: row.try_get("prev_hash")?,
+        hash: row.try_get("hash")?,
+    })
+}
+
+fn delivery(row: &SqliteRow) -> Result<WebhookDelivery> {
+    let status: String = row.try_get("status")?;
+    Ok(WebhookDelivery {
+        id: row.try_get("id")?,
+        webhook_id: row.try_get("webhook_id")?,
+        event_id: row.try_get::<i64, _>("event_id")? as u64,
+        event_type: row.try_get("event_type")?,
+        payload: row.try_get("payload")?,
+        status: DeliveryStatus::parse(&status)
+            .ok_or_else(|| anyhow::anyhow!("unknown delivery status {}", status))?,
+        attempts: row.try_get::<i64, _>("attempts")? as u32,
+        response_status: row.try_get::<Option<i64>, _>("response_status")?.map(|s| s as u16),
+        last_error: row.try_get("last_error")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+// Write one transition inside the caller's transaction
+async fn apply(conn: &mut SqliteConnection, transition: &Transition<'_>) -> Result<()> {
+    let now = Utc::now().to_rfc3339();
+    match *transition {
+        Transition::Queued(task) | Transition::Assigned(task, _) => {
+            let (state, worker_id) = match *transition {
+                Transition::Assigned(_, worker_id) => (QueueState::Assigned, Some(worker_id)),
+                _ => (QueueState::Queued, None),
+            };
+            // A task moving between states keeps its original position
+            sqlx::query(
+                "INSERT INTO task_queue (task_id, payload, state, worker_id, seq, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, (SELECT COALESCE(MAX(seq), 0) + 1 FROM task_queue), ?5)
+                 ON CONFLICT(task_id) DO UPDATE SET payload = excluded.payload, state = excluded.state,
+                     worker_id = excluded.worker_id, updated_at = excluded.updated_at",
+            )
+            .bind(&task.id)
+            .bind(serde_json::to_string(task)?)
+            .bind(state.as_str())
+            .bind(worker_id)
+            .bind(&now)
+            .execute(&mut *conn)
+            .await?;
+            sqlx::query(
+                "INSERT INTO tasks (id, command, status, worker_id, stdout, stderr, exit_code, duration_ms,
+                     created_at, completed_at, owner, attempts, labels, parent_id)
+                 VALUES (?1, ?2, ?3, ?4, '', '', NULL, 0, ?5, NULL, ?6, ?7, ?8, ?9)
+                 ON CONFLICT(id) DO UPDATE SET status = excluded.status, worker_id = excluded.worker_id,
+                     owner = excluded.owner, attempts = excluded.attempts, labels = excluded.labels",
+            )
+            .bind(&task.id)
+            .bind(&task.command)
+            .bind(state.task_status())
+            .bind(worker_id)
+            .bind(rfc3339(task.created_at))
+            .bind(&task.owner)
+            .bind(task.attempts)
+            .bind(serde_json::to_string(&task.labels)?)
+            .bind(&task.parent_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+        Transition::Running(task_id) => {
+            sqlx::query("UPDATE task_queue SET state = ?2, updated_at = ?3 WHERE task_id = ?1")
+                .bind(task_id)
+                .bind(QueueState::Running.as_str())
+                .bind(&now)
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("UPDATE tasks SET status = ?2 WHERE id = ?1")
+                .bind(task_id)
+                .bind(QueueState::Running.task_status())
+                .execute(&mut *conn)
+                .await?;
+        }
+        Transition::Finished(result) => {
+            let status = format!("{:?}", result.status);
+            let completed_at = rfc3339(result.completed_at);
+            sqlx::query(
+                "UPDATE tasks SET status = ?2, worker_id = ?3, stdout = ?4, stderr = ?5, exit_code = ?6,
+                     duration_ms = ?7, completed_at = ?8
+                 WHERE id = ?1",
+            )
+            .bind(&result.task_id)
+            .bind(&status)
+            .bind(&result.worker_id)
+            .bind(&result.stdout)
+            .bind(&result.stderr)
+            .bind(result.exit_code)
+            .bind(result.duration_ms as i64)
+            .bind(&completed_at)
+            .execute(&mut *conn)
+            .await?;
+            sqlx::query(
+                "INSERT OR REPLACE INTO results
+                 (task_id, worker_id, status, stdout, stderr, exit_code, duration_ms, completed_at, signer)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )
+            .bind(&result.task_id)
+            .bind(&result.worker_id)
+            .bind(&status)
+            .bind(&result.stdout)
+            .bind(&result.stderr)
+            .bind(result.exit_code)
+            .bind(result.duration_ms as i64)
+            .bind(&completed_at)
+            .bind(&result.signer)
+            .execute(&mut *conn)
+            .await?;
+            sqlx::query("DELETE FROM task_queue WHERE task_id = ?1")
+                .bind(&result.task_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl TaskStore for SqliteStore {
+    async fn record(&self, transitions: &[Transition<'_>]) -> Result<()> {
+        if transitions.is_empty() {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await?;
+        for transition in transitions {
+            apply(&mut tx, transition).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn load_pending(&self) -> Result<Vec<PendingTask>> {
+        let rows = sqlx::query("SELECT payload, state, worker_id FROM task_queue ORDER BY seq ASC")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter()
+            .map(|row| {
+                let task: crate::protocol::Task = serde_json::from_str(row.try_get("payload")?)?;
+                let state: &str = row.try_get("state")?;
+                let state = QueueState::parse(state)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown queue state '{}' for task {}", state, task.id))?;
+                Ok(PendingTask {
+                    task,
+                    state,
+                    worker_id: row.try_get("worker_id")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn load_results(&self, limit: usize) -> Result<Vec<TaskResult>> {
+        let rows = sqlx::query(
+            "SELECT task_id, worker_id, status, stdout, stderr, exit_code, duration_ms, completed_at, signer
+             FROM results ORDER BY completed_at DESC LIMIT ?1",
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                let status: String = row.try_get("status")?;
+                let completed_at: String = row.try_get("completed_at")?;
+                Ok(TaskResult {
+                    task_id: row.try_get("task_id")?,
+                    worker_id: row.try_get("worker_id")?,
+                    status: serde_json::from_value(serde_json::Value::String(status))?,
+                    stdout: row.try_get::<Option<String>, _>("stdout")?.unwrap_or_default(),
+                    stderr: row.try_get::<Option<String>, _>("stderr")?.unwrap_or_default(),
+                    exit_code: row.try_get("exit_code")?,
+                    duration_ms: row.try_get::<Option<i64>, _>("duration_ms")?.unwrap_or(0) as u64,
+                    completed_at: chrono::DateTime::parse_from_rfc3339(&completed_at)
+                        .map(|t| t.timestamp())
+                        .unwrap_or(0),
+                    signer: row.try_get("signer")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn store_task(&self, task: &StoredTask) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO tasks
+             (id, command, status, worker_id, stdout, stderr, exit_code, duration_ms, created_at, completed_at,
+              owner, labels, parent_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        )
+        .bind(&task.id)
+        .bind(&task.command)
+        .bind(&task.status)
+        .bind(&task.worker_id)
+        .bind(&task.stdout)
+        .bind(&task.stderr)
+        .bind(task.exit_code)
+        .bind(task.duration_ms as i64)
+        .bind(&task.created_at)
+        .bind(&task.completed_at)
+        .bind(&task.owner)
+        .bind(serde_json::to_string(&task.labels)?)
+        .bind(&task.parent_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_task(&self, task_id: &str) -> Result<Option<StoredTask>> {
+        let sql = format!("SELECT {} FROM tasks WHERE id = ?1", TASK_COLUMNS);
+        Ok(self.fetch_tasks(&sql, Some(task_id)).await?.into_iter().next())
+    }
+
+    async fn get_all_tasks(&self) -> Result<Vec<StoredTask>> {
+        let sql = format!("SELECT {} FROM tasks ORDER BY created_at DESC LIMIT 1000", TASK_COLUMNS);
+        self.fetch_tasks(&sql, None).await
+    }
+
+    async fn query_tasks(&self, query: &TaskQuery) -> Result<TaskPage> {
+        let page_size = query.page_size();
+        let mut sql: QueryBuilder<Sqlite> =
+            QueryBuilder::new(format!("SELECT {} FROM tasks WHERE 1 = 1", TASK_COLUMNS));
+        if let Some(status) = &query.status {
+            sql.push(" AND status = ").push_bind(status.clone());
+        }
+        if let Some(worker_id) = &query.worker_id {
+            sql.push(" AND worker_id = ").push_bind(worker_id.clone());
+        }
+        if let Some(owner) = &query.owner {
+            sql.push(" AND owner = ").push_bind(owner.clone());
+        }
+        if let Some((key, value)) = &query.label {
+            let path = format!("$.\"{}\"", key.replace('"', "\\\""));
+            sql.push(" AND json_extract(labels, ")
+                .push_bind(path)
+                .push(") = ")
+                .push_bind(value.clone());
+        }
+        if let Some(after) = query.created_after {
+            sql.push(" AND created_at >= ").push_bind(after.to_rfc3339());
+        }
+        if let Some(before) = query.created_before {
+            sql.push(" AND created_at < ").push_bind(before.to_rfc3339());
+        }
+        if let Some(parent_id) = &query.parent_id {
+            sql.push(" AND parent_id = ").push_bind(parent_id.clone());
+        }
+        if let Some(needle) = &query.command_contains {
+            sql.push(" AND instr(command, ").push_bind(needle.clone()).push(") > 0");
+        }
+        let (cmp, direction) = match query.order {
+            SortOrder::Desc => ("<", "DESC"),
+            SortOrder::Asc => (">", "ASC"),
+        };
+        if let Some(cursor) = &query.cursor {
+            let (created_at, id) = decode_cursor(cursor)?;
+            sql.push(format!(" AND (created_at {} ", cmp))
+                .push_bind(created_at.clone())
+                .push(" OR (created_at = ")
+                .push_bind(created_at)
+                .push(format!(" AND id {} ", cmp))
+                .push_bind(id)
+                .push("))");
+        }
+        sql.push(format!(" ORDER BY created_at {0}, id {0} LIMIT ", direction))
+            .push_bind((page_size + 1) as i64);
+
+        let rows = sql.build().fetch_all(&self.pool).await?;
+        let tasks = rows.iter().map(stored_task).collect::<Result<Vec<_>>>()?;
+        Ok(TaskPage::from_rows(tasks, page_size))
+    }
+
+    async fn array_status_counts(&self, parent_id: &str) -> Result<HashMap<String, usize>> {
+        let rows = sqlx::query("SELECT status, COUNT(*) FROM tasks WHERE parent_id = ?1 GROUP BY status")
+            .bind(parent_id)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter()
+            .map(|row| Ok((row.try_get(0)?, row.try_get::<i64, _>(1)? as usize)))
+            .collect()
+    }
+
+    async fn get_worker_tasks(&self, worker_id: &str) -> Result<Vec<StoredTask>> {
+        let sql = format!(
+            "SELECT {} FROM tasks WHERE worker_id = ?1 ORDER BY created_at DESC LIMIT 100",
+            TASK_COLUMNS
+        );
+        self.fetch_tasks(&sql, Some(worker_id)).await
+    }
+
+    async fn save_webhook(&self, webhook: &Webhook) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO webhooks (id, url, events, task_id, owner, secret, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .bind(&webhook.id)
+        .bind(&webhook.url)
+        .bind(serde_json::to_string(&webhook.events)?)
+        .bind(&webhook.task_id)
+        .bind(&webhook.owner)
+        .bind(&webhook.secret)
+        .bind(&webhook.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_webhooks(&self) -> Result<Vec<Webhook>> {
+        let rows = sqlx::query("SELECT * FROM webhooks ORDER BY created_at, id")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(webhook).collect()
+    }
+
+    async fn delete_webhook(&self, webhook_id: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let deleted = sqlx::query("DELETE FROM webhooks WHERE id = ?1")
+            .bind(webhook_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        sqlx::query("DELETE FROM webhook_deliveries WHERE webhook_id = ?1")
+            .bind(webhook_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(deleted > 0)
+    }
+
+    async fn record_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO webhook_deliveries
+                (id, webhook_id, event_id, event_type, payload, status, attempts,
+                 response_status, last_error, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )
+        .bind(&delivery.id)
+        .bind(&delivery.webhook_id)
+        .bind(delivery.event_id as i64)
+        .bind(&delivery.event_type)
+        .bind(&delivery.payload)
+        .bind(delivery.status.as_str())
+        .bind(delivery.attempts as i64)
+        .bind(delivery.response_status.map(|s| s as i64))
+        .bind(&delivery.last_error)
+        .bind(&delivery.created_at)
+        .bind(&delivery.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_deliveries(
+        &self,
+        webhook_id: Option<&str>,
+        status: Option<DeliveryStatus>,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new("SELECT * FROM webhook_deliveries WHERE 1 = 1");
+        if let Some(webhook_id) = webhook_id {
+            builder.push(" AND webhook_id = ").push_bind(webhook_id);
+        }
+        if let Some(status) = status {
+            builder.push(" AND status = ").push_bind(status.as_str());
+        }
+        builder
+            .push(" ORDER BY created_at DESC, id DESC LIMIT ")
+            .push_bind(limit as i64);
+        let rows = builder.build().fetch_all(&self.pool).await?;
+        rows.iter().map(delivery).collect()
+    }
+
+    async fn save_secret(&self, secret: &StoredSecret) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO secrets (name, projects, sealed, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(&secret.name)
+        .bind(serde_json::to_string(&secret.projects)?)
+        .bind(&secret.sealed)
+        .bind(&secret.created_at)
+        .bind(&secret.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_secret(&self, name: &str) -> Result<Option<StoredSecret>> {
+        let row = sqlx::query("SELECT * FROM secrets WHERE name = ?1")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(secret).transpose()
+    }
+
+    async fn list_secrets(&self) -> Result<Vec<StoredSecret>> {
+        let rows = sqlx::query("SELECT * FROM secrets ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(secret).collect()
+    }
+
+    async fn delete_secret(&self, name: &str) -> Result<bool> {
+        let deleted = sqlx::query("DELETE FROM secrets WHERE name = ?1")
+            .bind(name)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    async fn append_audit(&self, record: &AuditRecord) -> Result<()> {
+        let entry = &record.entry;
+        sqlx::query(
+            "INSERT INTO audit_log
+                (seq, timestamp, action, actor, subject, source_ip, allowed, reason, prev_hash, hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        )
+        .bind(record.seq as i64)
+        .bind(&entry.timestamp)
+        .bind(&entry.action)
+        .bind(&entry.actor)
+        .bind(&entry.subject)
+        .bind(&entry.source_ip)
+        .bind(entry.allowed)
+        .bind(&entry.reason)
+        .bind(&record.prev_hash)
+        .bind(&record.hash)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn last_audit(&self) -> Result<Option<AuditRecord>> {
+        let row = sqlx::query("SELECT * FROM audit_log ORDER BY seq DESC LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(audit_record).transpose()
+    }
+
+    async fn query_audit(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        let limit = query.limit.unwrap_or(super::DEFAULT_PAGE_SIZE).min(super::MAX_PAGE_SIZE);
+        let mut sql: QueryBuilder<Sqlite> = QueryBuilder::new("SELECT * FROM audit_log WHERE 1 = 1");
+        match query.action.as_deref() {
+            Some(prefix) if prefix.ends_with('.') => {
+                sql.push(" AND instr(action, ").push_bind(prefix.to_string()).push(") = 1");
+            }
+            Some(action) => {
+                sql.push(" AND action = ").push_bind(action.to_string());
+            }
+            None => {}
+        }
+        if let Some(actor) = &query.actor {
+            sql.push(" AND actor = ").push_bind(actor.clone());
+        }
+        if let Some(source_ip) = &query.source_ip {
+            sql.push(" AND source_ip = ").push_bind(source_ip.clone());
+        }
+        if let Some(allowed) = query.allowed {
+            sql.push(" AND allowed = ").push_bind(allowed);
+        }
+        if let Some(since) = &query.since {
+            sql.push(" AND timestamp >= ").push_bind(since.clone());
+        }
+        if let Some(until) = &query.until {
+            sql.push(" AND timestamp <= ").push_bind(until.clone());
+        }
+        if let Some(before) = query.before {
+            sql.push(" AND seq < ").push_bind(before as i64);
+        }
+        sql.push(" ORDER BY seq DESC LIMIT ").push_bind(limit as i64);
+        let rows = sql.build().fetch_all(&self.pool).await?;
+        rows.iter().map(audit_record).collect()
+    }
+
+    async fn audit_after(&self, seq: u64, limit: usize) -> Result<Vec<AuditRecord>> {
+        let rows = sqlx::query("SELECT * FROM audit_log WHERE seq > ?1 ORDER BY seq LIMIT ?2")
+            .bind(seq as i64)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(audit_record).collect()
+    }
+
+    async fn revoke_token(&self, token_id: &str) -> Result<()> {
+        sqlx::query("INSERT OR IGNORE INTO revoked_tokens (id, revoked_at) VALUES (?1, ?2)")
+            .bind(token_id)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn revoked_tokens(&self) -> Result<Vec<String>> {
+        let rows = sqlx::query("SELECT id FROM revoked_tokens ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(|row| Ok(row.try_get(0)?)).collect()
+    }
+
+    async fn get_stats(&self) -> Result<(usize, usize, usize)> {
+        let row = sqlx::query(
+            "SELECT COUNT(*),
+                    COALESCE(SUM(status = 'Completed'), 0),
+                    COALESCE(SUM(status = 'Failed'), 0)
+             FROM tasks",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok((
+            row.try_get::<i64, _>(0)? as usize,
+            row.try_get::<i64, _>(1)? as usize,
+            row.try_get::<i64, _>(2)? as usize,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::store_tests;
+
+    #[tokio::test]
+    async fn test_persistent_storage() {
+        let store = SqliteStore::in_memory().await.unwrap();
+        store_tests::store_and_retrieve_task(&store).await;
+    }
+
+    #[tokio::test]
+    async fn test_durable_queue_transitions() {
+        let store = SqliteStore::in_memory().await.unwrap();
+        store_tests::durable_queue_transitions(&store).await;
+    }
+
+    #[tokio::test]
+    async fn test_filter_and_paginate() {
+        let store = SqliteStore::in_memory().await.unwrap();
+        store_tests::filter_and_paginate(&store).await;
+    }
+
+    #[tokio::test]
+    async fn test_array_status_counts() {
+        let store = SqliteStore::in_memory().await.unwrap();
+        store_tests::array_counts(&store).await;
+    }
+
+    #[tokio::test]
+    async fn test_webhooks_and_deliveries() {
+        let store = SqliteStore::in_memory().await.unwrap();
+        store_tests::webhooks_and_deliveries(&store).await;
+    }
+
+    #[tokio::test]
+    async fn test_secrets() {
+        let store = SqliteStore::in_memory().await.unwrap();
+        store_tests::secrets(&store).await;
+    }
+
+    #[tokio::test]
+    async fn test_audit_chain() {
+        let store = SqliteStore::in_memory().await.unwrap();
+        store_tests::audit_chain(&store).await;
+        store_tests::revoked_tokens(&store).await;
+    }
+
+    #[tokio::test]
+    async fn test_edited_audit_row_breaks_the_chain() {
+        use crate::audit::{verify_store, AuditLog};
+        let store = std::sync::Arc::new(SqliteStore::in_memory().await.unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::open(&dir.path().join("audit.log"), store.clone()).await.unwrap();
+        for actor in ["alice", "bob", "carol"] {
+            log.record(AuditEntry::new("auth.login", actor, true)).await;
+        }
+        assert_eq!(verify_store(store.as_ref()).await.unwrap().unwrap().count(), 3);
+
+        // What someone with write access to the database file could do
+        sqlx::query("UPDATE audit_log SET actor = 'mallory' WHERE seq = 2")
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(verify_store(store.as_ref()).await.unwrap().unwrap_err().seq, 2);
+
+        sqlx::query("DELETE FROM audit_log WHERE seq = 2").execute(&store.pool).await.unwrap();
+        assert_eq!(verify_store(store.as_ref()).await.unwrap().unwrap_err().seq, 3);
+    }
+
+    #[tokio::test]
+    async fn test_listing_uses_indexes() {
+        let store = SqliteStore::in_memory().await.unwrap();
+        for (filter, index) in [
+            ("status = 'Completed'", "idx_tasks_status"),
+            ("worker_id = 'w1'", "idx_tasks_worker"),
+            ("owner = 'team-a'", "idx_tasks_owner"),
+            ("1 = 1", "idx_tasks_created"),
+        ] {
+            let plan: Vec<String> = sqlx::query(&format!(
+                "EXPLAIN QUERY PLAN SELECT {} FROM tasks WHERE {} ORDER BY created_at DESC, id DESC LIMIT 10",
+                TASK_COLUMNS, filter
+            ))
+            .fetch_all(&store.pool)
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get::<String, _>("detail"))
+            .collect();
+            assert!(plan.iter().any(|d| d.contains(index)), "{} not used: {:?}", index, plan);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reopen_file_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("octaskly.db");
+        let path = path.to_string_lossy();
+        let task = crate::protocol::Task::new("echo durable".to_string());
+        {
+            let store = SqliteStore::connect(&path).await.unwrap();
+            store.record_queued(&task).await.unwrap();
+            store.pool.close().await;
+        }
+        let store = SqliteStore::connect(&path).await.unwrap();
+        assert_eq!(store.load_pending().await.unwrap()[0].task.id, task.id);
+    }
+}
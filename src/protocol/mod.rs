@@ -2,6 +2,22 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
+/// Version of the dispatcher/worker wire protocol, advertised over mDNS
+/// Versi protokol kabel dispatcher/worker, diiklankan melalui mDNS
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// Owner assigned to tasks submitted without an explicit owner
+/// Pemilik yang diberikan ke tugas yang dikirim tanpa pemilik eksplisit
+pub const DEFAULT_OWNER: &str = "default";
+
+/// Times a task is requeued after its worker is lost before it is marked lost
+/// Berapa kali tugas dimasukkan ulang setelah worker-nya hilang sebelum ditandai hilang
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Timeout in seconds of tasks created without one
+/// Timeout dalam detik untuk tugas yang dibuat tanpa timeout
+pub const DEFAULT_TASK_TIMEOUT: u64 = 600;
+
 /// Represents a compute task to be executed
 /// Merepresentasikan tugas komputasi yang akan dieksekusi
 ///
@@ -41,23 +57,220 @@ pub struct Task {
     /// Task creation timestamp (Unix epoch)
     /// Stempel waktu pembuatan tugas (Unix epoch)
     pub created_at: i64,
+    
+    /// User or project the task is accounted to for fair-share scheduling
+    /// Pengguna atau proyek yang dibebani tugas untuk penjadwalan fair-share
+    pub owner: String,
+    
+    /// Resources the task needs reserved on the worker that runs it
+    /// Resource yang perlu dicadangkan tugas pada worker yang menjalankannya
+    pub resources: Resources,
+    
+    /// Constraints and preferences on which workers may run the task
+    /// Batasan dan preferensi worker mana yang boleh menjalankan tugas
+    pub placement: Placement,
+    
+    /// Requeues allowed when the assigned worker is lost
+    /// Jumlah pengulangan yang diizinkan saat worker yang ditugaskan hilang
+    pub max_retries: u32,
+    
+    /// Number of times the task has been dispatched to a worker
+    /// Berapa kali tugas telah dikirim ke worker
+    pub attempts: u32,
+    
+    /// Free-form labels for finding the task later; not used for placement
+    /// Label bebas untuk menemukan tugas nanti; tidak dipakai untuk penempatan
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    
+    /// Array this task was expanded from, if any
+    /// Array asal tugas ini diekspansi, jika ada
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    
+    /// Higher runs earlier among the same owner's queued tasks
+    /// Nilai lebih tinggi berjalan lebih dulu di antara tugas antrian pemilik yang sama
+    #[serde(default)]
+    pub priority: i32,
 }
 
 impl Task {
     /// Create a new task with sensible defaults
     /// Buat tugas baru dengan nilai default yang masuk akal
     ///
-    /// Sets default timeout to 10 minutes and generates UUID
-    /// Menetapkan timeout default ke 10 menit dan membuat UUID
+    /// Uses the default timeout (10 minutes) and generates UUID
+    /// Memakai timeout default (10 menit) dan membuat UUID
     pub fn new(command: String) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
             command,
             inputs: Vec::new(),
             outputs: Vec::new(),
-            timeout: 600, // 10 minutes default
+            timeout: DEFAULT_TASK_TIMEOUT,
             env: HashMap::new(),
             created_at: chrono::Local::now().timestamp(),
+            owner: DEFAULT_OWNER.to_string(),
+            resources: Resources::default(),
+            placement: Placement::default(),
+            max_retries: DEFAULT_MAX_RETRIES,
+            attempts: 0,
+            labels: HashMap::new(),
+            parent_id: None,
+            priority: 0,
+        }
+    }
+}
+
+/// Label-based placement rules for a task
+/// Aturan penempatan berbasis label untuk tugas
+///
+/// `node_selector` is a hard requirement: every entry must match a worker
+/// label exactly. `preferred` only ranks the eligible workers. Tasks sharing
+/// an `anti_affinity` group are never placed on the same host at once.
+/// 
+/// `node_selector` adalah syarat wajib: setiap entri harus sama persis dengan
+/// label worker. `preferred` hanya mengurutkan worker yang memenuhi syarat.
+/// Tugas dengan grup `anti_affinity` yang sama tidak pernah ditempatkan pada
+/// host yang sama secara bersamaan.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Placement {
+    /// Labels a worker must carry (key => value)
+    /// Label yang wajib dimiliki worker (kunci => nilai)
+    pub node_selector: HashMap<String, String>,
+    
+    /// Weighted soft preferences for worker labels
+    /// Preferensi lunak berbobot untuk label worker
+    pub preferred: Vec<LabelPreference>,
+    
+    /// Group name; at most one task of the group runs per host
+    /// Nama grup; paling banyak satu tugas dari grup berjalan per host
+    pub anti_affinity: Option<String>,
+}
+
+/// Soft preference for workers carrying `key=value`
+/// Preferensi lunak untuk worker yang memiliki `key=value`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabelPreference {
+    pub key: String,
+    pub value: String,
+    /// Relative weight added to a worker's score when it matches
+    /// Bobot relatif yang ditambahkan ke skor worker jika cocok
+    #[serde(default = "default_preference_weight")]
+    pub weight: u32,
+}
+
+fn default_preference_weight() -> u32 {
+    1
+}
+
+impl Placement {
+    /// Check the node selector against worker labels
+    /// Periksa node selector terhadap label worker
+    pub fn selects(&self, labels: &HashMap<String, String>) -> bool {
+        self.node_selector
+            .iter()
+            .all(|(key, value)| labels.get(key) == Some(value))
+    }
+
+    /// Sum of the weights of matching preferences
+    /// Jumlah bobot preferensi yang cocok
+    pub fn preference_score(&self, labels: &HashMap<String, String>) -> u32 {
+        self.preferred
+            .iter()
+            .filter(|p| labels.get(&p.key) == Some(&p.value))
+            .map(|p| p.weight)
+            .sum()
+    }
+}
+
+/// Amount of compute resources, used both for task requests and worker capacity
+/// Jumlah resource komputasi, digunakan untuk permintaan tugas dan kapasitas worker
+///
+/// CPU is measured in millicores (1000 = one full core) so that fractional
+/// requests can be added and compared exactly.
+/// 
+/// CPU diukur dalam milicore (1000 = satu core penuh) sehingga permintaan
+/// pecahan dapat dijumlahkan dan dibandingkan secara tepat.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Resources {
+    /// CPU in millicores
+    /// CPU dalam milicore
+    pub cpu_millicores: u64,
+    
+    /// Memory in megabytes
+    /// Memori dalam megabyte
+    pub memory_mb: u64,
+    
+    /// Scratch disk space in megabytes
+    /// Ruang disk sementara dalam megabyte
+    pub disk_mb: u64,
+    
+    /// Number of GPU devices
+    /// Jumlah perangkat GPU
+    pub gpus: u32,
+    
+    /// Named custom resources (e.g. "license-matlab" => 2)
+    /// Resource khusus bernama (mis. "license-matlab" => 2)
+    pub custom: HashMap<String, u64>,
+}
+
+impl Resources {
+    /// Check whether this request fits into the given free resources
+    /// Periksa apakah permintaan ini muat dalam resource bebas yang diberikan
+    pub fn fits_within(&self, available: &Resources) -> bool {
+        self.cpu_millicores <= available.cpu_millicores
+            && self.memory_mb <= available.memory_mb
+            && self.disk_mb <= available.disk_mb
+            && self.gpus <= available.gpus
+            && self
+                .custom
+                .iter()
+                .all(|(name, amount)| *amount <= available.custom.get(name).copied().unwrap_or(0))
+    }
+
+    /// Add another amount of resources to this one
+    /// Tambahkan jumlah resource lain ke resource ini
+    pub fn add(&mut self, other: &Resources) {
+        self.cpu_millicores += other.cpu_millicores;
+        self.memory_mb += other.memory_mb;
+        self.disk_mb += other.disk_mb;
+        self.gpus += other.gpus;
+        for (name, amount) in &other.custom {
+            *self.custom.entry(name.clone()).or_insert(0) += amount;
+        }
+    }
+
+    /// Subtract another amount of resources, saturating at zero
+    /// Kurangi dengan jumlah resource lain, berhenti di nol
+    pub fn subtract(&mut self, other: &Resources) {
+        self.cpu_millicores = self.cpu_millicores.saturating_sub(other.cpu_millicores);
+        self.memory_mb = self.memory_mb.saturating_sub(other.memory_mb);
+        self.disk_mb = self.disk_mb.saturating_sub(other.disk_mb);
+        self.gpus = self.gpus.saturating_sub(other.gpus);
+        for (name, amount) in &other.custom {
+            if let Some(value) = self.custom.get_mut(name) {
+                *value = value.saturating_sub(*amount);
+            }
+        }
+    }
+
+    /// Resources left after subtracting `used`
+    /// Resource yang tersisa setelah dikurangi `used`
+    pub fn remaining(&self, used: &Resources) -> Resources {
+        let mut free = self.clone();
+        free.subtract(used);
+        free
+    }
+
+    /// Capacity of the local machine (CPU cores and memory are auto-detected)
+    /// Kapasitas mesin lokal (core CPU dan memori dideteksi otomatis)
+    pub fn detect_local() -> Self {
+        Self {
+            cpu_millicores: crate::util::detect_cpu_cores() as u64 * 1000,
+            memory_mb: crate::util::detect_memory_mb().unwrap_or(0),
+            ..Default::default()
         }
     }
 }
@@ -105,6 +318,29 @@ pub struct TaskResult {
     /// Completion timestamp (Unix epoch)
     /// Stempel waktu penyelesaian (Unix epoch)
     pub completed_at: i64,
+    
+    /// Trusted name of the key that signed the task, if it was signed
+    /// Nama tepercaya kunci yang menandatangani tugas, jika ditandatangani
+    #[serde(default)]
+    pub signer: Option<String>,
+}
+
+impl TaskResult {
+    /// Result of a task that failed without running, with the reason in stderr
+    /// Hasil tugas yang gagal tanpa dijalankan, dengan alasan di stderr
+    pub fn failed(task_id: &str, worker_id: &str, reason: String) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+            worker_id: worker_id.to_string(),
+            status: TaskStatus::Failed,
+            stdout: String::new(),
+            stderr: reason,
+            exit_code: None,
+            duration_ms: 0,
+            completed_at: chrono::Local::now().timestamp(),
+            signer: None,
+        }
+    }
 }
 
 /// Task execution status enumeration
@@ -134,6 +370,10 @@ pub enum TaskStatus {
     /// Exceeded timeout threshold
     /// Melampaui ambang timeout
     TimedOut,
+    
+    /// Worker was lost and the retry budget is exhausted
+    /// Worker hilang dan batas pengulangan sudah habis
+    Lost,
 }
 
 /// Represents a worker node in the cluster
@@ -183,6 +423,48 @@ pub struct WorkerInfo {
     /// Operating system platform (linux, windows, macos)
     /// Platform sistem operasi (linux, windows, macos)
     pub platform: String,
+    
+    /// Total resources this worker offers to tasks
+    /// Total resource yang ditawarkan worker ini untuk tugas
+    pub capacity: Resources,
+    
+    /// Resources currently reserved by tasks assigned to this worker
+    /// Resource yang saat ini dicadangkan oleh tugas yang ditugaskan ke worker ini
+    pub allocated: Resources,
+    
+    /// Free-form labels used by task node selectors and affinities
+    /// Label bebas yang digunakan oleh node selector dan afinitas tugas
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    
+    /// Anti-affinity groups of the tasks currently assigned to this worker
+    /// Grup anti-afinitas dari tugas yang saat ini ditugaskan ke worker ini
+    #[serde(default)]
+    pub affinity_groups: Vec<String>,
+    
+    /// Whether the scheduler may place new tasks on this worker
+    /// Apakah penjadwal boleh menempatkan tugas baru pada worker ini
+    #[serde(default)]
+    pub availability: Availability,
+}
+
+/// Scheduling state of a worker, changed by cordon/drain
+/// Status penjadwalan worker, diubah oleh cordon/drain
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Availability {
+    /// Receives new tasks
+    /// Menerima tugas baru
+    #[default]
+    Active,
+    
+    /// Keeps running tasks but receives no new ones
+    /// Tetap menjalankan tugas tetapi tidak menerima tugas baru
+    Cordoned,
+    
+    /// Cordoned and waiting for running tasks to finish before removal
+    /// Di-cordon dan menunggu tugas berjalan selesai sebelum dihapus
+    Draining,
 }
 
 impl WorkerInfo {
@@ -199,6 +481,11 @@ impl WorkerInfo {
             allow_shell: true,
             last_heartbeat: chrono::Local::now().timestamp(),
             platform: std::env::consts::OS.to_string(),
+            capacity: Resources::detect_local(),
+            allocated: Resources::default(),
+            labels: crate::util::platform_labels(),
+            affinity_groups: Vec::new(),
+            availability: Availability::Active,
         }
     }
 
@@ -207,6 +494,57 @@ impl WorkerInfo {
     pub fn is_idle(&self) -> bool {
         self.current_jobs < self.max_jobs
     }
+
+    /// Check if the worker is neither cordoned nor draining
+    /// Periksa apakah worker tidak sedang di-cordon maupun di-drain
+    pub fn is_schedulable(&self) -> bool {
+        self.availability == Availability::Active
+    }
+
+    /// Resources not yet reserved by assigned tasks
+    /// Resource yang belum dicadangkan oleh tugas yang ditugaskan
+    pub fn free_resources(&self) -> Resources {
+        self.capacity.remaining(&self.allocated)
+    }
+
+    /// Check if the task fits into a free slot and the free resources now
+    /// Periksa apakah tugas muat di slot kosong dan resource bebas saat ini
+    pub fn can_fit(&self, task: &Task) -> bool {
+        self.is_schedulable()
+            && self.is_idle()
+            && task.placement.selects(&self.labels)
+            && task.resources.fits_within(&self.free_resources())
+    }
+
+    /// Record a task assigned to this worker (slot, resources, affinity group)
+    /// Catat tugas yang ditugaskan ke worker ini (slot, resource, grup afinitas)
+    pub fn reserve(&mut self, task: &Task) {
+        self.current_jobs += 1;
+        self.allocated.add(&task.resources);
+        if let Some(group) = &task.placement.anti_affinity {
+            self.affinity_groups.push(group.clone());
+        }
+    }
+
+    /// Undo `reserve` once the task has finished or was requeued
+    /// Batalkan `reserve` setelah tugas selesai atau dikembalikan ke antrian
+    pub fn release(&mut self, task: &Task) {
+        self.current_jobs = self.current_jobs.saturating_sub(1);
+        self.allocated.subtract(&task.resources);
+        if let Some(group) = &task.placement.anti_affinity {
+            if let Some(pos) = self.affinity_groups.iter().position(|g| g == group) {
+                self.affinity_groups.remove(pos);
+            }
+        }
+    }
+
+    /// Check if the task could ever run here, ignoring current reservations
+    /// Periksa apakah tugas dapat berjalan di sini, mengabaikan reservasi saat ini
+    pub fn could_ever_fit(&self, task: &Task) -> bool {
+        self.max_jobs > 0
+            && task.placement.selects(&self.labels)
+            && task.resources.fits_within(&self.capacity)
+    }
 }
 
 /// Resource availability for P2P task sharing
@@ -337,4 +675,122 @@ pub enum Message {
         responder_id: String,
         resources: ResourceAvailability,
     },
+    
+    /// Worker is shutting down and accepts no new tasks
+    /// Worker sedang dimatikan dan tidak menerima tugas baru
+    WorkerDraining {
+        worker_id: String,
+    },
+    
+    /// Worker hands back a task it did not finish
+    /// Worker mengembalikan tugas yang tidak diselesaikannya
+    TaskReturned {
+        task_id: String,
+        worker_id: String,
+    },
+    
+    /// Worker has stopped and should be removed from the pool
+    /// Worker telah berhenti dan harus dihapus dari pool
+    WorkerLeaving {
+        worker_id: String,
+    },
+
+    /// Dispatcher asks a registering worker to prove the pre-shared key
+    /// Dispatcher meminta worker yang mendaftar membuktikan kunci pra-bagi
+    RegisterChallenge {
+        nonce: Vec<u8>,
+    },
+
+    /// Worker's HMAC of the challenge nonce and its id under the pre-shared key
+    /// HMAC worker atas nonce tantangan dan id-nya dengan kunci pra-bagi
+    RegisterProof {
+        proof: Vec<u8>,
+    },
+
+    /// Outcome of a registration or hello, sent on the same connection
+    /// Hasil pendaftaran atau sapaan, dikirim pada koneksi yang sama
+    RegisterResult {
+        accepted: bool,
+        reason: Option<String>,
+    },
+
+    /// Dispatcher assigns a task signed by a key workers can check
+    /// Dispatcher menugaskan tugas yang ditandatangani kunci yang dapat diperiksa worker
+    AssignSignedTask(TaskEnvelope),
+
+    /// Worker asks for the secrets of a task it was assigned (see `crate::secrets`)
+    /// Worker meminta rahasia dari tugas yang ditugaskan kepadanya
+    SecretRequest {
+        task_id: String,
+        worker_id: String,
+    },
+
+    /// Secret values by environment variable, or why they were refused
+    /// Nilai rahasia per variabel environment, atau alasan penolakannya
+    SecretResponse {
+        values: SecretValues,
+        error: Option<String>,
+    },
+
+    /// Worker opens a connection for its reports; proven like a registration
+    /// Worker membuka koneksi untuk laporannya; dibuktikan seperti pendaftaran
+    WorkerHello {
+        worker_id: String,
+    },
+
+    /// Worker passes on output of a running task as it is written
+    /// Worker meneruskan output tugas yang sedang berjalan saat ditulis
+    TaskOutput {
+        task_id: String,
+        worker_id: String,
+        stream: OutputStream,
+        text: String,
+    },
+}
+
+/// Which output of a task a piece of text was written to
+/// Output tugas mana yang menerima potongan teks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Secret values by environment variable; `Debug` prints the variables only,
+/// so logging a message never shows a value
+/// Nilai rahasia per variabel environment; `Debug` hanya mencetak nama
+/// variabelnya, sehingga mencatat pesan tidak pernah menampilkan nilai
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretValues(pub HashMap<String, String>);
+
+impl std::fmt::Debug for SecretValues {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut vars: Vec<&String> = self.0.keys().collect();
+        vars.sort();
+        f.debug_tuple("SecretValues").field(&vars).finish()
+    }
+}
+
+/// A task signed for a worker, see `crate::signing`
+/// Tugas yang ditandatangani untuk worker, lihat `crate::signing`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskEnvelope {
+    pub task: Task,
+    
+    /// Worker the task is assigned to; no other worker runs it
+    /// Worker yang ditugaskan; tidak ada worker lain yang menjalankannya
+    pub worker_id: String,
+    
+    /// Base64 Ed25519 public key of the signer
+    /// Kunci publik Ed25519 penandatangan dalam base64
+    pub signer: String,
+    
+    /// Unix time after which workers refuse the task
+    /// Waktu Unix setelah itu worker menolak tugas
+    pub expires_at: i64,
+    
+    /// Ed25519 signature over the canonical encoding of the fields above
+    /// Tanda tangan Ed25519 atas pengodean kanonik field di atas
+    pub signature: Vec<u8>,
 }
@@ -118,26 +118,30 @@ impl ProcessMonitor {
     }
 
     /// Get current memory usage in MB
+    #[cfg(all(target_os = "linux", feature = "procfs"))]
     pub fn get_memory_usage(&self) -> Result<u64> {
         // Note: procfs is Linux-only, using alternative for Windows
-        // use procfs::process::Process;
-        
-        let process = Process::new(self.pid as i32)?;
+        let process = procfs::process::Process::new(self.pid as i32)?;
         let stat = process.stat()?;
         
         // RSS is in pages, convert to MB
         let page_size = 4096; // typical page size
         let memory_mb = (stat.rss * page_size) / (1024 * 1024);
         
-        Ok(memory_mb as u64)
+        Ok(memory_mb)
+    }
+
+    /// Get current memory usage in MB
+    #[cfg(not(all(target_os = "linux", feature = "procfs")))]
+    pub fn get_memory_usage(&self) -> Result<u64> {
+        anyhow::bail!("cannot monitor process {}: needs Linux and the `procfs` feature", self.pid)
     }
 
     /// Get current CPU time in seconds
+    #[cfg(all(target_os = "linux", feature = "procfs"))]
     pub fn get_cpu_time(&self) -> Result<u64> {
         // Note: procfs is Linux-only, using alternative for Windows
-        // use procfs::process::Process;
-        
-        let process = Process::new(self.pid as i32)?;
+        let process = procfs::process::Process::new(self.pid as i32)?;
         let stat = process.stat()?;
         
         // utime + stime in jiffies (typically 1/100 second)
@@ -147,6 +151,12 @@ impl ProcessMonitor {
         Ok(seconds)
     }
 
+    /// Get current CPU time in seconds
+    #[cfg(not(all(target_os = "linux", feature = "procfs")))]
+    pub fn get_cpu_time(&self) -> Result<u64> {
+        anyhow::bail!("cannot monitor process {}: needs Linux and the `procfs` feature", self.pid)
+    }
+
     /// Check if process exceeds limits
     pub fn check_limits(&self, limits: &ResourceLimits) -> Result<Option<String>> {
         let mem = self.get_memory_usage()?;
@@ -0,0 +1,473 @@
+// Weighted fair-share queue using deficit round robin over per-owner sub-queues
+// Antrian fair-share berbobot menggunakan deficit round robin atas sub-antrian per pemilik
+//
+// Each owner (user or project) gets its own sub-queue, ordered by task priority
+// and then arrival. Owners are visited in
+// round-robin order and earn credit proportional to their weight, scaled down by
+// how much of the cluster they have recently consumed. Usage decays exponentially
+// so that past bursts are gradually forgiven.
+//
+// Setiap pemilik (pengguna atau proyek) memiliki sub-antrian sendiri, diurutkan
+// menurut prioritas tugas lalu waktu kedatangan. Pemilik
+// dikunjungi secara round-robin dan mendapat kredit sebanding dengan bobotnya,
+// dikurangi sesuai pemakaian cluster terbaru. Pemakaian meluruh secara eksponensial
+// sehingga lonjakan lama perlahan dimaafkan.
+
+use crate::protocol::Task;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+// Lower bound for the usage penalty so heavy owners are slowed down, never starved
+// Batas bawah penalti pemakaian agar pemilik berat diperlambat, tidak kelaparan
+const MIN_FAIR_FACTOR: f64 = 0.05;
+
+// Lower bound for configured weights to keep the round robin making progress
+// Batas bawah bobot yang dikonfigurasi agar round robin tetap berjalan
+const MIN_WEIGHT: f64 = 0.01;
+
+// Fair-share configuration (weights and usage decay)
+// Konfigurasi fair-share (bobot dan peluruhan pemakaian)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FairShareConfig {
+    /// Per-owner weights; owners not listed use `default_weight`
+    pub weights: HashMap<String, f64>,
+
+    /// Weight for owners without an explicit entry
+    pub default_weight: f64,
+
+    /// Half-life of recorded usage in seconds
+    pub usage_half_life_secs: u64,
+}
+
+impl Default for FairShareConfig {
+    fn default() -> Self {
+        Self {
+            weights: HashMap::new(),
+            default_weight: 1.0,
+            usage_half_life_secs: 3600, // 1 hour
+        }
+    }
+}
+
+impl FairShareConfig {
+    // Effective weight for an owner
+    // Bobot efektif untuk pemilik
+    pub fn weight_for(&self, owner: &str) -> f64 {
+        self.weights
+            .get(owner)
+            .copied()
+            .unwrap_or(self.default_weight)
+            .max(MIN_WEIGHT)
+    }
+}
+
+// Current share and usage of a single owner, exposed through the stats API
+// Bagian dan pemakaian saat ini dari satu pemilik, diekspos melalui API statistik
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnerShare {
+    pub owner: String,
+    pub weight: f64,
+    pub queued: usize,
+    /// Normalized target share (weight / sum of weights of known owners)
+    pub share: f64,
+    /// Decayed usage in task-seconds
+    pub usage_secs: f64,
+    /// Fraction of total decayed usage consumed by this owner
+    pub usage_share: f64,
+    /// Priority multiplier derived from share and usage (1.0 = unpenalized)
+    pub fair_factor: f64,
+}
+
+// Usage value that halves every `half_life` seconds
+// Nilai pemakaian yang berkurang setengah setiap `half_life` detik
+#[derive(Debug, Clone, Copy, Default)]
+struct DecayingUsage {
+    value: f64,
+    updated_at_ms: i64,
+}
+
+impl DecayingUsage {
+    fn value_at(&self, now_ms: i64, half_life_secs: u64) -> f64 {
+        if half_life_secs == 0 {
+            return 0.0;
+        }
+        let elapsed = (now_ms - self.updated_at_ms).max(0) as f64 / 1000.0;
+        self.value * 0.5f64.powf(elapsed / half_life_secs as f64)
+    }
+
+    fn add(&mut self, amount: f64, now_ms: i64, half_life_secs: u64) {
+        self.value = self.value_at(now_ms, half_life_secs) + amount;
+        self.updated_at_ms = now_ms;
+    }
+}
+
+// Deficit round robin queue keyed by task owner
+// Antrian deficit round robin berdasarkan pemilik tugas
+pub struct FairShareQueue {
+    config: FairShareConfig,
+    queues: HashMap<String, VecDeque<Task>>,
+    active: VecDeque<String>,
+    deficits: HashMap<String, f64>,
+    usage: HashMap<String, DecayingUsage>,
+    dispatched: HashMap<String, String>,
+}
+
+impl FairShareQueue {
+    pub fn new(config: FairShareConfig) -> Self {
+        Self {
+            config,
+            queues: HashMap::new(),
+            active: VecDeque::new(),
+            deficits: HashMap::new(),
+            usage: HashMap::new(),
+            dispatched: HashMap::new(),
+        }
+    }
+
+    // Add task to its owner's sub-queue, behind tasks of equal or higher priority
+    // Tambahkan tugas ke sub-antrian pemiliknya, di belakang tugas berprioritas sama atau lebih tinggi
+    pub fn push(&mut self, task: Task) {
+        let owner = task.owner.clone();
+        let queue = self.queues.entry(owner.clone()).or_default();
+        if queue.is_empty() && !self.active.contains(&owner) {
+            self.active.push_back(owner);
+        }
+        match queue.iter().position(|queued| queued.priority < task.priority) {
+            Some(index) => queue.insert(index, task),
+            None => queue.push_back(task),
+        }
+    }
+
+    // Return a popped task to the front of its owner's sub-queue, refunding its credit
+    // Kembalikan tugas yang diambil ke depan sub-antrian pemiliknya, mengembalikan kreditnya
+    pub fn push_front(&mut self, task: Task) {
+        let owner = task.owner.clone();
+        self.dispatched.remove(&task.id);
+        *self.deficits.entry(owner.clone()).or_insert(0.0) += 1.0;
+        let queue = self.queues.entry(owner.clone()).or_default();
+        if queue.is_empty() && !self.active.contains(&owner) {
+            self.active.push_front(owner);
+        }
+        queue.push_front(task);
+    }
+
+    // Pop the next task according to deficit round robin
+    // Ambil tugas berikutnya sesuai deficit round robin
+    pub fn pop(&mut self) -> Option<Task> {
+        self.pop_at(chrono::Local::now().timestamp_millis())
+    }
+
+    fn pop_at(&mut self, now_ms: i64) -> Option<Task> {
+        loop {
+            let owner = self.active.front()?.clone();
+            let deficit = self.deficits.get(&owner).copied().unwrap_or(0.0);
+
+            if deficit >= 1.0 {
+                let queue = self.queues.get_mut(&owner)?;
+                let task = queue.pop_front()?;
+                let remaining = deficit - 1.0;
+
+                if queue.is_empty() {
+                    // Idle owners do not bank credit
+                    // Pemilik yang menganggur tidak menyimpan kredit
+                    self.queues.remove(&owner);
+                    self.deficits.remove(&owner);
+                    self.active.pop_front();
+                } else {
+                    self.deficits.insert(owner.clone(), remaining);
+                    if remaining < 1.0 {
+                        self.active.rotate_left(1);
+                    }
+                }
+
+                self.dispatched.insert(task.id.clone(), owner);
+                return Some(task);
+            }
+
+            let quantum = self.config.weight_for(&owner) * self.fair_factor(&owner, now_ms);
+            let deficit = deficit + quantum;
+            self.deficits.insert(owner, deficit);
+            if deficit < 1.0 {
+                self.active.rotate_left(1);
+            }
+        }
+    }
+
+    // Take a queued task out of the queue wherever it is (e.g. a late result made it unnecessary)
+    // Keluarkan tugas dari antrian di posisi mana pun (mis. hasil terlambat membuatnya tidak diperlukan)
+    pub fn remove(&mut self, task_id: &str) -> Option<Task> {
+        let owner = self
+            .queues
+            .iter()
+            .find(|(_, queue)| queue.iter().any(|t| t.id == task_id))
+            .map(|(owner, _)| owner.clone())?;
+        let queue = self.queues.get_mut(&owner)?;
+        let index = queue.iter().position(|t| t.id == task_id)?;
+        let task = queue.remove(index);
+        if queue.is_empty() {
+            self.queues.remove(&owner);
+            self.deficits.remove(&owner);
+            self.active.retain(|o| o != &owner);
+        }
+        task
+    }
+
+    // Charge execution time of a dispatched task to its owner
+    // Bebankan waktu eksekusi tugas yang dikirim ke pemiliknya
+    pub fn charge(&mut self, task_id: &str, duration_ms: u64) {
+        self.charge_at(task_id, duration_ms, chrono::Local::now().timestamp_millis());
+    }
+
+    fn charge_at(&mut self, task_id: &str, duration_ms: u64, now_ms: i64) {
+        if let Some(owner) = self.dispatched.remove(task_id) {
+            let half_life = self.config.usage_half_life_secs;
+            self.usage
+                .entry(owner)
+                .or_default()
+                .add(duration_ms as f64 / 1000.0, now_ms, half_life);
+        }
+    }
+
+    // Drop a dispatched task that will never be charged (cancelled or lost)
+    // Buang tugas terkirim yang tidak akan pernah dibebankan (dibatalkan atau hilang)
+    pub fn forget(&mut self, task_id: &str) {
+        self.dispatched.remove(task_id);
+    }
+
+    // Queued tasks of every owner, in no particular order
+    // Tugas dalam antrian semua pemilik, tanpa urutan tertentu
+    pub fn iter(&self) -> impl Iterator<Item = &Task> {
+        self.queues.values().flatten()
+    }
+
+    // Total number of queued tasks across owners
+    // Jumlah total tugas dalam antrian untuk semua pemilik
+    pub fn len(&self) -> usize {
+        self.queues.values().map(|q| q.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Replace weights and decay settings, keeping queues and usage
+    // Ganti bobot dan pengaturan peluruhan, mempertahankan antrian dan pemakaian
+    pub fn set_config(&mut self, config: FairShareConfig) {
+        self.config = config;
+    }
+
+    pub fn config(&self) -> &FairShareConfig {
+        &self.config
+    }
+
+    // Snapshot of share and usage for every known owner
+    // Cuplikan bagian dan pemakaian untuk setiap pemilik yang dikenal
+    pub fn stats(&self) -> Vec<OwnerShare> {
+        let now_ms = chrono::Local::now().timestamp_millis();
+        let mut stats: Vec<OwnerShare> = self
+            .known_owners()
+            .into_iter()
+            .map(|owner| {
+                let usage_secs = self.usage_of(&owner, now_ms);
+                OwnerShare {
+                    weight: self.config.weight_for(&owner),
+                    queued: self.queues.get(&owner).map(|q| q.len()).unwrap_or(0),
+                    share: self.target_share(&owner),
+                    usage_secs,
+                    usage_share: self.usage_share(&owner, now_ms),
+                    fair_factor: self.fair_factor(&owner, now_ms),
+                    owner,
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| a.owner.cmp(&b.owner));
+        stats
+    }
+
+    fn known_owners(&self) -> Vec<String> {
+        let mut owners: Vec<String> = self.queues.keys().cloned().collect();
+        for owner in self.usage.keys() {
+            if !self.queues.contains_key(owner) {
+                owners.push(owner.clone());
+            }
+        }
+        owners
+    }
+
+    fn usage_of(&self, owner: &str, now_ms: i64) -> f64 {
+        self.usage
+            .get(owner)
+            .map(|u| u.value_at(now_ms, self.config.usage_half_life_secs))
+            .unwrap_or(0.0)
+    }
+
+    fn target_share(&self, owner: &str) -> f64 {
+        let total: f64 = self
+            .known_owners()
+            .iter()
+            .map(|o| self.config.weight_for(o))
+            .sum();
+        if total > 0.0 {
+            self.config.weight_for(owner) / total
+        } else {
+            0.0
+        }
+    }
+
+    fn usage_share(&self, owner: &str, now_ms: i64) -> f64 {
+        let total: f64 = self.usage.keys().map(|o| self.usage_of(o, now_ms)).sum();
+        if total > 0.0 {
+            self.usage_of(owner, now_ms) / total
+        } else {
+            0.0
+        }
+    }
+
+    // Classic fair-share factor 2^(-usage/share), clamped to keep progress
+    // Faktor fair-share klasik 2^(-pemakaian/bagian), dibatasi agar tetap maju
+    fn fair_factor(&self, owner: &str, now_ms: i64) -> f64 {
+        let share = self.target_share(owner);
+        if share <= 0.0 {
+            return 1.0;
+        }
+        2f64.powf(-self.usage_share(owner, now_ms) / share)
+            .max(MIN_FAIR_FACTOR)
+    }
+}
+
+impl Default for FairShareQueue {
+    fn default() -> Self {
+        Self::new(FairShareConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task_for(owner: &str) -> Task {
+        let mut task = Task::new(format!("echo {}", owner));
+        task.owner = owner.to_string();
+        task
+    }
+
+    #[test]
+    fn test_single_owner_is_fifo() {
+        let mut queue = FairShareQueue::default();
+        let first = task_for("alice");
+        let second = task_for("alice");
+        let first_id = first.id.clone();
+        queue.push(first);
+        queue.push(second);
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop().unwrap().id, first_id);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn test_priority_orders_within_owner() {
+        let mut queue = FairShareQueue::default();
+        let low = task_for("alice");
+        let mut urgent = task_for("alice");
+        urgent.priority = 10;
+        let mut also_urgent = task_for("alice");
+        also_urgent.priority = 10;
+        let order = vec![urgent.id.clone(), also_urgent.id.clone(), low.id.clone()];
+        queue.push(low);
+        queue.push(urgent);
+        queue.push(also_urgent);
+
+        let popped: Vec<String> = std::iter::from_fn(|| queue.pop()).map(|t| t.id).collect();
+        assert_eq!(popped, order);
+    }
+
+    #[test]
+    fn test_push_front_restores_order() {
+        let mut queue = FairShareQueue::default();
+        let first = task_for("alice");
+        let first_id = first.id.clone();
+        queue.push(first);
+        queue.push(task_for("alice"));
+
+        let popped = queue.pop().unwrap();
+        queue.push_front(popped);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop().unwrap().id, first_id);
+    }
+
+    #[test]
+    fn test_remove_queued_task() {
+        let mut queue = FairShareQueue::new(FairShareConfig::default());
+        let first = task_for("alice");
+        let first_id = first.id.clone();
+        queue.push(first);
+        queue.push(task_for("bob"));
+
+        assert_eq!(queue.remove(&first_id).map(|t| t.id), Some(first_id.clone()));
+        assert!(queue.remove(&first_id).is_none());
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.pop().unwrap().owner, "bob");
+    }
+
+    #[test]
+    fn test_forget_drops_dispatched_task() {
+        let mut queue = FairShareQueue::default();
+        let task = task_for("alice");
+        let task_id = task.id.clone();
+        queue.push(task);
+        queue.pop_at(0);
+        assert!(queue.dispatched.contains_key(&task_id));
+
+        queue.forget(&task_id);
+        assert!(queue.dispatched.is_empty());
+        queue.charge_at(&task_id, 3_600_000, 0);
+        assert_eq!(queue.usage_of("alice", 0), 0.0);
+    }
+
+    #[test]
+    fn test_small_owner_not_starved() {
+        let mut queue = FairShareQueue::default();
+        for _ in 0..1000 {
+            queue.push(task_for("big-team"));
+        }
+        queue.push(task_for("small-team"));
+
+        let popped: Vec<String> = (0..4).map(|_| queue.pop().unwrap().owner).collect();
+        assert!(popped.contains(&"small-team".to_string()));
+    }
+
+    #[test]
+    fn test_weights_shape_dispatch_ratio() {
+        let mut config = FairShareConfig::default();
+        config.weights.insert("gold".to_string(), 3.0);
+        let mut queue = FairShareQueue::new(config);
+        for _ in 0..100 {
+            queue.push(task_for("gold"));
+            queue.push(task_for("bronze"));
+        }
+
+        let gold = (0..40)
+            .filter(|_| queue.pop_at(0).unwrap().owner == "gold")
+            .count();
+        assert_eq!(gold, 30);
+    }
+
+    #[test]
+    fn test_usage_decays_and_penalizes() {
+        let mut queue = FairShareQueue::default();
+        let heavy = task_for("heavy");
+        let light = task_for("light");
+        let heavy_id = heavy.id.clone();
+        queue.push(heavy);
+        queue.push(light);
+        queue.pop_at(0);
+        queue.charge_at(&heavy_id, 3_600_000, 0);
+
+        assert!(queue.fair_factor("heavy", 0) < queue.fair_factor("light", 0));
+
+        let half_life_ms = 3_600_000;
+        let usage = queue.usage_of("heavy", half_life_ms);
+        assert!((usage - 1800.0).abs() < 1e-6);
+    }
+}
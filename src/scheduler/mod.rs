@@ -1,37 +1,361 @@
-use crate::protocol::{Task, WorkerInfo};
-use std::collections::VecDeque;
-use std::sync::Arc;
+pub mod fair_share;
+pub mod policy;
+pub mod simulation;
+
+pub use fair_share::{FairShareConfig, FairShareQueue, OwnerShare};
+pub use policy::{Assignment, PolicyKind, SchedulingPolicy};
+
+use crate::events::{Event, EventBus};
+use crate::metrics;
+use crate::persistence::{QueueState, TaskStore, Transition};
+use crate::protocol::{Availability, Task, TaskResult, WorkerInfo};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tokio::sync::RwLock;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+// Resources held on a worker by a dispatched task until it finishes
+// Resource yang ditahan pada worker oleh tugas terkirim hingga selesai
+#[derive(Debug, Clone)]
+pub struct Reservation {
+    pub task: Task,
+    pub worker_id: String,
+    pub assigned_at: i64,
+    /// Lease deadline; renewed by the worker's heartbeats
+    pub lease_expires_at: i64,
+}
+
+// Default lease length; a worker silent for longer is presumed dead
+// Panjang lease default; worker yang diam lebih lama dianggap mati
+pub const DEFAULT_LEASE_SECS: i64 = 30;
+
+// What happened to a task whose lease expired
+// Apa yang terjadi pada tugas yang lease-nya kedaluwarsa
+#[derive(Debug, Clone)]
+pub struct LeaseExpiry {
+    pub task: Task,
+    pub worker_id: String,
+    /// Requeued for another attempt; false means the task is now lost
+    pub requeued: bool,
+}
+
+// How a reported result relates to the scheduler's view of the task
+// Hubungan hasil yang dilaporkan dengan pandangan penjadwal atas tugas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultDisposition {
+    /// Result of the current assignment
+    Accepted,
+    /// Late result from a worker that held the task before it was requeued
+    /// or marked lost; the task had not run again yet (it was taken out of
+    /// the queue) or had been marked lost
+    Reconciled,
+    /// Late result while another worker now runs the task; ignore it
+    Stale,
+    /// Task is not tracked by the scheduler (finished before, or never
+    /// scheduled here), or the worker never held it; drop the result
+    Untracked,
+}
+
+// A task taken back by a cancel request
+// Tugas yang ditarik kembali oleh permintaan pembatalan
+#[derive(Debug, Clone)]
+pub struct Cancellation {
+    pub task: Task,
+    /// Worker still running the task, which must be told to stop
+    pub worker_id: Option<String>,
+}
+
+// How a drain treats tasks already running on the worker
+// Cara drain memperlakukan tugas yang sudah berjalan di worker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DrainMode {
+    /// Let running tasks finish
+    /// Biarkan tugas yang berjalan selesai
+    #[default]
+    Wait,
+    /// Hand running tasks back to the queue for other workers
+    /// Kembalikan tugas yang berjalan ke antrian untuk worker lain
+    Migrate,
+}
 
 // Task scheduler managing queue and worker assignment
 // Penjadwal tugas mengelola antrian dan penugasan worker
 pub struct Scheduler {
-    queue: Arc<RwLock<VecDeque<Task>>>,
+    queue: Arc<RwLock<FairShareQueue>>,
     workers: Arc<RwLock<Vec<WorkerInfo>>>,
+    policy: Arc<RwLock<Box<dyn SchedulingPolicy>>>,
+    in_flight: Arc<RwLock<HashMap<String, Reservation>>>,
+    lost: Arc<RwLock<HashSet<String>>>,
+    /// Workers that held each requeued or lost task; only they may report its result late
+    holders: Arc<RwLock<HashMap<String, HashSet<String>>>>,
+    lease_secs: i64,
+    /// Registrations beyond this many workers are refused
+    max_workers: Option<usize>,
+    store: Option<Arc<dyn TaskStore>>,
+    events: Option<Arc<EventBus>>,
+    /// When queued tasks were submitted, for the schedule latency histogram
+    submitted_at: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+// Tasks rebuilt from the database on startup
+// Tugas yang dibangun ulang dari basis data saat mulai
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Recovery {
+    pub queued: usize,
+    pub in_flight: usize,
 }
 
 impl Scheduler {
     // Initialize new scheduler with empty queue and workers
     // Inisialisasi penjadwal baru dengan antrian dan worker kosong
     pub fn new() -> Self {
+        Self::with_fair_share(FairShareConfig::default())
+    }
+
+    // Initialize scheduler with custom fair-share weights and usage decay
+    // Inisialisasi penjadwal dengan bobot fair-share dan peluruhan pemakaian khusus
+    pub fn with_fair_share(config: FairShareConfig) -> Self {
         Self {
-            queue: Arc::new(RwLock::new(VecDeque::new())),
+            queue: Arc::new(RwLock::new(FairShareQueue::new(config))),
             workers: Arc::new(RwLock::new(Vec::new())),
+            policy: Arc::new(RwLock::new(PolicyKind::default().build())),
+            in_flight: Arc::new(RwLock::new(HashMap::new())),
+            lost: Arc::new(RwLock::new(HashSet::new())),
+            holders: Arc::new(RwLock::new(HashMap::new())),
+            lease_secs: DEFAULT_LEASE_SECS,
+            max_workers: None,
+            store: None,
+            events: None,
+            submitted_at: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Persist every queue and assignment transition so a restart can recover them
+    // Simpan setiap transisi antrian dan penugasan agar restart dapat memulihkannya
+    pub fn with_store(mut self, store: Arc<dyn TaskStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    // Refuse new workers once this many are registered
+    // Tolak worker baru setelah sejumlah ini terdaftar
+    pub fn with_max_workers(mut self, max_workers: usize) -> Self {
+        self.max_workers = Some(max_workers);
+        self
+    }
+
+    // Publish submissions, assignments and worker membership changes
+    // Terbitkan pengiriman, penugasan dan perubahan keanggotaan worker
+    pub fn with_events(mut self, events: Arc<EventBus>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn emit(&self, event: Event) {
+        if let Some(events) = &self.events {
+            events.publish(event);
         }
     }
 
+    // Write transitions to the store in one batch; failures are logged, scheduling goes on
+    // Tulis transisi ke store dalam satu batch; kegagalan dicatat, penjadwalan tetap berjalan
+    async fn persist(&self, transitions: &[Transition<'_>]) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.record(transitions).await {
+                warn!("Failed to persist {} task state changes: {}", transitions.len(), e);
+            }
+        }
+    }
+
+    // Rebuild the queue and in-flight assignments saved before a restart.
+    // Assignments get a fresh lease: if their worker re-registers in time they
+    // are picked up again, otherwise the lease expires and the task is retried.
+    // Bangun ulang antrian dan penugasan yang tersimpan sebelum restart.
+    // Penugasan mendapat lease baru: jika worker-nya mendaftar ulang tepat
+    // waktu tugas dilanjutkan, jika tidak lease habis dan tugas diulang.
+    pub async fn recover(&self) -> anyhow::Result<Recovery> {
+        let Some(store) = &self.store else {
+            return Ok(Recovery::default());
+        };
+        let pending = store.load_pending().await?;
+        let now = chrono::Local::now().timestamp();
+        let mut recovery = Recovery::default();
+        let mut queue = self.queue.write().await;
+        let mut in_flight = self.in_flight.write().await;
+        for entry in pending {
+            match (entry.state, entry.worker_id) {
+                (QueueState::Assigned | QueueState::Running, Some(worker_id)) => {
+                    in_flight.insert(
+                        entry.task.id.clone(),
+                        Reservation {
+                            task: entry.task,
+                            worker_id,
+                            assigned_at: now,
+                            lease_expires_at: now + self.lease_secs,
+                        },
+                    );
+                    recovery.in_flight += 1;
+                }
+                _ => {
+                    queue.push(entry.task);
+                    recovery.queued += 1;
+                }
+            }
+        }
+        info!(
+            "Recovered {} queued and {} in-flight tasks",
+            recovery.queued, recovery.in_flight
+        );
+        Ok(recovery)
+    }
+
+    // Record that an assigned task was delivered to its worker
+    // Catat bahwa tugas yang ditugaskan telah dikirim ke worker-nya
+    pub async fn mark_running(&self, task_id: &str) {
+        self.persist(&[Transition::Running(task_id)]).await;
+    }
+
+    // Put a task back at the front of its owner's queue
+    // Kembalikan tugas ke depan antrian pemiliknya
+    async fn push_front(&self, task: Task) {
+        self.persist(&[Transition::Queued(&task)]).await;
+        self.queue.write().await.push_front(task);
+    }
+
+    // Set how long an assignment survives without a heartbeat from its worker
+    // Atur berapa lama penugasan bertahan tanpa heartbeat dari worker-nya
+    pub fn with_lease_timeout(mut self, secs: i64) -> Self {
+        self.lease_secs = secs;
+        self
+    }
+
+    // Replace the worker selection policy
+    // Ganti kebijakan pemilihan worker
+    pub async fn set_policy(&self, policy: Box<dyn SchedulingPolicy>) {
+        info!("Scheduling policy set to {}", policy.name());
+        *self.policy.write().await = policy;
+    }
+
+    // Replace fair-share weights and usage decay without touching the queue
+    // Ganti bobot fair-share dan peluruhan pemakaian tanpa mengubah antrian
+    pub async fn set_fair_share(&self, config: FairShareConfig) {
+        self.queue.write().await.set_config(config);
+    }
+
+    // Name of the active worker selection policy
+    // Nama kebijakan pemilihan worker yang aktif
+    pub async fn policy_name(&self) -> &'static str {
+        self.policy.read().await.name()
+    }
+
     // Add task to the work queue for distribution
     // Tambahkan tugas ke antrian kerja untuk distribusi
     pub async fn enqueue(&self, task: Task) {
-        info!("Enqueued task {}: {}", task.id, task.command);
-        self.queue.write().await.push_back(task);
+        info!("Enqueued task {} for {}: {}", task.id, task.owner, task.command);
+        self.persist(&[Transition::Queued(&task)]).await;
+        self.queue.write().await.push(task);
+    }
+
+    // Validate resource requests and enqueue; rejects tasks no registered worker could ever run
+    // Validasi permintaan resource lalu masukkan ke antrian; tolak tugas yang tidak
+    // akan pernah bisa dijalankan oleh worker terdaftar mana pun
+    pub async fn submit(&self, task: Task) -> anyhow::Result<()> {
+        self.submit_batch(vec![task]).await
+    }
+
+    // Validate and enqueue many tasks at once: either all are accepted and
+    // stored in one transaction, or none is
+    // Validasi dan masukkan banyak tugas sekaligus: semua diterima dan
+    // disimpan dalam satu transaksi, atau tidak ada sama sekali
+    pub async fn submit_batch(&self, tasks: Vec<Task>) -> anyhow::Result<()> {
+        {
+            let workers = self.workers.read().await;
+            for task in &tasks {
+                check_placeable(&workers, task)?;
+            }
+        }
+        if let Some(store) = &self.store {
+            let queued: Vec<Transition> = tasks.iter().map(Transition::Queued).collect();
+            store.record(&queued).await?;
+        }
+        let now = Instant::now();
+        let mut queue = self.queue.write().await;
+        let mut submitted_at = self.submitted_at.lock().unwrap();
+        for task in tasks {
+            info!("Enqueued task {} for {}: {}", task.id, task.owner, task.command);
+            metrics::global().tasks_submitted.inc();
+            submitted_at.insert(task.id.clone(), now);
+            self.emit(Event::TaskSubmitted {
+                task_id: task.id.clone(),
+                command: task.command.clone(),
+                owner: task.owner.clone(),
+                parent_id: task.parent_id.clone(),
+            });
+            queue.push(task);
+        }
+        Ok(())
+    }
+
+    // Take a task out of the queue or off its worker; returns None if the
+    // scheduler no longer tracks it (finished or unknown)
+    // Keluarkan tugas dari antrian atau dari worker-nya; mengembalikan None
+    // jika penjadwal tidak lagi melacaknya (selesai atau tidak dikenal)
+    pub async fn cancel(&self, task_id: &str) -> Option<Cancellation> {
+        self.holders.write().await.remove(task_id);
+        let queued = {
+            let mut queue = self.queue.write().await;
+            queue.forget(task_id);
+            queue.remove(task_id)
+        };
+        if let Some(task) = queued {
+            info!("Cancelled queued task {}", task_id);
+            self.submitted_at.lock().unwrap().remove(task_id);
+            return Some(Cancellation { task, worker_id: None });
+        }
+        let worker_id = self.in_flight.read().await.get(task_id)?.worker_id.clone();
+        let task = self.release_task(task_id).await?;
+        info!("Cancelled task {} running on worker {}", task_id, worker_id);
+        Some(Cancellation {
+            task,
+            worker_id: Some(worker_id),
+        })
+    }
+
+    // Cancel every unfinished task of an array
+    // Batalkan setiap tugas array yang belum selesai
+    pub async fn cancel_array(&self, parent_id: &str) -> Vec<Cancellation> {
+        let in_array = |task: &Task| task.parent_id.as_deref() == Some(parent_id);
+        let mut task_ids: Vec<String> = self
+            .queue
+            .read()
+            .await
+            .iter()
+            .filter(|t| in_array(t))
+            .map(|t| t.id.clone())
+            .collect();
+        task_ids.extend(
+            self.in_flight
+                .read()
+                .await
+                .values()
+                .filter(|r| in_array(&r.task))
+                .map(|r| r.task.id.clone()),
+        );
+        let mut cancelled = Vec::new();
+        for task_id in task_ids {
+            if let Some(cancellation) = self.cancel(&task_id).await {
+                cancelled.push(cancellation);
+            }
+        }
+        cancelled
     }
 
-    // Remove and return first task from queue (FIFO)
-    // Hapus dan kembalikan tugas pertama dari antrian (FIFO)
+    // Remove and return next task by fair share (FIFO within an owner)
+    // Hapus dan kembalikan tugas berikutnya menurut fair share (FIFO per pemilik)
     pub async fn dequeue(&self) -> Option<Task> {
-        self.queue.write().await.pop_front()
+        self.queue.write().await.pop()
     }
 
     // Get current number of pending tasks
@@ -40,11 +364,56 @@ impl Scheduler {
         self.queue.read().await.len()
     }
 
-    // Register new worker with scheduler
-    // Daftarkan worker baru dengan penjadwal
-    pub async fn register_worker(&self, worker: WorkerInfo) {
+    /// Register or refresh a worker; false if a new worker was refused
+    /// because the dispatcher already has `max_workers`
+    pub async fn register_worker(&self, worker: WorkerInfo) -> bool {
         debug!("Registering worker: {}", worker.name);
-        self.workers.write().await.push(worker);
+        let mut workers = self.workers.write().await;
+        match workers.iter_mut().find(|w| w.id == worker.id) {
+            // Re-announce: refresh the record but keep reservations and cordon state
+            // Pengumuman ulang: perbarui catatan tetapi pertahankan reservasi dan status cordon
+            Some(existing) => {
+                let WorkerInfo {
+                    current_jobs,
+                    allocated,
+                    affinity_groups,
+                    availability,
+                    ..
+                } = existing.clone();
+                *existing = WorkerInfo {
+                    current_jobs,
+                    allocated,
+                    affinity_groups,
+                    availability,
+                    ..worker
+                };
+            }
+            // New to this dispatcher: take over assignments recovered for it
+            // Baru bagi dispatcher ini: ambil alih penugasan yang dipulihkan untuknya
+            None => {
+                if self.max_workers.is_some_and(|max| workers.len() >= max) {
+                    warn!("Refusing worker {}: limit of {} workers reached", worker.name, workers.len());
+                    return false;
+                }
+                let mut worker = worker;
+                let deadline = chrono::Local::now().timestamp() + self.lease_secs;
+                for reservation in self.in_flight.write().await.values_mut() {
+                    if reservation.worker_id == worker.id {
+                        worker.reserve(&reservation.task);
+                        reservation.lease_expires_at = deadline;
+                        info!("Worker {} resumed task {}", worker.name, reservation.task.id);
+                    }
+                }
+                self.emit(Event::WorkerJoined {
+                    worker_id: worker.id.clone(),
+                    name: worker.name.clone(),
+                    address: worker.address.clone(),
+                    port: worker.port,
+                });
+                workers.push(worker);
+            }
+        }
+        true
     }
 
     // Update worker information
@@ -56,6 +425,19 @@ impl Scheduler {
         }
     }
 
+    // Record a heartbeat without touching the worker's job count or allocations
+    // Catat detak jantung tanpa mengubah jumlah pekerjaan atau alokasi worker
+    pub async fn touch_heartbeat(&self, worker_id: &str, timestamp: i64) -> bool {
+        let mut workers = self.workers.write().await;
+        match workers.iter_mut().find(|w| w.id == worker_id) {
+            Some(worker) => {
+                worker.last_heartbeat = timestamp;
+                true
+            }
+            None => false,
+        }
+    }
+
     // Decrement worker job count on task completion
     // Kurangi jumlah pekerjaan worker saat tugas selesai
     pub async fn worker_job_completed(&self, worker_id: &str) {
@@ -67,13 +449,183 @@ impl Scheduler {
         }
     }
 
+    // Release the slot and resources reserved for a task; returns the task if it was in flight
+    // Lepaskan slot dan resource yang dipesan untuk tugas; kembalikan tugas jika sedang berjalan
+    pub async fn release_task(&self, task_id: &str) -> Option<Task> {
+        let reservation = self.in_flight.write().await.remove(task_id)?;
+        let mut workers = self.workers.write().await;
+        if let Some(worker) = workers.iter_mut().find(|w| w.id == reservation.worker_id) {
+            worker.release(&reservation.task);
+        }
+        Some(reservation.task)
+    }
+
+    // Take a task off its worker without a result, remembering the worker in
+    // case its result still arrives
+    // Ambil tugas dari worker-nya tanpa hasil, dengan mengingat worker itu
+    // jika hasilnya masih datang
+    async fn take_back(&self, task_id: &str) -> Option<(Task, String)> {
+        let worker_id = self.in_flight.read().await.get(task_id)?.worker_id.clone();
+        let task = self.release_task(task_id).await?;
+        self.holders
+            .write()
+            .await
+            .entry(task_id.to_string())
+            .or_default()
+            .insert(worker_id.clone());
+        Some((task, worker_id))
+    }
+
+    // Return an unfinished task to the front of its owner's queue; only the
+    // worker holding its reservation may hand it back
+    // Kembalikan tugas yang belum selesai ke depan antrian pemiliknya; hanya
+    // worker yang memegang reservasinya yang boleh mengembalikannya
+    pub async fn requeue_task(&self, task_id: &str, worker_id: &str) -> bool {
+        let held_by = self.in_flight.read().await.get(task_id).map(|r| r.worker_id.clone());
+        match held_by {
+            Some(holder) if holder == worker_id => {}
+            Some(holder) => {
+                warn!("Worker {} cannot return task {} held by {}", worker_id, task_id, holder);
+                return false;
+            }
+            None => return false,
+        }
+        match self.take_back(task_id).await {
+            Some((task, _)) => {
+                info!("Requeued task {}", task.id);
+                self.push_front(task).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Stop placing new tasks on a worker
+    // Berhenti menempatkan tugas baru pada worker
+    pub async fn cordon(&self, worker_id: &str) -> bool {
+        self.set_availability(worker_id, Availability::Cordoned).await
+    }
+
+    // Allow new tasks on a cordoned or draining worker again
+    // Izinkan lagi tugas baru pada worker yang di-cordon atau di-drain
+    pub async fn uncordon(&self, worker_id: &str) -> bool {
+        self.set_availability(worker_id, Availability::Active).await
+    }
+
+    async fn set_availability(&self, worker_id: &str, availability: Availability) -> bool {
+        let mut workers = self.workers.write().await;
+        match workers.iter_mut().find(|w| w.id == worker_id) {
+            Some(worker) => {
+                info!("Worker {} is now {:?}", worker.name, availability);
+                worker.availability = availability;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Cordon a worker and, with `DrainMode::Migrate`, requeue its running tasks.
+    // Returns the requeued tasks, or None if the worker is unknown.
+    // Cordon worker dan, dengan `DrainMode::Migrate`, kembalikan tugas yang
+    // berjalan ke antrian. Mengembalikan tugas tersebut, atau None jika worker
+    // tidak dikenal.
+    pub async fn drain(&self, worker_id: &str, mode: DrainMode) -> Option<Vec<Task>> {
+        if !self.set_availability(worker_id, Availability::Draining).await {
+            return None;
+        }
+        if mode == DrainMode::Wait {
+            return Some(Vec::new());
+        }
+
+        let task_ids: Vec<String> = self
+            .in_flight
+            .read()
+            .await
+            .values()
+            .filter(|r| r.worker_id == worker_id)
+            .map(|r| r.task.id.clone())
+            .collect();
+        let mut migrated = Vec::new();
+        for task_id in task_ids {
+            if let Some((task, _)) = self.take_back(&task_id).await {
+                self.push_front(task.clone()).await;
+                migrated.push(task);
+            }
+        }
+        info!("Drain of worker {} migrated {} tasks", worker_id, migrated.len());
+        Some(migrated)
+    }
+
+    // True once a draining worker has no running tasks left
+    // True setelah worker yang di-drain tidak lagi memiliki tugas berjalan
+    pub async fn is_drained(&self, worker_id: &str) -> bool {
+        self.workers
+            .read()
+            .await
+            .iter()
+            .find(|w| w.id == worker_id)
+            .map(|w| w.availability == Availability::Draining && w.current_jobs == 0)
+            .unwrap_or(false)
+    }
+
+    // Remove a worker that left and requeue whatever it still held
+    // Hapus worker yang keluar dan kembalikan tugas yang masih dipegangnya ke antrian
+    pub async fn remove_worker(&self, worker_id: &str) -> Vec<Task> {
+        let task_ids: Vec<String> = self
+            .in_flight
+            .read()
+            .await
+            .values()
+            .filter(|r| r.worker_id == worker_id)
+            .map(|r| r.task.id.clone())
+            .collect();
+        let mut requeued = Vec::new();
+        for task_id in task_ids {
+            if let Some((task, _)) = self.take_back(&task_id).await {
+                self.push_front(task.clone()).await;
+                requeued.push(task);
+            }
+        }
+        self.workers.write().await.retain(|w| w.id != worker_id);
+        info!("Removed worker {} ({} tasks requeued)", worker_id, requeued.len());
+        self.emit(Event::WorkerLeft {
+            worker_id: worker_id.to_string(),
+            reason: "left".to_string(),
+        });
+        requeued
+    }
+
+    // Tasks currently holding resources on workers
+    // Tugas yang saat ini menahan resource pada worker
+    pub async fn reservations(&self) -> Vec<Reservation> {
+        self.in_flight.read().await.values().cloned().collect()
+    }
+
+    // Current assignment of one task, if it is on a worker
+    // Penugasan saat ini dari satu tugas, jika sedang berada di worker
+    pub async fn reservation(&self, task_id: &str) -> Option<Reservation> {
+        self.in_flight.read().await.get(task_id).cloned()
+    }
+
+    // Charge a finished task's run time to its owner's fair-share usage
+    // Bebankan waktu jalan tugas yang selesai ke pemakaian fair-share pemiliknya
+    pub async fn record_usage(&self, task_id: &str, duration_ms: u64) {
+        self.queue.write().await.charge(task_id, duration_ms);
+    }
+
+    // Current share and decayed usage per owner
+    // Bagian saat ini dan pemakaian yang meluruh per pemilik
+    pub async fn fair_share_stats(&self) -> Vec<OwnerShare> {
+        self.queue.read().await.stats()
+    }
+
     // Find first idle worker ready to accept tasks
     // Temukan worker menganggur pertama yang siap menerima tugas
     pub async fn get_idle_worker(&self) -> Option<WorkerInfo> {
         let workers = self.workers.read().await;
         workers
             .iter()
-            .find(|w| w.is_idle())
+            .find(|w| w.is_schedulable() && w.is_idle())
             .cloned()
     }
 
@@ -81,7 +633,17 @@ impl Scheduler {
     // Dapatkan semua worker di bawah kapasitas pekerjaan
     pub async fn get_idle_workers(&self) -> Vec<WorkerInfo> {
         let workers = self.workers.read().await;
-        workers.iter().filter(|w| w.is_idle()).cloned().collect()
+        workers
+            .iter()
+            .filter(|w| w.is_schedulable() && w.is_idle())
+            .cloned()
+            .collect()
+    }
+
+    // Whether a worker is currently registered
+    // Apakah worker saat ini terdaftar
+    pub async fn has_worker(&self, worker_id: &str) -> bool {
+        self.workers.read().await.iter().any(|w| w.id == worker_id)
     }
 
     // Retrieve list of all registered workers
@@ -90,43 +652,297 @@ impl Scheduler {
         self.workers.read().await.clone()
     }
 
-    // Remove inactive workers based on heartbeat timeout
-    // Hapus worker tidak aktif berdasarkan timeout detak jantung
-    pub async fn cleanup_offline_workers(&self, heartbeat_timeout_secs: i64) {
+    // Remove inactive workers based on heartbeat timeout; their tasks are
+    // requeued or marked lost right away. Returns the removed workers too.
+    // Hapus worker tidak aktif berdasarkan timeout detak jantung; tugasnya
+    // langsung dimasukkan ulang atau ditandai hilang. Juga mengembalikan worker yang dihapus.
+    pub async fn cleanup_offline_workers(&self, heartbeat_timeout_secs: i64) -> (Vec<String>, Vec<LeaseExpiry>) {
         let now = chrono::Local::now().timestamp();
-        let mut workers = self.workers.write().await;
-        
-        let initial_count = workers.len();
-        workers.retain(|w| (now - w.last_heartbeat) < heartbeat_timeout_secs);
-        
-        let removed = initial_count - workers.len();
-        if removed > 0 {
-            info!("Removed {} offline workers", removed);
+        let offline: Vec<String> = {
+            let mut workers = self.workers.write().await;
+            let offline = workers
+                .iter()
+                .filter(|w| (now - w.last_heartbeat) >= heartbeat_timeout_secs)
+                .map(|w| w.id.clone())
+                .collect::<Vec<_>>();
+            workers.retain(|w| !offline.contains(&w.id));
+            offline
+        };
+        if offline.is_empty() {
+            return (offline, Vec::new());
+        }
+        info!("Removed {} offline workers", offline.len());
+        for worker_id in &offline {
+            self.emit(Event::WorkerLeft {
+                worker_id: worker_id.clone(),
+                reason: "heartbeat timeout".to_string(),
+            });
+        }
+
+        let task_ids: Vec<String> = self
+            .in_flight
+            .read()
+            .await
+            .values()
+            .filter(|r| offline.contains(&r.worker_id))
+            .map(|r| r.task.id.clone())
+            .collect();
+        let mut expired = Vec::new();
+        for task_id in task_ids {
+            if let Some(expiry) = self.expire(&task_id).await {
+                expired.push(expiry);
+            }
+        }
+        (offline, expired)
+    }
+
+    // Extend the leases of every task assigned to a worker (on heartbeat)
+    // Perpanjang lease semua tugas yang ditugaskan ke worker (saat heartbeat)
+    pub async fn renew_leases(&self, worker_id: &str) {
+        let deadline = chrono::Local::now().timestamp() + self.lease_secs;
+        for reservation in self.in_flight.write().await.values_mut() {
+            if reservation.worker_id == worker_id {
+                reservation.lease_expires_at = deadline;
+            }
+        }
+    }
+
+    // Requeue or mark lost every task whose lease ran out
+    // Masukkan ulang atau tandai hilang setiap tugas yang lease-nya habis
+    pub async fn expire_leases(&self) -> Vec<LeaseExpiry> {
+        self.expire_leases_at(chrono::Local::now().timestamp()).await
+    }
+
+    async fn expire_leases_at(&self, now: i64) -> Vec<LeaseExpiry> {
+        let task_ids: Vec<String> = self
+            .in_flight
+            .read()
+            .await
+            .values()
+            .filter(|r| r.lease_expires_at <= now)
+            .map(|r| r.task.id.clone())
+            .collect();
+        let mut expired = Vec::new();
+        for task_id in task_ids {
+            if let Some(expiry) = self.expire(&task_id).await {
+                expired.push(expiry);
+            }
+        }
+        expired
+    }
+
+    // Apply the retry policy to one in-flight task whose worker is gone
+    // Terapkan kebijakan pengulangan pada satu tugas yang worker-nya hilang
+    async fn expire(&self, task_id: &str) -> Option<LeaseExpiry> {
+        let (task, worker_id) = self.take_back(task_id).await?;
+        let requeued = task.attempts <= task.max_retries;
+        if requeued {
+            warn!(
+                "Lease of task {} on worker {} expired, requeueing (attempt {} of {})",
+                task.id,
+                worker_id,
+                task.attempts,
+                task.max_retries + 1
+            );
+            self.push_front(task.clone()).await;
+        } else {
+            warn!("Lease of task {} on worker {} expired, task lost", task.id, worker_id);
+            self.queue.write().await.forget(&task.id);
+            self.lost.write().await.insert(task.id.clone());
         }
+        Some(LeaseExpiry {
+            task,
+            worker_id,
+            requeued,
+        })
     }
 
-    // Schedule next task using FIFO algorithm
-    // Jadwalkan tugas berikutnya menggunakan algoritma FIFO
+    // Retry a task that could not be sent to its worker, or give it up as lost
+    // once its retries are used up; the failed delivery counts as an attempt
+    // Ulangi tugas yang tidak dapat dikirim ke worker-nya, atau anggap hilang
+    // setelah batas pengulangannya habis; pengiriman yang gagal dihitung sebagai percobaan
+    pub async fn delivery_failed(&self, task_id: &str) -> Option<LeaseExpiry> {
+        let worker_id = self.in_flight.read().await.get(task_id)?.worker_id.clone();
+        let task = self.release_task(task_id).await?;
+        let requeued = task.attempts <= task.max_retries;
+        if requeued {
+            warn!(
+                "Could not deliver task {} to worker {}, requeueing (attempt {} of {})",
+                task.id,
+                worker_id,
+                task.attempts,
+                task.max_retries + 1
+            );
+            self.push_front(task.clone()).await;
+        } else {
+            warn!("Could not deliver task {} to worker {}, task lost", task.id, worker_id);
+            self.queue.write().await.forget(&task.id);
+        }
+        Some(LeaseExpiry {
+            task,
+            worker_id,
+            requeued,
+        })
+    }
+
+    // Match a result reported by a worker against the current assignment and
+    // release the assignment if the result is usable
+    // Cocokkan hasil yang dilaporkan worker dengan penugasan saat ini dan
+    // lepaskan penugasan jika hasilnya dapat dipakai
+    pub async fn accept_result(&self, result: &TaskResult) -> ResultDisposition {
+        let current = self
+            .in_flight
+            .read()
+            .await
+            .get(&result.task_id)
+            .map(|r| r.worker_id.clone());
+        match current {
+            Some(worker_id) if worker_id == result.worker_id => {
+                self.release_task(&result.task_id).await;
+                self.holders.write().await.remove(&result.task_id);
+                ResultDisposition::Accepted
+            }
+            Some(_) => ResultDisposition::Stale,
+            None => {
+                // Only a worker that held the task may finish it from the queue
+                // Hanya worker yang pernah memegang tugas yang boleh menyelesaikannya dari antrian
+                let mut holders = self.holders.write().await;
+                let held = holders
+                    .get(&result.task_id)
+                    .is_some_and(|workers| workers.contains(&result.worker_id));
+                if held
+                    && (self.queue.write().await.remove(&result.task_id).is_some()
+                        || self.lost.write().await.remove(&result.task_id))
+                {
+                    holders.remove(&result.task_id);
+                    ResultDisposition::Reconciled
+                } else {
+                    ResultDisposition::Untracked
+                }
+            }
+        }
+    }
+
+    // Schedule next task using weighted fair share across owners
+    // Jadwalkan tugas berikutnya menggunakan fair share berbobot antar pemilik
     pub async fn schedule_next_task(&self) -> Option<(Task, WorkerInfo)> {
-        // Get next task from queue
-        // Dapatkan tugas berikutnya dari antrian
-        if let Some(task) = self.dequeue().await {
-            // Find an idle worker
-            // Temukan worker menganggur
-            if let Some(mut worker) = self.get_idle_worker().await {
-                worker.current_jobs += 1;
-                self.update_worker(&worker.id, worker.clone()).await;
-                info!("Scheduled task {} to worker {}", task.id, worker.name);
-                return Some((task, worker));
-            } else {
-                // Re-queue the task if no worker available
-                // Masukkan kembali tugas jika tidak ada worker tersedia
-                self.enqueue(task).await;
+        self.schedule_batch(1).await.into_iter().next()
+    }
+
+    // Take up to `max` tasks in fair-share order and let the policy place them
+    // Ambil hingga `max` tugas sesuai urutan fair-share dan biarkan kebijakan menempatkannya
+    pub async fn schedule_batch(&self, max: usize) -> Vec<(Task, WorkerInfo)> {
+        let mut workers = self.workers.write().await;
+        let free_slots: usize = workers
+            .iter()
+            .filter(|w| w.is_schedulable())
+            .map(|w| w.max_jobs.saturating_sub(w.current_jobs))
+            .sum();
+
+        // Only take tasks off the queue once a worker is available, so
+        // waiting tasks keep their place in their owner's queue
+        // Hanya ambil tugas dari antrian saat worker tersedia, agar tugas
+        // yang menunggu tetap di posisinya dalam antrian pemiliknya
+        if free_slots == 0 {
+            return Vec::new();
+        }
+
+        let mut queue = self.queue.write().await;
+        let mut batch = Vec::new();
+        while batch.len() < max.min(free_slots) {
+            match queue.pop() {
+                Some(task) => batch.push(task),
+                None => break,
+            }
+        }
+        if batch.is_empty() {
+            return Vec::new();
+        }
+
+        let assignments = self.policy.write().await.assign(&batch, &workers);
+
+        let mut placed: Vec<Option<String>> = vec![None; batch.len()];
+        for assignment in assignments {
+            if let Some(slot) = placed.get_mut(assignment.task_index) {
+                *slot = Some(assignment.worker_id);
+            }
+        }
+
+        let mut in_flight = self.in_flight.write().await;
+        let now = chrono::Local::now().timestamp();
+        let mut scheduled = Vec::new();
+        let mut unplaced = Vec::new();
+        for (task, worker_id) in batch.into_iter().zip(placed) {
+            let worker = worker_id.and_then(|id| workers.iter_mut().find(|w| w.id == id));
+            match worker {
+                Some(worker) => {
+                    let mut task = task;
+                    task.attempts += 1;
+                    worker.reserve(&task);
+                    info!("Scheduled task {} to worker {}", task.id, worker.name);
+                    in_flight.insert(
+                        task.id.clone(),
+                        Reservation {
+                            task: task.clone(),
+                            worker_id: worker.id.clone(),
+                            assigned_at: now,
+                            lease_expires_at: now + self.lease_secs,
+                        },
+                    );
+                    scheduled.push((task, worker.clone()));
+                }
+                None => unplaced.push(task),
             }
         }
 
-        None
+        // Put unplaced tasks back in their original order
+        // Kembalikan tugas yang tidak ditempatkan sesuai urutan aslinya
+        for task in unplaced.into_iter().rev() {
+            queue.push_front(task);
+        }
+        drop((queue, in_flight, workers));
+
+        let assigned: Vec<Transition> = scheduled
+            .iter()
+            .map(|(task, worker)| Transition::Assigned(task, worker.id.as_str()))
+            .collect();
+        self.persist(&assigned).await;
+        let mut submitted_at = self.submitted_at.lock().unwrap();
+        for (task, worker) in &scheduled {
+            if let Some(at) = submitted_at.remove(&task.id) {
+                metrics::global().schedule_latency.observe(at.elapsed().as_secs_f64());
+            }
+            self.emit(Event::TaskAssigned {
+                task_id: task.id.clone(),
+                worker_id: worker.id.clone(),
+            });
+        }
+
+        scheduled
+    }
+}
+
+// Reject tasks that no registered worker could ever run
+// Tolak tugas yang tidak akan pernah bisa dijalankan worker terdaftar mana pun
+fn check_placeable(workers: &[WorkerInfo], task: &Task) -> anyhow::Result<()> {
+    if workers.is_empty() || workers.iter().any(|w| w.could_ever_fit(task)) {
+        return Ok(());
     }
+    if !workers.iter().any(|w| task.placement.selects(&w.labels)) {
+        anyhow::bail!(
+            "no worker matches the node selector of task {} ({:?})",
+            task.id,
+            task.placement.node_selector
+        );
+    }
+    anyhow::bail!(
+        "no worker can satisfy the resource request of task {} ({} mCPU, {} MB memory, {} MB disk, {} GPU)",
+        task.id,
+        task.resources.cpu_millicores,
+        task.resources.memory_mb,
+        task.resources.disk_mb,
+        task.resources.gpus
+    )
 }
 
 impl Default for Scheduler {
@@ -154,4 +970,459 @@ mod tests {
         assert!(dequeued.is_some());
         assert_eq!(dequeued.unwrap().id, task_id);
     }
+
+    #[tokio::test]
+    async fn test_schedule_keeps_task_queued_without_workers() {
+        let scheduler = Scheduler::new();
+        scheduler.enqueue(Task::new("echo hello".to_string())).await;
+
+        assert!(scheduler.schedule_next_task().await.is_none());
+        assert_eq!(scheduler.queue_size().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_schedule_batch_uses_policy() {
+        let scheduler = Scheduler::new();
+        scheduler.set_policy(PolicyKind::LeastLoaded.build()).await;
+        let first = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 4);
+        let second = WorkerInfo::new("w2".to_string(), "127.0.0.1".to_string(), 9002, 4);
+        scheduler.register_worker(first).await;
+        scheduler.register_worker(second).await;
+        scheduler.enqueue(Task::new("echo 1".to_string())).await;
+        scheduler.enqueue(Task::new("echo 2".to_string())).await;
+
+        let scheduled = scheduler.schedule_batch(10).await;
+        assert_eq!(scheduled.len(), 2);
+        assert_ne!(scheduled[0].1.id, scheduled[1].1.id);
+        assert_eq!(scheduler.queue_size().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_schedule_respects_free_resources() {
+        let scheduler = Scheduler::new();
+        let mut worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 4);
+        worker.capacity.memory_mb = 1024;
+        scheduler.register_worker(worker).await;
+
+        for _ in 0..2 {
+            let mut task = Task::new("echo big".to_string());
+            task.resources.memory_mb = 768;
+            scheduler.submit(task).await.unwrap();
+        }
+
+        let scheduled = scheduler.schedule_batch(10).await;
+        assert_eq!(scheduled.len(), 1);
+        assert_eq!(scheduler.queue_size().await, 1);
+        assert_eq!(scheduler.get_workers().await[0].allocated.memory_mb, 768);
+
+        // Completing the first task frees memory for the second
+        let released = scheduler.release_task(&scheduled[0].0.id).await;
+        assert!(released.is_some());
+        assert_eq!(scheduler.get_workers().await[0].allocated.memory_mb, 0);
+        assert_eq!(scheduler.schedule_batch(10).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_submit_rejects_unsatisfiable_task() {
+        let scheduler = Scheduler::new();
+        let mut worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 4);
+        worker.capacity.gpus = 0;
+        scheduler.register_worker(worker).await;
+
+        let mut task = Task::new("train".to_string());
+        task.resources.gpus = 1;
+        assert!(scheduler.submit(task).await.is_err());
+        assert_eq!(scheduler.queue_size().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_anti_affinity_spreads_group_across_hosts() {
+        let scheduler = Scheduler::new();
+        let first = WorkerInfo::new("w1".to_string(), "10.0.0.1".to_string(), 9001, 4);
+        let second = WorkerInfo::new("w2".to_string(), "10.0.0.2".to_string(), 9002, 4);
+        scheduler.register_worker(first).await;
+        scheduler.register_worker(second).await;
+
+        for shard in 0..3 {
+            let mut task = Task::new(format!("shard {}", shard));
+            task.placement.anti_affinity = Some("job-x".to_string());
+            scheduler.submit(task).await.unwrap();
+        }
+
+        let scheduled = scheduler.schedule_batch(10).await;
+        assert_eq!(scheduled.len(), 2);
+        assert_ne!(scheduled[0].1.address, scheduled[1].1.address);
+        assert_eq!(scheduler.queue_size().await, 1);
+
+        scheduler.release_task(&scheduled[0].0.id).await;
+        let next = scheduler.schedule_batch(10).await;
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].1.id, scheduled[0].1.id);
+    }
+
+    #[tokio::test]
+    async fn test_submit_rejects_unmatched_selector() {
+        let scheduler = Scheduler::new();
+        scheduler
+            .register_worker(WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 4))
+            .await;
+
+        let mut task = Task::new("nvcc".to_string());
+        task.placement.node_selector.insert("cuda".to_string(), "12".to_string());
+        assert!(scheduler.submit(task).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cordoned_worker_gets_no_tasks() {
+        let scheduler = Scheduler::new();
+        let worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 4);
+        let worker_id = worker.id.clone();
+        scheduler.register_worker(worker).await;
+        scheduler.enqueue(Task::new("echo hello".to_string())).await;
+
+        assert!(scheduler.cordon(&worker_id).await);
+        assert!(scheduler.schedule_batch(10).await.is_empty());
+        assert_eq!(scheduler.queue_size().await, 1);
+
+        assert!(scheduler.uncordon(&worker_id).await);
+        assert_eq!(scheduler.schedule_batch(10).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_max_workers_refuses_new_registrations() {
+        let scheduler = Scheduler::new().with_max_workers(1);
+        let first = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 4);
+        assert!(scheduler.register_worker(first.clone()).await);
+        assert!(!scheduler
+            .register_worker(WorkerInfo::new("w2".to_string(), "127.0.0.1".to_string(), 9002, 4))
+            .await);
+        // Re-announcing a known worker is not a new registration
+        assert!(scheduler.register_worker(first).await);
+        assert_eq!(scheduler.get_workers().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_drain_wait_and_migrate() {
+        let scheduler = Scheduler::new();
+        let worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 4);
+        let worker_id = worker.id.clone();
+        scheduler.register_worker(worker).await;
+        scheduler.enqueue(Task::new("sleep 60".to_string())).await;
+        let scheduled = scheduler.schedule_batch(10).await;
+        assert_eq!(scheduled.len(), 1);
+
+        // Waiting drain keeps the task running but places nothing new
+        let kept = scheduler.drain(&worker_id, DrainMode::Wait).await.unwrap();
+        assert!(kept.is_empty());
+        assert!(!scheduler.is_drained(&worker_id).await);
+        scheduler.enqueue(Task::new("echo later".to_string())).await;
+        assert!(scheduler.schedule_batch(10).await.is_empty());
+
+        // Migrating drain hands the running task back to the queue
+        let migrated = scheduler.drain(&worker_id, DrainMode::Migrate).await.unwrap();
+        assert_eq!(migrated.len(), 1);
+        assert_eq!(migrated[0].id, scheduled[0].0.id);
+        assert!(scheduler.is_drained(&worker_id).await);
+        assert_eq!(scheduler.queue_size().await, 2);
+        assert!(scheduler.drain("missing", DrainMode::Wait).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_only_the_holder_returns_a_task() {
+        let scheduler = Scheduler::new();
+        let worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 1);
+        let worker_id = worker.id.clone();
+        scheduler.register_worker(worker).await;
+        scheduler.enqueue(Task::new("sleep 60".to_string())).await;
+        let task_id = scheduler.schedule_batch(10).await[0].0.id.clone();
+
+        // Another worker cannot release the reservation
+        assert!(!scheduler.requeue_task(&task_id, "intruder").await);
+        assert_eq!(scheduler.reservations().await.len(), 1);
+        assert_eq!(scheduler.get_workers().await[0].current_jobs, 1);
+
+        assert!(scheduler.requeue_task(&task_id, &worker_id).await);
+        assert_eq!(scheduler.queue_size().await, 1);
+        assert_eq!(scheduler.get_workers().await[0].current_jobs, 0);
+        assert!(!scheduler.requeue_task(&task_id, &worker_id).await);
+    }
+
+    #[tokio::test]
+    async fn test_task_that_cannot_start_is_not_requeued() {
+        let scheduler = Scheduler::new();
+        let worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 1);
+        let worker_id = worker.id.clone();
+        scheduler.register_worker(worker).await;
+        scheduler.enqueue(Task::new("echo hello".to_string())).await;
+        let task_id = scheduler.schedule_batch(10).await[0].0.id.clone();
+
+        // The worker reports the start failure as a result, freeing the slot
+        let result = TaskResult::failed(&task_id, &worker_id, "Shell execution is not allowed".to_string());
+        assert_eq!(scheduler.accept_result(&result).await, ResultDisposition::Accepted);
+        assert_eq!(scheduler.queue_size().await, 0);
+        assert!(scheduler.schedule_batch(10).await.is_empty());
+        assert_eq!(scheduler.get_workers().await[0].current_jobs, 0);
+    }
+
+    #[tokio::test]
+    async fn test_recover_queue_and_assignments_after_restart() {
+        let store: Arc<dyn TaskStore> = Arc::new(crate::persistence::MemoryStore::new());
+        let worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 1);
+        let worker_id = worker.id.clone();
+
+        let before = Scheduler::new().with_store(store.clone());
+        before.register_worker(worker.clone()).await;
+        before.enqueue(Task::new("sleep 60".to_string())).await;
+        before.enqueue(Task::new("echo waiting".to_string())).await;
+        let scheduled = before.schedule_batch(10).await;
+        assert_eq!(scheduled.len(), 1);
+        before.mark_running(&scheduled[0].0.id).await;
+
+        // A fresh dispatcher sees the queued task and the running assignment
+        let after = Scheduler::new().with_store(store);
+        let recovery = after.recover().await.unwrap();
+        assert_eq!(recovery, Recovery { queued: 1, in_flight: 1 });
+        assert_eq!(after.queue_size().await, 1);
+
+        // The worker re-registers and its slot is taken by the resumed task
+        after.register_worker(worker).await;
+        assert_eq!(after.get_workers().await[0].current_jobs, 1);
+        assert!(after.schedule_batch(10).await.is_empty());
+        assert_eq!(
+            after.accept_result(&result_for(&scheduled[0].0.id, &worker_id)).await,
+            ResultDisposition::Accepted
+        );
+        assert_eq!(after.get_workers().await[0].current_jobs, 0);
+    }
+
+    #[tokio::test]
+    async fn test_batch_is_all_or_nothing_and_arrays_cancel() {
+        let scheduler = Scheduler::new();
+        let worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 1);
+        let worker_id = worker.id.clone();
+        scheduler.register_worker(worker).await;
+
+        let mut unplaceable = Task::new("echo gpu".to_string());
+        unplaceable.placement.node_selector.insert("gpu".to_string(), "a100".to_string());
+        let batch = vec![Task::new("echo ok".to_string()), unplaceable];
+        assert!(scheduler.submit_batch(batch).await.is_err());
+        assert_eq!(scheduler.queue_size().await, 0);
+
+        let array: Vec<Task> = (0..3)
+            .map(|i| Task {
+                parent_id: Some("array-1".to_string()),
+                ..Task::new(format!("echo {}", i))
+            })
+            .collect();
+        scheduler.submit_batch(array).await.unwrap();
+        scheduler.submit(Task::new("echo other".to_string())).await.unwrap();
+        assert_eq!(scheduler.schedule_batch(1).await.len(), 1);
+
+        // One array task is running, two are queued; the unrelated task stays
+        let cancelled = scheduler.cancel_array("array-1").await;
+        assert_eq!(cancelled.len(), 3);
+        assert_eq!(
+            cancelled.iter().filter(|c| c.worker_id.as_deref() == Some(worker_id.as_str())).count(),
+            1
+        );
+        assert_eq!(scheduler.queue_size().await, 1);
+        assert_eq!(scheduler.get_workers().await[0].current_jobs, 0);
+        assert!(scheduler.cancel(&cancelled[0].task.id).await.is_none());
+    }
+
+    fn result_for(task_id: &str, worker_id: &str) -> TaskResult {
+        TaskResult {
+            task_id: task_id.to_string(),
+            worker_id: worker_id.to_string(),
+            status: crate::protocol::TaskStatus::Completed,
+            stdout: String::new(),
+            stderr: String::new(),
+            exit_code: Some(0),
+            duration_ms: 10,
+            completed_at: 0,
+            signer: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_events_are_published() {
+        let events = Arc::new(EventBus::new());
+        let scheduler = Scheduler::new().with_events(events.clone());
+        let (_, mut receiver) = events.subscribe_from(None);
+        let worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 1);
+        let worker_id = worker.id.clone();
+        scheduler.register_worker(worker.clone()).await;
+        scheduler.register_worker(worker).await;
+        scheduler.submit(Task::new("echo 1".to_string())).await.unwrap();
+        scheduler.schedule_batch(1).await;
+        scheduler.remove_worker(&worker_id).await;
+
+        let mut kinds = Vec::new();
+        while let Ok(record) = receiver.try_recv() {
+            kinds.push(record.event.kind());
+        }
+        assert_eq!(kinds, vec!["worker_joined", "task_submitted", "task_assigned", "worker_left"]);
+    }
+
+    #[tokio::test]
+    async fn test_expired_lease_requeues_then_loses_task() {
+        let scheduler = Scheduler::new().with_lease_timeout(30);
+        scheduler
+            .register_worker(WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 4))
+            .await;
+        let mut task = Task::new("echo hello".to_string());
+        task.max_retries = 1;
+        scheduler.enqueue(task).await;
+        let far_future = chrono::Local::now().timestamp() + 3600;
+
+        // First loss: one retry left, so the task goes back to the queue
+        scheduler.schedule_batch(10).await;
+        let expired = scheduler.expire_leases_at(far_future).await;
+        assert_eq!(expired.len(), 1);
+        assert!(expired[0].requeued);
+        assert_eq!(scheduler.queue_size().await, 1);
+
+        // Second loss: retries exhausted
+        scheduler.schedule_batch(10).await;
+        let expired = scheduler.expire_leases_at(far_future).await;
+        assert!(!expired[0].requeued);
+        assert_eq!(expired[0].task.attempts, 2);
+        assert_eq!(scheduler.queue_size().await, 0);
+        assert_eq!(scheduler.get_workers().await[0].current_jobs, 0);
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_renews_lease() {
+        let scheduler = Scheduler::new().with_lease_timeout(30);
+        let worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 4);
+        let worker_id = worker.id.clone();
+        scheduler.register_worker(worker).await;
+        scheduler.enqueue(Task::new("echo hello".to_string())).await;
+        scheduler.schedule_batch(10).await;
+
+        let now = chrono::Local::now().timestamp();
+        assert!(scheduler.expire_leases_at(now + 10).await.is_empty());
+        scheduler.renew_leases(&worker_id).await;
+        let deadline = scheduler.reservations().await[0].lease_expires_at;
+        assert!(deadline >= now + 30);
+
+        // The heartbeat leaves the reservation's slot and resources in place
+        assert!(scheduler.touch_heartbeat(&worker_id, now + 5).await);
+        let worker = &scheduler.get_workers().await[0];
+        assert_eq!(worker.last_heartbeat, now + 5);
+        assert_eq!(worker.current_jobs, 1);
+        assert_eq!(worker.allocated.cpu_millicores, Task::new(String::new()).resources.cpu_millicores);
+        assert!(!scheduler.touch_heartbeat("missing", now).await);
+    }
+
+    #[tokio::test]
+    async fn test_late_results_are_reconciled() {
+        let scheduler = Scheduler::new();
+        let first = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 1);
+        let second = WorkerInfo::new("w2".to_string(), "127.0.0.2".to_string(), 9002, 1);
+        let (first_id, second_id) = (first.id.clone(), second.id.clone());
+        scheduler.register_worker(first).await;
+        scheduler.enqueue(Task::new("echo hello".to_string())).await;
+        let task_id = scheduler.schedule_batch(10).await[0].0.id.clone();
+        let far_future = chrono::Local::now().timestamp() + 3600;
+
+        // Presumed dead, then the late result arrives before a retry: use it
+        scheduler.expire_leases_at(far_future).await;
+        assert_eq!(
+            scheduler.accept_result(&result_for(&task_id, &first_id)).await,
+            ResultDisposition::Reconciled
+        );
+        assert_eq!(scheduler.queue_size().await, 0);
+        assert_eq!(
+            scheduler.accept_result(&result_for(&task_id, &first_id)).await,
+            ResultDisposition::Untracked
+        );
+
+        // Presumed dead and already rerunning elsewhere: ignore the late result
+        scheduler.enqueue(Task::new("echo again".to_string())).await;
+        let task_id = scheduler.schedule_batch(10).await[0].0.id.clone();
+        scheduler.expire_leases_at(far_future).await;
+        scheduler.cordon(&first_id).await;
+        scheduler.register_worker(second).await;
+        assert_eq!(scheduler.schedule_batch(10).await[0].1.id, second_id);
+        assert_eq!(
+            scheduler.accept_result(&result_for(&task_id, &first_id)).await,
+            ResultDisposition::Stale
+        );
+        assert_eq!(
+            scheduler.accept_result(&result_for(&task_id, &second_id)).await,
+            ResultDisposition::Accepted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_deliveries_use_up_retries() {
+        let scheduler = Scheduler::new();
+        scheduler
+            .register_worker(WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 2))
+            .await;
+        let mut task = Task::new("echo hello".to_string());
+        task.max_retries = 1;
+        let task_id = task.id.clone();
+        scheduler.enqueue(task).await;
+        scheduler.enqueue(Task::new("echo later".to_string())).await;
+
+        // Back at the front of the queue, with the attempt counted
+        scheduler.schedule_batch(1).await;
+        let expiry = scheduler.delivery_failed(&task_id).await.unwrap();
+        assert!(expiry.requeued);
+        assert_eq!(scheduler.get_workers().await[0].current_jobs, 0);
+        let (retried, _) = scheduler.schedule_batch(1).await.remove(0);
+        assert_eq!((retried.id.as_str(), retried.attempts), (task_id.as_str(), 2));
+
+        let expiry = scheduler.delivery_failed(&task_id).await.unwrap();
+        assert!(!expiry.requeued);
+        assert_eq!(scheduler.queue_size().await, 1);
+        assert!(scheduler.delivery_failed(&task_id).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_results_from_strangers_are_refused() {
+        let scheduler = Scheduler::new();
+        let worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 1);
+        let worker_id = worker.id.clone();
+        scheduler.register_worker(worker).await;
+
+        // Queued and never leased: nobody may complete it
+        let queued = Task::new("echo queued".to_string());
+        let queued_id = queued.id.clone();
+        scheduler.enqueue(queued).await;
+        assert_eq!(
+            scheduler.accept_result(&result_for(&queued_id, "stranger")).await,
+            ResultDisposition::Untracked
+        );
+        assert_eq!(scheduler.queue_size().await, 1);
+
+        // Requeued after its lease expired: only the former holder is reconciled
+        let task_id = scheduler.schedule_batch(10).await[0].0.id.clone();
+        scheduler.expire_leases_at(chrono::Local::now().timestamp() + 3600).await;
+        assert_eq!(
+            scheduler.accept_result(&result_for(&task_id, "stranger")).await,
+            ResultDisposition::Untracked
+        );
+        assert_eq!(scheduler.queue_size().await, 1);
+        assert_eq!(
+            scheduler.accept_result(&result_for(&task_id, &worker_id)).await,
+            ResultDisposition::Reconciled
+        );
+        assert_eq!(scheduler.queue_size().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_fair_share_stats_report_owners() {
+        let scheduler = Scheduler::new();
+        let mut task = Task::new("echo hello".to_string());
+        task.owner = "team-a".to_string();
+        scheduler.enqueue(task).await;
+
+        let stats = scheduler.fair_share_stats().await;
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].owner, "team-a");
+        assert_eq!(stats[0].queued, 1);
+    }
 }
@@ -0,0 +1,437 @@
+// Pluggable worker selection policies for the scheduler
+// Kebijakan pemilihan worker yang dapat diganti untuk penjadwal
+//
+// A policy receives the batch of pending tasks together with a snapshot of the
+// workers and returns task-to-worker assignments. Policies never mutate the real
+// scheduler state, which keeps them easy to test against a simulated cluster.
+//
+// Kebijakan menerima kumpulan tugas tertunda beserta cuplikan worker dan
+// mengembalikan penugasan tugas ke worker. Kebijakan tidak pernah mengubah status
+// penjadwal yang sebenarnya, sehingga mudah diuji dengan cluster simulasi.
+
+use crate::protocol::{Task, WorkerInfo};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+// Placement decision for one pending task
+// Keputusan penempatan untuk satu tugas tertunda
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    /// Index of the task in the batch given to the policy
+    pub task_index: usize,
+    /// Worker chosen for the task
+    pub worker_id: String,
+}
+
+// Mutable view of the workers used while a batch is being placed
+// Tampilan worker yang dapat diubah selama satu batch ditempatkan
+#[derive(Debug, Clone)]
+pub struct ClusterSnapshot {
+    workers: Vec<WorkerInfo>,
+}
+
+impl ClusterSnapshot {
+    pub fn new(workers: Vec<WorkerInfo>) -> Self {
+        Self { workers }
+    }
+
+    pub fn workers(&self) -> &[WorkerInfo] {
+        &self.workers
+    }
+
+    // Indices of workers that satisfy the task's hard constraints right now: a
+    // free slot, enough free resources, matching labels, and no task of the
+    // same anti-affinity group on the worker's host
+    // Indeks worker yang memenuhi batasan wajib tugas saat ini: slot kosong,
+    // resource bebas cukup, label cocok, dan tidak ada tugas dari grup
+    // anti-afinitas yang sama pada host worker
+    pub fn candidates(&self, task: &Task) -> Vec<usize> {
+        self.workers
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| w.can_fit(task))
+            .filter(|(_, w)| match &task.placement.anti_affinity {
+                Some(group) => !self.host_runs_group(&w.address, group),
+                None => true,
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // Narrow candidates to those with the highest preferred-affinity score
+    // Persempit kandidat ke yang memiliki skor afinitas preferensi tertinggi
+    pub fn preferred(&self, task: &Task, candidates: &[usize]) -> Vec<usize> {
+        let score = |i: &usize| task.placement.preference_score(&self.workers[*i].labels);
+        let best = candidates.iter().map(score).max().unwrap_or(0);
+        candidates.iter().copied().filter(|i| score(i) == best).collect()
+    }
+
+    fn host_runs_group(&self, address: &str, group: &str) -> bool {
+        self.workers
+            .iter()
+            .filter(|w| w.address == address)
+            .any(|w| w.affinity_groups.iter().any(|g| g == group))
+    }
+
+    // Dominant share of the worker in use: the highest fraction over job
+    // slots, CPU and memory (0.0 - 1.0)
+    // Bagian dominan worker yang dipakai: fraksi tertinggi dari slot
+    // pekerjaan, CPU, dan memori (0.0 - 1.0)
+    pub fn load(&self, index: usize) -> f64 {
+        let worker = &self.workers[index];
+        if worker.max_jobs == 0 {
+            return 1.0;
+        }
+        let slots = worker.current_jobs as f64 / worker.max_jobs as f64;
+        let cpu = fraction(worker.allocated.cpu_millicores, worker.capacity.cpu_millicores);
+        let memory = fraction(worker.allocated.memory_mb, worker.capacity.memory_mb);
+        slots.max(cpu).max(memory)
+    }
+
+    // Average fraction of the worker's declared capacity left after placing the task
+    // Rata-rata fraksi kapasitas worker yang tersisa setelah tugas ditempatkan
+    pub fn free_after(&self, index: usize, task: &Task) -> f64 {
+        let worker = &self.workers[index];
+        if worker.max_jobs == 0 {
+            return 0.0;
+        }
+        let mut after = worker.free_resources();
+        after.subtract(&task.resources);
+
+        let mut fractions = vec![
+            worker.max_jobs.saturating_sub(worker.current_jobs + 1) as f64 / worker.max_jobs as f64,
+        ];
+        let dimensions = [
+            (after.cpu_millicores, worker.capacity.cpu_millicores),
+            (after.memory_mb, worker.capacity.memory_mb),
+            (after.disk_mb, worker.capacity.disk_mb),
+            (after.gpus as u64, worker.capacity.gpus as u64),
+        ];
+        for (free, total) in dimensions {
+            if total > 0 {
+                fractions.push(free as f64 / total as f64);
+            }
+        }
+        fractions.iter().sum::<f64>() / fractions.len() as f64
+    }
+
+    // Account for a placement so later tasks in the batch see it
+    // Catat penempatan agar tugas berikutnya dalam batch melihatnya
+    pub fn reserve(&mut self, index: usize, task: &Task) {
+        self.workers[index].reserve(task);
+    }
+}
+
+fn fraction(used: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        used as f64 / total as f64
+    }
+}
+
+// Strategy for choosing workers for pending tasks
+// Strategi untuk memilih worker bagi tugas tertunda
+pub trait SchedulingPolicy: Send + Sync {
+    // Short policy name used in logs and the CLI
+    // Nama singkat kebijakan untuk log dan CLI
+    fn name(&self) -> &'static str;
+
+    // Pick one of the candidate workers for the task
+    // Pilih salah satu worker kandidat untuk tugas
+    fn pick(&mut self, task: &Task, cluster: &ClusterSnapshot, candidates: &[usize]) -> Option<usize>;
+
+    // Assign a batch of pending tasks; tasks left out stay queued
+    // Tugaskan sekumpulan tugas tertunda; tugas yang tidak ditugaskan tetap di antrian
+    fn assign(&mut self, tasks: &[Task], workers: &[WorkerInfo]) -> Vec<Assignment> {
+        let mut cluster = ClusterSnapshot::new(workers.to_vec());
+        let mut assignments = Vec::new();
+
+        for (task_index, task) in tasks.iter().enumerate() {
+            let candidates = cluster.candidates(task);
+            if candidates.is_empty() {
+                continue;
+            }
+            let candidates = cluster.preferred(task, &candidates);
+            if let Some(index) = self.pick(task, &cluster, &candidates) {
+                cluster.reserve(index, task);
+                assignments.push(Assignment {
+                    task_index,
+                    worker_id: cluster.workers()[index].id.clone(),
+                });
+            }
+        }
+
+        assignments
+    }
+}
+
+// First worker in registration order with a free slot
+// Worker pertama sesuai urutan pendaftaran yang memiliki slot kosong
+#[derive(Debug, Default)]
+pub struct FirstFit;
+
+impl SchedulingPolicy for FirstFit {
+    fn name(&self) -> &'static str {
+        "first-fit"
+    }
+
+    fn pick(&mut self, _task: &Task, _cluster: &ClusterSnapshot, candidates: &[usize]) -> Option<usize> {
+        candidates.first().copied()
+    }
+}
+
+// Worker with the lowest relative load
+// Worker dengan beban relatif terendah
+#[derive(Debug, Default)]
+pub struct LeastLoaded;
+
+impl SchedulingPolicy for LeastLoaded {
+    fn name(&self) -> &'static str {
+        "least-loaded"
+    }
+
+    fn pick(&mut self, _task: &Task, cluster: &ClusterSnapshot, candidates: &[usize]) -> Option<usize> {
+        candidates
+            .iter()
+            .copied()
+            .min_by(|a, b| cluster.load(*a).total_cmp(&cluster.load(*b)))
+    }
+}
+
+// Cycle through workers, skipping those that cannot take the task
+// Berputar melalui worker, melewati yang tidak dapat menerima tugas
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: usize,
+}
+
+impl SchedulingPolicy for RoundRobin {
+    fn name(&self) -> &'static str {
+        "round-robin"
+    }
+
+    fn pick(&mut self, _task: &Task, cluster: &ClusterSnapshot, candidates: &[usize]) -> Option<usize> {
+        let total = cluster.workers().len();
+        if total == 0 {
+            return None;
+        }
+        let start = self.next % total;
+        let chosen = candidates
+            .iter()
+            .copied()
+            .min_by_key(|index| (index + total - start) % total)?;
+        self.next = chosen + 1;
+        Some(chosen)
+    }
+}
+
+// Best fit: the worker left with the least free capacity after placement
+// Best fit: worker dengan sisa kapasitas paling sedikit setelah penempatan
+#[derive(Debug, Default)]
+pub struct BinPacking;
+
+impl SchedulingPolicy for BinPacking {
+    fn name(&self) -> &'static str {
+        "bin-packing"
+    }
+
+    fn pick(&mut self, task: &Task, cluster: &ClusterSnapshot, candidates: &[usize]) -> Option<usize> {
+        candidates
+            .iter()
+            .copied()
+            .min_by(|a, b| {
+                cluster
+                    .free_after(*a, task)
+                    .total_cmp(&cluster.free_after(*b, task))
+            })
+    }
+}
+
+// Power of two choices: sample two candidates, keep the less loaded one
+// Kekuatan dua pilihan: ambil dua kandidat acak, pilih yang bebannya lebih rendah
+pub struct RandomOfTwo {
+    rng: StdRng,
+}
+
+impl RandomOfTwo {
+    pub fn new() -> Self {
+        Self {
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    // Deterministic variant for tests and simulations
+    // Varian deterministik untuk pengujian dan simulasi
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Default for RandomOfTwo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SchedulingPolicy for RandomOfTwo {
+    fn name(&self) -> &'static str {
+        "random-two"
+    }
+
+    fn pick(&mut self, _task: &Task, cluster: &ClusterSnapshot, candidates: &[usize]) -> Option<usize> {
+        match candidates.len() {
+            0 => None,
+            1 => Some(candidates[0]),
+            len => {
+                let first = self.rng.gen_range(0..len);
+                let mut second = self.rng.gen_range(0..len - 1);
+                if second >= first {
+                    second += 1;
+                }
+                let (a, b) = (candidates[first], candidates[second]);
+                if cluster.load(b) < cluster.load(a) {
+                    Some(b)
+                } else {
+                    Some(a)
+                }
+            }
+        }
+    }
+}
+
+// Built-in policies selectable from the CLI and configuration
+// Kebijakan bawaan yang dapat dipilih dari CLI dan konfigurasi
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PolicyKind {
+    #[default]
+    FirstFit,
+    LeastLoaded,
+    RoundRobin,
+    BinPacking,
+    RandomTwo,
+}
+
+impl PolicyKind {
+    // Instantiate the policy
+    // Buat instance kebijakan
+    pub fn build(self) -> Box<dyn SchedulingPolicy> {
+        match self {
+            PolicyKind::FirstFit => Box::new(FirstFit),
+            PolicyKind::LeastLoaded => Box::new(LeastLoaded),
+            PolicyKind::RoundRobin => Box::new(RoundRobin::default()),
+            PolicyKind::BinPacking => Box::new(BinPacking),
+            PolicyKind::RandomTwo => Box::new(RandomOfTwo::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::simulation::SimulatedCluster;
+
+    fn cluster(slots: &[usize]) -> SimulatedCluster {
+        SimulatedCluster::new(slots)
+    }
+
+    #[test]
+    fn test_first_fit_fills_first_worker() {
+        let mut sim = cluster(&[4, 4]);
+        let report = sim.run(&mut FirstFit, &[10; 3]);
+        assert_eq!(report.placements_per_worker, vec![3, 0]);
+    }
+
+    #[test]
+    fn test_least_loaded_spreads_tasks() {
+        let mut sim = cluster(&[4, 4, 4]);
+        let report = sim.run(&mut LeastLoaded, &[10; 6]);
+        assert_eq!(report.placements_per_worker, vec![2, 2, 2]);
+    }
+
+    #[test]
+    fn test_round_robin_cycles() {
+        let mut sim = cluster(&[4, 4, 4]);
+        let report = sim.run(&mut RoundRobin::default(), &[1; 4]);
+        assert_eq!(report.placement_order, vec![0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn test_bin_packing_prefers_fullest_worker() {
+        let mut sim = cluster(&[2, 8]);
+        let report = sim.run(&mut BinPacking, &[10; 3]);
+        // The small worker fills up first, the remainder goes to the big one
+        assert_eq!(report.placements_per_worker, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_bin_packing_by_declared_memory() {
+        let mut workers = SimulatedCluster::new(&[4, 4]).workers().to_vec();
+        workers[0].capacity.memory_mb = 1024;
+        workers[1].capacity.memory_mb = 4096;
+        let mut sim = SimulatedCluster::from_workers(workers);
+
+        let tasks: Vec<Task> = (0..3)
+            .map(|i| {
+                let mut task = Task::new(format!("task-{}", i));
+                task.resources.memory_mb = 512;
+                task
+            })
+            .collect();
+        let report = sim.run_tasks(&mut BinPacking, tasks, &[10; 3]);
+        // Two tasks fill the small worker's memory, the third must go elsewhere
+        assert_eq!(report.placements_per_worker, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_oversized_task_is_never_placed() {
+        let mut workers = SimulatedCluster::new(&[4]).workers().to_vec();
+        workers[0].capacity.gpus = 0;
+        let mut sim = SimulatedCluster::from_workers(workers);
+        let mut task = Task::new("train".to_string());
+        task.resources.gpus = 1;
+
+        let report = sim.run_tasks(&mut FirstFit, vec![task], &[1]);
+        assert_eq!(report.unplaced, 1);
+    }
+
+    #[test]
+    fn test_node_selector_and_preferred_affinity() {
+        let mut workers = SimulatedCluster::new(&[4, 4, 4]).workers().to_vec();
+        workers[1].labels.insert("disk".to_string(), "large".to_string());
+        workers[2].labels.insert("disk".to_string(), "large".to_string());
+        workers[2].labels.insert("zone".to_string(), "b".to_string());
+
+        let mut required = Task::new("backup".to_string());
+        required.placement.node_selector.insert("disk".to_string(), "large".to_string());
+        let mut preferred = Task::new("build".to_string());
+        preferred.placement.preferred.push(crate::protocol::LabelPreference {
+            key: "zone".to_string(),
+            value: "b".to_string(),
+            weight: 1,
+        });
+
+        let assignments = FirstFit.assign(&[required, preferred], &workers);
+        assert_eq!(assignments[0].worker_id, "sim-1");
+        assert_eq!(assignments[1].worker_id, "sim-2");
+    }
+
+    #[test]
+    fn test_random_of_two_is_deterministic_with_seed() {
+        let durations = [3, 1, 4, 1, 5, 9, 2, 6, 5, 3];
+        let first = cluster(&[2, 2, 2, 2]).run(&mut RandomOfTwo::with_seed(7), &durations);
+        let second = cluster(&[2, 2, 2, 2]).run(&mut RandomOfTwo::with_seed(7), &durations);
+        assert_eq!(first.placement_order, second.placement_order);
+        assert_eq!(first.placements_per_worker.iter().sum::<usize>(), durations.len());
+    }
+
+    #[test]
+    fn test_policy_kind_builds_named_policy() {
+        assert_eq!(PolicyKind::BinPacking.build().name(), "bin-packing");
+        assert_eq!(PolicyKind::default().build().name(), "first-fit");
+    }
+}
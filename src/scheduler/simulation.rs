@@ -0,0 +1,164 @@
+// Deterministic simulated cluster for evaluating scheduling policies
+// Cluster simulasi deterministik untuk mengevaluasi kebijakan penjadwalan
+//
+// Time advances in discrete ticks. Every task is submitted at tick 0 with a fixed
+// duration; each tick finished tasks free their slots and the policy places as
+// many pending tasks as it can. No clocks, sockets or randomness are involved
+// beyond what the policy itself uses.
+//
+// Waktu berjalan dalam tick diskrit. Semua tugas dikirim pada tick 0 dengan durasi
+// tetap; setiap tick tugas yang selesai membebaskan slotnya dan kebijakan
+// menempatkan sebanyak mungkin tugas tertunda. Tidak ada jam, soket, atau
+// keacakan selain yang digunakan kebijakan itu sendiri.
+
+use super::policy::SchedulingPolicy;
+use crate::protocol::{Resources, Task, WorkerInfo};
+use std::collections::VecDeque;
+
+// Outcome of a simulation run
+// Hasil dari satu simulasi
+#[derive(Debug, Clone, Default)]
+pub struct SimulationReport {
+    /// Number of tasks placed on each worker
+    pub placements_per_worker: Vec<usize>,
+    /// Worker index for each placement, in placement order
+    pub placement_order: Vec<usize>,
+    /// Tick at which the last task finished
+    pub makespan: u64,
+    /// Tasks that could never be placed
+    pub unplaced: usize,
+}
+
+// Simulated workers with fixed identities
+// Worker simulasi dengan identitas tetap
+pub struct SimulatedCluster {
+    workers: Vec<WorkerInfo>,
+}
+
+impl SimulatedCluster {
+    // Build a cluster with one worker per entry of `slots` (max concurrent jobs)
+    // Buat cluster dengan satu worker per entri `slots` (pekerjaan bersamaan maksimum)
+    pub fn new(slots: &[usize]) -> Self {
+        let workers = slots
+            .iter()
+            .enumerate()
+            .map(|(i, max_jobs)| Self::worker(i, *max_jobs))
+            .collect();
+        Self { workers }
+    }
+
+    // Build a cluster from prepared worker records (ids must be unique)
+    // Buat cluster dari catatan worker yang sudah disiapkan (id harus unik)
+    pub fn from_workers(workers: Vec<WorkerInfo>) -> Self {
+        Self { workers }
+    }
+
+    fn worker(index: usize, max_jobs: usize) -> WorkerInfo {
+        let mut worker = WorkerInfo::new(
+            format!("sim-worker-{}", index),
+            "127.0.0.1".to_string(),
+            9000 + index as u16,
+            max_jobs,
+        );
+        worker.id = format!("sim-{}", index);
+        worker.last_heartbeat = 0;
+        // Fixed capacity so results do not depend on the host machine
+        // Kapasitas tetap agar hasil tidak bergantung pada mesin host
+        worker.capacity = Resources {
+            cpu_millicores: 4000,
+            memory_mb: 8192,
+            ..Default::default()
+        };
+        worker
+    }
+
+    pub fn workers(&self) -> &[WorkerInfo] {
+        &self.workers
+    }
+
+    // Run tasks with the given durations (in ticks) until all finish
+    // Jalankan tugas dengan durasi yang diberikan (dalam tick) hingga semuanya selesai
+    pub fn run(&mut self, policy: &mut dyn SchedulingPolicy, durations: &[u64]) -> SimulationReport {
+        let tasks = durations
+            .iter()
+            .enumerate()
+            .map(|(i, _)| {
+                let mut task = Task::new(format!("sim-task-{}", i));
+                task.id = format!("sim-task-{}", i);
+                task.created_at = 0;
+                task
+            })
+            .collect();
+        self.run_tasks(policy, tasks, durations)
+    }
+
+    // Run prepared tasks; `durations[i]` is the run time of `tasks[i]`
+    // Jalankan tugas yang disiapkan; `durations[i]` adalah waktu jalan `tasks[i]`
+    pub fn run_tasks(
+        &mut self,
+        policy: &mut dyn SchedulingPolicy,
+        tasks: Vec<Task>,
+        durations: &[u64],
+    ) -> SimulationReport {
+        let mut report = SimulationReport {
+            placements_per_worker: vec![0; self.workers.len()],
+            ..Default::default()
+        };
+        let mut pending: VecDeque<(Task, u64)> = tasks
+            .into_iter()
+            .zip(durations.iter().copied())
+            .collect();
+        let mut running: Vec<(usize, Task, u64)> = Vec::new();
+        let mut tick = 0;
+
+        while !pending.is_empty() || !running.is_empty() {
+            // Retire finished tasks
+            // Selesaikan tugas yang sudah berakhir
+            let workers = &mut self.workers;
+            running.retain(|(index, task, finish)| {
+                if *finish <= tick {
+                    workers[*index].release(task);
+                    false
+                } else {
+                    true
+                }
+            });
+
+            let batch: Vec<Task> = pending.iter().map(|(t, _)| t.clone()).collect();
+            let assignments = policy.assign(&batch, &self.workers);
+
+            if assignments.is_empty() && running.is_empty() {
+                // Nothing runs and nothing fits: the rest can never be placed
+                // Tidak ada yang berjalan dan tidak ada yang muat: sisanya tidak akan pernah ditempatkan
+                report.unplaced = pending.len();
+                break;
+            }
+
+            let mut placed = vec![None; batch.len()];
+            for assignment in assignments {
+                if let Some(index) = self.workers.iter().position(|w| w.id == assignment.worker_id) {
+                    placed[assignment.task_index] = Some(index);
+                }
+            }
+
+            let mut remaining = VecDeque::new();
+            for ((task, duration), slot) in pending.drain(..).zip(placed) {
+                match slot {
+                    Some(index) => {
+                        self.workers[index].reserve(&task);
+                        report.placements_per_worker[index] += 1;
+                        report.placement_order.push(index);
+                        let finish = tick + duration.max(1);
+                        report.makespan = report.makespan.max(finish);
+                        running.push((index, task, finish));
+                    }
+                    None => remaining.push_back((task, duration)),
+                }
+            }
+            pending = remaining;
+            tick += 1;
+        }
+
+        report
+    }
+}
@@ -0,0 +1,337 @@
+// Named secrets for task environments, encrypted at rest by the dispatcher
+// Rahasia bernama untuk environment tugas, dienkripsi saat disimpan oleh dispatcher
+//
+// A task refers to a secret by setting an environment variable to
+// `secret://<name>`. Only the reference is stored, logged and shown by the
+// API. The worker asks the dispatcher for the values when it starts the task,
+// and the dispatcher only answers for a task assigned to that worker whose
+// project (owner) the secret is shared with. The worker then removes the
+// values from the task's output.
+//
+// Tugas merujuk rahasia dengan mengisi variabel environment dengan
+// `secret://<nama>`. Hanya rujukannya yang disimpan, dicatat dan ditampilkan
+// API. Worker meminta nilainya ke dispatcher saat memulai tugas, dan dispatcher
+// hanya menjawab untuk tugas yang ditugaskan ke worker itu dan yang proyeknya
+// (pemilik) diberi akses ke rahasia tersebut. Worker lalu menghapus nilai
+// tersebut dari keluaran tugas.
+
+use crate::persistence::{StoredSecret, TaskStore};
+use crate::protocol::Task;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
+use anyhow::{Context, Result};
+use base64::Engine;
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Prefix of an environment value naming a secret
+pub const SCHEME: &str = "secret://";
+
+/// What secret values in task output are replaced with
+pub const REDACTED: &str = "[redacted]";
+
+const NONCE_LEN: usize = 12;
+
+/// Name in a `secret://<name>` value, if it is one
+pub fn reference(value: &str) -> Option<&str> {
+    value.strip_prefix(SCHEME)
+}
+
+/// Secret names referenced by a task, by environment variable
+pub fn references(task: &Task) -> BTreeMap<String, String> {
+    task.env
+        .iter()
+        .filter_map(|(var, value)| reference(value).map(|name| (var.clone(), name.to_string())))
+        .collect()
+}
+
+/// Token permission to submit tasks using the secrets of `project`
+pub fn project_permission(project: &str) -> String {
+    format!("project:{}", project)
+}
+
+/// Names are path-like: letters, digits, `_`, `-`, `.` and `/`-separated parts
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= 128
+        && name.split('/').all(|part| {
+            !part.is_empty()
+                && part != "."
+                && part != ".."
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid secret name '{}': use letters, digits, '_', '-', '.' and '/' between parts",
+            name
+        ))
+    }
+}
+
+/// Replace every occurrence of the values in `text`, longest first so a
+/// value containing another is not half-replaced
+pub fn redact<'a>(text: &str, values: impl IntoIterator<Item = &'a String>) -> String {
+    let mut values: Vec<&String> = values.into_iter().filter(|v| !v.is_empty()).collect();
+    values.sort_by_key(|v| std::cmp::Reverse(v.len()));
+    values
+        .into_iter()
+        .fold(text.to_string(), |text, value| text.replace(value.as_str(), REDACTED))
+}
+
+/// Redacts output that arrives in chunks. The last bytes of each chunk are
+/// held back until the next one (or `finish`), so a value split across
+/// chunks, or spanning a newline, is still replaced.
+pub struct StreamRedactor<'a> {
+    values: Vec<&'a String>,
+    /// Longest value minus one: the most of a value a chunk can end with
+    hold: usize,
+    pending: String,
+}
+
+impl<'a> StreamRedactor<'a> {
+    pub fn new(values: impl IntoIterator<Item = &'a String>) -> Self {
+        let values: Vec<&String> = values.into_iter().filter(|v| !v.is_empty()).collect();
+        let hold = values.iter().map(|v| v.len()).max().unwrap_or(0).saturating_sub(1);
+        Self { values, hold, pending: String::new() }
+    }
+
+    /// Add a chunk; returns the redacted text that can be sent now
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.pending.push_str(chunk);
+        let redacted = redact(&self.pending, self.values.iter().copied());
+        let mut split = redacted.len().saturating_sub(self.hold);
+        while !redacted.is_char_boundary(split) {
+            split -= 1;
+        }
+        self.pending = redacted[split..].to_string();
+        redacted[..split].to_string()
+    }
+
+    /// The held-back end of the stream, redacted
+    pub fn finish(&mut self) -> String {
+        redact(&std::mem::take(&mut self.pending), self.values.iter().copied())
+    }
+}
+
+/// Why a task may not have its secrets
+#[derive(Debug, thiserror::Error)]
+pub enum SecretError {
+    /// Unknown secret or one not shared with the task's project; both read
+    /// the same so callers cannot probe for names
+    #[error("{0}")]
+    Denied(String),
+    #[error(transparent)]
+    Store(#[from] anyhow::Error),
+}
+
+/// AES-256-GCM key sealing secret values at rest
+pub struct SecretKey {
+    cipher: Aes256Gcm,
+}
+
+impl SecretKey {
+    pub fn generate() -> (Self, [u8; 32]) {
+        let bytes: [u8; 32] = rand::thread_rng().gen();
+        (Self::from_bytes(&bytes), bytes)
+    }
+
+    fn from_bytes(bytes: &[u8; 32]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(bytes)),
+        }
+    }
+
+    /// Read the key file (base64), creating it readable by the owner only if missing
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("cannot read secrets key {}", path.display()))?;
+            let bytes: [u8; 32] = base64::engine::general_purpose::STANDARD
+                .decode(text.trim())
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| anyhow::anyhow!("invalid secrets key {}", path.display()))?;
+            return Ok(Self::from_bytes(&bytes));
+        }
+        let (key, bytes) = Self::generate();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+        crate::util::write_private(path, format!("{}\n", encoded).as_bytes())
+            .with_context(|| format!("cannot write secrets key {}", path.display()))?;
+        Ok(key)
+    }
+
+    // Nonce, then ciphertext; the name is authenticated so a sealed value
+    // cannot be moved to another secret
+    // Nonce, lalu ciphertext; nama ikut diautentikasi agar nilai tersegel
+    // tidak dapat dipindahkan ke rahasia lain
+    fn seal(&self, name: &str, value: &str) -> Result<Vec<u8>> {
+        let nonce: [u8; NONCE_LEN] = rand::thread_rng().gen();
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: value.as_bytes(), aad: name.as_bytes() })
+            .map_err(|e| anyhow::anyhow!("Encryption failed: {}", e))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(sealed)
+    }
+
+    fn open(&self, name: &str, sealed: &[u8]) -> Result<String> {
+        if sealed.len() < NONCE_LEN {
+            anyhow::bail!("secret {} is corrupt", name);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: name.as_bytes() })
+            .map_err(|_| anyhow::anyhow!("cannot decrypt secret {} (wrong secrets key?)", name))?;
+        Ok(String::from_utf8(plaintext)?)
+    }
+}
+
+/// Secrets of a dispatcher: sealed values in the task store
+pub struct SecretVault {
+    key: SecretKey,
+    store: Arc<dyn TaskStore>,
+}
+
+impl SecretVault {
+    pub fn new(key: SecretKey, store: Arc<dyn TaskStore>) -> Self {
+        Self { key, store }
+    }
+
+    /// Create or replace a secret, shared with `projects`
+    pub async fn put(&self, name: &str, value: &str, projects: Vec<String>) -> Result<StoredSecret> {
+        validate_name(name).map_err(anyhow::Error::msg)?;
+        let now = chrono::Utc::now().to_rfc3339();
+        let created_at = match self.store.get_secret(name).await? {
+            Some(existing) => existing.created_at,
+            None => now.clone(),
+        };
+        let secret = StoredSecret {
+            name: name.to_string(),
+            projects,
+            sealed: self.key.seal(name, value)?,
+            created_at,
+            updated_at: now,
+        };
+        self.store.save_secret(&secret).await?;
+        Ok(secret)
+    }
+
+    /// Every secret, sealed
+    pub async fn list(&self) -> Result<Vec<StoredSecret>> {
+        self.store.list_secrets().await
+    }
+
+    pub async fn delete(&self, name: &str) -> Result<bool> {
+        self.store.delete_secret(name).await
+    }
+
+    async fn shared(&self, name: &str, project: &str) -> Result<StoredSecret, SecretError> {
+        let denied = || SecretError::Denied(format!("secret {} is not available to project {}", name, project));
+        validate_name(name).map_err(SecretError::Denied)?;
+        let secret = self.store.get_secret(name).await?.ok_or_else(denied)?;
+        if !secret.projects.iter().any(|p| p == project) {
+            return Err(denied());
+        }
+        Ok(secret)
+    }
+
+    /// Check that the task's project may use every secret it references
+    pub async fn authorize(&self, task: &Task) -> Result<(), SecretError> {
+        for name in references(task).values() {
+            self.shared(name, &task.owner).await?;
+        }
+        Ok(())
+    }
+
+    /// Values of the task's secrets, by environment variable
+    pub async fn resolve(&self, task: &Task) -> Result<HashMap<String, String>, SecretError> {
+        let mut values = HashMap::new();
+        for (var, name) in references(task) {
+            let secret = self.shared(&name, &task.owner).await?;
+            values.insert(var, self.key.open(&name, &secret.sealed)?);
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::MemoryStore;
+
+    fn task(owner: &str, env: &[(&str, &str)]) -> Task {
+        let mut task = Task::new("deploy".to_string());
+        task.owner = owner.to_string();
+        task.env = env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        task
+    }
+
+    #[test]
+    fn test_stream_redactor_catches_values_split_across_chunks() {
+        let values = ["hunter2".to_string(), "multi\nline".to_string()];
+        let mut redactor = StreamRedactor::new(values.iter());
+        let mut sent = String::new();
+        for chunk in ["token=hun", "ter2\n", "key=multi\n", "line done\n", "tail hunt"] {
+            sent.push_str(&redactor.push(chunk));
+        }
+        sent.push_str(&redactor.finish());
+        assert_eq!(sent, "token=[redacted]\nkey=[redacted] done\ntail hunt");
+
+        let mut nothing = StreamRedactor::new([].iter());
+        assert_eq!(nothing.push("plain"), "plain");
+        assert_eq!(nothing.finish(), "");
+    }
+
+    #[tokio::test]
+    async fn test_secrets_are_sealed_and_shared_per_project() {
+        let store = Arc::new(MemoryStore::new());
+        let vault = SecretVault::new(SecretKey::generate().0, store.clone());
+        vault.put("ci/token", "hunter2", vec!["ci".to_string()]).await.unwrap();
+
+        let sealed = store.get_secret("ci/token").await.unwrap().unwrap().sealed;
+        assert!(!sealed.windows(7).any(|w| w == b"hunter2"));
+
+        let ci = task("ci", &[("TOKEN", "secret://ci/token"), ("MODE", "fast")]);
+        vault.authorize(&ci).await.unwrap();
+        let values = vault.resolve(&ci).await.unwrap();
+        assert_eq!(values, HashMap::from([("TOKEN".to_string(), "hunter2".to_string())]));
+
+        // Another project, and a missing secret, are refused alike
+        let other = vault.resolve(&task("web", &[("TOKEN", "secret://ci/token")])).await;
+        assert!(matches!(other, Err(SecretError::Denied(_))));
+        let missing = vault.authorize(&task("ci", &[("X", "secret://ci/nope")])).await.unwrap_err();
+        assert_eq!(missing.to_string(), "secret ci/nope is not available to project ci");
+
+        // A value moved to another name does not decrypt
+        let mut moved = store.get_secret("ci/token").await.unwrap().unwrap();
+        moved.name = "ci/copy".to_string();
+        store.save_secret(&moved).await.unwrap();
+        assert!(vault.resolve(&task("ci", &[("X", "secret://ci/copy")])).await.is_err());
+    }
+
+    #[test]
+    fn test_names_and_redaction() {
+        assert!(validate_name("ci/token").is_ok());
+        for bad in ["", "/abs", "a//b", "../x", "a b"] {
+            assert!(validate_name(bad).is_err(), "{}", bad);
+        }
+        let values = ["abc".to_string(), "abcdef".to_string()];
+        assert_eq!(redact("x abcdef abc", &values), "x [redacted] [redacted]");
+    }
+
+    #[test]
+    fn test_key_file_is_created_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.key");
+        let first = SecretKey::load_or_create(&path).unwrap();
+        let sealed = first.seal("a", "value").unwrap();
+        let again = SecretKey::load_or_create(&path).unwrap();
+        assert_eq!(again.open("a", &sealed).unwrap(), "value");
+    }
+}
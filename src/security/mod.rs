@@ -1,8 +1,16 @@
 use anyhow::Result;
+use hmac::{Hmac, Mac};
+
+type HmacSha256 = Hmac<sha2::Sha256>;
+
+/// Length of a registration challenge in bytes
+pub const NONCE_LEN: usize = 32;
 
 /// Security module for optional encryption and authentication
 pub struct Security {
     preshared_key: Option<String>,
+    /// Keys still accepted while the cluster rotates to `preshared_key`
+    accepted_keys: Vec<String>,
     whitelist: Vec<String>,
 }
 
@@ -10,6 +18,7 @@ impl Security {
     pub fn new() -> Self {
         Self {
             preshared_key: None,
+            accepted_keys: Vec::new(),
             whitelist: Vec::new(),
         }
     }
@@ -19,6 +28,57 @@ impl Security {
         self.preshared_key = Some(key);
     }
 
+    /// Set or clear the pre-shared key
+    pub fn set_key(&mut self, key: Option<String>) {
+        self.preshared_key = key;
+    }
+
+    /// Keys whose proofs are accepted besides the pre-shared key
+    pub fn set_accepted_keys(&mut self, keys: Vec<String>) {
+        self.accepted_keys = keys;
+    }
+
+    /// Whether registering workers must prove the pre-shared key
+    pub fn requires_key(&self) -> bool {
+        self.preshared_key.is_some()
+    }
+
+    /// Fresh random challenge for a registering worker
+    pub fn challenge() -> Vec<u8> {
+        rand::random::<[u8; NONCE_LEN]>().to_vec()
+    }
+
+    // HMAC-SHA256 over the nonce and worker id, so a proof is only good for
+    // one challenge and one worker
+    // HMAC-SHA256 atas nonce dan id worker, sehingga bukti hanya berlaku untuk
+    // satu tantangan dan satu worker
+    fn mac(key: &str, nonce: &[u8], worker_id: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(b"octaskly-register");
+        mac.update(nonce);
+        mac.update(worker_id.as_bytes());
+        mac
+    }
+
+    /// Answer a registration challenge; None without a key
+    pub fn prove(&self, nonce: &[u8], worker_id: &str) -> Option<Vec<u8>> {
+        let key = self.preshared_key.as_ref()?;
+        Some(Self::mac(key, nonce, worker_id).finalize().into_bytes().to_vec())
+    }
+
+    /// Check a worker's answer to `nonce` in constant time
+    pub fn verify_proof(&self, nonce: &[u8], worker_id: &str, proof: &[u8]) -> bool {
+        match &self.preshared_key {
+            Some(key) => {
+                nonce.len() == NONCE_LEN
+                    && std::iter::once(key)
+                        .chain(&self.accepted_keys)
+                        .any(|key| Self::mac(key, nonce, worker_id).verify_slice(proof).is_ok())
+            }
+            None => true,
+        }
+    }
+
     /// Verify pre-shared key
     pub fn verify_key(&self, key: &str) -> bool {
         match &self.preshared_key {
@@ -32,6 +92,11 @@ impl Security {
         self.whitelist.push(worker_id);
     }
 
+    /// Replace the whole whitelist; an empty list allows every worker
+    pub fn set_whitelist(&mut self, whitelist: Vec<String>) {
+        self.whitelist = whitelist;
+    }
+
     /// Check if worker is whitelisted
     pub fn is_whitelisted(&self, worker_id: &str) -> bool {
         if self.whitelist.is_empty() {
@@ -77,4 +142,27 @@ mod tests {
         assert!(security.verify_key("secret"));
         assert!(!security.verify_key("wrong"));
     }
+
+    #[test]
+    fn test_registration_proof() {
+        let mut dispatcher = Security::new();
+        dispatcher.set_key(Some("secret".to_string()));
+        let mut worker = Security::new();
+        worker.set_key(Some("secret".to_string()));
+        let mut intruder = Security::new();
+        intruder.set_key(Some("guess".to_string()));
+
+        let nonce = Security::challenge();
+        let proof = worker.prove(&nonce, "w1").unwrap();
+        assert!(dispatcher.verify_proof(&nonce, "w1", &proof));
+        // Bound to the worker id and to this challenge
+        assert!(!dispatcher.verify_proof(&nonce, "w2", &proof));
+        assert!(!dispatcher.verify_proof(&Security::challenge(), "w1", &proof));
+        assert!(!dispatcher.verify_proof(&nonce, "w1", &intruder.prove(&nonce, "w1").unwrap()));
+        assert!(Security::new().prove(&nonce, "w1").is_none());
+
+        // During a rotation the previous key still proves
+        dispatcher.set_accepted_keys(vec!["guess".to_string()]);
+        assert!(dispatcher.verify_proof(&nonce, "w1", &intruder.prove(&nonce, "w1").unwrap()));
+    }
 }
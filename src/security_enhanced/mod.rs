@@ -1,82 +1,264 @@
-use anyhow::Result;
-use aes_gcm::{Aes256Gcm, Key, Nonce, aead::Aead};
+use anyhow::{bail, Context, Result};
+use aes_gcm::{Aes256Gcm, Key, Nonce, aead::{Aead, Payload}};
+use base64::Engine;
+use hkdf::Hkdf;
 use rand::Rng;
-use sha2::{Sha256, Digest};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
 
-/// Enhanced security module with encryption and key management
+/// Random bytes each side contributes to a session key
+pub const SESSION_NONCE_LEN: usize = 32;
+
+/// Bytes of sequence number in front of every sealed frame
+pub const SEQUENCE_LEN: usize = 8;
+
+/// Bytes of key id in front of every ciphertext and session hello
+pub const KEY_ID_LEN: usize = 4;
+
+/// Keys that ship in code or documentation and must not protect a cluster
+pub const WELL_KNOWN_KEYS: &[&str] = &["default-secret-key", "change-me", "shared-cluster-key"];
+
+/// Whether `key` is empty or one of the well-known keys
+pub fn is_default_key(key: &str) -> bool {
+    key.trim().is_empty() || WELL_KNOWN_KEYS.contains(&key)
+}
+
+/// KDF parameters and salt for turning pre-shared keys into cluster keys.
+///
+/// Every node must use the same key file, so the same pre-shared key yields
+/// the same key everywhere. The salt is not secret.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyFile {
+    /// Only "argon2id" for now
+    pub kdf: String,
+    /// Argon2 memory cost in KiB
+    pub memory_kib: u32,
+    /// Argon2 passes
+    pub iterations: u32,
+    /// Argon2 lanes
+    pub parallelism: u32,
+    /// Base64 salt
+    pub salt: String,
+}
+
+impl KeyFile {
+    /// Fresh key file with the OWASP recommended Argon2id parameters
+    pub fn generate() -> Self {
+        Self::with_params(19 * 1024, 2, 1)
+    }
+
+    /// Fresh key file with a random salt and the given Argon2id parameters
+    pub fn with_params(memory_kib: u32, iterations: u32, parallelism: u32) -> Self {
+        let salt: [u8; 16] = rand::thread_rng().gen();
+        Self {
+            kdf: "argon2id".to_string(),
+            memory_kib,
+            iterations,
+            parallelism,
+            salt: base64::engine::general_purpose::STANDARD.encode(salt),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| {
+            format!(
+                "cannot read key file {}; create one with `octaskly keys init` and copy it to every node",
+                path.display()
+            )
+        })?;
+        let keyfile: Self =
+            toml::from_str(&text).with_context(|| format!("invalid key file {}", path.display()))?;
+        keyfile
+            .params()
+            .and(keyfile.salt_bytes())
+            .with_context(|| format!("invalid key file {}", path.display()))?;
+        Ok(keyfile)
+    }
+
+    /// Write the key file, creating its directory
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string(self)?)
+            .with_context(|| format!("cannot write key file {}", path.display()))
+    }
+
+    fn params(&self) -> Result<argon2::Params> {
+        if self.kdf != "argon2id" {
+            bail!("unsupported kdf '{}' (expected argon2id)", self.kdf);
+        }
+        argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| anyhow::anyhow!("invalid argon2 parameters: {}", e))
+    }
+
+    fn salt_bytes(&self) -> Result<Vec<u8>> {
+        let salt = base64::engine::general_purpose::STANDARD
+            .decode(&self.salt)
+            .context("salt is not valid base64")?;
+        if salt.len() < 8 {
+            bail!("salt must be at least 8 bytes");
+        }
+        Ok(salt)
+    }
+
+    /// Derive a 256-bit key from a password
+    pub fn derive(&self, password: &str) -> Result<[u8; 32]> {
+        let argon2 = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, self.params()?);
+        let mut key = [0u8; 32];
+        argon2
+            .hash_password_into(password.as_bytes(), &self.salt_bytes()?, &mut key)
+            .map_err(|e| anyhow::anyhow!("key derivation failed: {}", e))?;
+        Ok(key)
+    }
+}
+
+// One derived cluster key
+struct ClusterKey {
+    id: u32,
+    secret: [u8; 32],
+    cipher: Aes256Gcm,
+}
+
+impl ClusterKey {
+    fn new(secret: [u8; 32]) -> Self {
+        let digest = Sha256::new().chain_update(b"octaskly key id").chain_update(secret).finalize();
+        Self {
+            id: u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]),
+            cipher: SecurityManager::cipher(&secret),
+            secret,
+        }
+    }
+}
+
+/// Enhanced security module with encryption and key management.
+///
+/// Holds the current cluster key, used for everything it sends, and any
+/// number of accepted keys it still decrypts with, so the pre-shared key can
+/// be rotated one node at a time. Ciphertexts and sessions carry the id of the
+/// key they use.
 pub struct SecurityManager {
-    preshared_key: String,
+    keyfile: KeyFile,
+    keys: Vec<ClusterKey>,
     whitelist: Vec<String>,
-    cipher: Option<Aes256Gcm>,
 }
 
 impl SecurityManager {
-    pub fn new(preshared_key: String) -> Self {
-        // Derive encryption key from preshared key
-        let mut hasher = Sha256::new();
-        hasher.update(preshared_key.as_bytes());
-        let key_bytes = hasher.finalize();
-        
-        let key = Key::<Aes256Gcm>::from_slice(&key_bytes[..]);
-        let cipher = <Aes256Gcm as aes_gcm::KeyInit>::new(key);
-
-        Self {
-            preshared_key,
+    /// Derive the current key from `preshared_key` with the key file's KDF
+    pub fn new(keyfile: &KeyFile, preshared_key: &str) -> Result<Self> {
+        let key = ClusterKey::new(keyfile.derive(preshared_key)?);
+        Ok(Self {
+            keyfile: keyfile.clone(),
+            keys: vec![key],
             whitelist: Vec::new(),
-            cipher: Some(cipher),
+        })
+    }
+
+    /// Also accept, but never send with, the key derived from `preshared_key`
+    pub fn accept(mut self, preshared_key: &str) -> Result<Self> {
+        let key = ClusterKey::new(self.keyfile.derive(preshared_key)?);
+        if !self.keys.iter().any(|k| k.id == key.id) {
+            self.keys.push(key);
         }
+        Ok(self)
     }
 
-    /// Create key from password
-    pub fn derive_key(password: &str) -> [u8; 32] {
-        let mut hasher = Sha256::new();
-        hasher.update(password.as_bytes());
-        let result = hasher.finalize();
-        
+    /// Id of the key used for sending
+    pub fn key_id(&self) -> u32 {
+        self.keys[0].id
+    }
+
+    /// Ids of every key this manager decrypts with, current first
+    pub fn key_ids(&self) -> Vec<u32> {
+        self.keys.iter().map(|k| k.id).collect()
+    }
+
+    fn key(&self, id: u32) -> Option<&ClusterKey> {
+        self.keys.iter().find(|k| k.id == id)
+    }
+
+    fn expand(ikm: &[u8], salt: Option<&[u8]>, info: &[u8]) -> [u8; 32] {
         let mut key = [0u8; 32];
-        key.copy_from_slice(&result[..32]);
+        Hkdf::<Sha256>::new(salt, ikm)
+            .expand(info, &mut key)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
         key
     }
 
-    /// Encrypt data with AES-256-GCM
+    fn cipher(key: &[u8; 32]) -> Aes256Gcm {
+        <Aes256Gcm as aes_gcm::KeyInit>::new(Key::<Aes256Gcm>::from_slice(key))
+    }
+
+    /// Fresh random nonce for one side of a session
+    pub fn session_nonce() -> [u8; SESSION_NONCE_LEN] {
+        rand::thread_rng().gen()
+    }
+
+    /// Frame cipher for one connection using key `key_id`, None if that key
+    /// is not accepted here. Both sides derive the same pair of keys from the
+    /// cluster key and the nonces they exchanged, one key per direction, so no
+    /// key is ever used on two connections.
+    pub fn session(
+        &self,
+        key_id: u32,
+        initiator_nonce: &[u8],
+        responder_nonce: &[u8],
+        initiator: bool,
+    ) -> Option<FrameCipher> {
+        let secret = &self.key(key_id)?.secret;
+        let salt = [initiator_nonce, responder_nonce].concat();
+        let outbound = Self::expand(secret, Some(&salt), b"octaskly frames initiator");
+        let inbound = Self::expand(secret, Some(&salt), b"octaskly frames responder");
+        let (seal, open) = if initiator { (outbound, inbound) } else { (inbound, outbound) };
+        Some(FrameCipher {
+            seal: Self::cipher(&seal),
+            open: Self::cipher(&open),
+            sent: 0,
+            received: 0,
+        })
+    }
+
+    /// Encrypt data with AES-256-GCM under the current key
     pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
-        if let Some(cipher) = &self.cipher {
-            let mut rng = rand::thread_rng();
-            let nonce_bytes: [u8; 12] = rng.gen();
-            let nonce = Nonce::from_slice(&nonce_bytes);
-            
-            let ciphertext = cipher.encrypt(nonce, plaintext)
-                .map_err(|e| anyhow::anyhow!("Encryption failed: {}", e))?;
-            
-            // Prepend nonce to ciphertext
-            let mut result = nonce_bytes.to_vec();
-            result.extend_from_slice(&ciphertext);
-            Ok(result)
-        } else {
-            Ok(plaintext.to_vec())
-        }
+        let key = &self.keys[0];
+        let header = key.id.to_be_bytes();
+        let nonce_bytes: [u8; 12] = rand::thread_rng().gen();
+        let ciphertext = key
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce_bytes), Payload { msg: plaintext, aad: &header })
+            .map_err(|e| anyhow::anyhow!("Encryption failed: {}", e))?;
+
+        // Key id, then nonce, then ciphertext
+        let mut result = header.to_vec();
+        result.extend_from_slice(&nonce_bytes);
+        result.extend_from_slice(&ciphertext);
+        Ok(result)
     }
 
-    /// Decrypt data with AES-256-GCM
+    /// Decrypt data with AES-256-GCM under whichever accepted key it names
     pub fn decrypt(&self, encrypted_data: &[u8]) -> Result<Vec<u8>> {
-        if let Some(cipher) = &self.cipher {
-            if encrypted_data.len() < 12 {
-                return Err(anyhow::anyhow!("Invalid encrypted data"));
-            }
-            
-            let nonce = Nonce::from_slice(&encrypted_data[..12]);
-            let ciphertext = &encrypted_data[12..];
-            
-            cipher.decrypt(nonce, ciphertext)
-                .map_err(|e| anyhow::anyhow!("Decryption failed: {}", e))
-        } else {
-            Ok(encrypted_data.to_vec())
+        if encrypted_data.len() < KEY_ID_LEN + 12 {
+            bail!("Invalid encrypted data");
         }
+        let (header, rest) = encrypted_data.split_at(KEY_ID_LEN);
+        let id = u32::from_be_bytes(header.try_into()?);
+        let key = self
+            .key(id)
+            .ok_or_else(|| anyhow::anyhow!("Data was encrypted with unknown key {:08x}", id))?;
+        let (nonce, ciphertext) = rest.split_at(12);
+        key.cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: header })
+            .map_err(|e| anyhow::anyhow!("Decryption failed: {}", e))
     }
 
-    /// Verify pre-shared key
+    /// Verify pre-shared key: true if it derives one of the accepted keys
     pub fn verify_key(&self, key: &str) -> bool {
-        self.preshared_key == key
+        match self.keyfile.derive(key) {
+            Ok(secret) => self.key(ClusterKey::new(secret).id).is_some_and(|k| k.secret == secret),
+            Err(_) => false,
+        }
     }
 
     /// Add worker to whitelist
@@ -91,43 +273,80 @@ impl SecurityManager {
         self.whitelist.is_empty() || self.whitelist.contains(&worker_id.to_string())
     }
 
-    /// Generate HMAC token
-    pub fn generate_token(&self) -> String {
+    fn token_for(secret: &[u8; 32], timestamp: i64) -> String {
         use hmac::{Hmac, Mac};
         
         type HmacSha256 = Hmac<sha2::Sha256>;
         
-        let mut mac = HmacSha256::new_from_slice(self.preshared_key.as_bytes())
-            .expect("Invalid key length");
-        
-        let timestamp = chrono::Utc::now().timestamp();
+        let mut mac = HmacSha256::new_from_slice(secret).expect("Invalid key length");
         mac.update(timestamp.to_string().as_bytes());
         
         let result = mac.finalize().into_bytes();
         result.iter().map(|b| format!("{:02x}", b)).collect::<String>()
     }
 
+    /// Generate HMAC token
+    pub fn generate_token(&self) -> String {
+        Self::token_for(&self.keys[0].secret, chrono::Utc::now().timestamp())
+    }
+
     /// Verify HMAC token
     pub fn verify_token(&self, token: &str) -> bool {
-        use hmac::{Hmac, Mac};
-        
-        type HmacSha256 = Hmac<sha2::Sha256>;
-        
-        let mut mac = HmacSha256::new_from_slice(self.preshared_key.as_bytes())
-            .expect("Invalid key length");
-        
         let timestamp = chrono::Utc::now().timestamp();
-        mac.update(timestamp.to_string().as_bytes());
-        
-        let result = mac.finalize().into_bytes();
-        let expected = result.iter().map(|b| format!("{:02x}", b)).collect::<String>();
-        expected == token
+        self.keys.iter().any(|key| Self::token_for(&key.secret, timestamp) == token)
     }
 }
 
-impl Default for SecurityManager {
-    fn default() -> Self {
-        Self::new("default-secret-key".to_string())
+/// Seals and opens the frames of one connection.
+///
+/// A sealed frame is the 8-byte big-endian sequence number followed by the
+/// AES-256-GCM ciphertext. The sequence number is both the nonce and the
+/// associated data, and frames must arrive in order, so a replayed, dropped,
+/// reordered or altered frame fails to open.
+pub struct FrameCipher {
+    seal: Aes256Gcm,
+    open: Aes256Gcm,
+    sent: u64,
+    received: u64,
+}
+
+impl FrameCipher {
+    fn nonce(sequence: u64) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&sequence.to_be_bytes());
+        nonce
+    }
+
+    /// Encrypt the next outgoing frame
+    pub fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let sequence = self.sent;
+        self.sent = sequence.checked_add(1).ok_or_else(|| anyhow::anyhow!("Frame sequence exhausted"))?;
+        let header = sequence.to_be_bytes();
+        let ciphertext = self
+            .seal
+            .encrypt(Nonce::from_slice(&Self::nonce(sequence)), Payload { msg: plaintext, aad: &header })
+            .map_err(|e| anyhow::anyhow!("Encryption failed: {}", e))?;
+        let mut frame = header.to_vec();
+        frame.extend_from_slice(&ciphertext);
+        Ok(frame)
+    }
+
+    /// Decrypt the next incoming frame
+    pub fn open(&mut self, frame: &[u8]) -> Result<Vec<u8>> {
+        if frame.len() < SEQUENCE_LEN {
+            bail!("Frame too short");
+        }
+        let (header, ciphertext) = frame.split_at(SEQUENCE_LEN);
+        let sequence = u64::from_be_bytes(header.try_into()?);
+        if sequence != self.received {
+            bail!("Frame out of sequence (expected {}, got {})", self.received, sequence);
+        }
+        let plaintext = self
+            .open
+            .decrypt(Nonce::from_slice(&Self::nonce(sequence)), Payload { msg: ciphertext, aad: header })
+            .map_err(|_| anyhow::anyhow!("Frame authentication failed (wrong key or tampered frame)"))?;
+        self.received += 1;
+        Ok(plaintext)
     }
 }
 
@@ -135,12 +354,22 @@ impl Default for SecurityManager {
 mod tests {
     use super::*;
 
+    // Cheap parameters so tests stay fast
+    fn keyfile() -> KeyFile {
+        KeyFile::with_params(64, 1, 1)
+    }
+
+    fn manager(keyfile: &KeyFile, key: &str) -> SecurityManager {
+        SecurityManager::new(keyfile, key).unwrap()
+    }
+
     #[test]
     fn test_encryption_decryption() {
-        let manager = SecurityManager::new("test-key".to_string());
+        let manager = manager(&keyfile(), "test-key");
         let plaintext = b"Hello, OCTASKLY!";
         
         let encrypted = manager.encrypt(plaintext).unwrap();
+        assert_eq!(encrypted[..KEY_ID_LEN], manager.key_id().to_be_bytes());
         let decrypted = manager.decrypt(&encrypted).unwrap();
         
         assert_eq!(plaintext, decrypted.as_slice());
@@ -148,14 +377,94 @@ mod tests {
 
     #[test]
     fn test_key_verification() {
-        let manager = SecurityManager::new("secret".to_string());
+        let manager = manager(&keyfile(), "secret");
         assert!(manager.verify_key("secret"));
         assert!(!manager.verify_key("wrong"));
     }
 
+    #[test]
+    fn test_keyfile_salts_the_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.toml");
+        let first = keyfile();
+        first.save(&path).unwrap();
+        assert_eq!(KeyFile::load(&path).unwrap(), first);
+        assert_eq!(first.derive("psk").unwrap(), KeyFile::load(&path).unwrap().derive("psk").unwrap());
+
+        // Same password, different salt: different key
+        assert_ne!(first.derive("psk").unwrap(), keyfile().derive("psk").unwrap());
+
+        std::fs::write(&path, toml::to_string(&KeyFile { kdf: "sha256".to_string(), ..first }).unwrap()).unwrap();
+        assert!(KeyFile::load(&path).is_err());
+        assert!(is_default_key("default-secret-key"));
+        assert!(!is_default_key("c0rrect-h0rse"));
+    }
+
+    #[test]
+    fn test_rotation_accepts_both_keys() {
+        let keyfile = keyfile();
+        let old = manager(&keyfile, "old");
+        let new = manager(&keyfile, "new");
+        let rotating = manager(&keyfile, "new").accept("old").unwrap();
+        assert_eq!(rotating.key_ids(), vec![new.key_id(), old.key_id()]);
+
+        // Data from nodes on either key still decrypts, new data uses the new key
+        assert_eq!(rotating.decrypt(&old.encrypt(b"a").unwrap()).unwrap(), b"a");
+        assert_eq!(rotating.decrypt(&new.encrypt(b"b").unwrap()).unwrap(), b"b");
+        assert!(old.decrypt(&rotating.encrypt(b"c").unwrap()).is_err());
+        assert!(rotating.verify_key("old"));
+
+        let a = SecurityManager::session_nonce();
+        let b = SecurityManager::session_nonce();
+        assert!(rotating.session(old.key_id(), &a, &b, false).is_some());
+        assert!(old.session(new.key_id(), &a, &b, false).is_none());
+    }
+
+    fn pair(keyfile: &KeyFile, initiator_key: &str, responder_key: &str) -> (FrameCipher, FrameCipher) {
+        let initiator = manager(keyfile, initiator_key);
+        let responder = manager(keyfile, responder_key).accept(initiator_key).unwrap();
+        let (a, b) = (SecurityManager::session_nonce(), SecurityManager::session_nonce());
+        let id = initiator.key_id();
+        (
+            initiator.session(id, &a, &b, true).unwrap(),
+            responder.session(id, &a, &b, false).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_session_frames() {
+        let (mut worker, mut dispatcher) = pair(&keyfile(), "psk", "psk");
+        let first = worker.seal(b"one").unwrap();
+        let second = worker.seal(b"two").unwrap();
+        assert_ne!(first[SEQUENCE_LEN..], second[SEQUENCE_LEN..]);
+
+        // Out of order and replayed frames are refused
+        assert!(dispatcher.open(&second).is_err());
+        assert_eq!(dispatcher.open(&first).unwrap(), b"one");
+        assert!(dispatcher.open(&first).is_err());
+        assert_eq!(dispatcher.open(&second).unwrap(), b"two");
+
+        // Each direction has its own key
+        let reply = dispatcher.seal(b"ok").unwrap();
+        assert_eq!(worker.open(&reply).unwrap(), b"ok");
+
+        let mut tampered = worker.seal(b"three").unwrap();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(dispatcher.open(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_session_keys_differ() {
+        // Fresh nonces give a fresh key for the same pre-shared key
+        let keyfile = keyfile();
+        let (mut first, _) = pair(&keyfile, "psk", "psk");
+        let (_, mut second) = pair(&keyfile, "psk", "psk");
+        assert!(second.open(&first.seal(b"hello").unwrap()).is_err());
+    }
+
     #[test]
     fn test_whitelist() {
-        let mut manager = SecurityManager::new("test".to_string());
+        let mut manager = manager(&keyfile(), "test");
         assert!(manager.is_whitelisted("any-worker"));
         
         manager.add_to_whitelist("worker-1".to_string());
@@ -0,0 +1,279 @@
+// Signed task envelopes, so workers only run tasks from keys they trust
+// Amplop tugas bertanda tangan, agar worker hanya menjalankan tugas dari kunci yang dipercaya
+//
+// The dispatcher signs every assignment with its Ed25519 key. The signature
+// covers the signer, the worker the task is assigned to, an expiry and the
+// task encoded as canonical JSON (object keys sorted), so the same task always
+// yields the same bytes whatever the order of its maps, and an envelope cannot
+// be replayed to another worker.
+//
+// Dispatcher menandatangani setiap penugasan dengan kunci Ed25519-nya. Tanda
+// tangan mencakup penandatangan, worker yang ditugaskan, waktu kedaluwarsa dan
+// tugas yang dikodekan sebagai JSON kanonik (kunci objek diurutkan), sehingga
+// tugas yang sama selalu menghasilkan byte yang sama apa pun urutan map-nya,
+// dan amplop tidak dapat diputar ulang ke worker lain.
+
+use crate::protocol::{Task, TaskEnvelope};
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+// Domain separation, so a task signature is never valid for anything else
+// Pemisahan domain, agar tanda tangan tugas tidak pernah berlaku untuk hal lain
+const CONTEXT: &[u8] = b"octaskly task envelope v2";
+
+fn encode(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+fn decode_key(text: &str) -> Result<[u8; 32]> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(text.trim())
+        .context("key is not valid base64")?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("key must be 32 bytes"))
+}
+
+/// Parse a base64 Ed25519 public key
+pub fn parse_public_key(text: &str) -> Result<VerifyingKey> {
+    VerifyingKey::from_bytes(&decode_key(text)?).context("not an Ed25519 public key")
+}
+
+fn write_canonical(value: &Value, out: &mut Vec<u8>) -> Result<()> {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push(b'{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                out.extend(serde_json::to_vec(key)?);
+                out.push(b':');
+                write_canonical(&map[key], out)?;
+            }
+            out.push(b'}');
+        }
+        Value::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical(item, out)?;
+            }
+            out.push(b']');
+        }
+        other => out.extend(serde_json::to_vec(other)?),
+    }
+    Ok(())
+}
+
+/// Task as JSON with sorted object keys and no whitespace
+pub fn canonical_json(task: &Task) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_canonical(&serde_json::to_value(task)?, &mut out)?;
+    Ok(out)
+}
+
+/// Bytes an envelope's signature covers
+pub fn signed_bytes(task: &Task, worker_id: &str, signer: &str, expires_at: i64) -> Result<Vec<u8>> {
+    let mut bytes = CONTEXT.to_vec();
+    bytes.push(0);
+    bytes.extend(signer.as_bytes());
+    bytes.push(0);
+    bytes.extend(worker_id.as_bytes());
+    bytes.push(0);
+    bytes.extend(expires_at.to_be_bytes());
+    bytes.extend(canonical_json(task)?);
+    Ok(bytes)
+}
+
+/// Signs task envelopes with an Ed25519 key
+pub struct TaskSigner {
+    key: SigningKey,
+    public: String,
+}
+
+impl TaskSigner {
+    pub fn generate() -> Self {
+        Self::from_seed(rand::random())
+    }
+
+    fn from_seed(seed: [u8; 32]) -> Self {
+        let key = SigningKey::from_bytes(&seed);
+        let public = encode(key.verifying_key().as_bytes());
+        Self { key, public }
+    }
+
+    /// Read a key file written by `save`
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| {
+            format!(
+                "cannot read signing key {}; create one with `octaskly keys signing`",
+                path.display()
+            )
+        })?;
+        let seed = decode_key(&text).with_context(|| format!("invalid signing key {}", path.display()))?;
+        Ok(Self::from_seed(seed))
+    }
+
+    /// Write the private key (base64), readable by the owner only
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let encoded = format!("{}\n", encode(self.key.as_bytes()));
+        crate::util::write_private(path, encoded.as_bytes())
+            .with_context(|| format!("cannot write signing key {}", path.display()))
+    }
+
+    /// Base64 public key, for workers' `security.trusted_keys`
+    pub fn public_key(&self) -> &str {
+        &self.public
+    }
+
+    /// Sign `task` for `worker_id`; workers refuse the envelope `ttl_secs` from now
+    pub fn sign(&self, task: Task, worker_id: &str, ttl_secs: u64) -> Result<TaskEnvelope> {
+        let expires_at = chrono::Utc::now().timestamp() + ttl_secs as i64;
+        let signature = self.key.sign(&signed_bytes(&task, worker_id, &self.public, expires_at)?);
+        Ok(TaskEnvelope {
+            task,
+            worker_id: worker_id.to_string(),
+            signer: self.public.clone(),
+            expires_at,
+            signature: signature.to_bytes().to_vec(),
+        })
+    }
+}
+
+/// Public keys a worker accepts tasks from, by name
+#[derive(Default)]
+pub struct TrustedKeys {
+    keys: Vec<(String, VerifyingKey)>,
+}
+
+impl TrustedKeys {
+    /// Parse a name => base64 public key map, as in `security.trusted_keys`
+    pub fn new(keys: &HashMap<String, String>) -> Result<Self> {
+        let mut parsed = keys
+            .iter()
+            .map(|(name, key)| {
+                parse_public_key(key)
+                    .map(|key| (name.clone(), key))
+                    .with_context(|| format!("security.trusted_keys.{}", name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        parsed.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(Self { keys: parsed })
+    }
+
+    /// No trusted keys: tasks are not checked
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Check an envelope addressed to `worker_id`; returns its task and the
+    /// trusted name of its signer
+    pub fn verify(&self, envelope: TaskEnvelope, worker_id: &str) -> Result<(Task, String)> {
+        let signer = parse_public_key(&envelope.signer).context("malformed signer key")?;
+        let Some((name, key)) = self.keys.iter().find(|(_, key)| *key == signer) else {
+            bail!("task {} is signed by an untrusted key", envelope.task.id);
+        };
+        if chrono::Utc::now().timestamp() > envelope.expires_at {
+            bail!("task {} envelope from {} has expired", envelope.task.id, name);
+        }
+        let signature = Signature::from_slice(&envelope.signature).context("malformed signature")?;
+        let signed = signed_bytes(&envelope.task, &envelope.worker_id, &envelope.signer, envelope.expires_at)?;
+        key.verify_strict(&signed, &signature)
+            .map_err(|_| anyhow::anyhow!("task {} has a bad signature", envelope.task.id))?;
+        if envelope.worker_id != worker_id {
+            bail!("task {} was signed for worker {}", envelope.task.id, envelope.worker_id);
+        }
+        Ok((envelope.task, name.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_json_sorts_keys() {
+        let mut task = Task::new("echo hi".to_string());
+        for i in 0..20 {
+            task.env.insert(format!("K{}", i), i.to_string());
+        }
+        let mut copy = task.clone();
+        // Same entries, inserted in reverse into a map of another capacity
+        let mut entries: Vec<_> = task.env.clone().into_iter().collect();
+        entries.reverse();
+        copy.env = HashMap::with_capacity(100);
+        copy.env.extend(entries);
+        assert_eq!(canonical_json(&task).unwrap(), canonical_json(&copy).unwrap());
+        let text = String::from_utf8(canonical_json(&task).unwrap()).unwrap();
+        assert!(text.find("\"K0\"").unwrap() < text.find("\"K1\"").unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_saved_key_is_private_even_over_an_open_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("octaskly-signing-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("signing.key");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let signer = TaskSigner::generate();
+        signer.save(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(TaskSigner::load(&path).unwrap().public_key(), signer.public_key());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_envelopes_are_checked() {
+        let dispatcher = TaskSigner::generate();
+        let trusted =
+            TrustedKeys::new(&HashMap::from([("dispatcher".to_string(), dispatcher.public_key().to_string())]))
+                .unwrap();
+        let task = Task::new("echo hi".to_string());
+
+        let (verified, signer) = trusted.verify(dispatcher.sign(task.clone(), "w1", 60).unwrap(), "w1").unwrap();
+        assert_eq!(verified.id, task.id);
+        assert_eq!(verified.command, task.command);
+        assert_eq!(signer, "dispatcher");
+
+        let mut tampered = dispatcher.sign(task.clone(), "w1", 60).unwrap();
+        tampered.task.command = "rm -rf /".to_string();
+        assert!(trusted.verify(tampered, "w1").unwrap_err().to_string().contains("bad signature"));
+
+        // Replayed to another worker, as is or readdressed
+        let replayed = dispatcher.sign(task.clone(), "w1", 60).unwrap();
+        assert!(trusted.verify(replayed, "w2").unwrap_err().to_string().contains("signed for worker w1"));
+        let mut readdressed = dispatcher.sign(task.clone(), "w1", 60).unwrap();
+        readdressed.worker_id = "w2".to_string();
+        assert!(trusted.verify(readdressed, "w2").unwrap_err().to_string().contains("bad signature"));
+
+        let mut expired = dispatcher.sign(task.clone(), "w1", 0).unwrap();
+        expired.expires_at -= 10;
+        assert!(trusted.verify(expired, "w1").is_err());
+
+        let spoofed = TaskSigner::generate().sign(task, "w1", 60).unwrap();
+        assert!(trusted.verify(spoofed, "w1").unwrap_err().to_string().contains("untrusted"));
+    }
+
+    #[test]
+    fn test_signing_key_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("signing.key");
+        let signer = TaskSigner::generate();
+        signer.save(&path).unwrap();
+        assert_eq!(TaskSigner::load(&path).unwrap().public_key(), signer.public_key());
+        assert!(TrustedKeys::new(&HashMap::from([("x".to_string(), "bogus".to_string())])).is_err());
+    }
+}
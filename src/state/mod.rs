@@ -1,8 +1,18 @@
-use crate::protocol::{Task, TaskResult};
+use crate::audit::AuditLog;
+use crate::events::{Event, EventBus};
+use crate::persistence::TaskStore;
+use crate::metrics;
+use crate::protocol::{Message, Task, TaskResult, TaskStatus};
+use crate::secrets::SecretVault;
+use crate::security::Security;
+use crate::signing::{TaskSigner, TrustedKeys};
+use crate::transport::Transport;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::debug;
+use tokio::task::AbortHandle;
+use tracing::{debug, warn};
 
 // Dispatcher state container for managing tasks and workers
 // Kontainer status dispatcher untuk mengelola tugas dan worker
@@ -12,6 +22,20 @@ pub struct DispatcherState {
     pub port: u16,
     pub task_results: Arc<RwLock<HashMap<String, TaskResult>>>,
     pub completed_tasks: Arc<RwLock<Vec<Task>>>,
+    pub events: Arc<EventBus>,
+    /// Timeout in seconds for submitted tasks that do not set one
+    pub task_timeout: u64,
+    /// Worker whitelist and keys, replaced on config reload
+    pub security: Arc<RwLock<Security>>,
+    /// Where rejected registrations and other security decisions are recorded
+    pub audit: Arc<AuditLog>,
+    /// Connections to workers, encrypted when security.encrypt is set
+    pub transport: Transport,
+    /// Signs assignments when security.signing_key is set, with their lifetime in seconds
+    signer: Option<(TaskSigner, u64)>,
+    /// Secrets tasks may reference as `secret://<name>`
+    pub secrets: Option<Arc<SecretVault>>,
+    store: Option<Arc<dyn TaskStore>>,
 }
 
 impl DispatcherState {
@@ -24,13 +48,103 @@ impl DispatcherState {
             port,
             task_results: Arc::new(RwLock::new(HashMap::new())),
             completed_tasks: Arc::new(RwLock::new(Vec::new())),
+            events: Arc::new(EventBus::new()),
+            task_timeout: crate::protocol::DEFAULT_TASK_TIMEOUT,
+            security: Arc::new(RwLock::new(Security::new())),
+            audit: Arc::new(AuditLog::disabled()),
+            transport: Transport::new(),
+            signer: None,
+            secrets: None,
+            store: None,
         }
     }
 
+    // Write terminal results through to the database
+    // Tulis hasil akhir langsung ke basis data
+    pub fn with_store(mut self, store: Arc<dyn TaskStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    // Record security decisions in this audit log
+    // Catat keputusan keamanan di log audit ini
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Arc::new(audit);
+        self
+    }
+
+    // Talk to workers over this transport
+    // Berkomunikasi dengan worker melalui transport ini
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    // Sign task assignments, valid for `ttl` seconds
+    // Tandatangani penugasan tugas, berlaku selama `ttl` detik
+    pub fn with_signer(mut self, signer: TaskSigner, ttl: u64) -> Self {
+        self.signer = Some((signer, ttl));
+        self
+    }
+
+    // Serve task secrets from this vault
+    // Layani rahasia tugas dari brankas ini
+    pub fn with_secrets(mut self, vault: SecretVault) -> Self {
+        self.secrets = Some(Arc::new(vault));
+        self
+    }
+
+    /// The message assigning `task` to `worker_id`, signed for it if a key is set
+    pub fn assignment(&self, task: Task, worker_id: &str) -> anyhow::Result<Message> {
+        match &self.signer {
+            Some((signer, ttl)) => Ok(Message::AssignSignedTask(signer.sign(task, worker_id, *ttl)?)),
+            None => Ok(Message::AssignTask(task)),
+        }
+    }
+
+    // Default timeout for submitted tasks that do not set one
+    // Timeout bawaan untuk tugas yang dikirim tanpa menetapkannya
+    pub fn with_task_timeout(mut self, secs: u64) -> Self {
+        self.task_timeout = secs;
+        self
+    }
+
+    // Load the most recent results saved before a restart
+    // Muat hasil terbaru yang tersimpan sebelum restart
+    pub async fn restore_results(&self, limit: usize) -> anyhow::Result<usize> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+        let results = store.load_results(limit).await?;
+        let count = results.len();
+        let mut task_results = self.task_results.write().await;
+        for result in results {
+            task_results.insert(result.task_id.clone(), result);
+        }
+        Ok(count)
+    }
+
     // Store task execution result
     // Simpan hasil eksekusi tugas
     pub async fn store_result(&self, result: TaskResult) {
         debug!("Storing result for task {}", result.task_id);
+        if let Some(store) = &self.store {
+            if let Err(e) = store.record_finished(&result).await {
+                warn!("Failed to persist result of task {}: {}", result.task_id, e);
+            }
+        }
+        let metrics = metrics::global();
+        metrics.tasks_finished.inc(&format!("{:?}", result.status));
+        if !matches!(result.status, TaskStatus::Cancelled | TaskStatus::Lost) {
+            metrics.task_duration.observe(result.duration_ms as f64 / 1000.0);
+        }
+        self.events.publish(Event::TaskCompleted {
+            task_id: result.task_id.clone(),
+            worker_id: result.worker_id.clone(),
+            status: result.status,
+            exit_code: result.exit_code,
+            duration_ms: result.duration_ms,
+        });
         self.task_results
             .write()
             .await
@@ -62,6 +176,12 @@ impl DispatcherState {
     }
 }
 
+/// Task executing on this worker, with a handle to stop it
+pub struct RunningTask {
+    pub task: Task,
+    pub abort: AbortHandle,
+}
+
 /// Worker state
 pub struct WorkerState {
     pub id: String,
@@ -69,6 +189,14 @@ pub struct WorkerState {
     pub port: u16,
     pub current_task: Arc<RwLock<Option<Task>>>,
     pub completed_tasks: Arc<RwLock<Vec<TaskResult>>>,
+    pub running: Arc<RwLock<HashMap<String, RunningTask>>>,
+    /// Connections to the dispatcher, encrypted when security.encrypt is set
+    pub transport: Transport,
+    /// Keys assignments must be signed with; empty accepts unsigned tasks
+    pub trusted_keys: TrustedKeys,
+    /// Pre-shared key this worker proves its id with
+    pub security: Arc<Security>,
+    draining: AtomicBool,
 }
 
 impl WorkerState {
@@ -79,9 +207,102 @@ impl WorkerState {
             port,
             current_task: Arc::new(RwLock::new(None)),
             completed_tasks: Arc::new(RwLock::new(Vec::new())),
+            running: Arc::new(RwLock::new(HashMap::new())),
+            transport: Transport::new(),
+            trusted_keys: TrustedKeys::default(),
+            security: Arc::new(Security::new()),
+            draining: AtomicBool::new(false),
         }
     }
 
+    // Talk to the dispatcher over this transport
+    // Berkomunikasi dengan dispatcher melalui transport ini
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    // Only run tasks signed by these keys
+    // Hanya jalankan tugas yang ditandatangani kunci-kunci ini
+    pub fn with_trusted_keys(mut self, keys: TrustedKeys) -> Self {
+        self.trusted_keys = keys;
+        self
+    }
+
+    // Prove this worker's id with these keys
+    // Buktikan id worker ini dengan kunci-kunci ini
+    pub fn with_security(mut self, security: Security) -> Self {
+        self.security = Arc::new(security);
+        self
+    }
+
+    /// Send a report to the dispatcher on a connection proving this worker's id
+    pub async fn report(&self, dispatcher: std::net::SocketAddr, message: &Message) -> anyhow::Result<()> {
+        self.transport.send_as(dispatcher, &self.id, &self.security, message).await
+    }
+
+    /// Track a task whose execution has been spawned
+    pub async fn start_task(&self, task: Task, abort: AbortHandle) {
+        self.running
+            .write()
+            .await
+            .insert(task.id.clone(), RunningTask { task, abort });
+    }
+
+    /// Spawn the execution of a task and track it. The lock is held while
+    /// spawning so that a fast task cannot finish before it is registered.
+    pub async fn spawn_task<F>(&self, task: Task, execution: F)
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let mut running = self.running.write().await;
+        let handle = tokio::spawn(execution);
+        running.insert(
+            task.id.clone(),
+            RunningTask {
+                task,
+                abort: handle.abort_handle(),
+            },
+        );
+    }
+
+    /// Forget a task that finished or was stopped
+    pub async fn finish_task(&self, task_id: &str) -> Option<Task> {
+        self.running.write().await.remove(task_id).map(|r| r.task)
+    }
+
+    /// Abort a running task; returns it if it was still running
+    pub async fn abort_task(&self, task_id: &str) -> Option<Task> {
+        let running = self.running.write().await.remove(task_id)?;
+        running.abort.abort();
+        Some(running.task)
+    }
+
+    /// Abort every running task and return them (end of the grace period)
+    pub async fn abort_all(&self) -> Vec<Task> {
+        let running: Vec<RunningTask> = self.running.write().await.drain().map(|(_, r)| r).collect();
+        running
+            .into_iter()
+            .map(|r| {
+                r.abort.abort();
+                r.task
+            })
+            .collect()
+    }
+
+    pub async fn running_count(&self) -> usize {
+        self.running.read().await.len()
+    }
+
+    /// Stop accepting new tasks
+    pub fn begin_drain(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
     pub async fn set_current_task(&self, task: Option<Task>) {
         *self.current_task.write().await = task;
     }
@@ -118,4 +339,18 @@ mod tests {
         let worker = WorkerState::new("worker-1".to_string(), 7879);
         assert!(worker.get_current_task().await.is_none());
     }
+
+    #[tokio::test]
+    async fn test_worker_state_abort_all() {
+        let worker = WorkerState::new("worker-1".to_string(), 7879);
+        let handle = tokio::spawn(std::future::pending::<()>());
+        worker.start_task(Task::new("sleep 60".to_string()), handle.abort_handle()).await;
+        assert_eq!(worker.running_count().await, 1);
+
+        worker.begin_drain();
+        assert!(worker.is_draining());
+        assert_eq!(worker.abort_all().await.len(), 1);
+        assert!(handle.await.unwrap_err().is_cancelled());
+        assert_eq!(worker.running_count().await, 0);
+    }
 }
@@ -1,18 +1,123 @@
-use crate::protocol::Message;
-use anyhow::Result;
+use crate::metrics;
+use crate::protocol::{Message, WorkerInfo};
+use crate::security::Security;
+use crate::security_enhanced::{FrameCipher, SecurityManager, KEY_ID_LEN, SESSION_NONCE_LEN};
+use anyhow::{bail, Context, Result};
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tracing::{debug, error, info};
 
+/// How long either side of a registration waits for the other's next message
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest frame either side accepts
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+// First bytes of an encrypted connection, followed by the initiator's key id and nonce
+// Byte pertama koneksi terenkripsi, diikuti id kunci dan nonce pemrakarsa
+const SESSION_MAGIC: &[u8; 4] = b"OCTE";
+
 /// Network transport for P2P communication
+#[derive(Clone, Default)]
 pub struct Transport {
-    listener: Option<TcpListener>,
+    listener: Option<Arc<TcpListener>>,
+    encryption: Option<Arc<SecurityManager>>,
+}
+
+/// One open connection; frames are sealed when the transport is encrypted
+pub struct Connection {
+    stream: TcpStream,
+    cipher: Option<FrameCipher>,
+}
+
+impl Connection {
+    /// Send one message
+    pub async fn send(&mut self, message: &Message) -> Result<()> {
+        let sent = async {
+            let mut frame = bincode::serialize(message)?;
+            if let Some(cipher) = &mut self.cipher {
+                frame = cipher.seal(&frame)?;
+            }
+            write_frame(&mut self.stream, &frame).await?;
+            Ok::<usize, anyhow::Error>(frame.len() + 4)
+        }
+        .await;
+
+        let metrics = metrics::global();
+        match sent {
+            Ok(bytes) => {
+                metrics.transport_frames_sent.inc();
+                metrics.transport_bytes_sent.add(bytes as u64);
+                Ok(())
+            }
+            Err(e) => {
+                metrics.transport_errors.inc();
+                Err(e)
+            }
+        }
+    }
+
+    /// Receive one message
+    pub async fn recv(&mut self) -> Result<Message> {
+        let mut frame = read_frame(&mut self.stream).await?;
+        let len = frame.len();
+        if let Some(cipher) = &mut self.cipher {
+            frame = cipher.open(&frame)?;
+        }
+        let message = bincode::deserialize(&frame)?;
+        let metrics = metrics::global();
+        metrics.transport_frames_received.inc();
+        metrics.transport_bytes_received.add(len as u64 + 4);
+        Ok(message)
+    }
+
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(self.stream.peer_addr()?)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+}
+
+// Length-prefixed (4 bytes, little endian) frame
+// Frame dengan awalan panjang (4 byte, little endian)
+async fn write_frame(stream: &mut TcpStream, frame: &[u8]) -> Result<()> {
+    stream.write_all(&(frame.len() as u32).to_le_bytes()).await?;
+    stream.write_all(frame).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_frame(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut len_buf = [0u8; 4];
+    stream.read_exact(&mut len_buf).await?;
+    let len = u32::from_le_bytes(len_buf) as usize;
+    if len > MAX_FRAME_LEN {
+        bail!("Frame of {} bytes exceeds the {} byte limit", len, MAX_FRAME_LEN);
+    }
+    let mut buf = vec![0u8; len];
+    stream.read_exact(&mut buf).await?;
+    Ok(buf)
 }
 
 impl Transport {
     pub fn new() -> Self {
-        Self { listener: None }
+        Self::default()
+    }
+
+    /// Seal every frame with keys derived from `manager`'s pre-shared key;
+    /// both ends of a connection must agree on this
+    pub fn with_encryption(mut self, manager: Option<Arc<SecurityManager>>) -> Self {
+        self.encryption = manager;
+        self
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
     }
 
     /// Start listening for incoming connections
@@ -20,66 +125,230 @@ impl Transport {
         let addr = format!("{}:{}", address, port).parse::<SocketAddr>()?;
         let listener = TcpListener::bind(&addr).await?;
         info!("Transport listening on {}", addr);
-        self.listener = Some(listener);
+        self.listener = Some(Arc::new(listener));
         Ok(())
     }
 
     /// Get the listener
     pub fn get_listener(&self) -> Option<&TcpListener> {
-        self.listener.as_ref()
+        self.listener.as_deref()
+    }
+
+    /// Open a connection to a peer, starting an encrypted session if enabled
+    pub async fn connect(&self, peer_addr: SocketAddr) -> Result<Connection> {
+        let opened = async {
+            let mut stream = TcpStream::connect(peer_addr).await?;
+            let Some(manager) = &self.encryption else {
+                return Ok(Connection { stream, cipher: None });
+            };
+
+            let key_id = manager.key_id();
+            let nonce = SecurityManager::session_nonce();
+            let mut hello = SESSION_MAGIC.to_vec();
+            hello.extend_from_slice(&key_id.to_be_bytes());
+            hello.extend_from_slice(&nonce);
+            stream.write_all(&hello).await?;
+            let mut theirs = [0u8; SESSION_NONCE_LEN];
+            tokio::time::timeout(HANDSHAKE_TIMEOUT, stream.read_exact(&mut theirs))
+                .await
+                .context("peer did not answer the encrypted session handshake")?
+                .context("peer closed the connection; do both ends set security.encrypt and share a key?")?;
+
+            // The responder's first frame is empty; opening it proves both
+            // ends derived the same keys
+            // Frame pertama penjawab kosong; membukanya membuktikan kedua
+            // ujung menurunkan kunci yang sama
+            let mut connection = Connection {
+                stream,
+                cipher: manager.session(key_id, &nonce, &theirs, true),
+            };
+            let confirm = tokio::time::timeout(HANDSHAKE_TIMEOUT, read_frame(&mut connection.stream)).await??;
+            if let Some(cipher) = &mut connection.cipher {
+                cipher
+                    .open(&confirm)
+                    .context("encrypted session rejected: pre-shared keys differ")?;
+            }
+            Ok::<Connection, anyhow::Error>(connection)
+        }
+        .await;
+        opened.inspect_err(|_| metrics::global().transport_errors.inc())
+    }
+
+    /// Take over an accepted stream, answering the peer's encrypted session
+    /// handshake if enabled
+    pub async fn accept(&self, mut stream: TcpStream) -> Result<Connection> {
+        let Some(manager) = &self.encryption else {
+            return Ok(Connection { stream, cipher: None });
+        };
+        let accepted = tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+            let mut hello = [0u8; 4 + KEY_ID_LEN + SESSION_NONCE_LEN];
+            stream.read_exact(&mut hello).await?;
+            if &hello[..4] != SESSION_MAGIC {
+                bail!("peer did not open an encrypted session (security.encrypt)");
+            }
+            let key_id = u32::from_be_bytes(hello[4..4 + KEY_ID_LEN].try_into()?);
+            let nonce = SecurityManager::session_nonce();
+            let Some(mut cipher) = manager.session(key_id, &hello[4 + KEY_ID_LEN..], &nonce, false) else {
+                bail!("peer uses key {:08x}, which is not accepted here", key_id);
+            };
+            stream.write_all(&nonce).await?;
+            write_frame(&mut stream, &cipher.seal(&[])?).await?;
+            Ok(cipher)
+        })
+        .await
+        .map_err(|_| anyhow::anyhow!("encrypted session handshake timed out"))
+        .and_then(|result| result);
+
+        match accepted {
+            Ok(cipher) => Ok(Connection { stream, cipher: Some(cipher) }),
+            Err(e) => {
+                metrics::global().transport_errors.inc();
+                Err(e)
+            }
+        }
     }
 
     /// Send a message to a peer
     pub async fn send_message(&self, peer_addr: SocketAddr, message: &Message) -> Result<()> {
-        let mut stream = TcpStream::connect(peer_addr).await?;
-        let serialized = bincode::serialize(message)?;
-        
-        // Send length prefix (4 bytes)
-        stream.write_all(&(serialized.len() as u32).to_le_bytes()).await?;
-        stream.write_all(&serialized).await?;
-        stream.flush().await?;
-        
+        let mut connection = self.connect(peer_addr).await?;
+        connection.send(message).await?;
         debug!("Sent message to {}", peer_addr);
         Ok(())
     }
 
-    /// Receive a message from a stream
-    pub async fn recv_message(stream: &mut TcpStream) -> Result<Message> {
-        // Read length prefix (4 bytes)
-        let mut len_buf = [0u8; 4];
-        stream.read_exact(&mut len_buf).await?;
-        let len = u32::from_le_bytes(len_buf) as usize;
+    /// Dispatcher side of a registration: challenge the worker for proof of
+    /// the pre-shared key; true if it answered correctly or no key is set
+    pub async fn challenge(connection: &mut Connection, worker_id: &str, security: &Security) -> Result<bool> {
+        if !security.requires_key() {
+            return Ok(true);
+        }
+        let nonce = Security::challenge();
+        connection.send(&Message::RegisterChallenge { nonce: nonce.clone() }).await?;
+        Ok(match tokio::time::timeout(HANDSHAKE_TIMEOUT, connection.recv()).await {
+            Ok(Ok(Message::RegisterProof { proof })) => security.verify_proof(&nonce, worker_id, &proof),
+            _ => false,
+        })
+    }
 
-        // Read message data
-        let mut buf = vec![0u8; len];
-        stream.read_exact(&mut buf).await?;
+    /// Register a worker with the dispatcher, answering its key challenge
+    /// when it sends one; fails if the dispatcher refuses the worker
+    pub async fn register(&self, dispatcher: SocketAddr, worker: &WorkerInfo, security: &Security) -> Result<()> {
+        let mut connection = self.connect(dispatcher).await?;
+        connection.send(&Message::WorkerAnnounce(worker.clone())).await?;
+        Self::prove_identity(&mut connection, &worker.id, security, "registration").await
+    }
 
-        let message = bincode::deserialize(&buf)?;
-        Ok(message)
+    /// Open a connection for a worker's reports, proving its id the way a
+    /// registration does; the dispatcher only takes reports about that worker on it
+    pub async fn connect_as(&self, dispatcher: SocketAddr, worker_id: &str, security: &Security) -> Result<Connection> {
+        let mut connection = self.connect(dispatcher).await?;
+        let hello = Message::WorkerHello {
+            worker_id: worker_id.to_string(),
+        };
+        connection.send(&hello).await?;
+        Self::prove_identity(&mut connection, worker_id, security, "hello").await?;
+        Ok(connection)
+    }
+
+    /// Send a worker's report to the dispatcher over a connection proving its id
+    pub async fn send_as(
+        &self,
+        dispatcher: SocketAddr,
+        worker_id: &str,
+        security: &Security,
+        message: &Message,
+    ) -> Result<()> {
+        let mut connection = self.connect_as(dispatcher, worker_id, security).await?;
+        connection.send(message).await?;
+        debug!("Sent message to {} as worker {}", dispatcher, worker_id);
+        Ok(())
+    }
+
+    // Worker side of a registration or hello: answer the key challenge if one
+    // comes, then read the dispatcher's verdict
+    // Sisi worker dari pendaftaran atau sapaan: jawab tantangan kunci bila ada,
+    // lalu baca keputusan dispatcher
+    async fn prove_identity(connection: &mut Connection, worker_id: &str, security: &Security, what: &str) -> Result<()> {
+        let mut reply = tokio::time::timeout(HANDSHAKE_TIMEOUT, connection.recv()).await??;
+        if let Message::RegisterChallenge { nonce } = &reply {
+            let Some(proof) = security.prove(nonce, worker_id) else {
+                bail!("dispatcher requires a pre-shared key (security.psk)");
+            };
+            connection.send(&Message::RegisterProof { proof }).await?;
+            reply = tokio::time::timeout(HANDSHAKE_TIMEOUT, connection.recv()).await??;
+        }
+        match reply {
+            Message::RegisterResult { accepted: true, .. } => Ok(()),
+            Message::RegisterResult { accepted: false, reason } => {
+                bail!("{} refused: {}", what, reason.unwrap_or_else(|| "no reason given".to_string()))
+            }
+            _ => bail!("unexpected reply to {}", what),
+        }
+    }
+
+    /// Fetch the secret values of a task assigned to this worker, by environment variable
+    pub async fn fetch_secrets(
+        &self,
+        dispatcher: SocketAddr,
+        task_id: &str,
+        worker_id: &str,
+        security: &Security,
+    ) -> Result<std::collections::HashMap<String, String>> {
+        let mut connection = self.connect_as(dispatcher, worker_id, security).await?;
+        let request = Message::SecretRequest {
+            task_id: task_id.to_string(),
+            worker_id: worker_id.to_string(),
+        };
+        connection.send(&request).await?;
+        match tokio::time::timeout(HANDSHAKE_TIMEOUT, connection.recv()).await?? {
+            Message::SecretResponse { error: Some(reason), .. } => bail!("secrets refused: {}", reason),
+            Message::SecretResponse { values, error: None } => Ok(values.0),
+            _ => bail!("unexpected reply to secret request"),
+        }
+    }
+
+    /// Ask a worker to stop tasks that were taken away from it (e.g. by a drain)
+    pub async fn cancel_on_worker(&self, worker: &WorkerInfo, task_ids: &[String]) -> Result<()> {
+        let addr = format!("{}:{}", worker.address, worker.port).parse::<SocketAddr>()?;
+        for task_id in task_ids {
+            let message = Message::CancelTask {
+                task_id: task_id.clone(),
+            };
+            self.send_message(addr, &message).await?;
+        }
+        Ok(())
     }
 
     /// Handle incoming connection
-    pub async fn handle_connection<F>(stream: TcpStream, handler: F) -> Result<()>
+    pub async fn handle_connection<F>(connection: Connection, handler: F) -> Result<()>
     where
         F: Fn(Message) -> futures::future::BoxFuture<'static, Result<()>> + 'static,
     {
-        let mut stream = stream;
-        let peer_addr = stream.peer_addr()?;
+        let mut connection = connection;
+        let peer_addr = connection.peer_addr()?;
         debug!("New connection from {}", peer_addr);
 
         loop {
-            match Self::recv_message(&mut stream).await {
+            match connection.recv().await {
                 Ok(message) => {
                     handler(message).await?;
                 }
                 Err(e) => {
                     // Check if it's EOF/disconnection
-                    if e.to_string().contains("unexpected end") || 
-                       e.to_string().contains("connection") {
+                    let closed = e.downcast_ref::<std::io::Error>().is_some_and(|io| {
+                        matches!(
+                            io.kind(),
+                            std::io::ErrorKind::UnexpectedEof
+                                | std::io::ErrorKind::ConnectionReset
+                                | std::io::ErrorKind::ConnectionAborted
+                        )
+                    });
+                    if closed {
                         debug!("Connection closed by {}", peer_addr);
                         break;
                     } else {
                         error!("Error receiving message from {}: {}", peer_addr, e);
+                        metrics::global().transport_errors.inc();
                         break;
                     }
                 }
@@ -90,19 +359,113 @@ impl Transport {
     }
 }
 
-impl Default for Transport {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::security_enhanced::KeyFile;
 
     #[test]
     fn test_transport_new() {
         let transport = Transport::new();
         assert!(transport.listener.is_none());
+        assert!(!transport.is_encrypted());
+    }
+
+    fn keyed(key: &str) -> Security {
+        let mut security = Security::new();
+        security.set_key(Some(key.to_string()));
+        security
+    }
+
+    // Minimal dispatcher: challenge every announcing or greeting worker and report the outcome
+    async fn dispatcher(security: Security) -> SocketAddr {
+        serve(Transport::new(), security).await
+    }
+
+    async fn serve(transport: Transport, security: Security) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok(mut connection) = transport.accept(stream).await else {
+                    continue;
+                };
+                let worker_id = match connection.recv().await {
+                    Ok(Message::WorkerAnnounce(worker)) => worker.id,
+                    Ok(Message::WorkerHello { worker_id }) => worker_id,
+                    _ => continue,
+                };
+                let accepted = Transport::challenge(&mut connection, &worker_id, &security).await.unwrap();
+                let reason = (!accepted).then(|| "pre-shared key proof failed".to_string());
+                let _ = connection.send(&Message::RegisterResult { accepted, reason }).await;
+                // Take the report a greeting worker sends next
+                let _ = connection.recv().await;
+            }
+        });
+        addr
+    }
+
+    fn encrypted(keyfile: &KeyFile, key: &str, accepted: &[&str]) -> Transport {
+        let mut manager = SecurityManager::new(keyfile, key).unwrap();
+        for key in accepted {
+            manager = manager.accept(key).unwrap();
+        }
+        Transport::new().with_encryption(Some(Arc::new(manager)))
+    }
+
+    #[tokio::test]
+    async fn test_register_with_preshared_key() {
+        let addr = dispatcher(keyed("psk")).await;
+        let worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 0, 1);
+        let transport = Transport::new();
+
+        transport.register(addr, &worker, &keyed("psk")).await.unwrap();
+
+        let wrong = transport.register(addr, &worker, &keyed("nope")).await.unwrap_err();
+        assert!(wrong.to_string().contains("pre-shared key proof failed"));
+
+        let missing = transport.register(addr, &worker, &Security::new()).await.unwrap_err();
+        assert!(missing.to_string().contains("security.psk"));
+
+        // Without a key on the dispatcher no challenge is sent
+        let open = dispatcher(Security::new()).await;
+        transport.register(open, &worker, &Security::new()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reports_prove_the_worker_id() {
+        let addr = dispatcher(keyed("psk")).await;
+        let transport = Transport::new();
+        let heartbeat = Message::Heartbeat {
+            worker_id: "w1".to_string(),
+            timestamp: 0,
+        };
+
+        transport.send_as(addr, "w1", &keyed("psk"), &heartbeat).await.unwrap();
+        let wrong = transport.send_as(addr, "w1", &keyed("nope"), &heartbeat).await.unwrap_err();
+        assert!(wrong.to_string().contains("hello refused: pre-shared key proof failed"));
+        assert!(transport.connect_as(addr, "w1", &Security::new()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_session() {
+        let keyfile = KeyFile::with_params(64, 1, 1);
+        let addr = serve(encrypted(&keyfile, "psk", &["old"]), keyed("psk")).await;
+        let worker = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 0, 1);
+
+        let transport = encrypted(&keyfile, "psk", &[]);
+        let connection = transport.connect(addr).await.unwrap();
+        assert!(connection.is_encrypted());
+        drop(connection);
+        transport.register(addr, &worker, &keyed("psk")).await.unwrap();
+
+        // A node still on the previous key is accepted during a rotation
+        encrypted(&keyfile, "old", &[]).connect(addr).await.unwrap();
+        assert!(encrypted(&keyfile, "nope", &[]).connect(addr).await.is_err());
+
+        // Plain and encrypted ends do not talk to each other
+        assert!(Transport::new().register(addr, &worker, &keyed("psk")).await.is_err());
+        let plain = dispatcher(keyed("psk")).await;
+        assert!(transport.register(plain, &worker, &keyed("psk")).await.is_err());
     }
 }
@@ -1,4 +1,5 @@
-use crate::protocol::WorkerInfo;
+use crate::protocol::{Availability, WorkerInfo};
+use crate::scheduler::DrainMode;
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
@@ -17,11 +18,22 @@ pub enum Tab {
     Logs,
 }
 
+// Operator action on the selected worker
+// Aksi operator pada worker yang dipilih
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkerAction {
+    Cordon(String),
+    Uncordon(String),
+    Drain(String, DrainMode),
+}
+
 pub struct Dashboard {
     current_tab: usize,
     tabs: Vec<&'static str>,
     logs: Vec<String>,
     workers_display: Vec<String>,
+    worker_ids: Vec<String>,
+    selected_worker: usize,
     tasks_display: Vec<String>,
 }
 
@@ -32,6 +44,8 @@ impl Dashboard {
             tabs: vec!["Workers", "Tasks", "Logs"],
             logs: Vec::new(),
             workers_display: Vec::new(),
+            worker_ids: Vec::new(),
+            selected_worker: 0,
             tasks_display: Vec::new(),
         }
     }
@@ -56,10 +70,52 @@ impl Dashboard {
         }
     }
 
+    pub fn select_next_worker(&mut self) {
+        if !self.worker_ids.is_empty() {
+            self.selected_worker = (self.selected_worker + 1) % self.worker_ids.len();
+        }
+    }
+
+    pub fn select_prev_worker(&mut self) {
+        if !self.worker_ids.is_empty() {
+            self.selected_worker = self
+                .selected_worker
+                .checked_sub(1)
+                .unwrap_or(self.worker_ids.len() - 1);
+        }
+    }
+
+    // Map a key on the Workers tab to an action: c = cordon, u = uncordon,
+    // d = drain and wait, m = drain and migrate running tasks
+    // Petakan tombol pada tab Workers ke aksi: c = cordon, u = uncordon,
+    // d = drain dan tunggu, m = drain dan pindahkan tugas yang berjalan
+    pub fn worker_action(&self, key: char) -> Option<WorkerAction> {
+        if self.current_tab != 0 {
+            return None;
+        }
+        let worker_id = self.worker_ids.get(self.selected_worker)?.clone();
+        match key {
+            'c' => Some(WorkerAction::Cordon(worker_id)),
+            'u' => Some(WorkerAction::Uncordon(worker_id)),
+            'd' => Some(WorkerAction::Drain(worker_id, DrainMode::Wait)),
+            'm' => Some(WorkerAction::Drain(worker_id, DrainMode::Migrate)),
+            _ => None,
+        }
+    }
+
     pub fn update_workers(&mut self, workers: Vec<WorkerInfo>) {
         self.workers_display.clear();
+        self.worker_ids = workers.iter().map(|w| w.id.clone()).collect();
+        if self.selected_worker >= self.worker_ids.len() {
+            self.selected_worker = self.worker_ids.len().saturating_sub(1);
+        }
         for worker in workers {
-            let status = if worker.is_idle() { "[IDLE]" } else { "[BUSY]" };
+            let status = match worker.availability {
+                Availability::Cordoned => "[CORDONED]",
+                Availability::Draining => "[DRAINING]",
+                Availability::Active if worker.is_idle() => "[IDLE]",
+                Availability::Active => "[BUSY]",
+            };
             let line = format!(
                 "{:20} | {} | Jobs: {}/{} | {}",
                 worker.name, status, worker.current_jobs, worker.max_jobs, worker.platform
@@ -115,11 +171,23 @@ impl Dashboard {
         let items: Vec<ListItem> = self
             .workers_display
             .iter()
-            .map(|w| ListItem::new(w.clone()))
+            .enumerate()
+            .map(|(i, w)| {
+                let item = ListItem::new(w.clone());
+                if i == self.selected_worker {
+                    item.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    item
+                }
+            })
             .collect();
 
         let list = List::new(items)
-            .block(Block::default().borders(Borders::ALL).title("Workers"))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Workers (c: cordon, u: uncordon, d: drain, m: drain+migrate)"),
+            )
             .style(Style::default().fg(Color::White));
 
         f.render_widget(list, area);
@@ -205,6 +273,18 @@ impl Ui {
     pub fn update_tasks(&mut self, completed: usize, queued: usize) {
         self.dashboard.update_tasks(completed, queued);
     }
+
+    pub fn select_next_worker(&mut self) {
+        self.dashboard.select_next_worker();
+    }
+
+    pub fn select_prev_worker(&mut self) {
+        self.dashboard.select_prev_worker();
+    }
+
+    pub fn worker_action(&self, key: char) -> Option<WorkerAction> {
+        self.dashboard.worker_action(key)
+    }
 }
 
 #[cfg(test)]
@@ -227,6 +307,24 @@ mod tests {
         assert_eq!(dashboard.current_tab, 0);
     }
 
+    #[test]
+    fn test_dashboard_worker_actions() {
+        let mut dashboard = Dashboard::new();
+        let first = WorkerInfo::new("w1".to_string(), "127.0.0.1".to_string(), 9001, 4);
+        let mut second = WorkerInfo::new("w2".to_string(), "127.0.0.1".to_string(), 9002, 4);
+        second.availability = Availability::Cordoned;
+        let second_id = second.id.clone();
+        dashboard.update_workers(vec![first, second]);
+
+        dashboard.select_next_worker();
+        assert!(dashboard.workers_display[1].contains("[CORDONED]"));
+        assert_eq!(dashboard.worker_action('u'), Some(WorkerAction::Uncordon(second_id)));
+        assert_eq!(dashboard.worker_action('x'), None);
+
+        dashboard.next_tab();
+        assert_eq!(dashboard.worker_action('c'), None);
+    }
+
     #[test]
     fn test_dashboard_logs() {
         let mut dashboard = Dashboard::new();
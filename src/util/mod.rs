@@ -1,4 +1,5 @@
 use anyhow::Result;
+use std::collections::HashMap;
 use std::path::Path;
 use tracing::info;
 
@@ -10,6 +11,12 @@ pub fn get_local_ip() -> Option<String> {
     Some(local_addr.ip().to_string())
 }
 
+// Swaps the log filter of the installed subscriber
+// Mengganti filter log dari subscriber yang terpasang
+type FilterReloader = Box<dyn Fn(tracing_subscriber::EnvFilter) -> Result<()> + Send + Sync>;
+
+static LOG_RELOAD: std::sync::OnceLock<FilterReloader> = std::sync::OnceLock::new();
+
 /// Setup tracing/logging with suppression for --help display
 pub fn setup_logging() {
     use tracing_subscriber::EnvFilter;
@@ -23,16 +30,28 @@ pub fn setup_logging() {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
 
-    tracing_subscriber::fmt()
+    let builder = tracing_subscriber::fmt()
         .with_env_filter(env_filter)
         .with_target(false)
         .with_thread_ids(false)
         .with_level(true)
-        .init();
+        .with_filter_reloading();
+    let handle = builder.reload_handle();
+    builder.init();
+    let _ = LOG_RELOAD.set(Box::new(move |filter| Ok(handle.reload(filter)?)));
 
     info!("Logging initialized");
 }
 
+/// Change the log filter at runtime, e.g. "info" or "octaskly=debug,warn"
+pub fn set_log_level(filter: &str) -> Result<()> {
+    let filter = tracing_subscriber::EnvFilter::try_new(filter)?;
+    match LOG_RELOAD.get() {
+        Some(reload) => reload(filter),
+        None => Ok(()),
+    }
+}
+
 /// Create directory if not exists
 pub async fn ensure_dir(path: &Path) -> Result<()> {
     if !path.exists() {
@@ -50,6 +69,89 @@ pub fn get_platform_info() -> String {
     )
 }
 
+/// Automatic worker labels describing this machine (`platform`, `arch`)
+pub fn platform_labels() -> HashMap<String, String> {
+    let info = get_platform_info();
+    let (platform, arch) = info.split_once(' ').unwrap_or((info.as_str(), ""));
+    let mut labels = HashMap::new();
+    labels.insert("platform".to_string(), platform.to_string());
+    labels.insert("arch".to_string(), arch.to_string());
+    labels
+}
+
+/// Number of logical CPU cores on this machine
+pub fn detect_cpu_cores() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Total physical memory in MB, `None` where it cannot be detected
+#[cfg(target_os = "linux")]
+pub fn detect_memory_mb() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemTotal:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb / 1024)
+}
+
+/// Total physical memory in MB, `None` where it cannot be detected
+#[cfg(target_os = "macos")]
+pub fn detect_memory_mb() -> Option<u64> {
+    let output = std::process::Command::new("sysctl")
+        .args(["-n", "hw.memsize"])
+        .output()
+        .ok()?;
+    let bytes: u64 = String::from_utf8(output.stdout).ok()?.trim().parse().ok()?;
+    Some(bytes / (1024 * 1024))
+}
+
+/// Total physical memory in MB, `None` where it cannot be detected
+#[cfg(windows)]
+pub fn detect_memory_mb() -> Option<u64> {
+    use windows_sys::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    // SAFETY: MEMORYSTATUSEX is plain data and dwLength is set as the API requires
+    let mut status: MEMORYSTATUSEX = unsafe { std::mem::zeroed() };
+    status.dwLength = std::mem::size_of::<MEMORYSTATUSEX>() as u32;
+    if unsafe { GlobalMemoryStatusEx(&mut status) } == 0 {
+        return None;
+    }
+    Some(status.ullTotalPhys / (1024 * 1024))
+}
+
+/// Total physical memory in MB, `None` where it cannot be detected
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn detect_memory_mb() -> Option<u64> {
+    None
+}
+
+// Written to a new file that replaces the old one, so a file created
+// earlier with looser permissions never receives the contents
+// Ditulis ke berkas baru yang menggantikan yang lama, agar berkas yang
+// dibuat sebelumnya dengan izin lebih longgar tidak pernah menerima isinya
+/// Write `contents` to `path`, readable by the current user only
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    use anyhow::Context;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut temp_name = path.file_name().context("no file name")?.to_os_string();
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+    let _ = std::fs::remove_file(&temp);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temp).with_context(|| format!("cannot write {}", temp.display()))?;
+    std::io::Write::write_all(&mut file, contents)?;
+    file.sync_all()?;
+    std::fs::rename(&temp, path).with_context(|| format!("cannot write {}", path.display()))?;
+    Ok(())
+}
+
 /// Format bytes to human readable
 pub fn format_bytes(bytes: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
@@ -73,4 +175,11 @@ mod tests {
         assert!(format_bytes(512).contains("B"));
         assert!(format_bytes(1024).contains("KB"));
     }
+
+    #[test]
+    fn test_platform_labels() {
+        let labels = platform_labels();
+        assert_eq!(labels.get("platform").map(String::as_str), Some(std::env::consts::OS));
+        assert_eq!(labels.get("arch").map(String::as_str), Some(std::env::consts::ARCH));
+    }
 }
@@ -0,0 +1,491 @@
+// Webhook delivery: POST matching cluster events to subscribed URLs, signed,
+// with retries and a persistent delivery log
+// Pengiriman webhook: POST event klaster yang cocok ke URL pelanggan, bertanda
+// tangan, dengan pengulangan dan log pengiriman yang persisten
+
+use crate::events::{Event, EventBus, EventRecord};
+use crate::persistence::{DeliveryStatus, TaskStore, Webhook, WebhookDelivery};
+use hmac::{Hmac, Mac};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+type HmacSha256 = Hmac<sha2::Sha256>;
+
+/// `sha256=<hex>` HMAC of `<timestamp>.<body>`, keyed with the webhook secret
+pub const SIGNATURE_HEADER: &str = "X-Octaskly-Signature";
+/// Unix time of the attempt, covered by the signature
+pub const TIMESTAMP_HEADER: &str = "X-Octaskly-Timestamp";
+/// Event type of the delivery
+pub const EVENT_HEADER: &str = "X-Octaskly-Event";
+/// Delivery id, stable across retries
+pub const DELIVERY_HEADER: &str = "X-Octaskly-Delivery";
+
+/// Oldest timestamp `verify` accepts, so captured deliveries cannot be replayed later
+pub const SIGNATURE_TOLERANCE_SECS: i64 = 300;
+
+// HMAC over the timestamp and body, so neither can be swapped
+// HMAC atas timestamp dan body, sehingga keduanya tidak dapat ditukar
+fn mac(secret: &str, timestamp: &str, body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Signature header value for a body sent with the given timestamp header
+pub fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let digest = mac(secret, timestamp, body).finalize().into_bytes();
+    format!("sha256={}", digest.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+/// Check a signature header in constant time and refuse timestamps older than
+/// `SIGNATURE_TOLERANCE_SECS` (for receivers and tests)
+pub fn verify(secret: &str, timestamp: &str, body: &[u8], signature: &str) -> bool {
+    verify_at(secret, timestamp, body, signature, chrono::Utc::now().timestamp())
+}
+
+fn verify_at(secret: &str, timestamp: &str, body: &[u8], signature: &str, now: i64) -> bool {
+    let Ok(sent) = timestamp.parse::<i64>() else {
+        return false;
+    };
+    if (now - sent).abs() > SIGNATURE_TOLERANCE_SECS {
+        return false;
+    }
+    let Some(hex) = signature.strip_prefix("sha256=") else {
+        return false;
+    };
+    if hex.len() != 64 || !hex.is_ascii() {
+        return false;
+    }
+    let Ok(expected) = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+    else {
+        return false;
+    };
+    mac(secret, timestamp, body).verify_slice(&expected).is_ok()
+}
+
+/// How often and how patiently deliveries are retried
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts before a delivery becomes a dead letter
+    pub max_attempts: u32,
+    /// Wait after the first failure; doubles after each further one
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Timeout of a single request
+    pub timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before the attempt following `attempt` (counted from 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+// Sends events to the webhooks subscribed to them
+// Mengirim event ke webhook yang berlangganan
+pub struct WebhookDispatcher {
+    store: Arc<dyn TaskStore>,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl WebhookDispatcher {
+    pub fn new(store: Arc<dyn TaskStore>) -> Self {
+        Self::with_retry(store, RetryPolicy::default())
+    }
+
+    pub fn with_retry(store: Arc<dyn TaskStore>, retry: RetryPolicy) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(retry.timeout)
+            .build()
+            .unwrap_or_default();
+        Self { store, client, retry }
+    }
+
+    /// Deliver every event published on the bus until it closes. A lagging
+    /// subscription is resumed from the bus history; task output that falls
+    /// behind is skipped.
+    pub async fn run(self: Arc<Self>, events: Arc<EventBus>) {
+        let mut last_id = None;
+        loop {
+            let mut subscription = events.subscribe(last_id, None);
+            for record in subscription.backlog {
+                last_id = Some(record.id);
+                self.spawn_delivery(record);
+            }
+            loop {
+                tokio::select! {
+                    received = subscription.events.recv() => match received {
+                        Ok(record) => {
+                            last_id = Some(record.id);
+                            self.spawn_delivery(record);
+                        }
+                        Err(RecvError::Lagged(missed)) => {
+                            warn!("[WEBHOOK] Fell behind by {} events, resuming from history", missed);
+                            break;
+                        }
+                        Err(RecvError::Closed) => return,
+                    },
+                    // Output ids never move `last_id`, so resuming cannot skip an event
+                    // Id output tidak pernah menggeser `last_id`, sehingga melanjutkan tidak melewatkan event
+                    Ok(record) = subscription.output.recv() => self.spawn_delivery(record),
+                }
+            }
+        }
+    }
+
+    fn spawn_delivery(self: &Arc<Self>, record: EventRecord) {
+        let dispatcher = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = dispatcher.deliver_event(&record).await {
+                warn!("[WEBHOOK] Failed to deliver event {}: {}", record.id, e);
+            }
+        });
+    }
+
+    /// Send one event to every matching webhook; returns the final state of each delivery
+    pub async fn deliver_event(&self, record: &EventRecord) -> anyhow::Result<Vec<WebhookDelivery>> {
+        let webhooks = self.matching(&record.event).await?;
+        if webhooks.is_empty() {
+            return Ok(Vec::new());
+        }
+        let payload = serde_json::to_string(record)?;
+        let now = chrono::Utc::now().to_rfc3339();
+        let deliveries = webhooks.iter().map(|webhook| {
+            let delivery = WebhookDelivery {
+                id: uuid::Uuid::new_v4().to_string(),
+                webhook_id: webhook.id.clone(),
+                event_id: record.id,
+                event_type: record.event.kind().to_string(),
+                payload: payload.clone(),
+                status: DeliveryStatus::Pending,
+                attempts: 0,
+                response_status: None,
+                last_error: None,
+                created_at: now.clone(),
+                updated_at: now.clone(),
+            };
+            self.deliver(webhook, delivery)
+        });
+        Ok(futures::future::join_all(deliveries).await)
+    }
+
+    /// Retry deliveries left pending by a previous run
+    pub async fn resume_pending(&self) -> anyhow::Result<usize> {
+        let pending = self
+            .store
+            .list_deliveries(None, Some(DeliveryStatus::Pending), usize::MAX >> 1)
+            .await?;
+        let webhooks = self.store.list_webhooks().await?;
+        let mut resumed = 0;
+        for delivery in pending {
+            if let Some(webhook) = webhooks.iter().find(|w| w.id == delivery.webhook_id) {
+                self.deliver(webhook, delivery).await;
+                resumed += 1;
+            }
+        }
+        Ok(resumed)
+    }
+
+    // Webhooks whose filters select the event; task output only goes to webhooks that list it
+    // Webhook yang filternya memilih event; output tugas hanya untuk webhook yang mencantumkannya
+    async fn matching(&self, event: &Event) -> anyhow::Result<Vec<Webhook>> {
+        let mut webhooks = self.store.list_webhooks().await?;
+        let explicit = matches!(event, Event::TaskOutput { .. });
+        webhooks.retain(|w| {
+            ((w.events.is_empty() && !explicit) || w.events.iter().any(|e| e == event.kind()))
+                && w.task_id.as_deref().is_none_or(|id| event.task_id() == Some(id))
+        });
+        if webhooks.iter().all(|w| w.owner.is_none()) {
+            return Ok(webhooks);
+        }
+
+        let owner = match event {
+            Event::TaskSubmitted { owner, .. } => Some(owner.clone()),
+            _ => match event.task_id() {
+                Some(task_id) => self.store.get_task(task_id).await?.map(|t| t.owner),
+                None => None,
+            },
+        };
+        webhooks.retain(|w| w.owner.is_none() || w.owner == owner);
+        Ok(webhooks)
+    }
+
+    // POST until accepted or out of attempts, logging every attempt
+    // POST hingga diterima atau kesempatan habis, mencatat setiap percobaan
+    async fn deliver(&self, webhook: &Webhook, mut delivery: WebhookDelivery) -> WebhookDelivery {
+        // Logged as pending first, so `resume_pending` retries it after a crash
+        // Dicatat sebagai tertunda dahulu, agar `resume_pending` mengulanginya setelah crash
+        if delivery.attempts == 0 {
+            if let Err(e) = self.store.record_delivery(&delivery).await {
+                warn!("[WEBHOOK] Failed to log delivery {}: {}", delivery.id, e);
+            }
+        }
+        while delivery.status == DeliveryStatus::Pending {
+            if delivery.attempts > 0 {
+                tokio::time::sleep(self.retry.delay(delivery.attempts)).await;
+            }
+            delivery.attempts += 1;
+            let timestamp = chrono::Utc::now().timestamp().to_string();
+            let signature = sign(&webhook.secret, &timestamp, delivery.payload.as_bytes());
+            let response = self
+                .client
+                .post(&webhook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(TIMESTAMP_HEADER, &timestamp)
+                .header(SIGNATURE_HEADER, &signature)
+                .header(EVENT_HEADER, &delivery.event_type)
+                .header(DELIVERY_HEADER, &delivery.id)
+                .body(delivery.payload.clone())
+                .send()
+                .await;
+            match response {
+                Ok(response) if response.status().is_success() => {
+                    delivery.response_status = Some(response.status().as_u16());
+                    delivery.last_error = None;
+                    delivery.status = DeliveryStatus::Delivered;
+                }
+                Ok(response) => {
+                    delivery.response_status = Some(response.status().as_u16());
+                    delivery.last_error = Some(format!("HTTP {}", response.status()));
+                }
+                Err(e) => {
+                    delivery.response_status = None;
+                    delivery.last_error = Some(e.to_string());
+                }
+            }
+            if delivery.status == DeliveryStatus::Pending && delivery.attempts >= self.retry.max_attempts {
+                warn!(
+                    "[WEBHOOK] Delivery {} to {} failed {} times, moved to dead letters",
+                    delivery.id, webhook.url, delivery.attempts
+                );
+                delivery.status = DeliveryStatus::Dead;
+            }
+            delivery.updated_at = chrono::Utc::now().to_rfc3339();
+            if let Err(e) = self.store.record_delivery(&delivery).await {
+                warn!("[WEBHOOK] Failed to log delivery {}: {}", delivery.id, e);
+            }
+        }
+        debug!("[WEBHOOK] Delivery {} to {}: {:?}", delivery.id, webhook.url, delivery.status);
+        delivery
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::MemoryStore;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    // Local HTTP endpoint answering 500 to the first `failures` requests and 200 after
+    async fn stub(failures: usize) -> (String, Arc<Mutex<Vec<(axum::http::HeaderMap, String)>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let log = received.clone();
+        let app = axum::Router::new().route(
+            "/hook",
+            axum::routing::post(move |headers: axum::http::HeaderMap, body: String| {
+                let log = log.clone();
+                let calls = calls.clone();
+                async move {
+                    log.lock().unwrap().push((headers, body));
+                    if calls.fetch_add(1, Ordering::SeqCst) < failures {
+                        axum::http::StatusCode::INTERNAL_SERVER_ERROR
+                    } else {
+                        axum::http::StatusCode::OK
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, received)
+    }
+
+    fn webhook(url: &str, events: &[&str]) -> Webhook {
+        Webhook {
+            id: uuid::Uuid::new_v4().to_string(),
+            url: url.to_string(),
+            events: events.iter().map(|e| e.to_string()).collect(),
+            task_id: None,
+            owner: None,
+            secret: "s3cret".to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    fn completed(id: u64) -> EventRecord {
+        EventRecord {
+            id,
+            timestamp: 0,
+            event: Event::TaskCompleted {
+                task_id: "t1".to_string(),
+                worker_id: "w1".to_string(),
+                status: crate::protocol::TaskStatus::Completed,
+                exit_code: Some(0),
+                duration_ms: 5,
+            },
+        }
+    }
+
+    fn quick_retries() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_signature_round_trip() {
+        let now = chrono::Utc::now().timestamp();
+        let timestamp = now.to_string();
+        let signature = sign("key", &timestamp, b"body");
+        assert!(signature.starts_with("sha256="));
+        assert!(verify("key", &timestamp, b"body", &signature));
+        assert!(!verify("other", &timestamp, b"body", &signature));
+        assert!(!verify("key", &timestamp, b"body", "sha256=zz"));
+        // The timestamp is signed, and a replay after the tolerance is refused
+        assert!(!verify("key", &(now + 1).to_string(), b"body", &signature));
+        assert!(!verify_at("key", &timestamp, b"body", &signature, now + SIGNATURE_TOLERANCE_SECS + 1));
+        assert_eq!(RetryPolicy::default().delay(3), Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn test_delivery_is_logged_pending_before_the_first_attempt() {
+        // Accepts connections but never answers, so the first attempt hangs
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let store = Arc::new(MemoryStore::new());
+        let hook = webhook(&url, &[]);
+        store.save_webhook(&hook).await.unwrap();
+        let dispatcher = Arc::new(WebhookDispatcher::with_retry(store.clone(), quick_retries()));
+
+        // Stop during the first attempt, as a crash would
+        let running = tokio::spawn({
+            let dispatcher = dispatcher.clone();
+            async move { dispatcher.deliver_event(&completed(1)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        running.abort();
+
+        let pending = store.list_deliveries(None, Some(DeliveryStatus::Pending), 10).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].event_id, 1);
+        assert_eq!(pending[0].attempts, 0);
+        drop(listener);
+    }
+
+    #[tokio::test]
+    async fn test_signed_delivery_with_retry() {
+        let (url, received) = stub(1).await;
+        let store = Arc::new(MemoryStore::new());
+        let hook = webhook(&url, &["task_completed"]);
+        store.save_webhook(&hook).await.unwrap();
+        let dispatcher = WebhookDispatcher::with_retry(store.clone(), quick_retries());
+
+        let deliveries = dispatcher.deliver_event(&completed(1)).await.unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].status, DeliveryStatus::Delivered);
+        assert_eq!(deliveries[0].attempts, 2);
+
+        let requests = received.lock().unwrap().clone();
+        assert_eq!(requests.len(), 2);
+        let (headers, body) = &requests[1];
+        let signature = headers[SIGNATURE_HEADER].to_str().unwrap();
+        let timestamp = headers[TIMESTAMP_HEADER].to_str().unwrap();
+        assert!(verify("s3cret", timestamp, body.as_bytes(), signature));
+        assert_eq!(headers[EVENT_HEADER], "task_completed");
+        assert_eq!(headers[DELIVERY_HEADER], deliveries[0].id.as_str());
+
+        // Not subscribed to assignments
+        let assigned = EventRecord {
+            id: 2,
+            timestamp: 0,
+            event: Event::TaskAssigned {
+                task_id: "t1".to_string(),
+                worker_id: "w1".to_string(),
+            },
+        };
+        assert!(dispatcher.deliver_event(&assigned).await.unwrap().is_empty());
+        assert_eq!(store.list_deliveries(Some(&hook.id), None, 10).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_retries_become_dead_letters() {
+        let (url, received) = stub(usize::MAX).await;
+        let store = Arc::new(MemoryStore::new());
+        let mut hook = webhook(&url, &[]);
+        hook.owner = Some("team-a".to_string());
+        store.save_webhook(&hook).await.unwrap();
+        let dispatcher = WebhookDispatcher::with_retry(store.clone(), quick_retries());
+
+        // Owner filter: unknown task, nothing sent
+        assert!(dispatcher.deliver_event(&completed(1)).await.unwrap().is_empty());
+
+        let submitted = EventRecord {
+            id: 2,
+            timestamp: 0,
+            event: Event::TaskSubmitted {
+                task_id: "t2".to_string(),
+                command: "echo".to_string(),
+                owner: "team-a".to_string(),
+                parent_id: None,
+            },
+        };
+        let deliveries = dispatcher.deliver_event(&submitted).await.unwrap();
+        assert_eq!(deliveries[0].status, DeliveryStatus::Dead);
+        assert_eq!(deliveries[0].attempts, 3);
+        assert_eq!(deliveries[0].response_status, Some(500));
+        assert_eq!(received.lock().unwrap().len(), 3);
+
+        let dead = store.list_deliveries(None, Some(DeliveryStatus::Dead), 10).await.unwrap();
+        assert_eq!(dead, deliveries);
+    }
+
+    #[tokio::test]
+    async fn test_task_output_only_reaches_webhooks_that_list_it() {
+        let (url, received) = stub(0).await;
+        let store = Arc::new(MemoryStore::new());
+        store.save_webhook(&webhook(&url, &[])).await.unwrap();
+        let listed = webhook(&url, &["task_output"]);
+        store.save_webhook(&listed).await.unwrap();
+        let dispatcher = WebhookDispatcher::with_retry(store.clone(), quick_retries());
+
+        let output = EventRecord {
+            id: 1,
+            timestamp: 0,
+            event: Event::TaskOutput {
+                task_id: "t1".to_string(),
+                stream: crate::protocol::OutputStream::Stdout,
+                text: "hi\n".to_string(),
+            },
+        };
+        let deliveries = dispatcher.deliver_event(&output).await.unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].webhook_id, listed.id);
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+}
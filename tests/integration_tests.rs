@@ -34,7 +34,7 @@ mod integration_tests {
         let executor = Executor::new(PathBuf::from("/tmp"), true);
         let task = Task::new("echo 'test'".to_string());
         
-        let result = executor.execute_with_timeout(&task).await;
+        let result = executor.execute_with_timeout(&task, None).await;
         assert!(result.is_ok());
         
         let result = result.unwrap();
@@ -48,7 +48,7 @@ mod integration_tests {
         let mut task = Task::new("sleep 10".to_string());
         task.timeout = 1; // 1 second timeout
         
-        let result = executor.execute_with_timeout(&task).await;
+        let result = executor.execute_with_timeout(&task, None).await;
         assert!(result.is_ok());
         
         let result = result.unwrap();
@@ -113,6 +113,7 @@ mod integration_tests {
             exit_code: Some(0),
             duration_ms: 100,
             completed_at: chrono::Local::now().timestamp(),
+            signer: None,
         };
         
         dispatcher.store_result(result.clone()).await;